@@ -0,0 +1,176 @@
+//! `--assert-status`, `--assert-header` and `--assert-body-contains`: check the response is what
+//! a health check expects, failing with what was expected next to what came back when it isn't
+
+use std::fmt::Write;
+
+use http::Response;
+use owo_colors::{OwoColorize, Stream};
+
+use crate::spool::{body_len, body_prefix};
+
+/// How much of the body is shown when it doesn't contain what it should
+const BODY_EXCERPT: usize = 200;
+
+/// Everything the response is checked for
+#[derive(Clone, Debug, Default)]
+pub struct Assertions {
+    /// Any of these, if there are any
+    pub statuses: Vec<u16>,
+    /// Lowercased name → text the value has to contain (ignoring case), or `""` to only need
+    /// the header to be there
+    pub headers: Vec<(String, String)>,
+    pub body_contains: Vec<String>,
+}
+
+/// An assertion that didn't hold
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Failure {
+    /// What was checked, like `status` or `header content-type`
+    pub what: String,
+    pub expected: String,
+    pub actual: String,
+}
+
+/// Parse an `--assert-header 'name: value'`, where the value can be left out to only check that
+/// the header is there
+/// ```
+/// # use httpc::assertions::parse_assert_header;
+/// assert_eq!(
+///     parse_assert_header("Content-Type: application/json").unwrap(),
+///     ("content-type".to_string(), "application/json".to_string())
+/// );
+/// assert_eq!(parse_assert_header("ETag").unwrap(), ("etag".to_string(), String::new()));
+/// assert!(parse_assert_header(": x").is_err());
+/// ```
+pub fn parse_assert_header(header: &str) -> Result<(String, String), String> {
+    let (name, value) = header.split_once(':').unwrap_or((header, ""));
+
+    match name.trim() {
+        "" => Err(format!(
+            "Expected 'name: value' or 'name', got '{}'",
+            header
+        )),
+        name => Ok((name.to_ascii_lowercase(), value.trim().to_string())),
+    }
+}
+
+impl Assertions {
+    pub fn is_empty(&self) -> bool {
+        self.statuses.is_empty() && self.headers.is_empty() && self.body_contains.is_empty()
+    }
+
+    /// Everything that doesn't hold for `response`
+    /// ```
+    /// # use httpc::assertions::Assertions;
+    /// let response = http::Response::builder()
+    ///     .status(503)
+    ///     .header("Content-Type", "text/html")
+    ///     .body(b"<h1>Down for maintenance</h1>".to_vec())
+    ///     .unwrap();
+    /// let assertions = Assertions {
+    ///     statuses: vec![200, 204],
+    ///     headers: vec![("content-type".to_string(), "TEXT/HTML".to_string())],
+    ///     body_contains: vec!["maintenance".to_string(), "ok".to_string()],
+    /// };
+    ///
+    /// let failures = assertions.check(&response).unwrap();
+    /// assert_eq!(failures.len(), 2);
+    /// assert_eq!(failures[0].expected, "200 or 204");
+    /// assert_eq!(failures[0].actual, "503 Service Unavailable");
+    /// assert_eq!(failures[1].what, "body");
+    /// ```
+    pub fn check(&self, response: &Response<Vec<u8>>) -> std::io::Result<Vec<Failure>> {
+        let mut failures = vec![];
+        let status = response.status();
+
+        if !self.statuses.is_empty() && !self.statuses.contains(&status.as_u16()) {
+            failures.push(Failure {
+                what: "status".to_string(),
+                expected: self
+                    .statuses
+                    .iter()
+                    .map(u16::to_string)
+                    .collect::<Vec<_>>()
+                    .join(" or "),
+                actual: status.to_string(),
+            });
+        }
+
+        for (name, expected) in &self.headers {
+            let values = response
+                .headers()
+                .get_all(name.as_str())
+                .iter()
+                .map(|value| String::from_utf8_lossy(value.as_bytes()).into_owned())
+                .collect::<Vec<_>>();
+
+            let found = values.iter().any(|value| {
+                value
+                    .to_ascii_lowercase()
+                    .contains(&expected.to_ascii_lowercase())
+            });
+
+            if !found {
+                failures.push(Failure {
+                    what: format!("header {}", name),
+                    expected: match expected.is_empty() {
+                        true => "to be there".to_string(),
+                        false => format!("to contain '{}'", expected),
+                    },
+                    actual: match values.is_empty() {
+                        true => "not there".to_string(),
+                        false => format!("'{}'", values.join("', '")),
+                    },
+                });
+            }
+        }
+
+        if !self.body_contains.is_empty() {
+            let body = body_prefix(response, body_len(response))?;
+            let body = String::from_utf8_lossy(&body);
+
+            for expected in &self.body_contains {
+                if !body.contains(expected.as_str()) {
+                    failures.push(Failure {
+                        what: "body".to_string(),
+                        expected: format!("to contain '{}'", expected),
+                        actual: excerpt(&body),
+                    });
+                }
+            }
+        }
+
+        Ok(failures)
+    }
+}
+
+/// The start of the body on one line, for saying what it was instead
+fn excerpt(body: &str) -> String {
+    let line = body.split_whitespace().collect::<Vec<_>>().join(" ");
+
+    match line.char_indices().nth(BODY_EXCERPT) {
+        Some((cut, _)) => format!("'{}…' ({} bytes)", &line[..cut], body.len()),
+        None if line.is_empty() => "empty".to_string(),
+        None => format!("'{}'", line),
+    }
+}
+
+/// The failures for stderr, each with what was expected and what came back
+pub fn format_failures(failures: &[Failure]) -> String {
+    let mut output = String::new();
+
+    for failure in failures {
+        let _ = writeln!(
+            output,
+            "{} {}\n    {} {}\n    {} {}",
+            "✗".if_supports_color(Stream::Stderr, |t| t.red()),
+            failure.what.if_supports_color(Stream::Stderr, |t| t.bold()),
+            "expected".if_supports_color(Stream::Stderr, |t| t.green()),
+            failure.expected,
+            "  actual".if_supports_color(Stream::Stderr, |t| t.red()),
+            failure.actual
+        );
+    }
+
+    output
+}
@@ -0,0 +1,172 @@
+use std::io::{BufReader, Cursor};
+
+use http::{header, HeaderMap, HeaderValue, Response};
+use owo_colors::OwoColorize;
+
+use crate::{
+    helpers::{format_response, MColorize},
+    http_request::{parse_http_response, RequestError},
+    multipart::{self, Part},
+    pretty::Pretty,
+    render::Renderers,
+};
+
+/// Build a multipart/mixed batch body out of sub-requests
+///
+/// Every sub-request is written like a (simplified) HTTP message:
+/// ```text
+/// METHOD /path[ HTTP/1.1]
+/// Header: value
+///
+/// body
+/// ```
+/// Only the request line is required, the version is added if missing. Lines can end with LF or
+/// CRLF.
+///
+/// Returns the Content-Type (including the boundary) and the body
+/// ```
+/// # use httpc::batch::build_batch;
+/// let requests = ["POST /a\r\nContent-Type: text/plain\r\n\r\nhi".to_string(), "GET /b".to_string()];
+/// let (content_type, body) = build_batch(&requests).unwrap();
+/// assert!(content_type.starts_with("multipart/mixed; boundary="));
+/// let body = String::from_utf8(body).unwrap();
+/// assert!(body.contains("POST /a HTTP/1.1\r\nContent-Type: text/plain\r\nContent-Length: 2\r\n\r\nhi"));
+/// assert!(body.contains("GET /b HTTP/1.1\r\n\r\n"));
+/// ```
+pub fn build_batch(requests: &[String]) -> Result<(String, Vec<u8>), RequestError> {
+    let boundary = multipart::generate_boundary();
+    let mut parts = vec![];
+
+    for (i, request) in requests.iter().enumerate() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("application/http"),
+        );
+        headers.insert("content-id", format!("<item{}>", i + 1).parse()?);
+
+        parts.push(Part {
+            headers,
+            body: to_http_message(request)?,
+        });
+    }
+
+    let content_type = format!("multipart/mixed; boundary={}", boundary);
+    Ok((content_type, multipart::encode(&boundary, &parts)))
+}
+
+/// Turn a user-written sub-request into a proper CRLF-delimited HTTP message
+fn to_http_message(request: &str) -> Result<Vec<u8>, RequestError> {
+    // The head ends at the first blank line, whether the lines end with LF or CRLF
+    let mut head_length = 0;
+    let mut body = None;
+
+    for line in request.split_inclusive('\n') {
+        if line.trim_end_matches(['\r', '\n']).is_empty() {
+            body = Some(&request[head_length + line.len()..]);
+            break;
+        }
+
+        head_length += line.len();
+    }
+
+    let head = match body {
+        Some(_) => &request[..head_length],
+        None => request.trim_end(),
+    };
+
+    let mut lines = head.lines().map(|line| line.trim_end_matches('\r'));
+    let request_line = lines.next().unwrap_or_default().trim();
+
+    let request_line = match request_line.split_whitespace().count() {
+        2 => format!("{} HTTP/1.1", request_line),
+        3 => request_line.to_string(),
+        _ => return Err(format!("Invalid batch request line: '{}'", request_line).into()),
+    };
+
+    let mut message = format!("{}\r\n", request_line);
+    let mut has_length = false;
+
+    for line in lines {
+        has_length |= line
+            .split_once(':')
+            .is_some_and(|(name, _)| name.trim().eq_ignore_ascii_case("content-length"));
+        message.push_str(line);
+        message.push_str("\r\n");
+    }
+
+    if let Some(body) = body {
+        if !has_length {
+            message.push_str(&format!("Content-Length: {}\r\n", body.len()));
+        }
+        message.push_str("\r\n");
+        message.push_str(body);
+    } else {
+        message.push_str("\r\n");
+    }
+
+    Ok(message.into_bytes())
+}
+
+/// Split a multipart/mixed batch response back into individual responses
+///
+/// Parts that aren't `application/http` are returned as-is with a 200 status
+pub fn split_batch(response: &Response<Vec<u8>>) -> Result<Vec<Response<Vec<u8>>>, RequestError> {
+    let boundary = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|ct| ct.to_str().ok())
+        .and_then(multipart::boundary_from_content_type)
+        .ok_or("Batch response is not multipart")?;
+
+    let mut responses = vec![];
+
+    for part in multipart::split(response.body(), &boundary)? {
+        let is_http = part
+            .headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|ct| ct.to_str().ok())
+            .is_some_and(|ct| ct.starts_with("application/http"));
+
+        if is_http {
+            responses.push(parse_http_response(&mut BufReader::new(Cursor::new(
+                part.body,
+            )))?);
+        } else {
+            let mut response = Response::new(part.body);
+            *response.headers_mut() = part.headers;
+            responses.push(response);
+        }
+    }
+
+    Ok(responses)
+}
+
+/// Print every response from a batch, one after the other
+pub fn print_batch(response: &Response<Vec<u8>>, verbosity: u8) -> Result<(), RequestError> {
+    let responses = split_batch(response)?;
+
+    for (i, response) in responses.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+
+        println!(
+            "{}",
+            format!("── Batch response {}/{} ──", i + 1, responses.len())
+                .out_color(|t| t.bright_black())
+        );
+        println!(
+            "{}",
+            format_response(
+                response,
+                verbosity,
+                false,
+                Pretty::None,
+                &Renderers::default()
+            )?
+        );
+    }
+
+    Ok(())
+}
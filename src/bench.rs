@@ -0,0 +1,250 @@
+//! `httpc bench`: send the same request over and over (some at once) and report how fast the
+//! server answered, like a small `ab`/`hey`. Connections are pooled like any other request, so
+//! there's only ever about one open per concurrent request
+
+use std::{collections::BTreeMap, time::Duration};
+
+use http::StatusCode;
+use owo_colors::OwoColorize;
+use serde_json::{json, Value};
+
+use crate::{
+    helpers::MColorize,
+    transfer::{format_bytes, Transferred},
+};
+
+/// How one request went
+#[derive(Clone, Debug)]
+pub struct Sample {
+    pub took: Duration,
+    pub result: Result<StatusCode, String>,
+}
+
+/// How long the requests that got a response took
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Latency {
+    pub min: Duration,
+    pub mean: Duration,
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+impl Latency {
+    /// `None` if nothing got a response
+    pub fn new(samples: &[Sample]) -> Option<Self> {
+        let mut took = samples
+            .iter()
+            .filter(|sample| sample.result.is_ok())
+            .map(|sample| sample.took)
+            .collect::<Vec<_>>();
+        took.sort();
+
+        Some(Self {
+            min: *took.first()?,
+            mean: took.iter().sum::<Duration>() / took.len() as u32,
+            p50: percentile(&took, 50.0),
+            p90: percentile(&took, 90.0),
+            p99: percentile(&took, 99.0),
+            max: *took.last()?,
+        })
+    }
+}
+
+/// The `p`th percentile of sorted durations (nearest rank), zero if there aren't any
+/// ```
+/// # use httpc::bench::percentile;
+/// # use std::time::Duration;
+/// let sorted = (1..=10).map(Duration::from_millis).collect::<Vec<_>>();
+/// assert_eq!(percentile(&sorted, 50.0), Duration::from_millis(5));
+/// assert_eq!(percentile(&sorted, 90.0), Duration::from_millis(9));
+/// assert_eq!(percentile(&sorted, 99.0), Duration::from_millis(10));
+/// ```
+pub fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    let rank = (p / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted
+        .get(rank.clamp(1, sorted.len().max(1)) - 1)
+        .copied()
+        .unwrap_or_default()
+}
+
+/// Everything the report shows
+#[derive(Clone, Debug)]
+pub struct Stats {
+    pub requests: usize,
+    pub concurrency: u16,
+    /// Wall-clock time for the whole run
+    pub took: Duration,
+    pub latency: Option<Latency>,
+    /// How many responses had each status
+    pub statuses: BTreeMap<u16, usize>,
+    /// How many requests failed with each error (no response at all)
+    pub errors: BTreeMap<String, usize>,
+    pub transferred: Transferred,
+}
+
+impl Stats {
+    pub fn new(
+        samples: &[Sample],
+        concurrency: u16,
+        took: Duration,
+        transferred: Transferred,
+    ) -> Self {
+        let mut statuses = BTreeMap::new();
+        let mut errors = BTreeMap::new();
+
+        for sample in samples {
+            match &sample.result {
+                Ok(status) => *statuses.entry(status.as_u16()).or_default() += 1,
+                Err(err) => *errors.entry(err.clone()).or_default() += 1,
+            }
+        }
+
+        Self {
+            requests: samples.len(),
+            concurrency,
+            took,
+            latency: Latency::new(samples),
+            statuses,
+            errors,
+            transferred,
+        }
+    }
+
+    /// Requests that didn't get a response
+    pub fn failures(&self) -> usize {
+        self.errors.values().sum()
+    }
+
+    /// Requests finished (successfully or not) per second
+    pub fn per_second(&self) -> f64 {
+        self.requests as f64 / self.took.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+/// The stats as text
+pub fn format_stats(stats: &Stats, url: &str) -> String {
+    let millis = |duration: Duration| format!("{:.1}ms", duration.as_secs_f64() * 1000.0);
+    let label = |label: &str| format!("{:<12}", label).out_color(|t| t.cyan()).to_string();
+
+    let mut report = format!(
+        "{}\n",
+        format!(
+            "{} requests to {}, {} at a time",
+            stats.requests, url, stats.concurrency
+        )
+        .out_color(|t| t.bold())
+    );
+
+    report.push_str(&format!(
+        "{} {:.2}s\n{} {:.1}\n{} {} sent, {} received ({}/s) over {} connection{}\n",
+        label("Took"),
+        stats.took.as_secs_f64(),
+        label("Requests/s"),
+        stats.per_second(),
+        label("Transferred"),
+        format_bytes(stats.transferred.sent),
+        format_bytes(stats.transferred.received),
+        format_bytes(
+            (stats.transferred.received as f64 / stats.took.as_secs_f64().max(f64::EPSILON)) as u64
+        ),
+        stats.transferred.connections,
+        if stats.transferred.connections == 1 {
+            ""
+        } else {
+            "s"
+        }
+    ));
+
+    if let Some(latency) = &stats.latency {
+        report.push_str(&format!(
+            "{} min {}, mean {}, p50 {}, p90 {}, p99 {}, max {}\n",
+            label("Latency"),
+            millis(latency.min),
+            millis(latency.mean),
+            millis(latency.p50),
+            millis(latency.p90),
+            millis(latency.p99),
+            millis(latency.max)
+        ));
+    }
+
+    if !stats.statuses.is_empty() {
+        let statuses = stats
+            .statuses
+            .iter()
+            .map(|(status, count)| {
+                let status = if *status >= 400 {
+                    status.out_color(|t| t.red()).to_string()
+                } else {
+                    status.out_color(|t| t.green()).to_string()
+                };
+                format!("{} × {}", status, count)
+            })
+            .collect::<Vec<_>>();
+
+        report.push_str(&format!("{} {}\n", label("Statuses"), statuses.join(", ")));
+    }
+
+    if !stats.errors.is_empty() {
+        report.push_str(&format!("{} {}\n", label("Errors"), stats.failures()));
+
+        for (err, count) in &stats.errors {
+            report.push_str(&format!(
+                "  {} {} × {}\n",
+                "✗".out_color(|t| t.red()),
+                err,
+                count
+            ));
+        }
+    }
+
+    report
+}
+
+/// The stats as JSON, with times in seconds and sizes in bytes
+/// ```
+/// # use httpc::bench::{stats_json, Sample, Stats};
+/// # use std::time::Duration;
+/// let samples = [
+///     Sample { took: Duration::from_millis(10), result: Ok(http::StatusCode::OK) },
+///     Sample { took: Duration::from_millis(30), result: Err("timed out".to_string()) },
+/// ];
+/// let stats = Stats::new(&samples, 2, Duration::from_secs(1), Default::default());
+/// let json = stats_json(&stats);
+/// assert_eq!(json["statuses"]["200"], 1);
+/// assert_eq!(json["errors"]["timed out"], 1);
+/// assert_eq!(json["latency"]["p99"], 0.01);
+/// ```
+pub fn stats_json(stats: &Stats) -> Value {
+    let latency = stats.latency.map(|latency| {
+        json!({
+            "min": latency.min.as_secs_f64(),
+            "mean": latency.mean.as_secs_f64(),
+            "p50": latency.p50.as_secs_f64(),
+            "p90": latency.p90.as_secs_f64(),
+            "p99": latency.p99.as_secs_f64(),
+            "max": latency.max.as_secs_f64(),
+        })
+    });
+
+    let statuses = stats
+        .statuses
+        .iter()
+        .map(|(status, count)| (status.to_string(), json!(count)))
+        .collect::<serde_json::Map<_, _>>();
+
+    json!({
+        "requests": stats.requests,
+        "concurrency": stats.concurrency,
+        "took": stats.took.as_secs_f64(),
+        "requests_per_second": stats.per_second(),
+        "latency": latency,
+        "statuses": statuses,
+        "errors": stats.errors,
+        "sent": stats.transferred.sent,
+        "received": stats.transferred.received,
+        "connections": stats.transferred.connections,
+    })
+}
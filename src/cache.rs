@@ -0,0 +1,419 @@
+//! A small private HTTP cache (RFC 9111) kept in a directory on disk
+//!
+//! Entries are stored as plain HTTP response messages (so they're parsed by the same code as
+//! responses from the network), preceded by a line with the time the request was sent and
+//! the time the response was received, which we need to calculate the age of a response
+
+use std::{
+    fs,
+    io::{self, BufRead, BufReader, Write},
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use http::{header, HeaderMap, HeaderValue, Method, Request, Response, StatusCode};
+use owo_colors::OwoColorize;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    cli::VERBOSE,
+    helpers::{expand_home, to_hex, MColorize},
+    http_request::{http_request, parse_http_response, RequestError, RequestOptions},
+    spool::{body_len, copy_body, Spooled},
+};
+
+const ENTRY_MAGIC: &str = "httpc-cache-1";
+
+/// Heuristic freshness is capped, so responses without any caching headers don't live forever
+const MAX_HEURISTIC_FRESHNESS: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Status codes that are "heuristically cacheable" (RFC 9110 15.1)
+const HEURISTICALLY_CACHEABLE: [u16; 10] = [200, 203, 204, 300, 301, 308, 404, 405, 410, 501];
+
+pub struct Cache {
+    dir: PathBuf,
+}
+
+/// A response we got out of the cache, along with the times we need to know how old it is
+struct Entry {
+    response: Response<Vec<u8>>,
+    request_time: SystemTime,
+    response_time: SystemTime,
+}
+
+impl Cache {
+    /// Open a cache directory, creating it if needed. A leading `~` is expanded to $HOME
+    pub fn open(dir: &str) -> io::Result<Self> {
+        let dir = expand_home(dir);
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    /// Send a request through the cache
+    ///
+    /// Fresh responses are served straight from disk, stale ones are revalidated with
+    /// the server if we can (using their ETag/Last-Modified), and anything storable we get
+    /// from the network is saved for next time
+    pub fn request(
+        &self,
+        mut req: Request<Option<&[u8]>>,
+        options: &RequestOptions,
+    ) -> Result<Response<Vec<u8>>, RequestError> {
+        // Only GETs are cached, and we leave requests that are already conditional
+        // (or ranged) to the user, since they know what they want from the server
+        let bypass = req.method() != Method::GET
+            || [
+                header::IF_NONE_MATCH,
+                header::IF_MODIFIED_SINCE,
+                header::IF_MATCH,
+                header::IF_UNMODIFIED_SINCE,
+                header::RANGE,
+            ]
+            .iter()
+            .any(|name| req.headers().contains_key(name));
+
+        if bypass {
+            return http_request(req, options);
+        }
+
+        let url = req.uri().to_string();
+        let request_directives = directives(req.headers());
+        let no_store = request_directives
+            .iter()
+            .any(|(name, _)| name == "no-store");
+        let no_cache = request_directives
+            .iter()
+            .any(|(name, _)| name == "no-cache")
+            || req
+                .headers()
+                .get(header::PRAGMA)
+                .and_then(|value| value.to_str().ok())
+                .is_some_and(|value| value.contains("no-cache"));
+
+        let cached = self.load(&url, req.headers());
+
+        if let Some(entry) = &cached {
+            let age = entry.current_age();
+            let must_revalidate = no_cache
+                || directives(entry.response.headers())
+                    .iter()
+                    .any(|(name, _)| name == "no-cache");
+
+            if !must_revalidate && age < entry.freshness_lifetime() {
+                if options.verbosity >= VERBOSE {
+                    println!(
+                        "{}",
+                        format!("Served from cache (age {}s)\n", age.as_secs())
+                            .out_color(|t| t.bright_black())
+                    );
+                }
+
+                return Ok(entry.to_response(age));
+            }
+
+            // Stale, so ask the server if what we have is still good
+            let headers = req.headers_mut();
+            if let Some(etag) = entry.response.headers().get(header::ETAG) {
+                headers.insert(header::IF_NONE_MATCH, etag.clone());
+            }
+            if let Some(last_modified) = entry.response.headers().get(header::LAST_MODIFIED) {
+                headers.insert(header::IF_MODIFIED_SINCE, last_modified.clone());
+            }
+        }
+
+        let request_headers = req.headers().clone();
+        let request_time = SystemTime::now();
+        let response = http_request(req, options)?;
+        let response_time = SystemTime::now();
+
+        if let (Some(mut entry), StatusCode::NOT_MODIFIED) = (cached, response.status()) {
+            if options.verbosity >= VERBOSE {
+                println!(
+                    "{}",
+                    "Revalidated cached response (304 Not Modified)\n"
+                        .out_color(|t| t.bright_black())
+                );
+            }
+
+            // The 304 has the up-to-date metadata, everything else stays as it was
+            for (name, value) in response.headers() {
+                if name != header::CONTENT_LENGTH && name != header::TRANSFER_ENCODING {
+                    entry.response.headers_mut().insert(name, value.clone());
+                }
+            }
+
+            entry.request_time = request_time;
+            entry.response_time = response_time;
+
+            if !no_store {
+                self.store(&url, &request_headers, &entry)?;
+            }
+
+            let age = entry.current_age();
+            return Ok(entry.to_response(age));
+        }
+
+        let entry = Entry {
+            response,
+            request_time,
+            response_time,
+        };
+
+        if !no_store && is_storable(&request_headers, &entry) {
+            self.store(&url, &request_headers, &entry)?;
+        }
+
+        Ok(entry.response)
+    }
+
+    /// Load the entry for a URL, if we have one for these request headers (see `Vary`)
+    fn load(&self, url: &str, request_headers: &HeaderMap) -> Option<Entry> {
+        let vary = fs::read_to_string(self.vary_path(url)).ok()?;
+        let path = self.entry_path(url, &vary, request_headers);
+        let mut reader = BufReader::new(fs::File::open(path).ok()?);
+
+        let mut meta = String::new();
+        reader.read_line(&mut meta).ok()?;
+        let mut meta = meta.split_whitespace();
+
+        if meta.next()? != ENTRY_MAGIC {
+            return None;
+        }
+
+        let mut next_time = || -> Option<SystemTime> {
+            let seconds = meta.next()?.parse::<u64>().ok()?;
+            UNIX_EPOCH.checked_add(Duration::from_secs(seconds))
+        };
+
+        let (request_time, response_time) = (next_time()?, next_time()?);
+        let response = parse_http_response(&mut reader).ok()?;
+
+        Some(Entry {
+            response,
+            request_time,
+            response_time,
+        })
+    }
+
+    /// Save an entry, as the only one for its URL
+    fn store(&self, url: &str, request_headers: &HeaderMap, entry: &Entry) -> io::Result<()> {
+        let vary = entry
+            .response
+            .headers()
+            .get_all(header::VARY)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(|name| name.trim().to_ascii_lowercase())
+            .filter(|name| !name.is_empty())
+            .collect::<Vec<_>>()
+            .join(",");
+
+        let unix = |time: SystemTime| {
+            time.duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default()
+        };
+
+        let mut message = format!(
+            "{} {} {}\r\n",
+            ENTRY_MAGIC,
+            unix(entry.request_time),
+            unix(entry.response_time)
+        )
+        .into_bytes();
+
+        write_message(&entry.response, &mut message)?;
+        fs::write(self.entry_path(url, &vary, request_headers), message)?;
+        fs::write(self.vary_path(url), vary)
+    }
+
+    /// Where we keep the list of headers the response for a URL varies on
+    fn vary_path(&self, url: &str) -> PathBuf {
+        self.dir.join(format!("{}.vary", hash(url)))
+    }
+
+    /// Where the entry for a URL + the request headers it varies on is kept
+    fn entry_path(&self, url: &str, vary: &str, request_headers: &HeaderMap) -> PathBuf {
+        let mut key = url.to_string();
+
+        for name in vary.split(',').filter(|name| !name.is_empty()) {
+            let values = request_headers
+                .get_all(name)
+                .iter()
+                .filter_map(|value| value.to_str().ok())
+                .collect::<Vec<_>>()
+                .join(",");
+            key.push_str(&format!("\n{}: {}", name, values));
+        }
+
+        self.dir.join(format!("{}.http", hash(&key)))
+    }
+}
+
+/// Write a response as a plain HTTP message, which [`parse_http_response`] can read back
+///
+/// The body is written as it was received (decoded, if it was chunked), so it gets a fresh
+/// Content-Length and no Transfer-Encoding
+pub(crate) fn write_message(response: &Response<Vec<u8>>, out: &mut dyn Write) -> io::Result<()> {
+    let status = response.status();
+    write!(
+        out,
+        "HTTP/1.1 {} {}\r\n",
+        status.as_u16(),
+        status.canonical_reason().unwrap_or_default()
+    )?;
+
+    for (name, value) in response.headers() {
+        if name == header::CONTENT_LENGTH || name == header::TRANSFER_ENCODING {
+            continue;
+        }
+
+        out.write_all(name.as_str().as_bytes())?;
+        out.write_all(b": ")?;
+        out.write_all(value.as_bytes())?;
+        out.write_all(b"\r\n")?;
+    }
+
+    write!(out, "content-length: {}\r\n\r\n", body_len(response))?;
+    copy_body(response, out)
+}
+
+impl Entry {
+    /// How long the response is fresh for after it was generated (RFC 9111 4.2.1)
+    fn freshness_lifetime(&self) -> Duration {
+        let headers = self.response.headers();
+
+        // We're a private cache, so s-maxage doesn't apply to us
+        if let Some(max_age) = directives(headers)
+            .iter()
+            .find(|(name, _)| name == "max-age")
+            .and_then(|(_, value)| value.as_deref()?.parse::<u64>().ok())
+        {
+            return Duration::from_secs(max_age);
+        }
+
+        let date = header_date(headers, header::DATE).unwrap_or(self.response_time);
+
+        if headers.contains_key(header::EXPIRES) {
+            // An invalid Expires (like "0") means already expired
+            return header_date(headers, header::EXPIRES)
+                .and_then(|expires| expires.duration_since(date).ok())
+                .unwrap_or_default();
+        }
+
+        // No explicit freshness, so guess based on how long ago it was last changed
+        if HEURISTICALLY_CACHEABLE.contains(&self.response.status().as_u16()) {
+            if let Some(last_modified) = header_date(headers, header::LAST_MODIFIED) {
+                let unchanged_for = date.duration_since(last_modified).unwrap_or_default();
+                return (unchanged_for / 10).min(MAX_HEURISTIC_FRESHNESS);
+            }
+        }
+
+        Duration::ZERO
+    }
+
+    /// How old the response is now (RFC 9111 4.2.3)
+    fn current_age(&self) -> Duration {
+        let headers = self.response.headers();
+        let date = header_date(headers, header::DATE).unwrap_or(self.response_time);
+
+        let age_value = headers
+            .get(header::AGE)
+            .and_then(|age| age.to_str().ok()?.trim().parse::<u64>().ok())
+            .map(Duration::from_secs)
+            .unwrap_or_default();
+
+        let apparent_age = self.response_time.duration_since(date).unwrap_or_default();
+        let response_delay = self
+            .response_time
+            .duration_since(self.request_time)
+            .unwrap_or_default();
+        // Saturated, since the Age is up to the server. That's as stale as it gets
+        let corrected_age_value = age_value.saturating_add(response_delay);
+        let corrected_initial_age = apparent_age.max(corrected_age_value);
+        let resident_time = self.response_time.elapsed().unwrap_or_default();
+
+        corrected_initial_age.saturating_add(resident_time)
+    }
+
+    /// The response to hand back to the user, with an Age header like caches are supposed to add
+    fn to_response(&self, age: Duration) -> Response<Vec<u8>> {
+        let mut response = Response::new(self.response.body().clone());
+        *response.status_mut() = self.response.status();
+        *response.headers_mut() = self.response.headers().clone();
+        response
+            .headers_mut()
+            .insert(header::AGE, HeaderValue::from(age.as_secs()));
+        response
+    }
+}
+
+/// Check if we're allowed to store a response (RFC 9111 3)
+fn is_storable(request_headers: &HeaderMap, entry: &Entry) -> bool {
+    let response_directives = directives(entry.response.headers());
+    let has = |directive: &str| {
+        response_directives
+            .iter()
+            .any(|(name, _)| name == directive)
+    };
+
+    if has("no-store") || entry.response.status().is_informational() {
+        return false;
+    }
+
+    // Too big to keep in memory, so too big to be worth caching
+    if entry.response.extensions().get::<Spooled>().is_some() {
+        return false;
+    }
+
+    // Can't store a response that varies on everything
+    if entry
+        .response
+        .headers()
+        .get_all(header::VARY)
+        .iter()
+        .any(|value| value.as_bytes().contains(&b'*'))
+    {
+        return false;
+    }
+
+    // Authenticated responses are only storable if the server explicitly says so
+    if request_headers.contains_key(header::AUTHORIZATION)
+        && !(has("public") || has("must-revalidate") || has("s-maxage"))
+    {
+        return false;
+    }
+
+    // Only keep things we could ever serve or revalidate, otherwise it's just wasting disk
+    entry.freshness_lifetime() > Duration::ZERO
+        || has("no-cache")
+        || entry.response.headers().contains_key(header::ETAG)
+        || entry.response.headers().contains_key(header::LAST_MODIFIED)
+}
+
+/// Parse all Cache-Control directives into lowercase `(name, value)`
+fn directives(headers: &HeaderMap) -> Vec<(String, Option<String>)> {
+    headers
+        .get_all(header::CACHE_CONTROL)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter(|directive| !directive.trim().is_empty())
+        .map(|directive| match directive.split_once('=') {
+            Some((name, value)) => (
+                name.trim().to_ascii_lowercase(),
+                Some(value.trim().trim_matches('"').to_string()),
+            ),
+            None => (directive.trim().to_ascii_lowercase(), None),
+        })
+        .collect()
+}
+
+fn header_date(headers: &HeaderMap, name: header::HeaderName) -> Option<SystemTime> {
+    httpdate::parse_http_date(headers.get(name)?.to_str().ok()?).ok()
+}
+
+fn hash(key: &str) -> String {
+    to_hex(&Sha256::digest(key.as_bytes()))
+}
@@ -0,0 +1,196 @@
+//! `--record DIR` & `--replay DIR`: VCR-style cassettes, so anything driving httpc (like a test
+//! suite) can run again later without the network, and get the exact same responses
+//!
+//! Each recording is a plain text file: a line with the request body's length, the request as it
+//! was sent, then the response as a plain HTTP message (like the cache keeps them). Requests are
+//! matched on their method, URL and body, headers don't count since they tend to have dates,
+//! tokens and such in them. Recording the same request again replaces it
+//!
+//! Credentials in the request (Authorization, Cookie, ...) are blanked out, since they're not
+//! needed to replay it. The files are still only readable by us, responses can set cookies too
+
+use std::{
+    fs::{self, File},
+    io::{self, BufRead, BufReader, BufWriter, Read, Write},
+    path::PathBuf,
+};
+
+use http::{Method, Response, Uri};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    cache::write_message,
+    helpers::{expand_home, to_hex},
+    http_request::{parse_http_response, RequestError, SentRequest},
+    middleware::{Exchange, Middleware},
+};
+
+const RECORDING_MAGIC: &str = "httpc-recording-1";
+
+/// Request headers that aren't written to recordings
+const REDACTED_HEADERS: [&str; 4] = [
+    "authorization",
+    "proxy-authorization",
+    "cookie",
+    "x-amz-security-token",
+];
+
+pub struct Cassette {
+    dir: PathBuf,
+}
+
+impl Cassette {
+    /// Open a cassette directory, creating it if needed. A leading `~` is expanded to $HOME
+    pub fn open(dir: &str) -> io::Result<Self> {
+        let dir = expand_home(dir);
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    /// Save the response to a request, replacing any earlier recording of it
+    /// ```
+    /// # use http::{Method, Response};
+    /// # use httpc::{cassette::Cassette, http_request::SentRequest};
+    /// let dir = std::env::temp_dir().join(format!("httpc-cassette-{}", std::process::id()));
+    /// let cassette = Cassette::open(dir.to_str().unwrap()).unwrap();
+    /// let uri = "http://example.com/".parse().unwrap();
+    ///
+    /// let mut response = Response::new(b"hi".to_vec());
+    /// response.extensions_mut().insert(SentRequest {
+    ///     method: "GET".to_string(),
+    ///     target: "/".to_string(),
+    ///     headers: Default::default(),
+    ///     head: "GET / HTTP/1.1\r\nHost: example.com\r\nAuthorization: Bearer secret\r\n\r\n".to_string(),
+    /// });
+    /// cassette.record(&Method::GET, &uri, None, &response).unwrap();
+    ///
+    /// let recording = std::fs::read_dir(&dir).unwrap().next().unwrap().unwrap().path();
+    /// let recorded = std::fs::read_to_string(&recording).unwrap();
+    /// assert!(recorded.contains("Host: example.com\r\nAuthorization: [redacted]\r\n"));
+    /// assert!(!recorded.contains("secret"));
+    /// assert_eq!(cassette.replay(&Method::GET, &uri, None).unwrap().body(), b"hi");
+    ///
+    /// #[cfg(unix)]
+    /// {
+    ///     use std::os::unix::fs::PermissionsExt;
+    ///     let mode = std::fs::metadata(&recording).unwrap().permissions().mode();
+    ///     assert_eq!(mode & 0o777, 0o600);
+    /// }
+    /// # std::fs::remove_dir_all(&dir).unwrap();
+    /// ```
+    pub fn record(
+        &self,
+        method: &Method,
+        uri: &Uri,
+        body: Option<&[u8]>,
+        response: &Response<Vec<u8>>,
+    ) -> Result<(), RequestError> {
+        let body = body.unwrap_or_default();
+
+        let mut file = fs::OpenOptions::new();
+        file.write(true).create(true).truncate(true);
+
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut file, 0o600);
+
+        let mut file = BufWriter::new(file.open(self.path(method, uri, body))?);
+
+        writeln!(file, "{} {}", RECORDING_MAGIC, body.len())?;
+
+        // Responses from the cache weren't sent as anything, but the request line is enough
+        match response.extensions().get::<SentRequest>() {
+            Some(sent) => file.write_all(redact(&sent.head).as_bytes())?,
+            None => write!(file, "{} {} HTTP/1.1\r\n\r\n", method, uri)?,
+        }
+
+        file.write_all(body)?;
+        write_message(response, &mut file)?;
+        file.flush()?;
+
+        Ok(())
+    }
+
+    /// The recorded response to a request, erroring if there isn't one
+    pub fn replay(
+        &self,
+        method: &Method,
+        uri: &Uri,
+        body: Option<&[u8]>,
+    ) -> Result<Response<Vec<u8>>, RequestError> {
+        let path = self.path(method, uri, body.unwrap_or_default());
+
+        let file = match File::open(&path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                return Err(format!(
+                    "No recording of {} {} in {}, record it with --record",
+                    method,
+                    uri,
+                    self.dir.display()
+                )
+                .into())
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        let mut reader = BufReader::new(file);
+        let invalid = || format!("{} isn't a recording", path.display());
+
+        let mut magic = String::new();
+        reader.read_line(&mut magic)?;
+        let body_length = magic
+            .trim_end()
+            .strip_prefix(RECORDING_MAGIC)
+            .and_then(|length| length.trim().parse::<u64>().ok())
+            .ok_or_else(invalid)?;
+
+        // Skip over the request, the response comes after its body
+        let mut line = String::new();
+        while line != "\r\n" {
+            line.clear();
+
+            if reader.read_line(&mut line)? == 0 {
+                return Err(invalid().into());
+            }
+        }
+
+        io::copy(&mut (&mut reader).take(body_length), &mut io::sink())?;
+        parse_http_response(&mut reader)
+    }
+
+    /// Where the recording of a request is kept
+    fn path(&self, method: &Method, uri: &Uri, body: &[u8]) -> PathBuf {
+        let mut hasher = Sha256::new();
+        hasher.update(format!("{} {}\n", method, uri));
+        hasher.update(body);
+
+        self.dir
+            .join(format!("{}.http", to_hex(&hasher.finalize())))
+    }
+}
+
+/// The request's head with the values of [`REDACTED_HEADERS`] blanked out
+fn redact(head: &str) -> String {
+    head.split_inclusive("\r\n")
+        .map(|line| match line.split_once(':') {
+            Some((name, _))
+                if REDACTED_HEADERS.contains(&name.trim().to_ascii_lowercase().as_str()) =>
+            {
+                format!("{}: [redacted]\r\n", name)
+            }
+            _ => line.to_string(),
+        })
+        .collect()
+}
+
+/// `--record`: save every response, redirects included
+impl Middleware for Cassette {
+    fn after_receive(
+        &self,
+        exchange: &Exchange,
+        response: &mut Response<Vec<u8>>,
+    ) -> Result<(), RequestError> {
+        let request = exchange.request;
+        self.record(request.method(), request.uri(), *request.body(), response)
+    }
+}
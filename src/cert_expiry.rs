@@ -0,0 +1,142 @@
+//! When server certificates expire: a warning after any TLS handshake with a certificate that
+//! expires within `--cert-expiry-check` (14 days by default), and `httpc cert-expiry` to check
+//! URLs on their own, for monitoring
+//!
+//! Only the certificate's notAfter date is read, from its DER, so this doesn't depend on what the
+//! platform's TLS library can tell us about certificates
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::{
+    helpers::{civil_from_days, days_from_civil},
+    warnings::{warn, WarningKind},
+};
+
+/// When a certificate (as DER) expires, `None` if it can't be parsed
+/// ```
+/// # use std::time::{Duration, UNIX_EPOCH};
+/// # use httpc::cert_expiry::not_after;
+/// let mut certificate = b"\x30\x2e\x30\x2c\xa0\x03\x02\x01\x02\x02\x01\x01\x30\x00\x30\x00".to_vec();
+/// certificate.extend_from_slice(b"\x30\x1e\x17\x0d240101000000Z\x17\x0d341231235959Z");
+/// assert_eq!(not_after(&certificate), Some(UNIX_EPOCH + Duration::from_secs(2051222399)));
+/// assert_eq!(not_after(b"\x30\x00"), None);
+/// ```
+pub fn not_after(der: &[u8]) -> Option<SystemTime> {
+    let (_, certificate, _) = element(der)?;
+    let (_, mut tbs, _) = element(certificate)?;
+
+    // The version is optional, and tagged [0] when it's there
+    let (tag, _, rest) = element(tbs)?;
+    if tag == 0xa0 {
+        tbs = rest;
+    }
+
+    // Then the serial number, signature algorithm and issuer come before the validity
+    for _ in 0..3 {
+        tbs = element(tbs)?.2;
+    }
+
+    let (_, validity, _) = element(tbs)?;
+    let (_, _, validity) = element(validity)?;
+    let (tag, time, _) = element(validity)?;
+
+    parse_time(tag, std::str::from_utf8(time).ok()?)
+}
+
+/// The tag, contents, and what's after the first DER element in `der`
+fn element(der: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let tag = *der.first()?;
+    let first = *der.get(1)?;
+
+    let (length, header) = if first < 0x80 {
+        (usize::from(first), 2)
+    } else {
+        let count = usize::from(first & 0x7f);
+
+        if count == 0 || count > 4 {
+            return None;
+        }
+
+        let bytes = der.get(2..2 + count)?;
+        let length = bytes
+            .iter()
+            .fold(0, |length, &byte| length << 8 | usize::from(byte));
+        (length, 2 + count)
+    };
+
+    let contents = der.get(header..header + length)?;
+    Some((tag, contents, &der[header + length..]))
+}
+
+/// A UTCTime (`YYMMDDHHMMSSZ`) or GeneralizedTime (`YYYYMMDDHHMMSSZ`)
+fn parse_time(tag: u8, time: &str) -> Option<SystemTime> {
+    let (year, rest) = match tag {
+        // Two digit years are 1950 to 2049
+        0x17 => {
+            let year = time.get(..2)?.parse::<i64>().ok()?;
+            (if year >= 50 { 1900 } else { 2000 } + year, &time[2..])
+        }
+        0x18 => (time.get(..4)?.parse().ok()?, &time[4..]),
+        _ => return None,
+    };
+
+    let field = |at: usize| rest.get(at..at + 2)?.parse::<i64>().ok();
+    let days = days_from_civil(year, field(0)?, field(2)?);
+    let seconds = days * 86400 + field(4)? * 3600 + field(6)? * 60 + field(8)?;
+
+    Some(UNIX_EPOCH + Duration::from_secs(u64::try_from(seconds).ok()?))
+}
+
+/// How long until a time, negative if it's already passed, in whole seconds
+pub fn seconds_until(time: SystemTime) -> i64 {
+    match time.duration_since(SystemTime::now()) {
+        Ok(left) => left.as_secs() as i64,
+        Err(err) => -(err.duration().as_secs() as i64),
+    }
+}
+
+/// When a certificate expires, e.g. `2026-12-01 12:00:00 UTC (in 46 days)`
+pub fn describe_expiry(expires: SystemTime) -> String {
+    let seconds = expires
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs() as i64);
+    let (year, month, day) = civil_from_days(seconds.div_euclid(86400));
+    let time_of_day = seconds.rem_euclid(86400);
+
+    let left = seconds_until(expires);
+    let relative = match left / 86400 {
+        _ if left < 0 => format!("{} days ago", -left / 86400),
+        0 => format!("in {} hours", left / 3600),
+        1 => "in 1 day".to_string(),
+        days => format!("in {} days", days),
+    };
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC ({})",
+        year,
+        month,
+        day,
+        time_of_day / 3600,
+        time_of_day % 3600 / 60,
+        time_of_day % 60,
+        relative
+    )
+}
+
+/// Warn if the certificate `host` presented expires within `within`
+pub fn check_expiry(host: &str, der: &[u8], within: Duration) {
+    let Some(expires) = not_after(der) else {
+        return;
+    };
+
+    if seconds_until(expires) < within.as_secs() as i64 {
+        warn(
+            WarningKind::CertExpiring,
+            format!(
+                "The certificate for {} expires {}",
+                host,
+                describe_expiry(expires)
+            ),
+        );
+    }
+}
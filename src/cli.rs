@@ -1,4 +1,51 @@
-use clap::{ArgEnum, ArgGroup, Parser, Subcommand, ValueHint};
+use std::{
+    collections::HashMap,
+    ffi::OsString,
+    net::IpAddr,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use clap::{ArgEnum, ArgGroup, CommandFactory, Parser, Subcommand, ValueHint};
+use http::{header, Uri};
+
+use crate::{
+    assertions::{parse_assert_header, Assertions},
+    conditional::TimeCondition,
+    config::Profile,
+    content_digest::{Checksum, ChecksumAlgorithm, DigestAlgorithm},
+    events::EventHook,
+    export_code::CodeLanguage,
+    grep::Grep,
+    har::HarRecorder,
+    helpers::{
+        has_header, parse_byte_size, parse_duration, parse_host_header, parse_proxy,
+        parse_request_target, parse_url, split_words,
+    },
+    hmac_sign::HmacSigning,
+    http_request::{IpFamily, RequestOptions, StreamHandler, TargetForm},
+    keylog,
+    middleware::SharedOutput,
+    ntlm::ConnectionAuth,
+    pace::{parse_rps, Pacer},
+    pretty::Pretty,
+    range::parse_range,
+    redirect::RedirectPolicy,
+    render::Renderers,
+    request_log::{LogFormat, RequestLog},
+    response_renderer::{
+        JsonRenderer, PlainRenderer, RawRenderer, ResponseRenderer, TerminalRenderer,
+    },
+    resume::ContinueAt,
+    session::Session,
+    sigv4::{parse_signing_scope, SigningScope},
+    template::parse_var,
+    timeout::Timeouts,
+    trace::Trace,
+    user_agent::UserAgent,
+    watch::Watch,
+};
 
 #[derive(ArgEnum, Clone, Copy, Debug)]
 pub enum Color {
@@ -18,12 +65,29 @@ impl Color {
     }
 }
 
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Readable (and colored) output
+    Text,
+    /// The same as text, without colors
+    Plain,
+    /// One JSON object per response with the status, headers, timings, redirects and body
+    Json,
+    /// The body exactly as it was received, after the status line and headers with -i
+    Raw,
+}
+
 // httpc help [get|post]
 // httpc get [-v] (-h "k:v")* URL
 // httpc post [-v] (-h "k:v")* [-d inline-data] [-f file] URL
 
 #[derive(Debug, Parser)]
-#[clap(version, about)]
+#[clap(
+    version,
+    about,
+    after_help = "Separate requests with --next to send them one after another with their own options, \
+                  reusing connections: httpc get -m 5s URL --next post -d '{}' URL"
+)]
 pub struct Cli {
     /// Get help for this command.
     #[clap(long)]
@@ -33,10 +97,141 @@ pub struct Cli {
     #[clap(long, arg_enum, global = true, default_value = "auto")]
     pub color: Color,
 
+    /// Exit with 1 if there were any warnings, even when everything else worked
+    #[clap(long, global = true)]
+    pub warnings_as_errors: bool,
+
     #[clap(subcommand)]
     pub command: Commands,
 }
 
+/// Expand an alias from the config (or aliases, if it starts with another one) in place of the
+/// subcommand, or put `get` in front of a URL given without one
+/// ```
+/// # use std::{collections::HashMap, ffi::OsString};
+/// # use httpc::cli::expand_args;
+/// let aliases = HashMap::from([("jget".to_string(), "get -h 'Accept: application/json'".to_string())]);
+/// let args = |args: &[&str]| args.iter().map(OsString::from).collect::<Vec<_>>();
+///
+/// assert_eq!(
+///     expand_args(args(&["httpc", "--color", "never", "jget", "example.com"]), &aliases).unwrap(),
+///     args(&["httpc", "--color", "never", "get", "-h", "Accept: application/json", "example.com"])
+/// );
+/// assert_eq!(
+///     expand_args(args(&["httpc", "https://example.com", "-v"]), &aliases).unwrap(),
+///     args(&["httpc", "get", "https://example.com", "-v"])
+/// );
+/// ```
+pub fn expand_args(
+    mut args: Vec<OsString>,
+    aliases: &HashMap<String, String>,
+) -> Result<Vec<OsString>, String> {
+    let command = Cli::command();
+    let mut expanded = vec![];
+
+    loop {
+        let Some(index) = command_index(&command, &args) else {
+            return Ok(args);
+        };
+        let Some(name) = args[index].to_str().map(str::to_string) else {
+            return Ok(args);
+        };
+
+        // Built-in subcommands can't be replaced
+        if name == "help" || command.find_subcommand(&name).is_some() {
+            return Ok(args);
+        }
+
+        match aliases.get(&name) {
+            Some(_) if expanded.contains(&name) => {
+                return Err(format!("Alias '{}' expands to itself", name));
+            }
+            Some(alias) => {
+                let words = split_words(alias)
+                    .map_err(|err| format!("Invalid alias '{}': {}", name, err))?;
+                args.splice(index..=index, words.into_iter().map(OsString::from));
+                expanded.push(name);
+            }
+            None if looks_like_url(&name) => {
+                args.insert(index, "get".into());
+                return Ok(args);
+            }
+            None => return Ok(args),
+        }
+    }
+}
+
+/// Split the arguments into one command line per request at each `--next`, every one starting
+/// with the program name so it can be parsed on its own
+/// ```
+/// # use std::ffi::OsString;
+/// # use httpc::cli::split_next;
+/// let args = |args: &[&str]| args.iter().map(OsString::from).collect::<Vec<_>>();
+///
+/// assert_eq!(
+///     split_next(args(&["httpc", "get", "-m", "1s", "a.com", "--next", "post", "b.com"])),
+///     vec![args(&["httpc", "get", "-m", "1s", "a.com"]), args(&["httpc", "post", "b.com"])]
+/// );
+/// assert_eq!(split_next(args(&["httpc", "a.com"])), vec![args(&["httpc", "a.com"])]);
+/// ```
+pub fn split_next(args: Vec<OsString>) -> Vec<Vec<OsString>> {
+    let mut args = args.into_iter();
+    let program = args.next().unwrap_or_else(|| "httpc".into());
+    let mut commands = vec![vec![program.clone()]];
+
+    for arg in args {
+        if arg == "--next" {
+            commands.push(vec![program.clone()]);
+        } else if let Some(command) = commands.last_mut() {
+            command.push(arg);
+        }
+    }
+
+    commands
+}
+
+/// Where the subcommand is, after any global options
+fn command_index(command: &clap::Command, args: &[OsString]) -> Option<usize> {
+    let mut index = 1;
+
+    while let Some(arg) = args.get(index)?.to_str() {
+        let Some((long, value)) = arg
+            .strip_prefix("--")
+            .map(|long| match long.split_once('=') {
+                Some((long, _)) => (long, true),
+                None => (long, false),
+            })
+        else {
+            return Some(index);
+        };
+
+        let Some(global) = command
+            .get_arguments()
+            .find(|global| global.is_global_set() && global.get_long() == Some(long))
+        else {
+            return Some(index);
+        };
+
+        index += if global.is_takes_value_set() && !value {
+            2
+        } else {
+            1
+        };
+    }
+
+    Some(index)
+}
+
+/// If the first argument is a URL instead of a subcommand
+fn looks_like_url(arg: &str) -> bool {
+    !arg.starts_with('-')
+        && (arg.contains("://")
+            || arg.contains('.')
+            || arg.contains(':')
+            || arg.starts_with('/')
+            || arg.starts_with("localhost"))
+}
+
 #[derive(Debug, Subcommand)]
 #[clap(group(ArgGroup::new("body")))]
 pub enum Commands {
@@ -44,6 +239,11 @@ pub enum Commands {
     Get {
         #[clap(flatten)]
         options: CommonOptions,
+
+        /// Only check if the URL(s) exist with a HEAD request, printing nothing.
+        /// Exits with 0 if every response is 2xx/3xx, 1 otherwise.
+        #[clap(long)]
+        exists: bool,
     },
 
     /// Executes an HTTP POST request and prints the response.
@@ -52,36 +252,1049 @@ pub enum Commands {
         options: CommonOptions,
 
         /// Associates an inline data to the body HTTP POST request.
-        #[clap(short, group = "body", value_parser)]
+        #[clap(short, long, group = "body", value_parser)]
         data: Option<String>,
 
         /// Associates the content of a file to the body HTTP POST request.
-        #[clap(short, group = "body", value_parser, value_hint = ValueHint::FilePath)]
-        file: Option<String>,
+        #[clap(short, long, group = "body", value_parser, value_hint = ValueHint::FilePath)]
+        file: Option<PathBuf>,
+
+        /// Sends a multipart/mixed batch request with these sub-requests, e.g. 'GET /users/1'.
+        /// Headers and a body can follow on the next lines, the body after an empty line.
+        #[clap(long, group = "body", value_name = "REQUEST")]
+        batch: Vec<String>,
+    },
+
+    /// Runs every request in a .http file in order, e.g. a login and then requests using its token.
+    Run(RunOptions),
+
+    /// Saves requests under a short name, then runs them with --var overrides.
+    Snippet {
+        #[clap(subcommand)]
+        command: SnippetCommand,
+    },
+
+    /// Replays requests exported from browser devtools as a .har file.
+    Har {
+        #[clap(subcommand)]
+        command: HarCommand,
+    },
+
+    /// Inspects the cookies saved in a session, or set by a response.
+    Cookies {
+        #[clap(subcommand)]
+        command: CookiesCommand,
+    },
+
+    /// Generates presigned URLs that can be used without any other credentials.
+    Presign {
+        #[clap(subcommand)]
+        target: PresignTarget,
     },
+
+    /// Opens a WebSocket (ws:// or wss://), sending each line of stdin as a message and printing the ones received.
+    /// Closes the connection when stdin ends.
+    Ws {
+        #[clap(flatten)]
+        options: CommonOptions,
+
+        /// Ask for these subprotocols, in order of preference, e.g. graphql-ws
+        #[clap(long, value_name = "PROTOCOL", value_delimiter = ',')]
+        protocol: Vec<String>,
+    },
+
+    /// Sends the same request over and over, some at once, then reports latency percentiles, throughput and errors.
+    Bench {
+        #[clap(flatten)]
+        options: CommonOptions,
+
+        /// How many requests to send in total
+        #[clap(short = 'n', long, value_name = "N", default_value_t = 100, value_parser = clap::value_parser!(u32).range(1..))]
+        requests: u32,
+
+        /// How many requests to have going at once
+        #[clap(short = 'c', long, value_name = "N", default_value_t = 1, value_parser = clap::value_parser!(u16).range(1..))]
+        concurrency: u16,
+
+        /// POST this body instead of sending a GET
+        #[clap(short, long, value_parser)]
+        data: Option<String>,
+    },
+
+    /// Sends the same request to two URLs, e.g. staging and production, then shows how the status, headers and bodies differ.
+    /// Headers that change every time, like Date, are ignored. Exits with 1 if the responses differ.
+    Diff {
+        #[clap(flatten)]
+        options: CommonOptions,
+
+        /// POST this body instead of sending a GET
+        #[clap(short, long, value_parser)]
+        data: Option<String>,
+
+        /// Also ignore these headers, e.g. Server
+        #[clap(long, value_name = "NAME", value_delimiter = ',')]
+        ignore_header: Vec<String>,
+    },
+
+    /// Opens a CONNECT tunnel through a proxy to any host:port, then connects it to stdin/stdout (like nc through the proxy).
+    Tunnel(TunnelOptions),
+
+    /// Serves the files in a directory over HTTP, and echoes any other request back as JSON, to test against.
+    Serve(ServeOptions),
+
+    /// Checks when the TLS certificates of https URLs expire, exiting with 1 if any expire within --within (or can't be checked).
+    CertExpiry(CertExpiryOptions),
+
+    /// Lists what this build of httpc supports, and what it was built without.
+    Features,
+}
+
+#[derive(Debug, Parser)]
+pub struct TunnelOptions {
+    /// Where the proxy should connect to, e.g. example.com:22
+    #[clap(value_name = "HOST:PORT")]
+    pub target: String,
+
+    /// The HTTP proxy to tunnel through
+    #[clap(long, value_name = "URL", value_parser = parse_proxy)]
+    pub proxy: Uri,
+
+    /// Basic auth for the proxy, sent in the CONNECT's Proxy-Authorization
+    #[clap(long, value_name = "USER:PASSWORD")]
+    pub proxy_user: Option<String>,
+
+    /// Say when the tunnel is open and closed (on stderr, stdout is only what the other end sends)
+    #[clap(short, long = "verbose", action = clap::ArgAction::Count)]
+    pub verbosity: u8,
+
+    /// Max time to connect to the proxy
+    #[clap(long, value_name = "DURATION", value_parser = parse_duration)]
+    pub connect_timeout: Option<Duration>,
+}
+
+#[derive(Debug, Parser)]
+pub struct ServeOptions {
+    /// Directory with the files to serve
+    #[clap(default_value = ".", value_hint = ValueHint::DirPath)]
+    pub dir: String,
+
+    /// Port to listen on, 0 picks any free one
+    #[clap(short, long, default_value_t = 8080)]
+    pub port: u16,
+
+    /// Address to listen on, 0.0.0.0 lets other machines connect too
+    #[clap(long, value_name = "ADDRESS", default_value = "127.0.0.1")]
+    pub bind: IpAddr,
 }
 
 #[derive(Debug, Parser)]
+pub struct CertExpiryOptions {
+    /// https URL(s) of the servers to check
+    #[clap(required = true, value_hint = ValueHint::Url, value_parser = parse_url)]
+    pub url: Vec<String>,
+
+    /// Fail if a certificate expires within this long, e.g. 30d, 12h
+    #[clap(long, value_name = "DURATION", default_value = "30d", value_parser = parse_duration)]
+    pub within: Duration,
+
+    /// Don't verify the server's TLS certificate, only check when it expires
+    #[clap(short = 'k', long)]
+    pub insecure: bool,
+
+    /// Trust the CA certificates in this PEM file, as well as the system ones
+    #[clap(long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+    pub cacert: Option<String>,
+
+    /// Connect through this HTTP proxy, tunneled with CONNECT
+    #[clap(long, value_name = "URL", value_parser = parse_proxy)]
+    pub proxy: Option<Uri>,
+
+    /// Basic auth for the proxy, sent in the CONNECT's Proxy-Authorization
+    #[clap(long, value_name = "USER:PASSWORD", requires = "proxy")]
+    pub proxy_user: Option<String>,
+}
+
+#[derive(Debug, Parser)]
+pub struct RunOptions {
+    /// File with the requests, separated by '###' lines.
+    #[clap(value_parser, value_hint = ValueHint::FilePath)]
+    pub file: PathBuf,
+
+    /// Verbosity of the output, same as get/post.
+    #[clap(short, long = "verbose", action = clap::ArgAction::Count)]
+    pub verbosity: u8,
+
+    /// Sets a variable for {{NAME}} placeholders, overriding the file's '@NAME = value'.
+    #[clap(long, value_name = "KEY=VALUE", value_parser = parse_var)]
+    pub var: Vec<(String, String)>,
+
+    /// Follow 'Location' header redirects by repeating requests
+    #[clap(short, long, visible_alias = "follow")]
+    pub location: bool,
+
+    /// Keep running the rest of the requests after one fails (an error or a 4xx/5xx status).
+    #[clap(long)]
+    pub keep_going: bool,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum SnippetCommand {
+    /// Saves a request as a snippet, replacing any with the same name. It can use {{NAME}} placeholders anywhere.
+    Save {
+        /// What to call it, letters, numbers, - and _ only
+        name: String,
+
+        /// URL to send the request to
+        #[clap(value_hint = ValueHint::Url)]
+        url: String,
+
+        /// HTTP method of the request
+        #[clap(short = 'X', long, default_value = "GET")]
+        method: String,
+
+        /// Associates headers to the request with the format 'key:value'.
+        #[clap(short, long, value_name = "key:value")]
+        header: Vec<String>,
+
+        /// Body of the request.
+        #[clap(short, long, value_parser)]
+        data: Option<String>,
+
+        /// Default value of a {{KEY}} placeholder, used when running it without a --var for it
+        #[clap(long, value_name = "KEY=VALUE", value_parser = parse_var)]
+        var: Vec<(String, String)>,
+
+        /// Get help for this command.
+        #[clap(long)]
+        help: bool,
+    },
+
+    /// Sends a saved snippet's request and prints the response.
+    Run {
+        name: String,
+
+        /// Verbosity of the output, same as get/post.
+        #[clap(short, long = "verbose", action = clap::ArgAction::Count)]
+        verbosity: u8,
+
+        /// Sets a variable for {{KEY}} placeholders, overriding the snippet's default.
+        #[clap(long, value_name = "KEY=VALUE", value_parser = parse_var)]
+        var: Vec<(String, String)>,
+
+        /// Follow 'Location' header redirects by repeating requests
+        #[clap(short, long, visible_alias = "follow")]
+        location: bool,
+    },
+
+    /// Lists the saved snippets and their requests.
+    List,
+
+    /// Prints a saved snippet's .http file.
+    Show { name: String },
+
+    /// Deletes a saved snippet.
+    Delete { name: String },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum HarCommand {
+    /// Sends the requests in a .har file in order (or only the --index ones) and prints the responses.
+    Import(HarImportOptions),
+}
+
+#[derive(Debug, Parser)]
+pub struct HarImportOptions {
+    /// HAR file, e.g. from "Save all as HAR" in the browser's network tab.
+    #[clap(value_parser, value_hint = ValueHint::FilePath)]
+    pub file: PathBuf,
+
+    /// Only send these entries, numbered from 1 like --list shows them.
+    #[clap(long, value_name = "N", value_delimiter = ',', value_parser = clap::value_parser!(u32).range(1..))]
+    pub index: Vec<u32>,
+
+    /// Lists the entries and their numbers instead of sending them.
+    #[clap(long)]
+    pub list: bool,
+
+    /// Send the requests to another host instead, e.g. localhost:8080 or http://localhost:8080 to change the scheme too.
+    #[clap(long)]
+    pub host: Option<String>,
+
+    /// Verbosity of the output, same as get/post.
+    #[clap(short, long = "verbose", action = clap::ArgAction::Count)]
+    pub verbosity: u8,
+
+    /// Follow 'Location' header redirects by repeating requests
+    #[clap(short, long, visible_alias = "follow")]
+    pub location: bool,
+
+    /// Don't verify the server's TLS certificate
+    #[clap(short = 'k', long)]
+    pub insecure: bool,
+
+    /// Keep sending the rest of the requests after one fails.
+    #[clap(long)]
+    pub keep_going: bool,
+}
+
+// Only ever parsed once, so the size doesn't matter
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Subcommand)]
+pub enum CookiesCommand {
+    /// Lists the cookies saved in a session's jar (a --session name, or a path).
+    List { session: String },
+
+    /// Requests a URL and shows the cookies its response sets, and why any were rejected.
+    Show {
+        #[clap(flatten)]
+        options: CommonOptions,
+
+        /// Get help for this command.
+        #[clap(long)]
+        help: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum PresignTarget {
+    /// Presigns the URL of an S3 (or S3-compatible) object with AWS Signature Version 4.
+    S3(PresignS3Options),
+}
+
+#[derive(ArgEnum, Clone, Copy, Debug)]
+pub enum PresignMethod {
+    Get,
+    Put,
+    Head,
+    Delete,
+}
+
+#[derive(Debug, Parser)]
+pub struct PresignS3Options {
+    /// Bucket the object is in.
+    #[clap(long)]
+    pub bucket: String,
+
+    /// Key of the object.
+    #[clap(long)]
+    pub key: String,
+
+    /// How long until the URL expires, e.g. 15m or 12h (at most a week). Plain numbers are seconds.
+    #[clap(long, value_name = "DURATION", default_value = "1h", value_parser = parse_duration)]
+    pub expires: Duration,
+
+    /// HTTP method the URL can be used with.
+    #[clap(long, arg_enum, default_value = "get")]
+    pub method: PresignMethod,
+
+    /// Region of the bucket, defaults to $AWS_REGION, $AWS_DEFAULT_REGION, or us-east-1.
+    #[clap(long)]
+    pub region: Option<String>,
+
+    /// Endpoint of an S3-compatible store, e.g. https://minio.example.com
+    #[clap(long, value_hint = ValueHint::Url)]
+    pub endpoint: Option<String>,
+
+    /// Put the bucket in the path instead of the host name, which most S3-compatible stores need.
+    #[clap(long)]
+    pub path_style: bool,
+
+    /// Access key ID, defaults to $AWS_ACCESS_KEY_ID.
+    #[clap(long)]
+    pub access_key: Option<String>,
+
+    /// Secret access key, defaults to $AWS_SECRET_ACCESS_KEY.
+    #[clap(long)]
+    pub secret_key: Option<String>,
+
+    /// Send the request with the presigned URL instead of printing it.
+    #[clap(long)]
+    pub send: bool,
+
+    /// With --send, uploads the content of a file (for PUT).
+    #[clap(short, long, requires = "send", value_hint = ValueHint::FilePath)]
+    pub file: Option<PathBuf>,
+
+    /// With --send, outputs to a file instead of stdout.
+    #[clap(short, long, requires = "send", value_name = "FILE", value_hint = ValueHint::FilePath)]
+    pub output: Option<String>,
+
+    /// With --send, verbosity of the output, same as get/post.
+    #[clap(short, long = "verbose", action = clap::ArgAction::Count)]
+    pub verbosity: u8,
+}
+
+#[derive(Clone, Debug, Parser)]
+#[clap(group(ArgGroup::new("output-file")))]
 pub struct CommonOptions {
     /// Verbosity of the output, -v = Prints the detail of the response such as protocol, status, and headers., -vv = and print request message
-    #[clap(short, action = clap::ArgAction::Count)]
+    #[clap(short, long = "verbose", action = clap::ArgAction::Count)]
     pub verbosity: u8,
 
-    /// Output to a file instead of stdout
-    #[clap(short, value_name = "FILE", value_hint = ValueHint::FilePath)]
+    /// Output to a file instead of stdout, '-' writes the raw body to stdout even in a terminal
+    #[clap(short, long, group = "output-file", value_name = "FILE", value_hint = ValueHint::FilePath)]
     pub output: Option<String>,
 
+    /// Save to a file in the current directory named after the last part of the URL's path
+    #[clap(short = 'O', long, group = "output-file")]
+    pub remote_name: bool,
+
+    /// With -O, use the file name from the server's Content-Disposition header if it sends one (never overwriting a file)
+    #[clap(
+        short = 'J',
+        long,
+        requires = "remote-name",
+        conflicts_with = "continue-at"
+    )]
+    pub remote_header_name: bool,
+
+    /// Show the body as a hexdump, for binary responses
+    #[clap(long, conflicts_with = "pretty")]
+    pub hex: bool,
+
+    /// Reindent and/or highlight HTML, XML, CSS and JSON bodies shown in the terminal
+    #[clap(long, arg_enum, value_name = "STYLE", default_value = "none")]
+    pub pretty: Pretty,
+
+    /// How each Content-Type is shown, from the `[renderers]` in the config
+    #[clap(skip)]
+    pub renderers: Renderers,
+
+    /// How the response is shown, json is for other tools to read (see the README)
+    #[clap(
+        long = "format",
+        alias = "output-format",
+        arg_enum,
+        value_name = "FORMAT",
+        default_value = "text"
+    )]
+    pub output_format: OutputFormat,
+
+    /// Write the response's status line and headers to this file ('-' for stdout).
+    /// With multiple URLs, it's a template like -o
+    #[clap(short = 'D', long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+    pub dump_header: Option<String>,
+
+    /// Include the status line and headers before the body, in the output file too
+    #[clap(short, long)]
+    pub include: bool,
+
+    /// Show the output in $PAGER (or less) when it's longer than the terminal
+    #[clap(long)]
+    pub pager: bool,
+
+    /// Only show this much of the body in the terminal, e.g. 10k (-o still saves all of it)
+    #[clap(long, value_name = "SIZE", value_parser = parse_byte_size)]
+    pub max_display_bytes: Option<u64>,
+
+    /// Only show the lines of the body with this text in them (not a regex), failing if there aren't any. -o still saves all of it
+    #[clap(long, value_name = "TEXT")]
+    pub grep: Option<String>,
+
+    /// Ignore case for --grep
+    #[clap(long, requires = "grep")]
+    pub grep_ignore_case: bool,
+
+    /// Only print the value(s) at this JSONPath in a JSON body, e.g. '$.data.id' or '$.items[*].name' (strings without quotes, one value per line). -o still saves all of it
+    #[clap(long, value_name = "PATH", conflicts_with = "grep")]
+    pub extract: Option<String>,
+
+    /// Fail (with exit code 22, like curl) on a 4xx or 5xx status, without printing the body
+    #[clap(long)]
+    pub fail: bool,
+
+    /// Fail unless the status is this one (or any of them, e.g. 200,204), after showing the response
+    #[clap(long, value_name = "STATUS", value_delimiter = ',', value_parser = clap::value_parser!(u16).range(100..600))]
+    pub assert_status: Vec<u16>,
+
+    /// Fail unless the response has this header with a value containing this (ignoring case), or only 'name' for it to be there at all
+    #[clap(long, value_name = "name: value", value_parser = parse_assert_header)]
+    pub assert_header: Vec<(String, String)>,
+
+    /// Fail unless the body contains this text
+    #[clap(long, value_name = "TEXT")]
+    pub assert_body_contains: Vec<String>,
+
+    /// Send the request again every this long (e.g. 5s), printing a line for each response and how the body changed. Runs until Ctrl-C or --until-*
+    #[clap(long, value_name = "DURATION", value_parser = parse_duration, conflicts_with_all = &["mirror", "url-file"])]
+    pub watch: Option<Duration>,
+
+    /// With --watch, stop once the status is this one (or any of them, e.g. 200,204)
+    #[clap(long, value_name = "STATUS", requires = "watch", value_delimiter = ',', value_parser = clap::value_parser!(u16).range(100..600))]
+    pub until_status: Vec<u16>,
+
+    /// With --watch, stop once the body contains this text
+    #[clap(long, value_name = "TEXT", requires = "watch")]
+    pub until_body_contains: Option<String>,
+
+    /// Write bodies bigger than this to a temp file instead of keeping them in memory (0 keeps them all in memory)
+    #[clap(long, value_name = "SIZE", default_value = "64M", value_parser = parse_byte_size)]
+    pub max_memory_body: u64,
+
+    /// Stop (and exit with 63) instead of downloading a body bigger than this, e.g. 100M. Checked against Content-Length first, then as it arrives
+    #[clap(long, value_name = "SIZE", value_parser = parse_byte_size)]
+    pub max_filesize: Option<u64>,
+
+    /// Resume a download into the -o/-O file from this byte offset, '-' to continue from the end of the file
+    #[clap(
+        short = 'C',
+        long,
+        value_name = "OFFSET",
+        requires = "output-file",
+        allow_hyphen_values = true
+    )]
+    pub continue_at: Option<ContinueAt>,
+
+    /// Only request these byte ranges, e.g. 0-1023, -500 (last 500 bytes), or 0-99,200-299
+    #[clap(short, long, value_name = "RANGE", value_parser = parse_range, conflicts_with = "continue-at")]
+    pub range: Option<String>,
+
+    /// Save the ETag of the response to this file
+    #[clap(long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+    pub etag_save: Option<PathBuf>,
+
+    /// Only get the resource if its ETag differs from the one saved in this file (If-None-Match)
+    #[clap(long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+    pub etag_compare: Option<PathBuf>,
+
+    /// Only get the resource if it changed since this date (or file's modification time), a leading '-' inverts it
+    #[clap(short = 'z', long, value_name = "TIME", allow_hyphen_values = true)]
+    pub time_cond: Option<TimeCondition>,
+
     /// Follow 'Location' header redirects by repeating requests
-    #[clap(short)]
+    #[clap(short, long, visible_alias = "follow")]
     pub location: bool,
 
+    /// With -l, also follow HTML <meta http-equiv="refresh"> redirects (with a GET, ignoring the delay)
+    #[clap(long, requires = "location")]
+    pub follow_meta_refresh: bool,
+
+    #[clap(flatten)]
+    pub redirect: RedirectPolicy,
+
+    /// Refuse to connect to loopback, private and link-local addresses (checked after DNS, for every redirect too), and file:// or ftp:// URLs, for fetching untrusted URLs
+    #[clap(long)]
+    pub safe_fetch: bool,
+
+    /// Error if a TLS server closes the connection without a close_notify, since the body might've been cut short
+    #[clap(long)]
+    pub strict_tls: bool,
+
+    /// Append the TLS secrets of each connection to this file, to decrypt a capture of it in Wireshark (defaults to $SSLKEYLOGFILE)
+    #[clap(long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+    pub keylog: Option<String>,
+
+    /// Send a HEAD instead of a GET, only falling back to GET (and ignoring the body) if the server doesn't support HEAD
+    #[clap(long)]
+    pub prefer_head: bool,
+
+    /// Print the fully-resolved URL the response came from (after redirects) once it's done
+    #[clap(long)]
+    pub show_final_url: bool,
+
+    /// Print this once the request is done, with %{url_effective}, %{http_code}, %{http_version},
+    /// %{content_type}, %{size_download}, %{num_redirects}, %{time_connect}, %{time_starttransfer},
+    /// %{time_total} and %{request_headers} (the request exactly as sent, minus the body) replaced
+    #[clap(short, long, value_name = "FORMAT", conflicts_with = "show-final-url")]
+    pub write_out: Option<String>,
+
+    /// Add a parameter to the URL's query string (after any it already has), percent-encoding the key and value
+    #[clap(short, long, value_name = "KEY=VALUE", value_parser = parse_var)]
+    pub query: Vec<(String, String)>,
+
+    /// After the response, print how long each step took (DNS, connecting, TLS, sending, waiting for the first byte, receiving), and what the server says it spent its time on (Server-Timing)
+    #[clap(long)]
+    pub timing: bool,
+
+    /// Fetch whatever the response says to preload (Link: rel=preload, in 103 Early Hints or the response), in parallel, and report how much starting on them at the hint would've saved
+    #[clap(long)]
+    pub prefetch_hints: bool,
+
+    /// Once everything's done, print how many requests & bytes were sent to and received from each host (headers, redirects and retries included), to stderr (or as a last JSON line with --format json)
+    #[clap(long)]
+    pub summary: bool,
+
+    /// Instead of the request, send the CORS preflight (OPTIONS) a browser at this origin would, and show what the server allows
+    #[clap(long, value_name = "ORIGIN")]
+    pub cors: Option<String>,
+
+    /// Print the curl command that sends the same request (headers, body, TLS options and all) instead of sending it
+    #[clap(long, conflicts_with = "cors")]
+    pub print_curl: bool,
+
+    /// Print code that sends the same request (Python requests, JavaScript fetch or Rust reqwest) instead of sending it
+    #[clap(long, arg_enum, value_name = "LANGUAGE", conflicts_with_all = &["cors", "print-curl"])]
+    pub export_code: Option<CodeLanguage>,
+
     /// Associates headers to HTTP Request with the format 'key:value'.
-    #[clap(short, value_name = "key:value")]
+    #[clap(short, long, value_name = "key:value")]
     pub header: Vec<String>,
 
-    /// URL to send the request to.
-    #[clap(required = true, value_hint = ValueHint::Url)]
-    pub url: String,
+    /// Read more headers from a file, one 'key: value' per line ('#' starts a comment). Headers given with -h win
+    #[clap(long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+    pub headers_from: Vec<PathBuf>,
+
+    /// Send a header only to HOST ('*.example.com' for its subdomains), including when a redirect goes there, so secrets don't follow redirects to other hosts
+    #[clap(long, value_name = "HOST=key:value", value_parser = parse_host_header)]
+    pub host_header: Vec<(String, String)>,
+
+    /// The headers before {{VAR}} placeholders were filled in, to fill them in again after logging in
+    #[clap(skip)]
+    pub raw_headers: Vec<String>,
+
+    /// Send this User-Agent: a preset (chrome, firefox, safari, httpc) or any other string
+    #[clap(
+        short = 'A',
+        long,
+        visible_alias = "user-agent",
+        value_name = "PRESET|STRING"
+    )]
+    pub ua: Option<UserAgent>,
+
+    /// Send this Referer. Add ';auto' (or only give ';auto') to send the URL that redirected as the Referer when following redirects
+    #[clap(short = 'e', long, value_name = "URL")]
+    pub referer: Option<String>,
+
+    /// With a --ua browser preset, also send the Accept, Accept-Language and sec-ch-ua headers that browser would
+    #[clap(long, requires = "ua")]
+    pub ua_headers: bool,
+
+    /// Set a variable for {{KEY}} placeholders in the URL, headers and -d body (environment variables work too)
+    #[clap(long, value_name = "KEY=VALUE", value_parser = parse_var)]
+    pub var: Vec<(String, String)>,
+
+    /// Use the defaults from this profile in ~/.config/httpc/config.toml (instead of 'default')
+    #[clap(long, value_name = "NAME")]
+    pub profile: Option<String>,
+
+    /// The profile from --profile (or the default one), if there is one
+    #[clap(skip)]
+    pub loaded_profile: Option<Arc<Profile>>,
+
+    /// Remember cookies, headers and auth in this session, and send them with later requests
+    #[clap(long, value_name = "NAME")]
+    pub session: Option<String>,
+
+    /// The session loaded from --session, shared by every request (and thread)
+    #[clap(skip)]
+    pub loaded_session: Option<Arc<Mutex<Session>>>,
+
+    /// URLs starting with '/' are appended to this, e.g. https://api.example.com/v1
+    #[clap(long, value_name = "URL", value_hint = ValueHint::Url)]
+    pub base_url: Option<String>,
+
+    /// Send Basic authentication with this username and password
+    #[clap(short, long, value_name = "USER:PASSWORD")]
+    pub user: Option<String>,
+
+    /// Sign the request with AWS Signature Version 4 for this region and service, e.g. us-east-1/execute-api.
+    /// The credentials are -u ACCESS_KEY:SECRET_KEY, or $AWS_ACCESS_KEY_ID and $AWS_SECRET_ACCESS_KEY ($AWS_SESSION_TOKEN too)
+    #[clap(
+        long,
+        value_name = "REGION/SERVICE",
+        value_parser = parse_signing_scope,
+        conflicts_with = "oauth2-token-url"
+    )]
+    pub aws_sigv4: Option<SigningScope>,
+
+    #[clap(flatten)]
+    pub sign: HmacSigning,
+
+    /// Get an access token from this OAuth2 token URL with the client credentials grant, and send
+    /// it as a Bearer token. It's cached until it expires
+    #[clap(
+        long,
+        value_name = "URL",
+        value_hint = ValueHint::Url,
+        requires_all = &["client-id", "client-secret"]
+    )]
+    pub oauth2_token_url: Option<String>,
+
+    /// The client ID for --oauth2-token-url
+    #[clap(long, value_name = "ID", requires = "oauth2-token-url")]
+    pub client_id: Option<String>,
+
+    /// The client secret for --oauth2-token-url
+    #[clap(long, value_name = "SECRET", requires = "oauth2-token-url")]
+    pub client_secret: Option<String>,
+
+    /// Authenticate with NTLM, for IIS and other Windows servers. The credentials are -u DOMAIN\\user:password
+    #[clap(long, requires = "user", conflicts_with_all = &["aws-sigv4", "oauth2-token-url"])]
+    pub ntlm: bool,
+
+    /// Like --ntlm, but in a Negotiate (SPNEGO) header. Only NTLM is spoken, not Kerberos
+    #[clap(
+        long,
+        requires = "user",
+        conflicts_with_all = &["ntlm", "aws-sigv4", "oauth2-token-url"]
+    )]
+    pub negotiate: bool,
+
+    /// Only share pooled connections with requests with the same tag, set for NTLM's handshake
+    #[clap(skip)]
+    pub connection_tag: Option<String>,
+
+    /// Send requests through this HTTP proxy, https is tunneled with CONNECT
+    #[clap(long, value_name = "URL", value_parser = parse_proxy)]
+    pub proxy: Option<Uri>,
+
+    /// Basic auth for the proxy, sent in Proxy-Authorization (or put them in the URL, like
+    /// --proxy user:password@localhost:3128)
+    #[clap(long, value_name = "USER:PASSWORD")]
+    pub proxy_user: Option<String>,
+
+    /// Don't verify the server's TLS certificate
+    #[clap(short = 'k', long)]
+    pub insecure: bool,
+
+    /// Only connect over IPv4
+    #[clap(short = '4', long, conflicts_with = "ipv6")]
+    pub ipv4: bool,
+
+    /// Only connect over IPv6
+    #[clap(short = '6', long)]
+    pub ipv6: bool,
+
+    /// Trust the CA certificates in this PEM file, as well as the system ones
+    #[clap(long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+    pub cacert: Option<String>,
+
+    /// Send the request-target (after the method) in this form instead of just the path, for testing proxies and servers. asterisk sends OPTIONS *
+    #[clap(long, arg_enum, value_name = "FORM")]
+    pub target_form: Option<TargetForm>,
+
+    /// Send exactly this as the request-target instead of the URL's path, for testing how a server routes odd paths (like /../admin or //a)
+    #[clap(long, value_name = "TARGET", value_parser = parse_request_target, conflicts_with = "target-form")]
+    pub request_target: Option<String>,
+
+    /// Warn when the server's certificate expires within this long, e.g. 30d (0 to never warn)
+    #[clap(long, value_name = "DURATION", default_value = "14d", value_parser = parse_duration)]
+    pub cert_expiry_check: Duration,
+
+    /// Limit upload and download speed in bytes per second, e.g. 500k, 2M
+    #[clap(long, value_name = "SPEED", value_parser = parse_byte_size)]
+    pub limit_rate: Option<u64>,
+
+    /// Open a new connection (and resolve DNS again) instead of reusing an existing one
+    #[clap(long)]
+    pub fresh_connect: bool,
+
+    /// Don't reuse connections older than this, e.g. 30s, 500ms, 2m
+    #[clap(long, value_name = "DURATION", value_parser = parse_duration)]
+    pub max_conn_age: Option<Duration>,
+
+    /// Max time for the whole request, e.g. 30s
+    #[clap(short = 'm', long, value_name = "DURATION", value_parser = parse_duration)]
+    pub max_time: Option<Duration>,
+
+    /// Max time to resolve the host name
+    #[clap(long, value_name = "DURATION", value_parser = parse_duration)]
+    pub dns_timeout: Option<Duration>,
+
+    /// Max time to open the TCP connection (to each address the host resolved to)
+    #[clap(long, value_name = "DURATION", value_parser = parse_duration)]
+    pub connect_timeout: Option<Duration>,
+
+    /// Max time for the TLS handshake
+    #[clap(long, value_name = "DURATION", value_parser = parse_duration)]
+    pub tls_timeout: Option<Duration>,
+
+    /// Max time from sending the request until all the response headers are received
+    #[clap(long, value_name = "DURATION", value_parser = parse_duration)]
+    pub response_header_timeout: Option<Duration>,
+
+    /// Max time to wait for any data to arrive while receiving the response
+    #[clap(long, value_name = "DURATION", value_parser = parse_duration)]
+    pub read_timeout: Option<Duration>,
+
+    /// Attach a digest of the request body as Content-Digest (or Content-MD5 for md5)
+    #[clap(long, arg_enum, value_name = "ALGORITHM")]
+    pub send_digest: Option<DigestAlgorithm>,
+
+    /// Verify the response body against its Content-Digest/Content-MD5, failing on a mismatch
+    #[clap(long)]
+    pub verify_digest: bool,
+
+    /// Fail if the body's SHA-256 isn't this hex hash (a line of sha256sum's output works too). Nothing is written when it doesn't match
+    #[clap(long, value_name = "HEX", value_parser = |hex: &str| Checksum::parse(ChecksumAlgorithm::Sha256, hex))]
+    pub expect_sha256: Option<Checksum>,
+
+    /// Like --expect-sha256, for a SHA-1 hash
+    #[clap(long, value_name = "HEX", value_parser = |hex: &str| Checksum::parse(ChecksumAlgorithm::Sha1, hex))]
+    pub expect_sha1: Option<Checksum>,
+
+    /// Like --expect-sha256, for an MD5 hash
+    #[clap(long, value_name = "HEX", value_parser = |hex: &str| Checksum::parse(ChecksumAlgorithm::Md5, hex))]
+    pub expect_md5: Option<Checksum>,
+
+    /// Send 'TE: trailers' to ask for trailer fields after a chunked body (shown with -v and in JSON output)
+    #[clap(long)]
+    pub trailers: bool,
+
+    /// Dump every byte sent and received (after TLS) to this file, as hex and text ('-' for stderr)
+    #[clap(long, value_name = "FILE", conflicts_with = "trace-ascii", value_hint = ValueHint::FilePath)]
+    pub trace: Option<String>,
+
+    /// Like --trace, but only as text, one line of the data per line of output
+    #[clap(long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+    pub trace_ascii: Option<String>,
+
+    /// The file opened for --trace/--trace-ascii, shared by every request (and thread)
+    #[clap(skip)]
+    pub opened_trace: Option<Trace>,
+
+    /// Save every request and response (redirects included) to this file as a HAR, for browser devtools and HAR viewers
+    #[clap(long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+    pub har: Option<PathBuf>,
+
+    /// Where --har writes, shared by every request (and thread)
+    #[clap(skip)]
+    pub opened_har: Option<HarRecorder>,
+
+    /// Append a line about every request (redirects included) to this file: when, method, URL, status, bytes, how long it took and any error
+    #[clap(long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+    pub log_file: Option<PathBuf>,
+
+    /// How --log-file lines are written
+    #[clap(long, arg_enum, value_name = "FORMAT", default_value = "json")]
+    pub log_format: LogFormat,
+
+    /// Where --log-file writes, shared by every request (and thread)
+    #[clap(skip)]
+    pub opened_log: Option<RequestLog>,
+
+    /// Where -v shows the responses that redirected, stdout if `None`
+    #[clap(skip)]
+    pub redirect_output: Option<SharedOutput>,
+
+    /// Treat the response as Server-Sent Events (like a text/event-stream one would be), printing each event as it arrives
+    #[clap(long, conflicts_with_all = &["output-file", "hex", "pager", "cache-dir"])]
+    pub sse: bool,
+
+    /// Gets the body as it arrives, see [`crate::sse`]
+    #[clap(skip)]
+    pub stream: Option<Arc<Mutex<dyn StreamHandler>>>,
+
+    /// Cache responses in this directory, serving fresh ones without a request and revalidating stale ones
+    #[clap(long, value_name = "DIR", value_hint = ValueHint::DirPath, conflicts_with = "replay")]
+    pub cache_dir: Option<String>,
+
+    /// Save every request & response (redirects included) in this directory, for --replay
+    #[clap(long, value_name = "DIR", value_hint = ValueHint::DirPath, conflicts_with = "replay")]
+    pub record: Option<String>,
+
+    /// Answer requests with the responses --record saved in this directory, without touching the network. Requests match on their method, URL and body
+    #[clap(long, value_name = "DIR", value_hint = ValueHint::DirPath)]
+    pub replay: Option<String>,
+
+    /// Read more URLs to request from a file, one per line ('#' starts a comment)
+    #[clap(long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+    pub url_file: Option<PathBuf>,
+
+    /// How many requests to run at once when requesting multiple URLs.
+    /// With multiple URLs, -o is a template that can use %{host} and %{path}
+    #[clap(long, value_name = "N", default_value_t = 1, value_parser = clap::value_parser!(u16).range(1..))]
+    pub parallel: u16,
+
+    /// With --parallel, don't run more than this many requests to the same host at once
+    #[clap(long, value_name = "N", value_parser = clap::value_parser!(u16).range(1..))]
+    pub max_per_host: Option<u16>,
+
+    /// Send the request(s) this many times, e.g. to backfill an API or with --rps to test a rate limit
+    #[clap(long, value_name = "N", default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..), conflicts_with_all = &["watch", "mirror"])]
+    pub repeat: u32,
+
+    /// Send at most this many requests a second (e.g. 10 or 0.5), across every --parallel one. While pacing, 429s (and 503s with Retry-After) are waited out and tried again
+    #[clap(long, value_name = "RATE", value_parser = parse_rps)]
+    pub rps: Option<f64>,
+
+    /// Wait at least this long between starting requests (e.g. 250ms), like --rps, which wins if it's slower
+    #[clap(long, value_name = "DURATION", value_parser = parse_duration)]
+    pub delay: Option<Duration>,
+
+    /// When the next request can go out with --rps/--delay, shared by every request (and thread)
+    #[clap(skip)]
+    pub pacer: Option<Pacer>,
+
+    /// Mirrors of the URL to send the same request to, in order, when it fails (an error or a 4xx/5xx) until one works
+    #[clap(long, value_name = "URL", conflicts_with = "url-file", value_hint = ValueHint::Url)]
+    pub mirror: Vec<String>,
+
+    /// URL(s) to send the request to.
+    #[clap(required_unless_present = "url-file", value_hint = ValueHint::Url, value_parser = parse_url)]
+    pub url: Vec<String>,
+}
+
+impl CommonOptions {
+    /// The connection-based auth (--ntlm or --negotiate) to answer a challenge with, if any
+    pub fn connection_auth(&self) -> Option<ConnectionAuth> {
+        match (self.ntlm, self.negotiate) {
+            (true, _) => Some(ConnectionAuth::Ntlm),
+            (_, true) => Some(ConnectionAuth::Negotiate),
+            _ => None,
+        }
+    }
+
+    /// How --format shows the response
+    pub fn response_renderer(&self) -> Box<dyn ResponseRenderer> {
+        match self.output_format {
+            OutputFormat::Text => Box::new(TerminalRenderer::from(self)),
+            OutputFormat::Plain => Box::new(PlainRenderer(TerminalRenderer::from(self))),
+            OutputFormat::Json => Box::new(JsonRenderer),
+            OutputFormat::Raw => Box::new(RawRenderer {
+                include: self.include,
+            }),
+        }
+    }
+
+    /// What the --assert-* flags check the response for
+    pub fn assertions(&self) -> Assertions {
+        Assertions {
+            statuses: self.assert_status.clone(),
+            headers: self.assert_header.clone(),
+            body_contains: self.assert_body_contains.clone(),
+        }
+    }
+
+    /// What --grep looks for
+    pub fn grep(&self) -> Option<Grep> {
+        self.grep.as_ref().map(|pattern| Grep {
+            pattern: pattern.clone(),
+            ignore_case: self.grep_ignore_case,
+        })
+    }
+
+    /// How often --watch repeats the request and what it waits for, if it's watching
+    pub fn watch(&self) -> Option<Watch> {
+        self.watch.map(|interval| Watch {
+            interval,
+            until_statuses: self.until_status.clone(),
+            until_body_contains: self.until_body_contains.clone(),
+        })
+    }
+
+    /// The --expect-sha256, --expect-sha1 and --expect-md5 hashes given
+    pub fn checksums(&self) -> Vec<&Checksum> {
+        [&self.expect_sha256, &self.expect_sha1, &self.expect_md5]
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+
+    /// The Referer to send from --referer, without any ';auto'
+    pub fn referer(&self) -> Option<&str> {
+        self.referer
+            .as_deref()
+            .map(|referer| referer.strip_suffix(";auto").unwrap_or(referer))
+            .filter(|referer| !referer.is_empty())
+    }
+
+    /// If --referer ends with ';auto', so redirects send the URL they came from
+    pub fn auto_referer(&self) -> bool {
+        self.referer
+            .as_deref()
+            .is_some_and(|referer| referer.ends_with(";auto"))
+    }
+
+    /// Take out everything that puts credentials on the request: Authorization and Cookie headers,
+    /// -u, --aws-sigv4, --sign and --ntlm/--negotiate. For redirects to another origin, which
+    /// they weren't meant for. Returns if there were any
+    /// ```
+    /// # use clap::Parser;
+    /// # use httpc::cli::CommonOptions;
+    /// let mut options = CommonOptions::parse_from([
+    ///     "httpc", "-u", "key:secret", "--aws-sigv4", "us-east-1/s3", "-h", "Cookie: a=1",
+    ///     "-h", "Accept: text/plain", "http://example.com/",
+    /// ]);
+    /// assert!(options.strip_credentials());
+    /// assert!(options.user.is_none() && options.aws_sigv4.is_none());
+    /// assert_eq!(options.header, ["Accept: text/plain"]);
+    /// assert!(!options.strip_credentials());
+    /// ```
+    pub fn strip_credentials(&mut self) -> bool {
+        let is_credential = |header: &String| {
+            [header::AUTHORIZATION, header::COOKIE]
+                .iter()
+                .any(|name| has_header(std::slice::from_ref(header), name.as_str()))
+        };
+
+        let had_any = self.user.is_some()
+            || self.aws_sigv4.is_some()
+            || self.sign.key.is_some()
+            || self.connection_auth().is_some()
+            || self.header.iter().any(is_credential);
+
+        self.header.retain(|header| !is_credential(header));
+        self.raw_headers.retain(|header| !is_credential(header));
+        self.user = None;
+        self.aws_sigv4 = None;
+        self.sign.key = None;
+        self.ntlm = false;
+        self.negotiate = false;
+
+        had_any
+    }
+
+    /// Every variable for {{VAR}} placeholders, the session's then --var (so --var wins)
+    pub fn vars(&self) -> Vec<(String, String)> {
+        let mut vars = self
+            .loaded_session
+            .as_ref()
+            .and_then(|session| session.lock().ok())
+            .map(|session| session.variables.clone().into_iter().collect::<Vec<_>>())
+            .unwrap_or_default();
+
+        vars.extend(self.var.iter().cloned());
+        vars
+    }
+}
+
+impl From<&CommonOptions> for RequestOptions {
+    fn from(options: &CommonOptions) -> Self {
+        Self {
+            verbosity: options.verbosity,
+            limit_rate: options.limit_rate,
+            fresh_connect: options.fresh_connect,
+            max_conn_age: options.max_conn_age,
+            proxy: options.proxy.clone(),
+            proxy_user: options.proxy_user.clone(),
+            insecure: options.insecure,
+            ca_cert: options.cacert.clone(),
+            cert_expiry_check: Some(options.cert_expiry_check).filter(|within| !within.is_zero()),
+            ip_family: match (options.ipv4, options.ipv6) {
+                (true, _) => Some(IpFamily::V4),
+                (_, true) => Some(IpFamily::V6),
+                _ => None,
+            },
+            timeouts: Timeouts {
+                dns: options.dns_timeout,
+                connect: options.connect_timeout,
+                tls: options.tls_timeout,
+                response_header: options.response_header_timeout,
+                read: options.read_timeout,
+                total: options.max_time,
+            },
+            trace: options.opened_trace.clone(),
+            safe_fetch: options.safe_fetch,
+            strict_tls: options.strict_tls,
+            // Only going by the environment where it's supported, other programs use it too
+            keylog: options.keylog.clone().or_else(|| {
+                std::env::var("SSLKEYLOGFILE")
+                    .ok()
+                    .filter(|file| keylog::SUPPORTED && !file.is_empty())
+            }),
+            stream: options.stream.clone(),
+            max_memory_body: Some(options.max_memory_body).filter(|max| *max > 0),
+            max_body: options.max_filesize,
+            connection_tag: options.connection_tag.clone(),
+            target_form: options.target_form,
+            request_target: options.request_target.clone(),
+            events: match options.verbosity >= VERY_VERBOSE {
+                true => vec![EventHook::print()],
+                false => vec![],
+            },
+        }
+    }
 }
 
 pub const VERBOSE: u8 = 1;
@@ -0,0 +1,330 @@
+//! Collections of requests in a `.http` file (the format used by VS Code's REST Client & co)
+//!
+//! ```text
+//! @host = https://api.example.com
+//!
+//! ### Log in
+//! # @capture token = $.access_token
+//! POST {{host}}/login
+//! Content-Type: application/json
+//!
+//! {"user": "me", "password": "{{PASSWORD}}"}
+//!
+//! ### Who am I
+//! GET {{host}}/me
+//! Authorization: Bearer {{token}}
+//! ```
+//!
+//! Requests are separated by `###` lines (anything after the `###` names the request), `#` and
+//! `//` lines are comments, and `@name = value` lines define variables. The method defaults to
+//! GET, headers follow the request line and the body comes after an empty line.
+//!
+//! `# @capture name = ...` saves part of the response as a variable for the requests after it:
+//! a JSON path into the body (`$.a.b[0]`), `header Name`, `status` or `body`
+//!
+//! A request can also change how it connects with the same lines, named like the flags:
+//! `# @proxy URL`, `# @insecure`, `# @cacert FILE`, `# @ipv4`/`# @ipv6`, `# @max-time 10s` and
+//! `# @dns-timeout`/`connect-timeout`/`tls-timeout`/`response-header-timeout`/`read-timeout`
+
+use http::{Method, Response, Uri};
+
+use crate::{
+    cli::CommonOptions,
+    helpers::{parse_duration, parse_proxy},
+    http_request::{IpFamily, RequestError},
+    jsonpath,
+    template::substitute,
+    timeout::Timeouts,
+};
+
+#[derive(Debug, Default)]
+pub struct Collection {
+    /// `@name = value` lines, in order. Values can use variables defined before them
+    pub variables: Vec<(String, String)>,
+    pub requests: Vec<CollectionRequest>,
+}
+
+#[derive(Debug)]
+pub struct CollectionRequest {
+    /// Whatever followed the `###` before this request, if anything
+    pub name: Option<String>,
+    pub method: Method,
+    pub url: String,
+    /// As `Name: value`
+    pub headers: Vec<String>,
+    pub body: Option<String>,
+    pub captures: Vec<Capture>,
+    pub settings: ConnectionSettings,
+}
+
+/// Connection settings a request overrides, on top of the profile's (or the defaults)
+#[derive(Debug, Default)]
+pub struct ConnectionSettings {
+    pub proxy: Option<Uri>,
+    pub insecure: bool,
+    pub cacert: Option<String>,
+    pub ip_family: Option<IpFamily>,
+    pub timeouts: Timeouts,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Capture {
+    pub name: String,
+    pub source: CaptureSource,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum CaptureSource {
+    /// A JSON path into the response body
+    Json(String),
+    Header(String),
+    Status,
+    Body,
+}
+
+/// Parse a `.http` file
+/// ```
+/// # use httpc::collection::{parse_collection, CaptureSource};
+/// # use std::time::Duration;
+/// let collection = parse_collection(
+///     "@host = example.com\n\n### First\n# @capture id = $.id\n# @connect-timeout 2s\nPOST {{host}}/a\nX-A: 1\n\n{}\n###\n{{host}}/b",
+/// )
+/// .unwrap();
+/// assert_eq!(collection.variables, [("host".to_string(), "example.com".to_string())]);
+/// assert_eq!(collection.requests.len(), 2);
+/// assert_eq!(collection.requests[0].name.as_deref(), Some("First"));
+/// assert_eq!(collection.requests[0].headers, ["X-A: 1"]);
+/// assert_eq!(collection.requests[0].body.as_deref(), Some("{}"));
+/// assert_eq!(collection.requests[0].captures[0].source, CaptureSource::Json("$.id".to_string()));
+/// assert_eq!(collection.requests[0].settings.timeouts.connect, Some(Duration::from_secs(2)));
+/// assert_eq!(collection.requests[1].method, http::Method::GET);
+/// ```
+pub fn parse_collection(text: &str) -> Result<Collection, RequestError> {
+    let mut collection = Collection::default();
+    let mut name = None;
+    let mut block = vec![];
+
+    for line in text.lines().map(|line| line.trim_end_matches('\r')) {
+        if let Some(rest) = line.strip_prefix("###") {
+            parse_block(&mut collection, name.take(), &block)?;
+            block.clear();
+
+            let rest = rest.trim();
+            name = (!rest.is_empty()).then(|| rest.to_string());
+        } else {
+            block.push(line);
+        }
+    }
+
+    parse_block(&mut collection, name, &block)?;
+
+    if collection.requests.is_empty() {
+        return Err("No requests found in the collection".into());
+    }
+
+    Ok(collection)
+}
+
+/// Parse everything between two `###`, which is either a request or only variables & comments
+fn parse_block(
+    collection: &mut Collection,
+    name: Option<String>,
+    lines: &[&str],
+) -> Result<(), RequestError> {
+    let mut lines = lines.iter();
+    let mut captures = vec![];
+    let mut settings = ConnectionSettings::default();
+
+    // Comments, variables, captures & settings, up to the request line
+    let request_line = loop {
+        let Some(line) = lines.next() else {
+            return Ok(());
+        };
+        let line = line.trim();
+
+        if let Some(comment) = line.strip_prefix('#').or_else(|| line.strip_prefix("//")) {
+            let comment = comment.trim();
+
+            if let Some(capture) = comment.strip_prefix("@capture") {
+                captures.push(parse_capture(capture)?);
+            } else if let Some(setting) = comment.strip_prefix('@') {
+                settings.parse(setting)?;
+            }
+        } else if let Some(variable) = line.strip_prefix('@') {
+            let (key, value) = variable
+                .split_once('=')
+                .ok_or_else(|| format!("Expected '@name = value', got '{}'", line))?;
+            collection
+                .variables
+                .push((key.trim().to_string(), value.trim().to_string()));
+        } else if !line.is_empty() {
+            break line;
+        }
+    };
+
+    let mut parts = request_line.split_whitespace();
+    let (method, url) = match (parts.next(), parts.next()) {
+        (Some(url), None) => (Method::GET, url),
+        (Some(method), Some(url)) => (Method::from_bytes(method.as_bytes())?, url),
+        _ => unreachable!("request line isn't empty"),
+    };
+
+    let mut headers = vec![];
+
+    for line in lines.by_ref() {
+        if line.trim().is_empty() {
+            break;
+        }
+
+        if !line.starts_with('#') && !line.starts_with("//") {
+            headers.push(line.trim().to_string());
+        }
+    }
+
+    let body = lines.copied().collect::<Vec<_>>().join("\n");
+    let body = body.trim_end();
+
+    collection.requests.push(CollectionRequest {
+        name,
+        method,
+        url: url.to_string(),
+        headers,
+        body: (!body.is_empty()).then(|| body.to_string()),
+        captures,
+        settings,
+    });
+
+    Ok(())
+}
+
+/// Parse what comes after `@capture`, e.g. ` token = $.access_token`
+fn parse_capture(capture: &str) -> Result<Capture, RequestError> {
+    let (name, source) = capture
+        .split_once('=')
+        .ok_or_else(|| format!("Expected '@capture name = source', got '{}'", capture))?;
+    let source = source.trim();
+
+    let source = match source.split_once(char::is_whitespace) {
+        _ if source.starts_with('$') => CaptureSource::Json(source.to_string()),
+        Some(("header", header)) => CaptureSource::Header(header.trim().to_string()),
+        None if source == "status" => CaptureSource::Status,
+        None if source == "body" => CaptureSource::Body,
+        _ => {
+            let expected = "a JSON path ($.a.b), 'header Name', 'status' or 'body'";
+            return Err(format!("Unknown capture '{}', expected {}", source, expected).into());
+        }
+    };
+
+    Ok(Capture {
+        name: name.trim().to_string(),
+        source,
+    })
+}
+
+impl ConnectionSettings {
+    /// Parse what comes after the `@` of a setting, e.g. `proxy localhost:3128`. Anything that
+    /// isn't a setting is left as a comment, other clients have their own `@` lines
+    fn parse(&mut self, setting: &str) -> Result<(), RequestError> {
+        let (name, value) = match setting.split_once(char::is_whitespace) {
+            Some((name, value)) => (name, Some(value.trim())),
+            None => (setting, None),
+        };
+        let value = || value.ok_or_else(|| format!("'@{}' needs a value", name));
+
+        match name {
+            "proxy" => self.proxy = Some(parse_proxy(value()?)?),
+            "insecure" => self.insecure = true,
+            "cacert" => self.cacert = Some(value()?.to_string()),
+            "ipv4" => self.ip_family = Some(IpFamily::V4),
+            "ipv6" => self.ip_family = Some(IpFamily::V6),
+            "max-time" => self.timeouts.total = Some(parse_duration(value()?)?),
+            "dns-timeout" => self.timeouts.dns = Some(parse_duration(value()?)?),
+            "connect-timeout" => self.timeouts.connect = Some(parse_duration(value()?)?),
+            "tls-timeout" => self.timeouts.tls = Some(parse_duration(value()?)?),
+            "response-header-timeout" => {
+                self.timeouts.response_header = Some(parse_duration(value()?)?)
+            }
+            "read-timeout" => self.timeouts.read = Some(parse_duration(value()?)?),
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    /// Override `options` with these settings, leaving the rest as they were
+    pub fn apply(&self, options: &mut CommonOptions) {
+        if let Some(proxy) = &self.proxy {
+            options.proxy = Some(proxy.clone());
+        }
+
+        options.insecure |= self.insecure;
+        options.cacert = self.cacert.clone().or(options.cacert.take());
+
+        if let Some(family) = self.ip_family {
+            options.ipv4 = family == IpFamily::V4;
+            options.ipv6 = family == IpFamily::V6;
+        }
+
+        let timeouts = &self.timeouts;
+        options.max_time = timeouts.total.or(options.max_time);
+        options.dns_timeout = timeouts.dns.or(options.dns_timeout);
+        options.connect_timeout = timeouts.connect.or(options.connect_timeout);
+        options.tls_timeout = timeouts.tls.or(options.tls_timeout);
+        options.response_header_timeout =
+            timeouts.response_header.or(options.response_header_timeout);
+        options.read_timeout = timeouts.read.or(options.read_timeout);
+    }
+}
+
+impl CollectionRequest {
+    /// The request's name, or its method & URL if it doesn't have one
+    pub fn title(&self) -> String {
+        match &self.name {
+            Some(name) => name.clone(),
+            None => format!("{} {}", self.method, self.url),
+        }
+    }
+}
+
+/// The collection's variables with `vars` (from `--var`) taking priority, with each one's
+/// `{{NAME}}` placeholders filled in
+pub fn collection_vars(
+    collection: &Collection,
+    vars: &[(String, String)],
+) -> Result<Vec<(String, String)>, RequestError> {
+    let mut all = vars.to_vec();
+
+    for (name, value) in &collection.variables {
+        if !vars.iter().any(|(key, _)| key == name) {
+            let value = substitute(value, &all)?;
+            all.push((name.clone(), value));
+        }
+    }
+
+    Ok(all)
+}
+
+impl Capture {
+    /// Get the captured value out of a response
+    pub fn extract(&self, response: &Response<Vec<u8>>) -> Result<String, RequestError> {
+        let value = match &self.source {
+            CaptureSource::Json(path) => {
+                let json: serde_json::Value =
+                    serde_json::from_slice(response.body()).map_err(|err| {
+                        format!("Can't capture {}, body isn't JSON: {}", self.name, err)
+                    })?;
+                jsonpath::to_text(jsonpath::select(&json, path)?)
+            }
+            CaptureSource::Header(header) => response
+                .headers()
+                .get(header)
+                .ok_or_else(|| format!("Can't capture {}, no {} header", self.name, header))?
+                .to_str()?
+                .to_string(),
+            CaptureSource::Status => response.status().as_u16().to_string(),
+            CaptureSource::Body => String::from_utf8_lossy(response.body()).into_owned(),
+        };
+
+        Ok(value)
+    }
+}
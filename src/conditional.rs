@@ -0,0 +1,126 @@
+use std::{
+    fs, io,
+    path::Path,
+    str::FromStr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use http::{header, Response};
+
+use crate::helpers::days_from_civil;
+
+/// A `-z` time condition, the request only succeeds if the resource changed (or didn't) since then
+///
+/// ```
+/// use httpc::conditional::TimeCondition;
+///
+/// let condition: TimeCondition = "-2022-05-01".parse().unwrap();
+/// assert_eq!(condition.header(), "If-Unmodified-Since: Sun, 01 May 2022 00:00:00 GMT");
+///
+/// // HTTP dates end at year 9999
+/// assert!("300000000000-01-01".parse::<TimeCondition>().is_err());
+/// assert!("10000-01-01".parse::<TimeCondition>().is_err());
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimeCondition {
+    /// `If-Modified-Since`
+    ModifiedSince(SystemTime),
+    /// `If-Unmodified-Since`, written with a leading `-`
+    UnmodifiedSince(SystemTime),
+}
+
+impl FromStr for TimeCondition {
+    type Err = String;
+
+    /// Either an HTTP date, `YYYY-MM-DD`, or a path to a file whose modification time is used
+    ///
+    /// A leading `-` flips the condition to "not modified since"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (unmodified, spec) = match s.strip_prefix('-') {
+            Some(spec) => (true, spec),
+            None => (false, s),
+        };
+
+        let time = if Path::new(spec).exists() {
+            fs::metadata(spec)
+                .and_then(|metadata| metadata.modified())
+                .map_err(|err| format!("Can't read modification time of '{}': {}", spec, err))?
+        } else {
+            parse_date(spec).ok_or_else(|| {
+                format!(
+                    "Invalid time condition: '{}', expected an HTTP date, YYYY-MM-DD, or an existing file",
+                    spec
+                )
+            })?
+        };
+
+        Ok(if unmodified {
+            TimeCondition::UnmodifiedSince(time)
+        } else {
+            TimeCondition::ModifiedSince(time)
+        })
+    }
+}
+
+impl TimeCondition {
+    /// The header line (`Name: value`) to send for this condition
+    pub fn header(self) -> String {
+        match self {
+            TimeCondition::ModifiedSince(time) => {
+                format!("If-Modified-Since: {}", httpdate::fmt_http_date(time))
+            }
+            TimeCondition::UnmodifiedSince(time) => {
+                format!("If-Unmodified-Since: {}", httpdate::fmt_http_date(time))
+            }
+        }
+    }
+}
+
+/// Parse an HTTP date or a plain `YYYY-MM-DD` (midnight UTC)
+fn parse_date(date: &str) -> Option<SystemTime> {
+    if let Ok(time) = httpdate::parse_http_date(date.trim()) {
+        return Some(time);
+    }
+
+    let mut parts = date.trim().splitn(3, '-').map(|part| part.parse::<i64>());
+    let (year, month, day) = match (parts.next()?, parts.next()?, parts.next()?) {
+        (Ok(year), Ok(month), Ok(day)) => (year, month, day),
+        _ => return None,
+    };
+
+    // HTTP dates can't be written past 9999, and it keeps `days_from_civil` from overflowing
+    if !(1..=9999).contains(&year) || !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    let days = days_from_civil(year, month, day);
+    let seconds = u64::try_from(days.checked_mul(24 * 60 * 60)?).ok()?;
+    UNIX_EPOCH.checked_add(Duration::from_secs(seconds))
+}
+
+/// Read a previously saved ETag, a missing or empty file means we don't have one yet
+pub fn read_etag(file: &Path) -> io::Result<Option<String>> {
+    match fs::read_to_string(file) {
+        Ok(etag) if etag.trim().is_empty() => Ok(None),
+        Ok(etag) => Ok(Some(etag.trim().to_string())),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+/// Save the ETag of a response for a later `--etag-compare`
+///
+/// 304 responses keep the previously saved one, since it's still valid.
+/// Responses without an ETag empty the file, so we don't compare against a stale one
+pub fn save_etag(response: &Response<Vec<u8>>, file: &Path) -> io::Result<()> {
+    let etag = response
+        .headers()
+        .get(header::ETAG)
+        .and_then(|etag| etag.to_str().ok());
+
+    match etag {
+        Some(etag) => fs::write(file, format!("{}\n", etag)),
+        None if response.status() == http::StatusCode::NOT_MODIFIED => Ok(()),
+        None => fs::write(file, ""),
+    }
+}
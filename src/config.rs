@@ -0,0 +1,325 @@
+//! Named profiles of default options, loaded from `~/.config/httpc/config.toml`
+//!
+//! ```toml
+//! # Used when no --profile is given
+//! [profiles.default]
+//! headers = ["Accept: application/json"]
+//!
+//! [profiles.staging]
+//! base_url = "https://staging.example.com/api"
+//! headers = ["X-Env: staging"]
+//! user = "me:hunter2"
+//! proxy = "http://localhost:3128"
+//! insecure = true
+//! cacert = "~/certs/staging-ca.pem"
+//!
+//! [profiles.staging.redirect]
+//! same_origin = true
+//!
+//! # Logs in with login.http whenever the session doesn't have a token yet
+//! [profiles.api]
+//! base_url = "https://api.example.com"
+//! session = "api"
+//! bootstrap = "~/.config/httpc/login.http"
+//! refresh = "~/.config/httpc/refresh.http"
+//! headers = ["Authorization: Bearer {{token}}"]
+//!
+//! # Only sent to these hosts, even after a redirect (like --host-header)
+//! [profiles.default.host_headers]
+//! "api.example.com" = ["Authorization: Bearer {{api_token}}"]
+//! "*.internal.example.com" = ["X-Team: infra"]
+//! ```
+//!
+//! Anything given on the command line wins over the profile.
+//!
+//! A project can have its own config too: the closest `.httpc.toml` in the current directory or
+//! any above it is put on top of the user's. Its profiles are merged into the user's ones with the
+//! same name (its headers replacing ones with the same name, anything else it sets winning), so
+//! a repo can commit its base URLs and headers while everyone keeps their own credentials.
+//! Relative `cacert`, `bootstrap` and `refresh` paths in it are relative to the `.httpc.toml`
+//!
+//! A `bootstrap` is a [request collection](crate::collection) that's run before the first
+//! request when the session is missing any of the variables it captures (or has no cookies, if it
+//! doesn't capture anything). What it captures is saved in the session, so headers can use it.
+//!
+//! It's also run again when the login expires (a captured JWT's `exp`, or an `expires_in`
+//! capture) or a request gets a 401, which is then retried once. If the profile has a `refresh`
+//! collection, that's tried first, and it can use what the bootstrap captured (like a
+//! `{{refresh_token}}`)
+//!
+//! `[alias]` adds shortcuts for whole command lines, so `httpc jpost URL` is
+//! `httpc post -h 'Content-Type: application/json' -h 'X-Team: infra' URL`. They're split like a
+//! shell would, and can start with another alias
+//!
+//! ```toml
+//! [alias]
+//! jpost = "post -h 'Content-Type: application/json' -h 'X-Team: infra'"
+//! staging = "get --profile staging"
+//! ```
+//!
+//! `[renderers]` picks how bodies of a Content-Type are shown, see [`crate::render`]
+//!
+//! ```toml
+//! [renderers]
+//! "application/vnd.api+json" = "json"
+//! "application/octet-stream" = "hex"
+//! ```
+
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use crate::{
+    cli::CommonOptions,
+    helpers::{has_header, parse_host_header, parse_proxy},
+    http_request::RequestError,
+    redirect::RedirectPolicy,
+    render::Renderer,
+};
+
+/// The profile used when no `--profile` is given, if the config has one
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// A project's own config, looked for in the current directory and up
+pub const PROJECT_CONFIG: &str = ".httpc.toml";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default)]
+    pub profiles: HashMap<String, Profile>,
+    /// Content-Type (or pattern like `image/*`) → renderer
+    #[serde(default)]
+    pub renderers: HashMap<String, Renderer>,
+    /// Alias → the command line it's short for, see [`crate::cli::expand_args`]
+    #[serde(default, rename = "alias")]
+    pub aliases: HashMap<String, String>,
+    /// The files this was loaded from
+    #[serde(skip)]
+    pub sources: Vec<PathBuf>,
+}
+
+/// Defaults for [`CommonOptions`], each one the same as the flag of the same name
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    #[serde(default)]
+    pub headers: Vec<String>,
+    /// Host (or `*.domain`) → headers only sent to it, see `--host-header`
+    #[serde(default)]
+    pub host_headers: HashMap<String, Vec<String>>,
+    pub base_url: Option<String>,
+    pub user: Option<String>,
+    pub proxy: Option<String>,
+    #[serde(default)]
+    pub insecure: bool,
+    pub cacert: Option<String>,
+    /// The session to use when no `--session` is given
+    pub session: Option<String>,
+    /// A `.http` collection that logs into the session, see the module docs
+    pub bootstrap: Option<String>,
+    /// A `.http` collection that refreshes an expired login, instead of running the bootstrap again
+    pub refresh: Option<String>,
+    /// The `--redirect-*` flags, see [`RedirectPolicy`]
+    #[serde(default)]
+    pub redirect: RedirectPolicy,
+}
+
+/// Where httpc keeps its files: `$XDG_CONFIG_HOME/httpc`, or `~/.config/httpc`
+pub fn config_dir() -> Option<PathBuf> {
+    let config_home = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => Path::new(&std::env::var_os("HOME")?).join(".config"),
+    };
+
+    Some(config_home.join("httpc"))
+}
+
+impl Config {
+    /// Where the config file lives, `config.toml` in the [`config_dir`]
+    pub fn path() -> Option<PathBuf> {
+        Some(config_dir()?.join("config.toml"))
+    }
+
+    /// The closest [`PROJECT_CONFIG`] in the current directory or any above it
+    pub fn project_path() -> Option<PathBuf> {
+        let cwd = std::env::current_dir().ok()?;
+
+        cwd.ancestors()
+            .map(|dir| dir.join(PROJECT_CONFIG))
+            .find(|path| path.is_file())
+    }
+
+    /// Load the config file with the project's on top, which is empty if there isn't either
+    pub fn load() -> Result<Self, RequestError> {
+        let mut config = match Self::path() {
+            Some(path) => Self::read(&path)?,
+            None => Self::default(),
+        };
+
+        if let Some(path) = Self::project_path() {
+            let mut project = Self::read(&path)?;
+
+            if let Some(dir) = path.parent() {
+                for profile in project.profiles.values_mut() {
+                    profile.resolve_paths(dir);
+                }
+            }
+
+            config.merge(project);
+        }
+
+        Ok(config)
+    }
+
+    /// Read one config file, which is empty if it doesn't exist
+    fn read(path: &Path) -> Result<Self, RequestError> {
+        let mut config: Self = match fs::read_to_string(path) {
+            Ok(contents) => toml::from_str(&contents)
+                .map_err(|err| format!("Invalid config {}: {}", path.display(), err))?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => return Err(format!("Can't read config {}: {}", path.display(), err).into()),
+        };
+
+        config.sources.push(path.to_path_buf());
+        Ok(config)
+    }
+
+    /// Put `other` on top of this config, its profiles merged into the ones with the same name
+    fn merge(&mut self, other: Config) {
+        for (name, profile) in other.profiles {
+            match self.profiles.get_mut(&name) {
+                Some(base) => base.merge(profile),
+                None => {
+                    self.profiles.insert(name, profile);
+                }
+            }
+        }
+
+        self.renderers.extend(other.renderers);
+        self.aliases.extend(other.aliases);
+        self.sources.extend(other.sources);
+    }
+}
+
+impl Profile {
+    /// Put `other` on top of this profile: its headers replace ones with the same name, and
+    /// anything it sets wins
+    fn merge(&mut self, other: Profile) {
+        self.headers.retain(|header| {
+            let name = header
+                .split_once(':')
+                .map_or(header.as_str(), |(name, _)| name);
+            !has_header(&other.headers, name.trim())
+        });
+        self.headers.splice(0..0, other.headers);
+        self.host_headers.extend(other.host_headers);
+
+        self.base_url = other.base_url.or(self.base_url.take());
+        self.user = other.user.or(self.user.take());
+        self.proxy = other.proxy.or(self.proxy.take());
+        self.insecure |= other.insecure;
+        self.cacert = other.cacert.or(self.cacert.take());
+        self.session = other.session.or(self.session.take());
+        self.bootstrap = other.bootstrap.or(self.bootstrap.take());
+        self.refresh = other.refresh.or(self.refresh.take());
+
+        let mut redirect = other.redirect;
+        redirect.merge(&self.redirect);
+        self.redirect = redirect;
+    }
+
+    /// Make the relative paths in a project config relative to its directory instead of wherever
+    /// httpc is run from
+    fn resolve_paths(&mut self, dir: &Path) {
+        for path in [&mut self.cacert, &mut self.bootstrap, &mut self.refresh]
+            .into_iter()
+            .flatten()
+        {
+            if !path.starts_with('~') && Path::new(path.as_str()).is_relative() {
+                *path = dir.join(&*path).to_string_lossy().into_owned();
+            }
+        }
+    }
+
+    /// Fill in whatever wasn't given on the command line from this profile
+    pub fn apply(&self, options: &mut CommonOptions) -> Result<(), RequestError> {
+        // Profile headers go first, and are skipped if the same header was given explicitly
+        let headers = self
+            .headers
+            .iter()
+            .filter(|header| {
+                let name = header
+                    .split_once(':')
+                    .map_or(header.as_str(), |(name, _)| name);
+                !has_header(&options.header, name.trim())
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        options.header.splice(0..0, headers);
+
+        // Same for each host, a --host-header for it wins over the profile's
+        for (host, headers) in &self.host_headers {
+            for header in headers {
+                let (host, header) = parse_host_header(&format!("{}={}", host, header))?;
+                let name = header.split_once(':').map_or("", |(name, _)| name).trim();
+                let given = options.host_header.iter().any(|(given_host, given)| {
+                    *given_host == host && has_header(std::slice::from_ref(given), name)
+                });
+
+                if !given {
+                    options.host_header.push((host, header));
+                }
+            }
+        }
+
+        if options.proxy.is_none() {
+            options.proxy = self.proxy.as_deref().map(parse_proxy).transpose()?;
+        }
+
+        options.base_url = options.base_url.take().or_else(|| self.base_url.clone());
+        options.user = options.user.take().or_else(|| self.user.clone());
+        options.cacert = options.cacert.take().or_else(|| self.cacert.clone());
+        options.insecure |= self.insecure;
+        options.redirect.merge(&self.redirect);
+
+        Ok(())
+    }
+}
+
+/// Take the `--profile` (or the default profile) out of the config
+///
+/// Asking for a profile that doesn't exist is an error, not having a default profile isn't
+pub fn load_profile(
+    config: &mut Config,
+    options: &CommonOptions,
+) -> Result<Option<Profile>, RequestError> {
+    let name = options.profile.as_deref().unwrap_or(DEFAULT_PROFILE);
+
+    match config.profiles.remove(name) {
+        Some(profile) => Ok(Some(profile)),
+        None if options.profile.is_none() => Ok(None),
+        None => {
+            let sources = config
+                .sources
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect::<Vec<_>>();
+
+            Err(format!(
+                "No profile named '{}' in {}",
+                name,
+                if sources.is_empty() {
+                    "the config".to_string()
+                } else {
+                    sources.join(" or ")
+                }
+            )
+            .into())
+        }
+    }
+}
@@ -0,0 +1,249 @@
+use std::io::{self, Read};
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use clap::ArgEnum;
+use http::{header::HeaderName, HeaderMap, HeaderValue, Response};
+use md5::Md5;
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha512};
+
+use crate::{helpers::to_hex, http_request::RequestError, spool::Spooled};
+
+pub const CONTENT_DIGEST: &str = "content-digest";
+pub const CONTENT_MD5: &str = "content-md5";
+
+/// Hash algorithms we can create/check body digests with
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DigestAlgorithm {
+    #[clap(name = "sha-256")]
+    Sha256,
+    #[clap(name = "sha-512")]
+    Sha512,
+    /// Legacy `Content-MD5` header instead of `Content-Digest`
+    Md5,
+}
+
+impl DigestAlgorithm {
+    /// The algorithm key used in `Content-Digest`, from the IANA registry
+    pub fn key(self) -> &'static str {
+        match self {
+            DigestAlgorithm::Sha256 => "sha-256",
+            DigestAlgorithm::Sha512 => "sha-512",
+            DigestAlgorithm::Md5 => "md5",
+        }
+    }
+
+    fn from_key(key: &str) -> Option<Self> {
+        match key.to_ascii_lowercase().as_str() {
+            "sha-256" => Some(DigestAlgorithm::Sha256),
+            "sha-512" => Some(DigestAlgorithm::Sha512),
+            "md5" => Some(DigestAlgorithm::Md5),
+            _ => None,
+        }
+    }
+
+    pub fn hash(self, body: &[u8]) -> Vec<u8> {
+        match self {
+            DigestAlgorithm::Sha256 => Sha256::digest(body).to_vec(),
+            DigestAlgorithm::Sha512 => Sha512::digest(body).to_vec(),
+            DigestAlgorithm::Md5 => Md5::digest(body).to_vec(),
+        }
+    }
+
+    /// [`Self::hash`] everything `reader` has, a bit at a time
+    pub fn hash_reader(self, reader: impl Read) -> io::Result<Vec<u8>> {
+        match self {
+            DigestAlgorithm::Sha256 => digest::<Sha256>(reader),
+            DigestAlgorithm::Sha512 => digest::<Sha512>(reader),
+            DigestAlgorithm::Md5 => digest::<Md5>(reader),
+        }
+    }
+}
+
+/// Hash everything `reader` has with `D`, a bit at a time
+fn digest<D: Digest>(mut reader: impl Read) -> io::Result<Vec<u8>> {
+    let mut hasher = D::new();
+    let mut buf = vec![0; 64 * 1024];
+
+    loop {
+        match reader.read(&mut buf)? {
+            0 => return Ok(hasher.finalize().to_vec()),
+            read => hasher.update(&buf[..read]),
+        }
+    }
+}
+
+/// The hash a downloaded body should have, from `--expect-sha256` (or `--expect-sha1`,
+/// `--expect-md5`)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Checksum {
+    pub algorithm: ChecksumAlgorithm,
+    pub hash: Vec<u8>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChecksumAlgorithm {
+    Md5,
+    Sha1,
+    Sha256,
+}
+
+impl ChecksumAlgorithm {
+    pub fn name(self) -> &'static str {
+        match self {
+            ChecksumAlgorithm::Md5 => "MD5",
+            ChecksumAlgorithm::Sha1 => "SHA-1",
+            ChecksumAlgorithm::Sha256 => "SHA-256",
+        }
+    }
+
+    /// How many bytes long its hashes are
+    fn len(self) -> usize {
+        match self {
+            ChecksumAlgorithm::Md5 => 16,
+            ChecksumAlgorithm::Sha1 => 20,
+            ChecksumAlgorithm::Sha256 => 32,
+        }
+    }
+}
+
+impl Checksum {
+    /// Parse a hex hash, like `sha256sum` prints. Anything after the hash (like its file name)
+    /// is left out, so a line of `sha256sum`'s output works too
+    /// ```
+    /// # use httpc::content_digest::{Checksum, ChecksumAlgorithm};
+    /// let md5 = Checksum::parse(ChecksumAlgorithm::Md5, "5D41402ABC4B2A76B9719D911017C592  hello.txt");
+    /// assert_eq!(md5.unwrap().hash[..2], [0x5d, 0x41]);
+    /// assert!(Checksum::parse(ChecksumAlgorithm::Md5, "5d41402a").is_err());
+    /// assert!(Checksum::parse(ChecksumAlgorithm::Sha1, "zz").is_err());
+    /// ```
+    pub fn parse(algorithm: ChecksumAlgorithm, hex: &str) -> Result<Self, String> {
+        let hex = hex.split_whitespace().next().unwrap_or_default();
+
+        if hex.len() != algorithm.len() * 2 {
+            return Err(format!(
+                "A {} hash is {} hex characters, not {}",
+                algorithm.name(),
+                algorithm.len() * 2,
+                hex.len()
+            ));
+        }
+
+        let hash = (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| format!("Invalid {} hash '{}', expected hex", algorithm.name(), hex))?;
+
+        Ok(Self { algorithm, hash })
+    }
+
+    /// Hash everything `body` has, erroring if it isn't this checksum
+    pub fn verify(&self, body: impl Read) -> Result<(), RequestError> {
+        let actual = match self.algorithm {
+            ChecksumAlgorithm::Md5 => digest::<Md5>(body)?,
+            ChecksumAlgorithm::Sha1 => digest::<Sha1>(body)?,
+            ChecksumAlgorithm::Sha256 => digest::<Sha256>(body)?,
+        };
+
+        if actual != self.hash {
+            return Err(format!(
+                "The body's {} is {}, not the expected {}",
+                self.algorithm.name(),
+                to_hex(&actual),
+                to_hex(&self.hash)
+            )
+            .into());
+        }
+
+        Ok(())
+    }
+}
+
+/// Create the header to send a digest of `body` with
+///
+/// This is `Content-Digest: sha-256=:<base64>:` (RFC 9530) for the SHA algorithms,
+/// or the legacy `Content-MD5: <base64>` (RFC 1864) for MD5
+pub fn digest_header(
+    algorithm: DigestAlgorithm,
+    body: &[u8],
+) -> Result<(HeaderName, HeaderValue), RequestError> {
+    let hash = BASE64.encode(algorithm.hash(body));
+
+    Ok(match algorithm {
+        DigestAlgorithm::Md5 => (HeaderName::from_static(CONTENT_MD5), hash.parse()?),
+        _ => (
+            HeaderName::from_static(CONTENT_DIGEST),
+            format!("{}=:{}:", algorithm.key(), hash).parse()?,
+        ),
+    })
+}
+
+/// Check the `Content-Digest` (and `Content-MD5`) of a response against its body
+///
+/// Returns how many digests were checked, so callers can warn when there was nothing to verify.
+/// Algorithms we don't know are skipped, a digest that doesn't match is an error
+pub fn verify_digests(headers: &HeaderMap, body: &[u8]) -> Result<usize, RequestError> {
+    verify_with(headers, |algorithm| Ok(algorithm.hash(body)))
+}
+
+/// [`verify_digests`] for a response, hashing its body from the temp file if it was spooled
+pub fn verify_response_digests(response: &Response<Vec<u8>>) -> Result<usize, RequestError> {
+    let Some(spooled) = response.extensions().get::<Spooled>() else {
+        return verify_digests(response.headers(), response.body());
+    };
+
+    verify_with(response.headers(), |algorithm| {
+        Ok(algorithm.hash_reader(spooled.open()?)?)
+    })
+}
+
+/// Check the digests in `headers`, `hash_body` hashes the body with an algorithm
+fn verify_with(
+    headers: &HeaderMap,
+    hash_body: impl Fn(DigestAlgorithm) -> Result<Vec<u8>, RequestError>,
+) -> Result<usize, RequestError> {
+    let mut expected: Vec<(DigestAlgorithm, String)> = vec![];
+
+    for value in headers.get_all(CONTENT_DIGEST) {
+        let value = value.to_str()?;
+
+        // A structured field dictionary: `sha-256=:abc=:, sha-512=:def=:`
+        for member in value.split(',') {
+            let Some((key, hash)) = member.split_once('=') else {
+                continue;
+            };
+
+            // Byte sequences are wrapped in colons, anything else isn't a valid digest
+            let hash = hash.trim();
+            let hash = hash
+                .strip_prefix(':')
+                .and_then(|hash| hash.strip_suffix(':'))
+                .ok_or_else(|| format!("Invalid Content-Digest value: '{}'", member.trim()))?;
+
+            if let Some(algorithm) = DigestAlgorithm::from_key(key.trim()) {
+                expected.push((algorithm, hash.to_string()));
+            }
+        }
+    }
+
+    if let Some(value) = headers.get(CONTENT_MD5) {
+        expected.push((DigestAlgorithm::Md5, value.to_str()?.trim().to_string()));
+    }
+
+    for (algorithm, hash) in &expected {
+        let hash = BASE64
+            .decode(hash)
+            .map_err(|_| format!("Invalid base64 in {} digest: '{}'", algorithm.key(), hash))?;
+
+        if hash != hash_body(*algorithm)? {
+            return Err(format!(
+                "Body doesn't match its {} digest, the response may be corrupted",
+                algorithm.key()
+            )
+            .into());
+        }
+    }
+
+    Ok(expected.len())
+}
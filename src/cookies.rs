@@ -0,0 +1,301 @@
+//! A cookie jar following the storage & matching rules of RFC 6265 (minus the public suffix list)
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use http::{header, HeaderMap, Uri};
+use serde::{Deserialize, Serialize};
+
+/// 9999-12-31 23:59:59 UTC, the last moment an HTTP date can name
+const LATEST_EXPIRY: u64 = 253_402_300_799;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Cookie {
+    pub name: String,
+    pub value: String,
+    /// Lowercase, without a leading dot
+    pub domain: String,
+    pub path: String,
+    /// Unix timestamp, `None` for a session cookie (which we still keep until the session is deleted)
+    pub expires: Option<u64>,
+    #[serde(default)]
+    pub secure: bool,
+    #[serde(default)]
+    pub http_only: bool,
+    /// Only sent to exactly `domain`, not its subdomains, since no Domain attribute was given
+    #[serde(default)]
+    pub host_only: bool,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct CookieJar {
+    pub cookies: Vec<Cookie>,
+}
+
+impl Cookie {
+    /// Parse a `Set-Cookie` value received in response to a request for `uri`
+    ///
+    /// Returns `None` for invalid cookies, or ones the server isn't allowed to set for us
+    /// ```
+    /// # use httpc::cookies::Cookie;
+    /// let uri = "https://www.example.com/a/b".parse().unwrap();
+    /// let cookie = Cookie::parse("id=abc; Domain=.example.com; Secure", &uri).unwrap();
+    /// assert_eq!((cookie.domain.as_str(), cookie.path.as_str()), ("example.com", "/a"));
+    /// assert!(cookie.secure && !cookie.host_only);
+    /// assert!(Cookie::parse("id=abc; Domain=evil.com", &uri).is_none());
+    /// ```
+    pub fn parse(set_cookie: &str, uri: &Uri) -> Option<Self> {
+        Self::try_parse(set_cookie, uri).ok()
+    }
+
+    /// Same as [`Cookie::parse`], but says why a cookie was rejected
+    /// ```
+    /// # use httpc::cookies::Cookie;
+    /// let uri = "https://www.example.com/".parse().unwrap();
+    /// let err = Cookie::try_parse("id=abc; Domain=evil.com", &uri).unwrap_err();
+    /// assert_eq!(err, "Domain=evil.com doesn't match the host www.example.com");
+    /// ```
+    pub fn try_parse(set_cookie: &str, uri: &Uri) -> Result<Self, String> {
+        let host = uri
+            .host()
+            .ok_or("The URL has no host to set cookies for")?
+            .to_ascii_lowercase();
+        let mut attributes = set_cookie.split(';');
+        let (name, value) = attributes
+            .next()
+            .and_then(|pair| pair.split_once('='))
+            .ok_or("No '=' between the cookie's name and value")?;
+        let name = name.trim();
+
+        if name.is_empty() {
+            return Err("The cookie has no name".to_string());
+        }
+
+        let mut cookie = Cookie {
+            name: name.to_string(),
+            value: value.trim().trim_matches('"').to_string(),
+            domain: host.clone(),
+            path: default_path(uri.path()),
+            expires: None,
+            secure: false,
+            http_only: false,
+            host_only: true,
+        };
+        let mut max_age = None;
+
+        for attribute in attributes {
+            let (key, value) = attribute.split_once('=').unwrap_or((attribute, ""));
+            let value = value.trim();
+
+            match key.trim().to_ascii_lowercase().as_str() {
+                "expires" => {
+                    cookie.expires = httpdate::parse_http_date(value).ok().map(unix_time);
+                }
+                "max-age" => max_age = value.parse::<i64>().ok(),
+                "domain" if !value.is_empty() => {
+                    let domain = value.trim_start_matches('.').to_ascii_lowercase();
+
+                    if !domain_matches(&host, &domain) {
+                        return Err(format!("Domain={} doesn't match the host {}", value, host));
+                    }
+
+                    cookie.domain = domain;
+                    cookie.host_only = false;
+                }
+                "path" if value.starts_with('/') => cookie.path = value.to_string(),
+                "secure" => cookie.secure = true,
+                "httponly" => cookie.http_only = true,
+                _ => {}
+            }
+        }
+
+        // Max-Age wins over Expires, <= 0 means it's expired right away.
+        // Anything past the latest date we can write is capped to it, like RFC 6265 says
+        if let Some(max_age) = max_age {
+            let expires = unix_time(SystemTime::now()).saturating_add_signed(max_age);
+            cookie.expires = Some(expires.min(LATEST_EXPIRY));
+        }
+
+        Ok(cookie)
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires
+            .is_some_and(|expires| expires <= unix_time(SystemTime::now()))
+    }
+
+    /// Check if this cookie should be sent with a request to `uri`
+    pub fn matches(&self, uri: &Uri) -> bool {
+        let Some(host) = uri.host().map(str::to_ascii_lowercase) else {
+            return false;
+        };
+
+        let domain_ok = if self.host_only {
+            host == self.domain
+        } else {
+            domain_matches(&host, &self.domain)
+        };
+
+        domain_ok
+            && path_matches(uri.path(), &self.path)
+            && (!self.secure || uri.scheme_str() == Some("https"))
+            && !self.is_expired()
+    }
+}
+
+impl CookieJar {
+    /// Store (or remove) all the cookies set by a response to a request for `uri`
+    pub fn store(&mut self, uri: &Uri, headers: &HeaderMap) {
+        for set_cookie in headers.get_all(header::SET_COOKIE) {
+            let Some(cookie) = set_cookie
+                .to_str()
+                .ok()
+                .and_then(|set_cookie| Cookie::parse(set_cookie, uri))
+            else {
+                continue;
+            };
+
+            // A cookie with the same name/domain/path replaces the old one
+            self.cookies.retain(|old| {
+                (&old.name, &old.domain, &old.path) != (&cookie.name, &cookie.domain, &cookie.path)
+            });
+
+            if !cookie.is_expired() {
+                self.cookies.push(cookie);
+            }
+        }
+
+        self.cookies.retain(|cookie| !cookie.is_expired());
+    }
+
+    /// The `Cookie` header to send with a request to `uri`, if any cookies match it
+    ///
+    /// Cookies with longer paths go first, as RFC 6265 suggests
+    pub fn header_for(&self, uri: &Uri) -> Option<String> {
+        let mut cookies: Vec<&Cookie> = self
+            .cookies
+            .iter()
+            .filter(|cookie| cookie.matches(uri))
+            .collect();
+
+        if cookies.is_empty() {
+            return None;
+        }
+
+        cookies.sort_by_key(|cookie| std::cmp::Reverse(cookie.path.len()));
+
+        Some(
+            cookies
+                .iter()
+                .map(|cookie| format!("{}={}", cookie.name, cookie.value))
+                .collect::<Vec<_>>()
+                .join("; "),
+        )
+    }
+}
+
+/// A table of cookies to read in a terminal, with a header row
+/// ```
+/// # use httpc::cookies::{format_table, Cookie};
+/// let uri = "http://example.com/".parse().unwrap();
+/// let table = format_table(&[Cookie::parse("id=abc; HttpOnly", &uri).unwrap()]);
+/// assert!(table.lines().nth(1).unwrap().starts_with("id    abc    example.com  /     session  HttpOnly, HostOnly"));
+///
+/// let forever = Cookie::parse("id=abc; Max-Age=9223372036854775807", &uri).unwrap();
+/// assert!(format_table(&[forever]).contains("Fri, 31 Dec 9999 23:59:59 GMT"));
+///
+/// // A hand-edited jar can still have anything in it
+/// let edited = Cookie { expires: Some(u64::MAX), ..Cookie::parse("id=abc", &uri).unwrap() };
+/// assert!(format_table(&[edited]).contains("far future"));
+/// ```
+pub fn format_table(cookies: &[Cookie]) -> String {
+    const HEADER: [&str; 6] = ["NAME", "VALUE", "DOMAIN", "PATH", "EXPIRES", "FLAGS"];
+    const MAX_VALUE: usize = 40;
+
+    let rows = cookies.iter().map(|cookie| {
+        let value = match cookie.value.char_indices().nth(MAX_VALUE) {
+            Some((end, _)) => format!("{}…", &cookie.value[..end]),
+            None => cookie.value.clone(),
+        };
+
+        let expires = match cookie.expires {
+            Some(_) if cookie.is_expired() => "expired".to_string(),
+            Some(expires) if expires > LATEST_EXPIRY => "far future".to_string(),
+            Some(expires) => httpdate::fmt_http_date(UNIX_EPOCH + Duration::from_secs(expires)),
+            None => "session".to_string(),
+        };
+
+        let flags = [
+            (cookie.secure, "Secure"),
+            (cookie.http_only, "HttpOnly"),
+            (cookie.host_only, "HostOnly"),
+        ]
+        .iter()
+        .filter(|(set, _)| *set)
+        .map(|(_, flag)| *flag)
+        .collect::<Vec<_>>()
+        .join(", ");
+
+        [
+            cookie.name.clone(),
+            value,
+            cookie.domain.clone(),
+            cookie.path.clone(),
+            expires,
+            flags,
+        ]
+    });
+
+    let rows = std::iter::once(HEADER.map(String::from))
+        .chain(rows)
+        .collect::<Vec<_>>();
+
+    let mut widths = [0; 6];
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    rows.iter()
+        .map(|row| {
+            row.iter()
+                .zip(widths)
+                .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+                .collect::<Vec<_>>()
+                .join("  ")
+                .trim_end()
+                .to_string()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// `host` is `domain` or a subdomain of it
+fn domain_matches(host: &str, domain: &str) -> bool {
+    host == domain
+        || (host.ends_with(domain)
+            && host[..host.len() - domain.len()].ends_with('.')
+            && host.parse::<std::net::IpAddr>().is_err())
+}
+
+/// `path` is `cookie_path` or below it
+fn path_matches(path: &str, cookie_path: &str) -> bool {
+    path == cookie_path
+        || (path.starts_with(cookie_path)
+            && (cookie_path.ends_with('/') || path[cookie_path.len()..].starts_with('/')))
+}
+
+/// The directory of the request path, used when no Path attribute is given
+fn default_path(path: &str) -> String {
+    match path.rfind('/') {
+        Some(0) | None => "/".to_string(),
+        Some(end) => path[..end].to_string(),
+    }
+}
+
+fn unix_time(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::ZERO)
+        .as_secs()
+}
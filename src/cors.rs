@@ -0,0 +1,256 @@
+//! `--cors ORIGIN`: send the OPTIONS preflight a browser at `ORIGIN` would send before the
+//! request, then show the server's CORS policy and whether the browser would go ahead with it
+//!
+//! Follows the [Fetch spec's](https://fetch.spec.whatwg.org/#cors-preflight-fetch) checks, with
+//! credentials (cookies or auth) if the request would send any
+
+use http::{header, HeaderMap, Method, Response};
+use owo_colors::OwoColorize;
+
+use crate::helpers::{parse_headers, MColorize};
+
+/// What the preflight asks the server for
+#[derive(Debug)]
+pub struct Preflight {
+    pub origin: String,
+    pub method: Method,
+    /// Lowercased & sorted, only the ones that aren't safelisted
+    pub headers: Vec<String>,
+    /// If the request would include cookies or auth, which a `*` doesn't cover
+    pub credentials: bool,
+}
+
+impl Preflight {
+    /// The preflight for a request with these `-h` headers
+    /// ```
+    /// # use httpc::cors::Preflight;
+    /// let headers = ["Content-Type: application/json".to_string(), "Accept: */*".to_string(), "X-Api-Key: 1".to_string()];
+    /// let preflight = Preflight::new("https://app.example", http::Method::POST, &headers, false).unwrap();
+    /// assert_eq!(preflight.headers, ["content-type", "x-api-key"]);
+    /// ```
+    pub fn new(
+        origin: &str,
+        method: Method,
+        headers: &[String],
+        credentials: bool,
+    ) -> Result<Self, crate::http_request::RequestError> {
+        let mut names = parse_headers(&headers.to_vec())?
+            .into_iter()
+            .filter(|(name, value)| !is_safelisted(name.as_str(), value.to_str().unwrap_or("")))
+            .map(|(name, _)| name.as_str().to_string())
+            .collect::<Vec<_>>();
+        names.sort();
+        names.dedup();
+
+        Ok(Self {
+            origin: origin.trim_end_matches('/').to_string(),
+            method,
+            headers: names,
+            credentials,
+        })
+    }
+
+    /// The headers to send the preflight with
+    pub fn request_headers(&self) -> Vec<String> {
+        let mut headers = vec![
+            format!("Origin: {}", self.origin),
+            format!("Access-Control-Request-Method: {}", self.method),
+        ];
+
+        if !self.headers.is_empty() {
+            headers.push(format!(
+                "Access-Control-Request-Headers: {}",
+                self.headers.join(",")
+            ));
+        }
+
+        headers
+    }
+
+    /// Why a browser would block the request after this preflight response, if it would
+    /// ```
+    /// # use httpc::cors::Preflight;
+    /// let preflight = Preflight::new("https://app.example", http::Method::PUT, &["X-A: 1".to_string()], false).unwrap();
+    /// let response = http::Response::builder()
+    ///     .header("access-control-allow-origin", "https://app.example")
+    ///     .header("access-control-allow-methods", "GET, PUT")
+    ///     .body(vec![])
+    ///     .unwrap();
+    /// assert_eq!(preflight.problems(&response), ["x-a isn't in Access-Control-Allow-Headers"]);
+    /// ```
+    pub fn problems(&self, response: &Response<Vec<u8>>) -> Vec<String> {
+        let headers = response.headers();
+        let mut problems = vec![];
+
+        if !response.status().is_success() {
+            problems.push(format!(
+                "The preflight got a {}, it needs a 2xx",
+                response.status()
+            ));
+        }
+
+        let origins = headers
+            .get_all(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+            .iter()
+            .collect::<Vec<_>>();
+
+        match origins.as_slice() {
+            [] => problems.push("There's no Access-Control-Allow-Origin".to_string()),
+            [origin] if *origin == "*" && self.credentials => problems.push(
+                "Access-Control-Allow-Origin is *, which doesn't cover requests with credentials"
+                    .to_string(),
+            ),
+            [origin] if *origin == "*" || *origin == self.origin.as_str() => {}
+            [origin] => problems.push(format!(
+                "Access-Control-Allow-Origin is {}, not {}",
+                String::from_utf8_lossy(origin.as_bytes()),
+                self.origin
+            )),
+            _ => problems.push("There's more than one Access-Control-Allow-Origin".to_string()),
+        }
+
+        if self.credentials && !is_true(headers, header::ACCESS_CONTROL_ALLOW_CREDENTIALS) {
+            problems.push(
+                "The request has credentials, but Access-Control-Allow-Credentials isn't true"
+                    .to_string(),
+            );
+        }
+
+        let wildcard = !self.credentials;
+        let methods = list(headers, header::ACCESS_CONTROL_ALLOW_METHODS);
+        let method_allowed = matches!(self.method, Method::GET | Method::HEAD | Method::POST)
+            || methods.iter().any(|method| method == self.method.as_str())
+            || (wildcard && methods.iter().any(|method| method == "*"));
+
+        if !method_allowed {
+            problems.push(format!(
+                "{} isn't in Access-Control-Allow-Methods",
+                self.method
+            ));
+        }
+
+        let allowed_headers = list(headers, header::ACCESS_CONTROL_ALLOW_HEADERS);
+
+        for name in &self.headers {
+            let allowed = allowed_headers
+                .iter()
+                .any(|allowed| allowed.eq_ignore_ascii_case(name))
+                // * never covers Authorization
+                || (wildcard && name != "authorization" && allowed_headers.iter().any(|a| a == "*"));
+
+            if !allowed {
+                problems.push(format!("{} isn't in Access-Control-Allow-Headers", name));
+            }
+        }
+
+        problems
+    }
+}
+
+/// The policy from a preflight response, as a table, then what the browser would do
+pub fn format_policy(preflight: &Preflight, response: &Response<Vec<u8>>) -> String {
+    let headers = response.headers();
+    let value = |name: header::HeaderName| {
+        let values = headers
+            .get_all(name)
+            .iter()
+            .map(|value| String::from_utf8_lossy(value.as_bytes()).into_owned())
+            .collect::<Vec<_>>();
+
+        if values.is_empty() {
+            "(not set)".out_color(|t| t.bright_black()).to_string()
+        } else {
+            values.join(", ")
+        }
+    };
+
+    let rows = [
+        ("Status", response.status().to_string()),
+        ("Allowed origin", value(header::ACCESS_CONTROL_ALLOW_ORIGIN)),
+        (
+            "Allowed methods",
+            value(header::ACCESS_CONTROL_ALLOW_METHODS),
+        ),
+        (
+            "Allowed headers",
+            value(header::ACCESS_CONTROL_ALLOW_HEADERS),
+        ),
+        (
+            "Credentials",
+            value(header::ACCESS_CONTROL_ALLOW_CREDENTIALS),
+        ),
+        ("Max age", value(header::ACCESS_CONTROL_MAX_AGE)),
+        ("Vary", value(header::VARY)),
+    ];
+
+    let asked = if preflight.headers.is_empty() {
+        String::new()
+    } else {
+        format!(" with {}", preflight.headers.join(", "))
+    };
+    let mut output = format!(
+        "{}\n",
+        format!(
+            "CORS preflight for a {}{} from {}",
+            preflight.method, asked, preflight.origin
+        )
+        .out_color(|t| t.bold())
+    );
+
+    for (name, value) in rows {
+        // Padded before coloring, the color codes would throw off the width
+        let name = format!("{:<16}", name);
+        output.push_str(&format!("{} {}\n", name.out_color(|t| t.cyan()), value));
+    }
+
+    output.push('\n');
+
+    match preflight.problems(response).as_slice() {
+        [] => output.push_str(&format!(
+            "{} A browser at {} would send this request\n",
+            "✓".out_color(|t| t.green()),
+            preflight.origin
+        )),
+        problems => {
+            for problem in problems {
+                output.push_str(&format!("{} {}\n", "✗".out_color(|t| t.red()), problem));
+            }
+        }
+    }
+
+    output
+}
+
+/// If a request header doesn't need the server's permission (the CORS-safelisted ones)
+fn is_safelisted(name: &str, value: &str) -> bool {
+    match name {
+        "accept" | "accept-language" | "content-language" => true,
+        "content-type" => {
+            let essence = value.split(';').next().unwrap_or("").trim();
+            [
+                "application/x-www-form-urlencoded",
+                "multipart/form-data",
+                "text/plain",
+            ]
+            .iter()
+            .any(|allowed| essence.eq_ignore_ascii_case(allowed))
+        }
+        _ => false,
+    }
+}
+
+/// A comma-separated header's values, across every time it's given
+fn list(headers: &HeaderMap, name: header::HeaderName) -> Vec<String> {
+    headers
+        .get_all(name)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+        .collect()
+}
+
+fn is_true(headers: &HeaderMap, name: header::HeaderName) -> bool {
+    headers.get(name).is_some_and(|value| value == "true")
+}
@@ -0,0 +1,191 @@
+//! `--print-curl`: the `curl` command that sends the same request, for sharing with people who
+//! don't have httpc
+//!
+//! The request is built exactly like it would be sent (profile, session cookies, auth, --var...),
+//! so the command has everything in it, secrets included. Options curl doesn't have an equivalent
+//! for (pooling, --safe-fetch, --cache-dir...) are left out
+
+use http::{header, Method, Request};
+
+use crate::{
+    cli::CommonOptions,
+    http_request::RemovedHeaders,
+    resume::ContinueAt,
+    warnings::{warn, WarningKind},
+};
+
+/// The curl command for a request, one option per line
+/// ```
+/// # use clap::Parser;
+/// # use httpc::{cli::CommonOptions, curl::curl_command};
+/// let options = CommonOptions::parse_from(["httpc", "-k", "-l", "https://example.com"]);
+/// let request = http::Request::post("https://example.com/things")
+///     .header("Content-Type", "application/json")
+///     .header("User-Agent", "me")
+///     .body(Some(&br#"{"it's": 1}"#[..]))
+///     .unwrap();
+/// assert_eq!(
+///     curl_command(&request, &options),
+///     "curl \\\n  -H 'content-type: application/json' \\\n  -H 'user-agent: me' \\\n  --data-binary '{\"it'\\''s\": 1}' \\\n  -k \\\n  -L \\\n  https://example.com/things"
+/// );
+/// ```
+pub fn curl_command(request: &Request<Option<&[u8]>>, options: &CommonOptions) -> String {
+    let mut args: Vec<String> = vec![];
+    let mut arg = |option: &str, value: Option<&str>| match value {
+        Some(value) => args.push(format!("{} {}", option, shell_quote(value))),
+        None => args.push(option.to_string()),
+    };
+
+    let body = *request.body();
+
+    // curl picks GET, or POST for a body, on its own
+    match request.method() {
+        &Method::HEAD => arg("--head", None),
+        &Method::GET if body.is_none() => {}
+        &Method::POST if body.is_some() => {}
+        method => arg("-X", Some(method.as_str())),
+    }
+
+    for (name, value) in request.headers() {
+        match String::from_utf8_lossy(value.as_bytes()) {
+            // "name:" would remove it
+            value if value.is_empty() => arg("-H", Some(&format!("{};", name))),
+            value => arg("-H", Some(&format!("{}: {}", name, value))),
+        }
+    }
+
+    let removed = request
+        .extensions()
+        .get::<RemovedHeaders>()
+        .map(|removed| removed.0.as_slice())
+        .unwrap_or_default();
+
+    for name in removed {
+        arg("-H", Some(&format!("{}:", name)));
+    }
+
+    if !request.headers().contains_key(header::USER_AGENT) && !removed.contains(&header::USER_AGENT)
+    {
+        arg("-A", Some(&format!("httpc/{}", env!("CARGO_PKG_VERSION"))));
+    }
+
+    match body.map(std::str::from_utf8) {
+        Some(Ok(text)) if !text.contains('\0') => arg("--data-binary", Some(text)),
+        Some(_) => {
+            warn(
+                WarningKind::Usage,
+                "The body isn't text, so the curl command reads it from stdin",
+            );
+            arg("--data-binary", Some("@-"));
+        }
+        None => {}
+    }
+
+    if options.insecure {
+        arg("-k", None);
+    }
+    if let Some(cacert) = &options.cacert {
+        arg("--cacert", Some(cacert));
+    }
+    if options.ipv4 {
+        arg("-4", None);
+    }
+    if options.ipv6 {
+        arg("-6", None);
+    }
+    if let Some(proxy) = &options.proxy {
+        arg("--proxy", Some(&proxy.to_string()));
+    }
+    if let Some(proxy_user) = &options.proxy_user {
+        arg("--proxy-user", Some(proxy_user));
+    }
+    if let Some(target) = &options.request_target {
+        arg("--request-target", Some(target));
+    } else if let Some(form) = options.target_form {
+        arg("--request-target", Some(&form.target(request.uri())));
+    }
+    if options.location {
+        arg("-L", None);
+    }
+    if options.location && options.redirect.trusted {
+        arg("--location-trusted", None);
+    }
+    if options.location && !options.redirect.protocols.is_empty() {
+        arg(
+            "--proto-redir",
+            Some(&format!("={}", options.redirect.protocols.join(","))),
+        );
+    }
+    if options.auto_referer() {
+        arg("-e", Some(";auto"));
+    }
+    if let Some(max_time) = options.max_time {
+        arg("--max-time", Some(&max_time.as_secs_f64().to_string()));
+    }
+    if let Some(connect_timeout) = options.connect_timeout {
+        arg(
+            "--connect-timeout",
+            Some(&connect_timeout.as_secs_f64().to_string()),
+        );
+    }
+    if let Some(max_filesize) = options.max_filesize {
+        arg("--max-filesize", Some(&max_filesize.to_string()));
+    }
+    if let Some(limit_rate) = options.limit_rate {
+        arg("--limit-rate", Some(&limit_rate.to_string()));
+    }
+    if let Some(trace) = &options.trace {
+        arg("--trace", Some(trace));
+    }
+    if let Some(trace_ascii) = &options.trace_ascii {
+        arg("--trace-ascii", Some(trace_ascii));
+    }
+    if options.include {
+        arg("-i", None);
+    }
+    if options.fail {
+        arg("--fail", None);
+    }
+    if let Some(dump_header) = &options.dump_header {
+        arg("-D", Some(dump_header));
+    }
+    if options.remote_name {
+        arg("-O", None);
+
+        if options.remote_header_name {
+            arg("-J", None);
+        }
+    } else if let Some(output) = &options.output {
+        arg("-o", Some(output));
+    }
+    match options.continue_at {
+        Some(ContinueAt::Auto) => arg("-C", Some("-")),
+        Some(ContinueAt::Offset(offset)) => arg("-C", Some(&offset.to_string())),
+        None => {}
+    }
+    if let Some(write_out) = &options.write_out {
+        arg("-w", Some(write_out));
+    }
+
+    arg(&shell_quote(&request.uri().to_string()), None);
+
+    format!("curl \\\n  {}", args.join(" \\\n  "))
+}
+
+/// Quote a string for a POSIX shell, if it needs it
+/// ```
+/// # use httpc::curl::shell_quote;
+/// assert_eq!(shell_quote("https://example.com/a"), "https://example.com/a");
+/// assert_eq!(shell_quote("a b&c"), "'a b&c'");
+/// assert_eq!(shell_quote("it's"), r"'it'\''s'");
+/// assert_eq!(shell_quote(""), "''");
+/// ```
+pub fn shell_quote(value: &str) -> String {
+    let safe = |c: char| c.is_ascii_alphanumeric() || "-_./:=@%+,".contains(c);
+
+    if !value.is_empty() && value.chars().all(safe) {
+        value.to_string()
+    } else {
+        format!("'{}'", value.replace('\'', r"'\''"))
+    }
+}
@@ -0,0 +1,269 @@
+//! `httpc diff URL1 URL2`: send the same request to both, then show how the responses differ,
+//! for comparing staging with production or checking a migration didn't change anything
+//!
+//! Headers that are different on every response anyway (like Date) are left out. JSON, HTML,
+//! XML and CSS bodies are reindented before they're compared, so a change shows up as the lines
+//! it's on instead of the whole body
+
+use std::fmt::Write;
+
+use http::{header, HeaderMap, Response};
+use owo_colors::OwoColorize;
+
+use crate::{
+    helpers::{is_binary, MColorize},
+    pretty::{prettify, Pretty},
+    spool::{body_len, body_prefix},
+};
+
+/// Headers that change from one response to the next even when nothing else does
+pub const VOLATILE_HEADERS: [&str; 14] = [
+    "date",
+    "age",
+    "expires",
+    "last-modified",
+    "etag",
+    "x-request-id",
+    "x-correlation-id",
+    "x-amzn-requestid",
+    "x-amzn-trace-id",
+    "x-amz-cf-id",
+    "cf-ray",
+    "server-timing",
+    "x-runtime",
+    "x-response-time",
+];
+
+/// Unchanged lines kept around each change, the rest are left out
+const CONTEXT: usize = 3;
+
+/// Past this many lines on both sides multiplied, bodies are only said to be different instead of
+/// diffed line by line, since that takes lines × lines memory
+const MAX_DIFF_CELLS: usize = 16_000_000;
+
+/// One line of a diff
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Line<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// The lines that changed from `old` to `new` (and the ones that didn't), as the shortest edit
+/// that keeps the longest run of common lines in order
+/// ```
+/// # use httpc::diff::{diff_lines, Line};
+/// assert_eq!(
+///     diff_lines(&["a", "b", "c"], &["a", "c", "d"]),
+///     vec![Line::Same("a"), Line::Removed("b"), Line::Same("c"), Line::Added("d")]
+/// );
+/// assert_eq!(diff_lines(&[], &["a"]), vec![Line::Added("a")]);
+/// ```
+pub fn diff_lines<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<Line<'a>> {
+    // common[i][j] is how many lines old[i..] and new[j..] have in common
+    let mut common = vec![vec![0u32; new.len() + 1]; old.len() + 1];
+
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = match old[i] == new[j] {
+                true => common[i + 1][j + 1] + 1,
+                false => common[i + 1][j].max(common[i][j + 1]),
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut lines = vec![];
+
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            lines.push(Line::Same(old[i]));
+            i += 1;
+            j += 1;
+        } else if common[i + 1][j] >= common[i][j + 1] {
+            lines.push(Line::Removed(old[i]));
+            i += 1;
+        } else {
+            lines.push(Line::Added(new[j]));
+            j += 1;
+        }
+    }
+
+    lines.extend(old[i..].iter().map(|line| Line::Removed(line)));
+    lines.extend(new[j..].iter().map(|line| Line::Added(line)));
+    lines
+}
+
+/// The diff of two responses for the terminal, and if there was any difference
+pub fn diff_responses(
+    (old_url, old): (&str, &Response<Vec<u8>>),
+    (new_url, new): (&str, &Response<Vec<u8>>),
+    ignored_headers: &[String],
+) -> std::io::Result<(String, bool)> {
+    let mut output = String::new();
+    let mut different = false;
+
+    let _ = writeln!(
+        output,
+        "{}\n{}\n",
+        format!("--- {}", old_url).out_color(|t| t.red()),
+        format!("+++ {}", new_url).out_color(|t| t.green())
+    );
+
+    if old.status() == new.status() {
+        let _ = writeln!(output, "  {}", old.status());
+    } else {
+        different = true;
+        let _ = writeln!(
+            output,
+            "{}\n{}",
+            format!("- {}", old.status()).out_color(|t| t.red()),
+            format!("+ {}", new.status()).out_color(|t| t.green())
+        );
+    }
+
+    let ignored = |name: &str| {
+        VOLATILE_HEADERS.contains(&name)
+            || ignored_headers
+                .iter()
+                .any(|ignored| ignored.eq_ignore_ascii_case(name))
+    };
+    let old_headers = header_lines(old.headers(), ignored);
+    let new_headers = header_lines(new.headers(), ignored);
+    let old_headers = old_headers.iter().map(String::as_str).collect::<Vec<_>>();
+    let new_headers = new_headers.iter().map(String::as_str).collect::<Vec<_>>();
+
+    if write_diff(&mut output, &diff_lines(&old_headers, &new_headers)) {
+        different = true;
+    } else {
+        let _ = writeln!(
+            output,
+            "{}",
+            format!("Headers are the same ({})", old_headers.len()).out_color(|t| t.bright_black())
+        );
+    }
+
+    output.push('\n');
+
+    match diff_bodies(old, new)? {
+        Some(diff) => {
+            different = true;
+            output.push_str(&diff);
+        }
+        None => {
+            let _ = write!(
+                output,
+                "{}",
+                format!("Bodies are the same ({} bytes)", body_len(old))
+                    .out_color(|t| t.bright_black())
+            );
+        }
+    }
+
+    Ok((output.trim_end().to_string(), different))
+}
+
+/// How the body of `new` differs from `old`'s, or `None` if they're the same
+pub fn diff_bodies(
+    old: &Response<Vec<u8>>,
+    new: &Response<Vec<u8>>,
+) -> std::io::Result<Option<String>> {
+    let old_body = body_prefix(old, body_len(old))?;
+    let new_body = body_prefix(new, body_len(new))?;
+
+    if old_body == new_body {
+        return Ok(None);
+    }
+
+    if is_binary(old.headers(), &old_body) || is_binary(new.headers(), &new_body) {
+        return Ok(Some(
+            format!(
+                "Binary bodies differ ({} and {} bytes)",
+                old_body.len(),
+                new_body.len()
+            )
+            .out_color(|t| t.yellow())
+            .to_string(),
+        ));
+    }
+
+    let old_text = body_text(old.headers(), &old_body);
+    let new_text = body_text(new.headers(), &new_body);
+    let old_lines = old_text.lines().collect::<Vec<_>>();
+    let new_lines = new_text.lines().collect::<Vec<_>>();
+
+    if old_lines.len().saturating_mul(new_lines.len()) > MAX_DIFF_CELLS {
+        return Ok(Some(
+            format!(
+                "Bodies differ ({} and {} lines, too many to compare line by line)",
+                old_lines.len(),
+                new_lines.len()
+            )
+            .out_color(|t| t.yellow())
+            .to_string(),
+        ));
+    }
+
+    let mut output = String::new();
+    write_diff(&mut output, &diff_lines(&old_lines, &new_lines));
+    Ok(Some(output.trim_end().to_string()))
+}
+
+/// `name: value` lines for the headers that aren't `ignored`, sorted so their order doesn't
+/// count as a difference
+fn header_lines(headers: &HeaderMap, ignored: impl Fn(&str) -> bool) -> Vec<String> {
+    let mut lines = headers
+        .iter()
+        .filter(|(name, _)| !ignored(name.as_str()))
+        .map(|(name, value)| format!("{}: {}", name, String::from_utf8_lossy(value.as_bytes())))
+        .collect::<Vec<_>>();
+
+    lines.sort();
+    lines
+}
+
+/// The body as text, reindented if it's a format we know
+fn body_text(headers: &HeaderMap, body: &[u8]) -> String {
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+
+    prettify(content_type, &String::from_utf8_lossy(body), Pretty::Format)
+}
+
+/// Write the changed lines of a diff with the [`CONTEXT`] around them, returning if anything
+/// changed
+fn write_diff(output: &mut String, lines: &[Line]) -> bool {
+    let changed = |line: &Line| !matches!(line, Line::Same(_));
+    let near_change = |index: usize| {
+        let start = index.saturating_sub(CONTEXT);
+        let end = (index + CONTEXT + 1).min(lines.len());
+        lines[start..end].iter().any(changed)
+    };
+    let mut skipped = false;
+
+    for (index, line) in lines.iter().enumerate() {
+        if !near_change(index) {
+            skipped = true;
+            continue;
+        }
+
+        if skipped {
+            let _ = writeln!(output, "{}", "  …".out_color(|t| t.bright_black()));
+            skipped = false;
+        }
+
+        let _ = match line {
+            Line::Same(line) => writeln!(output, "  {}", line),
+            Line::Removed(line) => {
+                writeln!(output, "{}", format!("- {}", line).out_color(|t| t.red()))
+            }
+            Line::Added(line) => {
+                writeln!(output, "{}", format!("+ {}", line).out_color(|t| t.green()))
+            }
+        };
+    }
+
+    lines.iter().any(changed)
+}
@@ -0,0 +1,205 @@
+//! `--prefetch-hints`: fetch what a response said to preload (`Link: <...>; rel=preload`, in a
+//! `103 Early Hints` or the response itself) and report how much a browser starting on them at
+//! the hint would've saved, compared to only finding them once the response had all arrived
+
+use std::time::Duration;
+
+use http::{header, HeaderMap, Response, StatusCode, Uri};
+use owo_colors::OwoColorize;
+
+use crate::{
+    helpers::{resolve_url, MColorize},
+    http_request::Informational,
+    write_out::Timings,
+};
+
+/// A resource the response hinted at
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Hint {
+    pub url: String,
+    /// Hinted in a `103 Early Hints`, before the response itself
+    pub early: bool,
+}
+
+/// How fetching a hinted resource went
+#[derive(Clone, Debug)]
+pub struct Prefetched {
+    pub status: StatusCode,
+    pub size: usize,
+    pub took: Duration,
+}
+
+/// The targets of the `rel=preload` (or `modulepreload`) links in a `Link` header
+/// ```
+/// # use httpc::early_hints::preload_links;
+/// let links = preload_links(r#"</a.css>; rel=preload; as=style, </b,c.js>; rel="modulepreload", </next>; rel=prefetch"#);
+/// assert_eq!(links, ["/a.css", "/b,c.js"]);
+/// ```
+pub fn preload_links(value: &str) -> Vec<String> {
+    let mut links = vec![];
+    let mut rest = value;
+
+    while let Some(start) = rest.find('<') {
+        let Some(end) = rest[start..].find('>') else {
+            break;
+        };
+        let target = &rest[start + 1..start + end];
+        rest = &rest[start + end + 1..];
+
+        // The params go up to the next link, commas in quoted values don't count
+        let mut quoted = false;
+        let params_end = rest
+            .char_indices()
+            .find(|&(_, c)| {
+                quoted ^= c == '"';
+                c == ',' && !quoted
+            })
+            .map_or(rest.len(), |(i, _)| i);
+        let params = &rest[..params_end];
+        rest = &rest[params_end..];
+
+        let preload = params.split(';').any(|param| {
+            param.split_once('=').is_some_and(|(key, value)| {
+                key.trim().eq_ignore_ascii_case("rel")
+                    && value
+                        .trim()
+                        .trim_matches('"')
+                        .split_whitespace()
+                        .any(|rel| {
+                            rel.eq_ignore_ascii_case("preload")
+                                || rel.eq_ignore_ascii_case("modulepreload")
+                        })
+            })
+        });
+
+        if preload {
+            links.push(target.trim().to_string());
+        }
+    }
+
+    links
+}
+
+/// Everything the response hinted at, resolved against `base`. Early hints come first, and
+/// anything hinted twice only counts the first time
+pub fn preload_hints(response: &Response<Vec<u8>>, base: &Uri) -> Vec<Hint> {
+    let early = response
+        .extensions()
+        .get::<Informational>()
+        .into_iter()
+        .flat_map(|informational| &informational.0)
+        .filter(|(status, _)| status.as_u16() == 103)
+        .map(|(_, headers)| (true, headers));
+    let heads = early.chain([(false, response.headers())]);
+
+    let mut hints: Vec<Hint> = vec![];
+
+    for (is_early, headers) in heads {
+        for url in header_links(headers) {
+            // Protocol-relative, like CDNs tend to use
+            let url = match url.strip_prefix("//") {
+                Some(url) => format!("{}://{}", base.scheme_str().unwrap_or("http"), url),
+                None => resolve_url(base, &url),
+            };
+
+            if !hints.iter().any(|hint| hint.url == url) {
+                hints.push(Hint {
+                    url,
+                    early: is_early,
+                });
+            }
+        }
+    }
+
+    hints
+}
+
+fn header_links(headers: &HeaderMap) -> Vec<String> {
+    headers
+        .get_all(header::LINK)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(preload_links)
+        .collect()
+}
+
+/// How each prefetch went, then how much sooner they could've been done if they were started at
+/// their hint instead of after the whole response arrived
+pub fn format_report(
+    hints: &[Hint],
+    results: &[Result<Prefetched, String>],
+    timings: Option<&Timings>,
+    took: Duration,
+) -> String {
+    let millis = |duration: Duration| format!("{:.1}ms", duration.as_secs_f64() * 1000.0);
+
+    // How much earlier than the end of the response each kind of hint was known
+    let lead = |early: bool| {
+        timings.and_then(|timings| {
+            let hinted_at = if early {
+                timings.early_hints?
+            } else {
+                timings.first_byte
+            };
+            Some(timings.total.saturating_sub(hinted_at))
+        })
+    };
+
+    let early = hints.iter().filter(|hint| hint.early).count();
+    let mut report = format!(
+        "{} {} ({} from 103 Early Hints",
+        "Preload hints:".out_color(|t| t.cyan()),
+        hints.len(),
+        early
+    );
+
+    if let Some(timings) = timings {
+        if let Some(early_hints) = timings.early_hints {
+            report.push_str(&format!(
+                ", {} before the response",
+                millis(timings.first_byte.saturating_sub(early_hints))
+            ));
+        }
+    }
+    report.push_str(")\n");
+
+    let mut saved = Duration::ZERO;
+
+    for (hint, result) in hints.iter().zip(results) {
+        let source = if hint.early { "103" } else { "Link" };
+
+        match result {
+            Ok(prefetched) => {
+                let could_save = lead(hint.early).map(|lead| lead.min(prefetched.took));
+                saved = saved.max(could_save.unwrap_or_default());
+
+                report.push_str(&format!(
+                    "  {} {:>8} {:>9}  {}  ({}{})\n",
+                    prefetched.status.as_u16(),
+                    millis(prefetched.took),
+                    format!("{} B", prefetched.size),
+                    hint.url,
+                    source,
+                    could_save.map_or(String::new(), |saved| format!(
+                        ", saves up to {}",
+                        millis(saved)
+                    ))
+                ));
+            }
+            Err(err) => report.push_str(&format!(
+                "  {} {}: {}  ({})\n",
+                "✗".out_color(|t| t.red()),
+                hint.url,
+                err,
+                source
+            )),
+        }
+    }
+
+    report.push_str(&format!(
+        "Prefetched in {} (in parallel), starting at the hints could've saved up to {}\n",
+        millis(took),
+        millis(saved)
+    ));
+    report
+}
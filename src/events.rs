@@ -0,0 +1,245 @@
+//! What happens on the way to a response, as it happens: the DNS lookup, connecting, the TLS
+//! handshake, writing the request, the first byte of the response and the end of its body
+//!
+//! Each [`EventHook`] in [`RequestOptions::events`] is called with every [`ConnectionEvent`],
+//! which says how long that step took and who's on the other end. `-vv` prints them, and the
+//! events of each request are kept in its response's [`ConnectionEvents`] for `--timing`
+//!
+//! ```no_run
+//! use httpc::{
+//!     events::{EventHook, EventKind},
+//!     http_request::{http_request, RequestOptions},
+//! };
+//!
+//! let options = RequestOptions {
+//!     events: vec![EventHook::new(|event| {
+//!         if let EventKind::TcpConnected { .. } = event.kind {
+//!             eprintln!("connected to {:?} in {:?}", event.peer, event.took);
+//!         }
+//!     })],
+//!     ..Default::default()
+//! };
+//! let request = http::Request::get("http://example.com/").body(None).unwrap();
+//! http_request(request, &options).unwrap();
+//! ```
+//!
+//! [`RequestOptions::events`]: crate::http_request::RequestOptions::events
+
+use std::{
+    fmt,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use owo_colors::OwoColorize;
+
+use crate::{helpers::MColorize, transfer::format_bytes};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EventKind {
+    /// The host was looked up (or found in the DNS cache)
+    DnsResolved { addresses: Vec<SocketAddr> },
+    /// A TCP connection was made, from `local`
+    TcpConnected { local: Option<SocketAddr> },
+    /// The TLS handshake is done and the certificate checked out
+    TlsNegotiated,
+    /// The request's head and body were written
+    RequestWritten { bytes: u64 },
+    /// The response started arriving (which could be an interim `1xx` response)
+    FirstByte,
+    /// The whole response has arrived
+    BodyComplete { bytes: u64 },
+}
+
+/// One step of a request
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConnectionEvent {
+    pub kind: EventKind,
+    /// The host:port the step was for, the proxy's when connecting to one
+    pub authority: String,
+    /// The address connected to, once there is a connection
+    pub peer: Option<SocketAddr>,
+    /// When it happened
+    pub at: Instant,
+    /// How long the step took: the lookup, connecting, the handshake, writing the request,
+    /// waiting for the response or reading it
+    pub took: Duration,
+}
+
+impl ConnectionEvent {
+    /// A line saying what happened, for `-vv`
+    /// ```
+    /// # use httpc::events::{ConnectionEvent, EventKind};
+    /// # use std::time::{Duration, Instant};
+    /// let event = ConnectionEvent {
+    ///     kind: EventKind::TcpConnected { local: Some("127.0.0.1:50000".parse().unwrap()) },
+    ///     authority: "localhost:8080".to_string(),
+    ///     peer: Some("127.0.0.1:8080".parse().unwrap()),
+    ///     at: Instant::now(),
+    ///     took: Duration::from_micros(1250),
+    /// };
+    /// assert_eq!(event.describe(), "Connected to 127.0.0.1:8080 from 127.0.0.1:50000 (1.2ms)");
+    /// ```
+    pub fn describe(&self) -> String {
+        let peer = self
+            .peer
+            .map_or_else(|| self.authority.clone(), |peer| peer.to_string());
+
+        let what = match &self.kind {
+            EventKind::DnsResolved { addresses } => format!(
+                "Resolved {} to {}",
+                self.authority,
+                addresses
+                    .iter()
+                    .map(|address| address.ip().to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            EventKind::TcpConnected { local: Some(local) } => {
+                format!("Connected to {} from {}", peer, local)
+            }
+            EventKind::TcpConnected { local: None } => format!("Connected to {}", peer),
+            EventKind::TlsNegotiated => format!("TLS handshake with {} done", peer),
+            EventKind::RequestWritten { bytes } => {
+                format!("Sent {} to {}", format_bytes(*bytes), peer)
+            }
+            EventKind::FirstByte => format!("First byte from {}", peer),
+            EventKind::BodyComplete { bytes } => {
+                format!("Received {} from {}", format_bytes(*bytes), peer)
+            }
+        };
+
+        format!("{} ({:.1}ms)", what, self.took.as_secs_f64() * 1000.0)
+    }
+}
+
+/// Something to call with each [`ConnectionEvent`], it's called on the thread sending the
+/// request so it should be quick
+#[derive(Clone)]
+pub struct EventHook(Arc<dyn Fn(&ConnectionEvent) + Send + Sync>);
+
+impl fmt::Debug for EventHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("EventHook")
+    }
+}
+
+impl EventHook {
+    pub fn new(hook: impl Fn(&ConnectionEvent) + Send + Sync + 'static) -> Self {
+        Self(Arc::new(hook))
+    }
+
+    /// Print each event as it happens, for `-vv`
+    pub fn print() -> Self {
+        Self::new(|event| {
+            println!(
+                "{}",
+                format!("* {}", event.describe()).out_color(|t| t.bright_black())
+            );
+
+            // The response is shown next
+            if let EventKind::BodyComplete { .. } = event.kind {
+                println!();
+            }
+        })
+    }
+
+    /// A hook that keeps every event, and the events it's kept so far
+    pub fn recorder() -> (Self, Arc<Mutex<Vec<ConnectionEvent>>>) {
+        let events = Arc::new(Mutex::new(vec![]));
+        let recorded = events.clone();
+
+        let hook = Self::new(move |event| {
+            if let Ok(mut events) = recorded.lock() {
+                events.push(event.clone());
+            }
+        });
+
+        (hook, events)
+    }
+}
+
+/// Call every hook with an event
+pub fn emit(
+    hooks: &[EventHook],
+    kind: EventKind,
+    authority: &str,
+    peer: Option<SocketAddr>,
+    took: Duration,
+) {
+    if hooks.is_empty() {
+        return;
+    }
+
+    let event = ConnectionEvent {
+        kind,
+        authority: authority.to_string(),
+        peer,
+        at: Instant::now(),
+        took,
+    };
+
+    for hook in hooks {
+        (hook.0)(&event);
+    }
+}
+
+/// Every event on the way to a response, in order. Stored in the response's extensions (unless
+/// it came from the cache). A reused connection has no DNS, connect or TLS events
+#[derive(Clone, Debug, Default)]
+pub struct ConnectionEvents(pub Vec<ConnectionEvent>);
+
+impl ConnectionEvents {
+    /// How long each step took, named like `--timing` shows them
+    /// ```
+    /// # use httpc::events::{ConnectionEvent, ConnectionEvents, EventKind};
+    /// # use std::time::{Duration, Instant};
+    /// let event = |kind, millis| ConnectionEvent {
+    ///     kind,
+    ///     authority: "example.com:80".to_string(),
+    ///     peer: None,
+    ///     at: Instant::now(),
+    ///     took: Duration::from_millis(millis),
+    /// };
+    /// let events = ConnectionEvents(vec![
+    ///     event(EventKind::RequestWritten { bytes: 78 }, 1),
+    ///     event(EventKind::FirstByte, 30),
+    ///     event(EventKind::BodyComplete { bytes: 512 }, 2),
+    /// ]);
+    /// assert_eq!(
+    ///     events.phases(),
+    ///     [("send", Duration::from_millis(1)), ("wait", Duration::from_millis(30)), ("receive", Duration::from_millis(2))]
+    /// );
+    /// ```
+    pub fn phases(&self) -> Vec<(&'static str, Duration)> {
+        self.0
+            .iter()
+            .map(|event| {
+                let name = match event.kind {
+                    EventKind::DnsResolved { .. } => "dns",
+                    EventKind::TcpConnected { .. } => "connect",
+                    EventKind::TlsNegotiated => "tls",
+                    EventKind::RequestWritten { .. } => "send",
+                    EventKind::FirstByte => "wait",
+                    EventKind::BodyComplete { .. } => "receive",
+                };
+
+                (name, event.took)
+            })
+            .collect()
+    }
+
+    /// The address the response came from
+    pub fn peer(&self) -> Option<SocketAddr> {
+        self.0.iter().rev().find_map(|event| event.peer)
+    }
+
+    /// If the request went over a connection that was already open
+    pub fn reused(&self) -> bool {
+        !self
+            .0
+            .iter()
+            .any(|event| matches!(event.kind, EventKind::TcpConnected { .. }))
+    }
+}
@@ -0,0 +1,334 @@
+//! `--export-code`: the request as code using another language's usual HTTP client, to paste
+//! into an app or script
+//!
+//! Like `--print-curl`, it's the request exactly as it'd be sent, default headers (like
+//! User-Agent) included, minus the ones every client sets itself (Host, Connection,
+//! Content-Length). Options the client has no way to do are left as a comment
+
+use std::fmt::Write;
+
+use clap::ArgEnum;
+use http::{
+    header::{self, HeaderName},
+    Method, Request,
+};
+
+use crate::{
+    cli::CommonOptions,
+    http_request::{resolved_headers, RequestError},
+};
+
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CodeLanguage {
+    /// Python with requests
+    Python,
+    /// JavaScript with fetch
+    Js,
+    /// Rust with reqwest's blocking client
+    Rust,
+}
+
+/// Headers the client sets on its own
+const CLIENT_HEADERS: [HeaderName; 3] = [header::HOST, header::CONNECTION, header::CONTENT_LENGTH];
+
+/// The code that sends a request
+/// ```
+/// # use clap::Parser;
+/// # use httpc::{cli::CommonOptions, export_code::{export_code, CodeLanguage}};
+/// let options = CommonOptions::parse_from(["httpc", "-k", "https://example.com"]);
+/// let request = http::Request::post("https://example.com/things")
+///     .header("Content-Type", "application/json")
+///     .header("User-Agent", "me")
+///     .body(Some(&br#"{"a": 1}"#[..]))
+///     .unwrap();
+/// assert_eq!(
+///     export_code(CodeLanguage::Python, &request, &options).unwrap(),
+///     r#"import requests
+///
+/// response = requests.request(
+///     "POST",
+///     "https://example.com/things",
+///     headers={
+///         "content-type": "application/json",
+///         "user-agent": "me",
+///     },
+///     data="{\"a\": 1}",
+///     verify=False,
+///     allow_redirects=False,
+/// )
+///
+/// print(response.status_code)
+/// print(response.text)
+/// "#
+/// );
+/// ```
+pub fn export_code(
+    language: CodeLanguage,
+    request: &Request<Option<&[u8]>>,
+    options: &CommonOptions,
+) -> Result<String, RequestError> {
+    let headers = headers(request)?;
+    let url = request.uri().to_string();
+    let body = *request.body();
+
+    let code = match language {
+        CodeLanguage::Python => python(request.method(), &url, &headers, body, options),
+        CodeLanguage::Js => js(request.method(), &url, &headers, body, options),
+        CodeLanguage::Rust => rust(request.method(), &url, &headers, body, options),
+    }?;
+
+    Ok(code)
+}
+
+/// Each header once, since not every client can repeat them, with repeated ones joined together
+fn headers(request: &Request<Option<&[u8]>>) -> Result<Vec<(String, String)>, RequestError> {
+    let resolved = resolved_headers(request)?;
+
+    let headers = resolved
+        .keys()
+        .filter(|name| !CLIENT_HEADERS.contains(name))
+        .map(|name| {
+            let separator = if name == header::COOKIE { "; " } else { ", " };
+            let value = resolved
+                .get_all(name)
+                .iter()
+                .map(|value| String::from_utf8_lossy(value.as_bytes()).into_owned())
+                .collect::<Vec<_>>()
+                .join(separator);
+
+            (name.to_string(), value)
+        })
+        .collect();
+
+    Ok(headers)
+}
+
+/// A string literal that works in both Python and JavaScript
+fn quoted(text: &str) -> String {
+    serde_json::Value::from(text).to_string()
+}
+
+/// A `b"..."` byte string literal, for both Python and Rust
+/// ```
+/// # use httpc::export_code::byte_string;
+/// assert_eq!(byte_string(b"a\"\n\xff"), r#"b"a\"\n\xff""#);
+/// ```
+pub fn byte_string(bytes: &[u8]) -> String {
+    let mut literal = String::from("b\"");
+
+    for &byte in bytes {
+        match byte {
+            b'"' => literal.push_str("\\\""),
+            b'\\' => literal.push_str("\\\\"),
+            b'\n' => literal.push_str("\\n"),
+            b'\r' => literal.push_str("\\r"),
+            b'\t' => literal.push_str("\\t"),
+            0x20..=0x7e => literal.push(byte as char),
+            _ => literal.push_str(&format!("\\x{:02x}", byte)),
+        }
+    }
+
+    literal.push('"');
+    literal
+}
+
+fn python(
+    method: &Method,
+    url: &str,
+    headers: &[(String, String)],
+    body: Option<&[u8]>,
+    options: &CommonOptions,
+) -> Result<String, std::fmt::Error> {
+    let mut code = String::from("import requests\n\nresponse = requests.request(\n");
+    writeln!(code, "    {},", quoted(method.as_str()))?;
+    writeln!(code, "    {},", quoted(url))?;
+
+    if !headers.is_empty() {
+        code.push_str("    headers={\n");
+        for (name, value) in headers {
+            writeln!(code, "        {}: {},", quoted(name), quoted(value))?;
+        }
+        code.push_str("    },\n");
+    }
+
+    // requests sends str bodies as Latin-1, so anything else needs encoding first
+    match body.map(std::str::from_utf8) {
+        Some(Ok(text)) if text.is_ascii() => writeln!(code, "    data={},", quoted(text))?,
+        Some(Ok(text)) => writeln!(code, "    data={}.encode(),", quoted(text))?,
+        Some(Err(_)) => writeln!(code, "    data={},", byte_string(body.unwrap_or_default()))?,
+        None => {}
+    }
+
+    if options.insecure {
+        code.push_str("    verify=False,\n");
+    } else if let Some(cacert) = &options.cacert {
+        writeln!(code, "    verify={},", quoted(cacert))?;
+    }
+
+    if let Some(proxy) = &options.proxy {
+        let proxy = quoted(&proxy.to_string());
+        writeln!(
+            code,
+            "    proxies={{\"http\": {0}, \"https\": {0}}},",
+            proxy
+        )?;
+    }
+
+    if let Some(max_time) = options.max_time {
+        writeln!(code, "    timeout={},", max_time.as_secs_f64())?;
+    }
+
+    let redirects = if options.location { "True" } else { "False" };
+    writeln!(code, "    allow_redirects={},", redirects)?;
+    code.push_str(")\n\nprint(response.status_code)\nprint(response.text)\n");
+
+    Ok(code)
+}
+
+fn js(
+    method: &Method,
+    url: &str,
+    headers: &[(String, String)],
+    body: Option<&[u8]>,
+    options: &CommonOptions,
+) -> Result<String, std::fmt::Error> {
+    let mut code = String::new();
+
+    if options.insecure {
+        code.push_str("// -k: Node's fetch only skips certificate checks with NODE_TLS_REJECT_UNAUTHORIZED=0\n");
+    } else if let Some(cacert) = &options.cacert {
+        writeln!(
+            code,
+            "// --cacert: Node's fetch trusts extra CAs with NODE_EXTRA_CA_CERTS={}",
+            cacert
+        )?;
+    }
+
+    if let Some(proxy) = &options.proxy {
+        writeln!(
+            code,
+            "// --proxy: fetch has no proxy option, use undici's ProxyAgent for {} as the dispatcher",
+            proxy
+        )?;
+    }
+
+    writeln!(code, "const response = await fetch({}, {{", quoted(url))?;
+    writeln!(code, "  method: {},", quoted(method.as_str()))?;
+
+    if !headers.is_empty() {
+        code.push_str("  headers: {\n");
+        for (name, value) in headers {
+            writeln!(code, "    {}: {},", quoted(name), quoted(value))?;
+        }
+        code.push_str("  },\n");
+    }
+
+    match body.map(std::str::from_utf8) {
+        Some(Ok(text)) => writeln!(code, "  body: {},", quoted(text))?,
+        Some(Err(_)) => {
+            let bytes = body
+                .unwrap_or_default()
+                .iter()
+                .map(u8::to_string)
+                .collect::<Vec<_>>();
+            writeln!(code, "  body: new Uint8Array([{}]),", bytes.join(", "))?;
+        }
+        None => {}
+    }
+
+    let redirect = if options.location { "follow" } else { "manual" };
+    writeln!(code, "  redirect: {},", quoted(redirect))?;
+
+    if let Some(max_time) = options.max_time {
+        writeln!(
+            code,
+            "  signal: AbortSignal.timeout({}),",
+            max_time.as_millis()
+        )?;
+    }
+
+    code.push_str("});\n\nconsole.log(response.status);\nconsole.log(await response.text());\n");
+
+    Ok(code)
+}
+
+fn rust(
+    method: &Method,
+    url: &str,
+    headers: &[(String, String)],
+    body: Option<&[u8]>,
+    options: &CommonOptions,
+) -> Result<String, std::fmt::Error> {
+    let mut code = String::from(
+        "fn main() -> Result<(), Box<dyn std::error::Error>> {\n    let client = reqwest::blocking::Client::builder()\n",
+    );
+
+    if !options.location {
+        code.push_str("        .redirect(reqwest::redirect::Policy::none())\n");
+    }
+
+    if options.insecure {
+        code.push_str("        .danger_accept_invalid_certs(true)\n");
+    }
+
+    if let Some(cacert) = &options.cacert {
+        writeln!(
+            code,
+            "        .add_root_certificate(reqwest::Certificate::from_pem(&std::fs::read({:?})?)?)",
+            cacert
+        )?;
+    }
+
+    if let Some(proxy) = &options.proxy {
+        writeln!(
+            code,
+            "        .proxy(reqwest::Proxy::all({:?})?)",
+            proxy.to_string()
+        )?;
+    }
+
+    if let Some(max_time) = options.max_time {
+        writeln!(
+            code,
+            "        .timeout(std::time::Duration::from_millis({}))",
+            max_time.as_millis()
+        )?;
+    }
+
+    code.push_str("        .build()?;\n\n    let response = client\n");
+
+    let method = match *method {
+        Method::GET
+        | Method::POST
+        | Method::PUT
+        | Method::DELETE
+        | Method::HEAD
+        | Method::OPTIONS
+        | Method::CONNECT
+        | Method::PATCH
+        | Method::TRACE => format!("reqwest::Method::{}", method),
+        _ => format!(
+            "reqwest::Method::from_bytes({})?",
+            byte_string(method.as_str().as_bytes())
+        ),
+    };
+    writeln!(code, "        .request({}, {:?})", method, url)?;
+
+    for (name, value) in headers {
+        writeln!(code, "        .header({:?}, {:?})", name, value)?;
+    }
+
+    match body.map(std::str::from_utf8) {
+        Some(Ok(text)) => writeln!(code, "        .body({:?})", text)?,
+        Some(Err(_)) => writeln!(
+            code,
+            "        .body(&{}[..])",
+            byte_string(body.unwrap_or_default())
+        )?,
+        None => {}
+    }
+
+    code.push_str("        .send()?;\n\n    println!(\"{}\", response.status());\n    println!(\"{}\", response.text()?);\n    Ok(())\n}\n");
+
+    Ok(code)
+}
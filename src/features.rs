@@ -0,0 +1,108 @@
+//! What this build of httpc can do: `httpc features` lists it, and flags that need something
+//! it was built without are refused before anything's sent, instead of failing mid-request
+//!
+//! Cargo features (like `async`) and the platform's TLS library both decide this
+
+use std::fmt::Write;
+
+use owo_colors::OwoColorize;
+
+use crate::{cli::CommonOptions, helpers::MColorize, keylog};
+
+/// Something this build does or doesn't support
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Feature {
+    pub name: &'static str,
+    pub enabled: bool,
+    /// What it's for, and how to get it when it's missing
+    pub about: &'static str,
+}
+
+/// Every feature, whether this build has it or not
+pub fn features() -> Vec<Feature> {
+    vec![
+        Feature {
+            name: "async",
+            enabled: cfg!(feature = "async"),
+            about: "http_request_async for library users, build with --features async",
+        },
+        Feature {
+            name: "keylog",
+            enabled: keylog::SUPPORTED,
+            about: "--keylog and SSLKEYLOGFILE, needs OpenSSL as the TLS library (not on Windows or macOS)",
+        },
+        Feature {
+            name: "tls",
+            enabled: true,
+            about: "https with the platform's TLS library (OpenSSL, SChannel or Security.framework)",
+        },
+        Feature {
+            name: "http2",
+            enabled: false,
+            about: "Only HTTP/1.1 (and HTTP/1.0 responses) is implemented, in any build",
+        },
+    ]
+}
+
+/// If this build has the feature called `name`
+pub fn has_feature(name: &str) -> bool {
+    features()
+        .iter()
+        .any(|feature| feature.name == name && feature.enabled)
+}
+
+/// Refuse flags that need a feature this build doesn't have, saying which
+pub fn check_options(options: &CommonOptions) -> Result<(), String> {
+    let needed = [("--keylog", options.keylog.is_some(), "keylog")];
+
+    for (flag, used, name) in needed {
+        if used && !has_feature(name) {
+            return Err(missing_feature(flag, name));
+        }
+    }
+
+    Ok(())
+}
+
+/// The error for a `flag` that needs the feature called `name`
+/// ```
+/// # use httpc::features::missing_feature;
+/// assert_eq!(
+///     missing_feature("--keylog", "keylog"),
+///     "--keylog needs the keylog feature, which this httpc was built without (see httpc features)"
+/// );
+/// ```
+pub fn missing_feature(flag: &str, name: &str) -> String {
+    format!(
+        "{} needs the {} feature, which this httpc was built without (see httpc features)",
+        flag, name
+    )
+}
+
+/// One line per feature, with whether it's there
+pub fn format_features(features: &[Feature]) -> String {
+    let width = features
+        .iter()
+        .map(|feature| feature.name.len())
+        .max()
+        .unwrap_or_default();
+    let mut output = String::new();
+
+    for feature in features {
+        let enabled = match feature.enabled {
+            true => format!("{}", "yes".out_color(|t| t.green())),
+            false => format!("{}", " no".out_color(|t| t.red())),
+        };
+
+        let _ = writeln!(
+            output,
+            "{:width$}  {}  {}",
+            feature.name,
+            enabled,
+            feature.about,
+            width = width
+        );
+    }
+
+    output
+}
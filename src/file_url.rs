@@ -0,0 +1,106 @@
+//! `file://` URLs: the file is read and handed back as a made up `200 OK` response, so it goes
+//! through the same output as anything fetched (-o, -v, --json, pretty printing...)
+//!
+//! Only local files can be read, `file:///path` or `file://localhost/path`. Whatever only makes
+//! sense for HTTP (cookies, auth, caching, redirects) is skipped
+
+use std::path::PathBuf;
+
+use http::{header, Method, Response, StatusCode};
+
+use crate::{
+    helpers::percent_decode,
+    http_request::{BodyTooBig, RequestError},
+    serve::content_type,
+};
+
+/// If `url` is a `file://` URL
+/// ```
+/// # use httpc::file_url::is_file_url;
+/// assert!(is_file_url("FILE:///tmp/a.txt"));
+/// assert!(!is_file_url("https://example.com/file:///a"));
+/// ```
+pub fn is_file_url(url: &str) -> bool {
+    url.get(..7)
+        .is_some_and(|scheme| scheme.eq_ignore_ascii_case("file://"))
+}
+
+/// The local path a `file://` URL points to, with any percent-encoding decoded
+/// ```
+/// # use std::path::PathBuf;
+/// # use httpc::file_url::file_path;
+/// assert_eq!(file_path("file:///tmp/my%20notes.txt").unwrap(), PathBuf::from("/tmp/my notes.txt"));
+/// assert_eq!(file_path("file://localhost/tmp/a?b#c").unwrap(), PathBuf::from("/tmp/a"));
+/// assert!(file_path("file://fileserver/share/a").is_err());
+/// ```
+pub fn file_path(url: &str) -> Result<PathBuf, RequestError> {
+    let rest = url.get(7..).ok_or("Not a file:// URL")?;
+    let rest = rest.split(['?', '#']).next().unwrap_or_default();
+    let (host, path) = match rest.find('/') {
+        Some(slash) => rest.split_at(slash),
+        None => (rest, ""),
+    };
+
+    if !host.is_empty() && !host.eq_ignore_ascii_case("localhost") {
+        return Err(format!(
+            "Can't read files on other hosts ({}), only local ones",
+            host
+        )
+        .into());
+    }
+
+    if path.is_empty() {
+        return Err(format!("{} has no path to a file", url).into());
+    }
+
+    let path = String::from_utf8(percent_decode(path))
+        .map_err(|_| format!("{}'s path isn't valid UTF-8", url))?;
+
+    // file:///C:/Users/... on Windows
+    #[cfg(windows)]
+    let path = path.strip_prefix('/').unwrap_or(&path).to_string();
+
+    Ok(PathBuf::from(path))
+}
+
+/// Read the file a `file://` URL points to as a `200 OK` response, with a Content-Type going by
+/// its extension. A HEAD only gets the headers, and a file bigger than `max_body` is a
+/// [`BodyTooBig`]
+pub fn file_response(
+    method: &Method,
+    url: &str,
+    max_body: Option<u64>,
+) -> Result<Response<Vec<u8>>, RequestError> {
+    if method != Method::GET && method != Method::HEAD {
+        return Err(format!("file:// URLs can only be read, not sent a {}", method).into());
+    }
+
+    let path = file_path(url)?;
+
+    if let (Some(limit), Ok(metadata)) = (max_body, std::fs::metadata(&path)) {
+        if *method == Method::GET && metadata.len() > limit {
+            return Err(Box::new(BodyTooBig {
+                limit,
+                length: Some(metadata.len()),
+            }));
+        }
+    }
+    let body =
+        std::fs::read(&path).map_err(|err| format!("Can't read {}: {}", path.display(), err))?;
+
+    let mut response = Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, content_type(&path, &body))
+        .header(header::CONTENT_LENGTH, body.len());
+
+    if let Ok(modified) = std::fs::metadata(&path).and_then(|metadata| metadata.modified()) {
+        response = response.header(header::LAST_MODIFIED, httpdate::fmt_http_date(modified));
+    }
+
+    let body = match *method {
+        Method::HEAD => vec![],
+        _ => body,
+    };
+
+    Ok(response.body(body)?)
+}
@@ -0,0 +1,97 @@
+//! `--grep`: only show the lines of the body that have some text in them, failing when none do,
+//! for checking if an endpoint says something from a script
+//!
+//! The text is matched as-is, not as a regex. JSON, HTML, XML and CSS bodies are reindented
+//! first, so a match is the line it's on instead of the whole body
+
+use std::io::{self, BufRead, BufReader};
+
+use http::{header, Response};
+use owo_colors::{OwoColorize, Style};
+
+use crate::{
+    helpers::MColorize,
+    pretty::{prettify, Pretty},
+    spool::Spooled,
+};
+
+/// What to look for in the body
+#[derive(Clone, Debug)]
+pub struct Grep {
+    pub pattern: String,
+    /// Ignore ASCII case when matching
+    pub ignore_case: bool,
+}
+
+impl Grep {
+    /// Where `pattern` is in `line`, as byte ranges
+    /// ```
+    /// # use httpc::grep::Grep;
+    /// let grep = Grep { pattern: "ok".to_string(), ignore_case: true };
+    /// assert_eq!(grep.find("OK, ok!"), vec![0..2, 4..6]);
+    /// assert!(Grep { pattern: "ok".to_string(), ignore_case: false }.find("OK").is_empty());
+    /// ```
+    pub fn find(&self, line: &str) -> Vec<std::ops::Range<usize>> {
+        if self.pattern.is_empty() {
+            return vec![];
+        }
+
+        // ASCII lowercasing keeps the offsets the same
+        let (line, pattern) = match self.ignore_case {
+            true => (line.to_ascii_lowercase(), self.pattern.to_ascii_lowercase()),
+            false => (line.to_string(), self.pattern.clone()),
+        };
+
+        line.match_indices(&pattern)
+            .map(|(start, found)| start..start + found.len())
+            .collect()
+    }
+
+    /// `line` with each match highlighted, if it has any
+    pub fn highlight(&self, line: &str) -> Option<String> {
+        let matches = self.find(line);
+        let mut highlighted = String::new();
+        let mut end = 0;
+
+        for range in &matches {
+            highlighted.push_str(&line[end..range.start]);
+            let found = &line[range.clone()];
+            highlighted.push_str(&format!(
+                "{}",
+                found.out_color(|t| t.style(Style::new().red().bold()))
+            ));
+            end = range.end;
+        }
+
+        highlighted.push_str(&line[end..]);
+        (!matches.is_empty()).then_some(highlighted)
+    }
+
+    /// The matching lines of a response's body, highlighted. A spooled body is read a line at a
+    /// time and isn't reindented, since it'd all have to be in memory for that
+    pub fn matching_lines(&self, response: &Response<Vec<u8>>) -> io::Result<Vec<String>> {
+        if let Some(spooled) = response.extensions().get::<Spooled>() {
+            let mut lines = vec![];
+
+            for line in BufReader::new(spooled.open()?).split(b'\n') {
+                let line = String::from_utf8_lossy(&line?).into_owned();
+                lines.extend(self.highlight(line.trim_end_matches('\r')));
+            }
+
+            return Ok(lines);
+        }
+
+        let body = String::from_utf8_lossy(response.body());
+        let content_type = response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default();
+
+        // Only reindented, the highlighting would get in the way of matching
+        Ok(prettify(content_type, &body, Pretty::Format)
+            .lines()
+            .filter_map(|line| self.highlight(line))
+            .collect())
+    }
+}
@@ -0,0 +1,439 @@
+//! HTTP Archives (`.har`), the format browser devtools import and export their network tab as
+//!
+//! `httpc har import` replays the requests in one: each entry's method, URL, headers and body are
+//! sent the same way `httpc get`/`post` would send them. Headers the browser's connection set up
+//! itself (HTTP/2 pseudo-headers, Host, Content-Length...) are left out, and so is Accept-Encoding
+//! since httpc doesn't decompress bodies
+//!
+//! `--har FILE` goes the other way, writing every request sent and its response (redirects
+//! included) as a HAR 1.2 file. It's rewritten after each response, so it has everything up to a
+//! failure too. Bodies that aren't valid UTF-8 are base64 encoded, and ones too big to keep in
+//! memory (`--max-memory-body`) are left out
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use http::{header, uri::Authority, HeaderMap, Method, Response, Uri};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::{
+    helpers::{iso_date, percent_decode, percent_encode},
+    http_request::{RequestError, SentRequest},
+    json_output::headers_json,
+    middleware::{Exchange, Middleware},
+    spool::{body_len, Spooled},
+    write_out::Timings,
+};
+
+/// Headers that belong to the browser's connection, not the request
+const SKIPPED_HEADERS: [&str; 7] = [
+    "host",
+    "content-length",
+    "connection",
+    "keep-alive",
+    "transfer-encoding",
+    "accept-encoding",
+    "upgrade",
+];
+
+/// The parts of a HAR file needed to replay its requests
+#[derive(Debug, Deserialize)]
+pub struct Har {
+    pub log: HarLog,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HarLog {
+    pub entries: Vec<HarEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HarEntry {
+    pub request: HarRequest,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HarRequest {
+    pub method: String,
+    pub url: String,
+    #[serde(default)]
+    pub headers: Vec<HarHeader>,
+    pub post_data: Option<HarPostData>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HarHeader {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HarPostData {
+    #[serde(default)]
+    pub mime_type: String,
+    pub text: Option<String>,
+    #[serde(default)]
+    pub params: Vec<HarParam>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HarParam {
+    pub name: String,
+    pub value: Option<String>,
+}
+
+/// Read a HAR file
+pub fn read_har(path: &Path) -> Result<Har, RequestError> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|err| format!("Can't read {}: {}", path.display(), err))?;
+
+    serde_json::from_str(&text)
+        .map_err(|err| format!("Invalid HAR file {}: {}", path.display(), err).into())
+}
+
+impl HarRequest {
+    pub fn method(&self) -> Result<Method, RequestError> {
+        Ok(self.method.to_ascii_uppercase().parse()?)
+    }
+
+    /// The headers to send, as 'name: value'
+    /// ```
+    /// # use httpc::har::{HarHeader, HarRequest};
+    /// let header = |name: &str, value: &str| HarHeader { name: name.into(), value: value.into() };
+    /// let request = HarRequest {
+    ///     method: "GET".into(),
+    ///     url: "https://example.com/".into(),
+    ///     headers: vec![header(":authority", "example.com"), header("Accept-Encoding", "br"), header("Accept", "*/*")],
+    ///     post_data: None,
+    /// };
+    /// assert_eq!(request.headers(), ["Accept: */*"]);
+    /// ```
+    pub fn headers(&self) -> Vec<String> {
+        self.headers
+            .iter()
+            .filter(|header| {
+                let name = header.name.to_ascii_lowercase();
+                !name.starts_with(':') && !SKIPPED_HEADERS.contains(&name.as_str())
+            })
+            .map(|header| format!("{}: {}", header.name, header.value))
+            .collect()
+    }
+
+    /// The body, from the text the browser sent or else its form params
+    pub fn body(&self) -> Option<String> {
+        let post_data = self.post_data.as_ref()?;
+
+        if let Some(text) = &post_data.text {
+            return Some(text.clone());
+        }
+
+        // Only urlencoded forms can be rebuilt, multipart params don't have the files' content
+        if !post_data
+            .mime_type
+            .starts_with("application/x-www-form-urlencoded")
+        {
+            return None;
+        }
+
+        let form = post_data
+            .params
+            .iter()
+            .map(|param| {
+                format!(
+                    "{}={}",
+                    percent_encode(&param.name, false),
+                    percent_encode(param.value.as_deref().unwrap_or_default(), false)
+                )
+            })
+            .collect::<Vec<_>>();
+
+        Some(form.join("&"))
+    }
+
+    /// The Content-Type of the body, if the headers don't have one
+    pub fn content_type(&self) -> Option<&str> {
+        self.post_data
+            .as_ref()
+            .map(|post_data| post_data.mime_type.as_str())
+            .filter(|mime_type| !mime_type.is_empty())
+    }
+}
+
+/// Send a URL to another host, e.g. to replay production requests against a local server.
+/// `host` is a host with an optional port, or a scheme and a host to change both
+/// ```
+/// # use httpc::har::rewrite_host;
+/// let url = "https://example.com/api/things?a=1";
+/// assert_eq!(rewrite_host(url, "staging.example.com").unwrap(), "https://staging.example.com/api/things?a=1");
+/// assert_eq!(rewrite_host(url, "http://localhost:8080").unwrap(), "http://localhost:8080/api/things?a=1");
+/// ```
+pub fn rewrite_host(url: &str, host: &str) -> Result<String, RequestError> {
+    let mut parts = url.parse::<Uri>()?.into_parts();
+
+    match host.split_once("://") {
+        Some((scheme, authority)) => {
+            parts.scheme = Some(scheme.parse()?);
+            parts.authority = Some(authority.trim_end_matches('/').parse::<Authority>()?);
+        }
+        None => parts.authority = Some(host.parse::<Authority>()?),
+    }
+
+    Ok(Uri::from_parts(parts)?.to_string())
+}
+
+/// Where `--har` writes the entries, shared by every request (and thread)
+#[derive(Clone, Debug)]
+pub struct HarRecorder {
+    path: PathBuf,
+    entries: Arc<Mutex<Vec<Value>>>,
+}
+
+impl HarRecorder {
+    /// Start a new, empty HAR file, replacing any that's there
+    pub fn create(path: &Path) -> Result<Self, RequestError> {
+        let recorder = Self {
+            path: path.to_path_buf(),
+            entries: Arc::default(),
+        };
+
+        recorder.write(&[])?;
+        Ok(recorder)
+    }
+
+    /// Add a request and its response, then write the whole file again
+    pub fn record(
+        &self,
+        started: SystemTime,
+        method: &Method,
+        uri: &Uri,
+        headers: &HeaderMap,
+        body: Option<&[u8]>,
+        response: &Response<Vec<u8>>,
+    ) -> Result<(), RequestError> {
+        let entry = har_entry(started, method, uri, headers, body, response);
+        let mut entries = self
+            .entries
+            .lock()
+            .map_err(|_| "--har was poisoned by a panic")?;
+
+        entries.push(entry);
+        self.write(&entries)
+    }
+
+    fn write(&self, entries: &[Value]) -> Result<(), RequestError> {
+        let har = json!({
+            "log": {
+                "version": "1.2",
+                "creator": {"name": "httpc", "version": env!("CARGO_PKG_VERSION")},
+                "pages": [],
+                "entries": entries,
+            }
+        });
+
+        fs::write(&self.path, serde_json::to_vec_pretty(&har)?)
+            .map_err(|err| format!("Can't write {}: {}", self.path.display(), err))?;
+
+        Ok(())
+    }
+}
+
+impl Middleware for HarRecorder {
+    fn after_receive(
+        &self,
+        exchange: &Exchange,
+        response: &mut Response<Vec<u8>>,
+    ) -> Result<(), RequestError> {
+        let request = exchange.request;
+
+        self.record(
+            exchange.started,
+            request.method(),
+            request.uri(),
+            request.headers(),
+            *request.body(),
+            response,
+        )
+    }
+}
+
+/// A HAR entry for a request and its response. `headers` are used if the response doesn't have
+/// the ones that were actually sent (because it came from the cache)
+/// ```
+/// # use httpc::har::har_entry;
+/// let response = http::Response::builder()
+///     .status(301)
+///     .header("Location", "/b")
+///     .header("Set-Cookie", "id=1; Path=/")
+///     .body(b"moved".to_vec())
+///     .unwrap();
+/// let uri = "http://example.com/a?q=a%20b".parse().unwrap();
+/// let entry = har_entry(std::time::UNIX_EPOCH, &http::Method::GET, &uri, &http::HeaderMap::new(), None, &response);
+/// assert_eq!(entry["startedDateTime"], "1970-01-01T00:00:00.000Z");
+/// assert_eq!(entry["request"]["queryString"][0]["value"], "a b");
+/// assert_eq!(entry["response"]["redirectURL"], "/b");
+/// assert_eq!(entry["response"]["cookies"][0]["name"], "id");
+/// assert_eq!(entry["response"]["content"]["text"], "moved");
+/// ```
+pub fn har_entry(
+    started: SystemTime,
+    method: &Method,
+    uri: &Uri,
+    headers: &HeaderMap,
+    body: Option<&[u8]>,
+    response: &Response<Vec<u8>>,
+) -> Value {
+    let extensions = response.extensions();
+    let headers = extensions
+        .get::<SentRequest>()
+        .map_or(headers, |sent| &sent.headers);
+
+    let query = uri
+        .query()
+        .unwrap_or_default()
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            name_value(&decode(name), &decode(value))
+        })
+        .collect::<Vec<_>>();
+
+    let cookie_header = headers
+        .get_all(header::COOKIE)
+        .iter()
+        .map(|value| String::from_utf8_lossy(value.as_bytes()))
+        .collect::<Vec<_>>()
+        .join("; ");
+
+    let request_cookies = cookie_header
+        .split(';')
+        .filter_map(|cookie| {
+            let (name, value) = cookie.trim().split_once('=')?;
+            Some(name_value(name, value))
+        })
+        .collect::<Vec<_>>();
+
+    let post_data = body.map(|body| {
+        let mime_type = headers
+            .get(header::CONTENT_TYPE)
+            .map(|value| String::from_utf8_lossy(value.as_bytes()))
+            .unwrap_or_default();
+        let (text, encoding) = text_or_base64(body);
+        let mut post_data = json!({"mimeType": mime_type, "text": text, "params": []});
+
+        if let Some(encoding) = encoding {
+            post_data["encoding"] = encoding.into();
+        }
+
+        post_data
+    });
+
+    let mut request = json!({
+        "method": method.as_str(),
+        "url": uri.to_string(),
+        "httpVersion": "HTTP/1.1",
+        "cookies": request_cookies,
+        "headers": headers_json(headers),
+        "queryString": query,
+        "headersSize": -1,
+        "bodySize": body.map_or(0, |body| body.len()),
+    });
+
+    if let Some(post_data) = post_data {
+        request["postData"] = post_data;
+    }
+
+    let response_cookies = response
+        .headers()
+        .get_all(header::SET_COOKIE)
+        .iter()
+        .filter_map(|value| {
+            let cookie = String::from_utf8_lossy(value.as_bytes());
+            let (name, value) = cookie.split(';').next()?.split_once('=')?;
+            Some(name_value(name.trim(), value.trim()))
+        })
+        .collect::<Vec<_>>();
+
+    let header_str = |name| {
+        response
+            .headers()
+            .get(name)
+            .map(|value| String::from_utf8_lossy(value.as_bytes()).into_owned())
+            .unwrap_or_default()
+    };
+
+    let mut content = json!({
+        "size": body_len(response),
+        "mimeType": header_str(header::CONTENT_TYPE),
+    });
+
+    if extensions.get::<Spooled>().is_none() {
+        let (text, encoding) = text_or_base64(response.body());
+        content["text"] = text.into();
+
+        if let Some(encoding) = encoding {
+            content["encoding"] = encoding.into();
+        }
+    }
+
+    let millis = |duration: Duration| duration.as_secs_f64() * 1000.0;
+    let timings = extensions.get::<Timings>();
+    let connect = timings.and_then(|timings| timings.connect);
+    let (total, wait, receive) = timings.map_or((0.0, 0.0, 0.0), |timings| {
+        (
+            millis(timings.total),
+            millis(timings.first_byte) - connect.map_or(0.0, millis),
+            millis(timings.total) - millis(timings.first_byte),
+        )
+    });
+
+    json!({
+        "startedDateTime": iso_date(started),
+        "time": total,
+        "request": request,
+        "response": {
+            "status": response.status().as_u16(),
+            "statusText": response.status().canonical_reason().unwrap_or_default(),
+            "httpVersion": format!("{:?}", response.version()),
+            "cookies": response_cookies,
+            "headers": headers_json(response.headers()),
+            "content": content,
+            "redirectURL": header_str(header::LOCATION),
+            "headersSize": -1,
+            "bodySize": body_len(response),
+        },
+        "cache": {},
+        "timings": {
+            "blocked": -1,
+            "dns": -1,
+            "connect": connect.map_or(-1.0, millis),
+            "ssl": -1,
+            "send": 0,
+            "wait": wait,
+            "receive": receive,
+        },
+    })
+}
+
+fn name_value(name: &str, value: &str) -> Value {
+    json!({"name": name, "value": value})
+}
+
+fn decode(value: &str) -> String {
+    String::from_utf8_lossy(&percent_decode(&value.replace('+', " "))).into_owned()
+}
+
+fn text_or_base64(body: &[u8]) -> (String, Option<&'static str>) {
+    match std::str::from_utf8(body) {
+        Ok(text) => (text.to_string(), None),
+        Err(_) => (BASE64.encode(body), Some("base64")),
+    }
+}
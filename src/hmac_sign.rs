@@ -0,0 +1,246 @@
+//! `--sign`: an HMAC of parts of the request (the body, by default) sent in a header, like
+//! webhook-style APIs want
+//!
+//! The parts are joined with `--sign-separator` (a newline by default) and signed with the
+//! secret, then `--sign-format` puts the signature in the header's value, e.g. GitHub's
+//! `X-Hub-Signature-256: sha256=<hex>` is
+//! `--sign hmac-sha256:SECRET --sign-header X-Hub-Signature-256 --sign-format 'sha256={hex}'`
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use clap::Args;
+use hmac::{Hmac, Mac};
+use http::{header::HeaderName, HeaderMap, HeaderValue, Method, Uri};
+use sha1::Sha1;
+use sha2::{Sha256, Sha512};
+
+use crate::{helpers::to_hex, http_request::RequestError};
+
+#[derive(Args, Clone, Debug)]
+pub struct HmacSigning {
+    /// Sign the request with an HMAC, e.g. hmac-sha256:SECRET (hmac-sha1 and hmac-sha512 work too)
+    #[clap(
+        name = "sign",
+        long = "sign",
+        value_name = "ALGORITHM:SECRET",
+        value_parser = parse_hmac_key
+    )]
+    pub key: Option<HmacKey>,
+
+    /// The header to send the --sign signature in
+    #[clap(
+        name = "sign-header",
+        long = "sign-header",
+        value_name = "NAME",
+        default_value = "X-Signature",
+        requires = "sign"
+    )]
+    pub header: String,
+
+    /// What --sign signs, in order: method, path (with the query), body, timestamp (the Unix time,
+    /// also sent in a <sign-header>-Timestamp header) and header:NAME (a request header's value)
+    #[clap(
+        name = "sign-input",
+        long = "sign-input",
+        value_name = "PART",
+        value_delimiter = ',',
+        default_value = "body",
+        value_parser = parse_sign_input,
+        requires = "sign"
+    )]
+    pub inputs: Vec<SignInput>,
+
+    /// What goes between the parts --sign signs (a newline by default)
+    #[clap(
+        name = "sign-separator",
+        long = "sign-separator",
+        value_name = "TEXT",
+        requires = "sign"
+    )]
+    pub separator: Option<String>,
+
+    /// The header's value, with the signature in place of {hex} or {base64}
+    #[clap(
+        name = "sign-format",
+        long = "sign-format",
+        value_name = "FORMAT",
+        default_value = "{hex}",
+        requires = "sign"
+    )]
+    pub format: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HmacAlgorithm {
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HmacKey {
+    pub algorithm: HmacAlgorithm,
+    pub secret: String,
+}
+
+/// A part of the request to sign
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SignInput {
+    Method,
+    Path,
+    Body,
+    Timestamp,
+    Header(String),
+}
+
+/// Parse an `ALGORITHM:SECRET` like `hmac-sha256:s3cret`
+/// ```
+/// # use httpc::hmac_sign::{parse_hmac_key, HmacAlgorithm};
+/// let key = parse_hmac_key("hmac-sha256:a:b").unwrap();
+/// assert_eq!((key.algorithm, key.secret.as_str()), (HmacAlgorithm::Sha256, "a:b"));
+/// assert!(parse_hmac_key("md5:secret").is_err());
+/// ```
+pub fn parse_hmac_key(key: &str) -> Result<HmacKey, String> {
+    let (algorithm, secret) = key
+        .split_once(':')
+        .ok_or_else(|| format!("Expected ALGORITHM:SECRET, got '{}'", key))?;
+
+    let algorithm = match algorithm.to_ascii_lowercase().as_str() {
+        "hmac-sha1" => HmacAlgorithm::Sha1,
+        "hmac-sha256" => HmacAlgorithm::Sha256,
+        "hmac-sha512" => HmacAlgorithm::Sha512,
+        _ => {
+            return Err(format!(
+                "Unknown algorithm '{}', use hmac-sha1, hmac-sha256 or hmac-sha512",
+                algorithm
+            ))
+        }
+    };
+
+    Ok(HmacKey {
+        algorithm,
+        secret: secret.to_string(),
+    })
+}
+
+/// Parse one of the `--sign-input` parts
+/// ```
+/// # use httpc::hmac_sign::{parse_sign_input, SignInput};
+/// assert_eq!(parse_sign_input("body"), Ok(SignInput::Body));
+/// assert_eq!(parse_sign_input("header:X-Id"), Ok(SignInput::Header("X-Id".to_string())));
+/// assert!(parse_sign_input("everything").is_err());
+/// ```
+pub fn parse_sign_input(input: &str) -> Result<SignInput, String> {
+    match input.trim() {
+        "method" => Ok(SignInput::Method),
+        "path" => Ok(SignInput::Path),
+        "body" => Ok(SignInput::Body),
+        "timestamp" => Ok(SignInput::Timestamp),
+        input => match input.strip_prefix("header:") {
+            Some(name) if !name.is_empty() => Ok(SignInput::Header(name.to_string())),
+            _ => Err(format!(
+                "Unknown part '{}', use method, path, body, timestamp or header:NAME",
+                input
+            )),
+        },
+    }
+}
+
+/// The HMAC of a message
+/// ```
+/// # use httpc::hmac_sign::{hmac, HmacAlgorithm};
+/// # use httpc::helpers::to_hex;
+/// // RFC 4231 test case 2
+/// assert_eq!(
+///     to_hex(&hmac(HmacAlgorithm::Sha256, b"Jefe", b"what do ya want for nothing?")),
+///     "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+/// );
+/// ```
+pub fn hmac(algorithm: HmacAlgorithm, key: &[u8], message: &[u8]) -> Vec<u8> {
+    fn sign<M: Mac + hmac::digest::KeyInit>(key: &[u8], message: &[u8]) -> Vec<u8> {
+        let mut mac = <M as Mac>::new_from_slice(key).expect("HMAC takes keys of any size");
+        mac.update(message);
+        mac.finalize().into_bytes().to_vec()
+    }
+
+    match algorithm {
+        HmacAlgorithm::Sha1 => sign::<Hmac<Sha1>>(key, message),
+        HmacAlgorithm::Sha256 => sign::<Hmac<Sha256>>(key, message),
+        HmacAlgorithm::Sha512 => sign::<Hmac<Sha512>>(key, message),
+    }
+}
+
+impl HmacSigning {
+    /// Add the signature header (and the timestamp one, if it's signed) to a request's headers,
+    /// if there's a `--sign` key
+    /// ```
+    /// # use clap::Parser;
+    /// # use httpc::cli::CommonOptions;
+    /// let options = CommonOptions::parse_from([
+    ///     "httpc", "--sign", "hmac-sha256:Jefe", "--sign-format", "sha256={hex}", "example.com",
+    /// ]);
+    /// let mut headers = http::HeaderMap::new();
+    /// let uri = "https://example.com/hook".parse().unwrap();
+    /// let body = b"what do ya want for nothing?";
+    /// options.sign.sign(&http::Method::POST, &uri, &mut headers, Some(body), std::time::UNIX_EPOCH).unwrap();
+    /// assert_eq!(
+    ///     headers["x-signature"],
+    ///     "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+    /// );
+    /// ```
+    pub fn sign(
+        &self,
+        method: &Method,
+        uri: &Uri,
+        headers: &mut HeaderMap,
+        body: Option<&[u8]>,
+        time: SystemTime,
+    ) -> Result<(), RequestError> {
+        let Some(key) = &self.key else {
+            return Ok(());
+        };
+
+        let header = HeaderName::from_bytes(self.header.as_bytes())?;
+        let timestamp = time.duration_since(UNIX_EPOCH)?.as_secs().to_string();
+
+        if self.inputs.contains(&SignInput::Timestamp) {
+            let name = HeaderName::from_bytes(format!("{}-Timestamp", header).as_bytes())?;
+            headers.insert(name, HeaderValue::from_str(&timestamp)?);
+        }
+
+        let mut parts: Vec<&[u8]> = vec![];
+
+        for input in &self.inputs {
+            let part = match input {
+                SignInput::Method => method.as_str().as_bytes(),
+                SignInput::Path => uri
+                    .path_and_query()
+                    .map_or(b"/".as_slice(), |path| path.as_str().as_bytes()),
+                SignInput::Body => body.unwrap_or_default(),
+                SignInput::Timestamp => timestamp.as_bytes(),
+                SignInput::Header(name) => headers
+                    .get(name.as_str())
+                    .ok_or_else(|| format!("Can't sign header {}, it isn't being sent", name))?
+                    .as_bytes(),
+            };
+
+            parts.push(part);
+        }
+
+        let separator = self.separator.as_deref().unwrap_or("\n");
+        let signature = hmac(
+            key.algorithm,
+            key.secret.as_bytes(),
+            &parts.join(separator.as_bytes()),
+        );
+
+        let value = self
+            .format
+            .replace("{hex}", &to_hex(&signature))
+            .replace("{base64}", &BASE64.encode(&signature));
+        headers.insert(header, HeaderValue::from_str(&value)?);
+
+        Ok(())
+    }
+}
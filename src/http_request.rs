@@ -1,88 +1,1278 @@
 use std::{
     fmt::Write,
     io::{self, prelude::*, BufReader},
-    net::{SocketAddr, TcpStream, ToSocketAddrs},
+    net::{SocketAddr, TcpStream},
     str::from_utf8,
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant},
 };
 
+use clap::ArgEnum;
 use http::{
     header::{self, HeaderName},
-    HeaderMap, HeaderValue, Request, Response, Uri,
+    HeaderMap, HeaderValue, Method, Request, Response, StatusCode, Uri, Version,
 };
-use native_tls::TlsConnector;
+use native_tls::{Certificate, TlsConnector};
 use owo_colors::{OwoColorize, Style};
 
 use crate::{
+    cert_expiry::check_expiry,
     cli::VERY_VERBOSE,
-    helpers::{get_authority, MColorize},
+    events::{emit, ConnectionEvents, EventHook, EventKind},
+    helpers::{basic_auth, expand_home, get_authority, host_header, split_userinfo, MColorize},
+    keylog::keylog_connect,
+    pool::{self, Connection},
+    redirect::is_private,
+    spool::{body_len, Spool},
+    throttle::Throttled,
+    timeout::{Deadline, TimedStream, Timeouts},
+    trace::Trace,
+    transfer::{count_request, Counted},
+    warnings::{warn, WarningKind},
+    write_out::Timings,
 };
 
 // TODO: better error type...
 pub type RequestError = Box<dyn std::error::Error>;
 
+/// Options that change how a request is sent, rather than what is sent
+#[derive(Debug, Clone, Default)]
+pub struct RequestOptions {
+    pub verbosity: u8,
+    /// Max bytes/second to send and receive, `None` for no limit
+    pub limit_rate: Option<u64>,
+    /// Don't reuse a pooled connection or cached DNS results
+    pub fresh_connect: bool,
+    /// Don't reuse pooled connections older than this
+    pub max_conn_age: Option<Duration>,
+    /// HTTP proxy to send requests through, https is tunneled with CONNECT
+    pub proxy: Option<Uri>,
+    /// `user:password` to send the proxy as Basic auth, if it isn't in the proxy's URL already
+    pub proxy_user: Option<String>,
+    /// Don't verify the server's certificate (or that it matches the host)
+    pub insecure: bool,
+    /// Extra PEM file of CA certificates to trust
+    pub ca_cert: Option<String>,
+    /// Warn about server certificates that expire within this long, see [`crate::cert_expiry`]
+    pub cert_expiry_check: Option<Duration>,
+    /// Only connect over this IP version, `None` for whichever the host resolves to first
+    pub ip_family: Option<IpFamily>,
+    /// How long each phase of the request can take
+    pub timeouts: Timeouts,
+    /// Dump everything sent & received on new connections here
+    pub trace: Option<Trace>,
+    /// Refuse to connect to loopback, private & link-local addresses, see [`is_private`]
+    pub safe_fetch: bool,
+    /// Error if a TLS connection closes without a close_notify, instead of treating it as the end
+    pub strict_tls: bool,
+    /// Append the TLS secrets of new connections to this file, see [`crate::keylog`]
+    pub keylog: Option<String>,
+    /// Offered each response's head, to take its body as it arrives instead
+    pub stream: Option<Arc<Mutex<dyn StreamHandler>>>,
+    /// Bodies bigger than this go to a temp file instead of memory, see [`crate::spool`]
+    pub max_memory_body: Option<u64>,
+    /// Stop with [`BodyTooBig`] instead of downloading a body bigger than this
+    pub max_body: Option<u64>,
+    /// Only share pooled connections with requests with the same tag, for auth that authenticates
+    /// the connection instead of the request (like NTLM)
+    pub connection_tag: Option<String>,
+    /// Send the request-target in this form, `None` for origin-form (or absolute-form through a
+    /// proxy)
+    pub target_form: Option<TargetForm>,
+    /// Send exactly this as the request-target, whatever the URL is
+    pub request_target: Option<String>,
+    /// Called with each step of the request as it happens, see [`crate::events`]
+    pub events: Vec<EventHook>,
+}
+
+/// The forms the request-target (between the method and the version) can take, RFC 9112 3.2
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TargetForm {
+    /// The path and query: GET /path?query
+    Origin,
+    /// The whole URL, like requests to a proxy: GET http://host/path?query
+    Absolute,
+    /// Only host:port, like a CONNECT: CONNECT host:443
+    Authority,
+    /// A lone '*', for OPTIONS about the whole server: OPTIONS *
+    Asterisk,
+}
+
+impl TargetForm {
+    /// The request-target for `uri` in this form
+    /// ```
+    /// # use httpc::http_request::TargetForm;
+    /// let uri = "http://example.com/a?b".parse().unwrap();
+    /// assert_eq!(TargetForm::Origin.target(&uri), "/a?b");
+    /// assert_eq!(TargetForm::Absolute.target(&uri), "http://example.com/a?b");
+    /// assert_eq!(TargetForm::Authority.target(&uri), "example.com:80");
+    /// assert_eq!(TargetForm::Asterisk.target(&uri), "*");
+    /// assert_eq!(TargetForm::Absolute.target(&"http://example.com".parse().unwrap()), "http://example.com/");
+    /// ```
+    pub fn target(self, uri: &Uri) -> String {
+        let path = uri.path_and_query().map_or("/", |path| path.as_str());
+        let path = if path.is_empty() { "/" } else { path };
+
+        match self {
+            TargetForm::Origin => path.to_string(),
+            TargetForm::Absolute => format!(
+                "{}://{}{}",
+                uri.scheme_str().unwrap_or("http"),
+                uri.authority().map_or("", |authority| authority.as_str()),
+                path
+            ),
+            TargetForm::Authority => get_authority(uri),
+            TargetForm::Asterisk => "*".to_string(),
+        }
+    }
+}
+
+/// An IP version to stick to when connecting (`-4`/`-6`)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IpFamily {
+    V4,
+    V6,
+}
+
+impl IpFamily {
+    /// Only the addresses of this version, erroring if there aren't any
+    fn filter(
+        family: Option<Self>,
+        authority: &str,
+        addresses: Vec<SocketAddr>,
+    ) -> Result<Vec<SocketAddr>, RequestError> {
+        let Some(family) = family else {
+            return Ok(addresses);
+        };
+
+        let addresses: Vec<_> = addresses
+            .into_iter()
+            .filter(|address| address.is_ipv4() == (family == IpFamily::V4))
+            .collect();
+
+        if addresses.is_empty() {
+            return Err(format!("{} has no {} addresses", authority, family).into());
+        }
+
+        Ok(addresses)
+    }
+}
+
+impl std::fmt::Display for IpFamily {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IpFamily::V4 => write!(f, "IPv4"),
+            IpFamily::V6 => write!(f, "IPv6"),
+        }
+    }
+}
+
+/// Something that wants response bodies as they arrive, instead of all at once at the end (like
+/// [`crate::sse::SsePrinter`])
+pub trait StreamHandler: std::fmt::Debug + Send {
+    /// Called with the head of a response that has a body, returning if it should be streamed
+    fn start(&mut self, status: StatusCode, headers: &HeaderMap) -> bool;
+
+    /// The next part of a streamed body: up to the end of a line, or of a chunk if it's chunked.
+    /// Streamed bodies aren't kept in the response, which gets [`Streamed`] instead
+    fn data(&mut self, data: &[u8]) -> io::Result<()>;
+}
+
+/// Marks a response whose body went to the [`RequestOptions::stream`] handler
+#[derive(Clone, Copy, Debug)]
+pub struct Streamed;
+
+/// The connection ended before we received a full response
+#[derive(Debug, Default)]
+pub struct IncompleteResponse {
+    /// If it ended during the body: how much of it was received, and how much was expected (if
+    /// we knew, chunked bodies don't say)
+    pub body: Option<(usize, Option<usize>)>,
+}
+
+impl std::fmt::Display for IncompleteResponse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.body {
+            Some((received, Some(expected))) => write!(
+                f,
+                "Connection closed by the server during the body, after {} of {} bytes",
+                received, expected
+            ),
+            Some((received, None)) => write!(
+                f,
+                "Connection closed by the server during the chunked body, after {} bytes",
+                received
+            ),
+            None => write!(f, "Connection closed before the full response was received"),
+        }
+    }
+}
+
+impl std::error::Error for IncompleteResponse {}
+
+/// The connection failed (or was closed) before any of the response arrived, so the server can't
+/// have answered. A pooled connection the server closed while it sat idle ends up like this
+#[derive(Debug)]
+pub struct NothingReceived(pub io::Error);
+
+impl std::fmt::Display for NothingReceived {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0.kind() {
+            io::ErrorKind::UnexpectedEof => write!(
+                f,
+                "Connection closed by the server before any of the response arrived"
+            ),
+            _ => write!(f, "{}", self.0),
+        }
+    }
+}
+
+impl std::error::Error for NothingReceived {}
+
+/// If an error means the connection was closed on us, rather than something like a timeout
+fn is_closed(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::UnexpectedEof
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::BrokenPipe
+    )
+}
+
+/// Methods that can safely be sent again, since sending them twice does the same as once
+fn is_idempotent(method: &Method) -> bool {
+    [
+        Method::GET,
+        Method::HEAD,
+        Method::OPTIONS,
+        Method::PUT,
+        Method::DELETE,
+    ]
+    .contains(method)
+}
+
+/// The response body is bigger than [`RequestOptions::max_body`], so it wasn't (fully) downloaded
+#[derive(Debug)]
+pub struct BodyTooBig {
+    pub limit: u64,
+    /// The Content-Length, if that's how we knew before downloading it
+    pub length: Option<u64>,
+}
+
+impl std::fmt::Display for BodyTooBig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.length {
+            Some(length) => write!(
+                f,
+                "The body is {} bytes, more than the maximum of {}",
+                length, self.limit
+            ),
+            None => write!(
+                f,
+                "Stopped downloading the body after more than the maximum of {} bytes",
+                self.limit
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BodyTooBig {}
+
+/// The 1xx responses (like `103 Early Hints`) received before the final response, kept in the
+/// response's extensions if there were any
+#[derive(Clone, Debug, Default)]
+pub struct Informational(pub Vec<(StatusCode, HeaderMap)>);
+
+/// The trailer fields sent after a chunked body, kept in the response's extensions if there were
+/// any. Servers might only send them if the request said `TE: trailers`
+#[derive(Clone, Debug, Default)]
+pub struct Trailers(pub HeaderMap);
+
+/// Headers to leave out of a request, even the ones added by default (like User-Agent). Kept in
+/// the request's extensions
+#[derive(Clone, Debug, Default)]
+pub struct RemovedHeaders(pub Vec<HeaderName>);
+
+/// Connect for the request like with [`RequestOptions::safe_fetch`], checking the addresses
+/// actually connected to. Kept in the request's extensions, `--redirect-no-private` sets it on
+/// each redirect
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SafeFetch;
+
+/// The request's head (request line & headers) exactly as it went over the wire, after the
+/// defaults (Host, Content-Length, ...) were added. Kept in the response's extensions
+#[derive(Clone, Debug)]
+pub struct SentRequest {
+    pub method: String,
+    /// The path & query, or the full URL when sent to a proxy
+    pub target: String,
+    pub headers: HeaderMap,
+    /// All of it as text, `\r\n`s and the empty line at the end included
+    pub head: String,
+}
+
 /// Execute an HTTP 1.1 request, then parse the response
 /// This will build the request line, headers, and body (if any), then send it to the server
 ///
-/// Note: if the server returns an incorrect content-length that's:
-///   - too long: client will block until the tcp connection times out
-///   - too short: the returned body will be cut short
-///   - not present: content-length defaults to 0, so no body is returned
-pub fn http_request(
-    req: Request<Option<&[u8]>>,
-    verbosity: u8,
-) -> Result<Response<Vec<u8>>, RequestError> {
-    // Create HTTP request we'll send
-    let http_message = create_http_message(&req)?;
+/// Note: if the server returns an incorrect content-length that's:
+///   - too long: client will block until the tcp connection closes, then error
+///   - too short: the returned body will be cut short
+///   - not present: the body is everything until the server closes the connection
+pub fn http_request(
+    req: Request<Option<&[u8]>>,
+    options: &RequestOptions,
+) -> Result<Response<Vec<u8>>, RequestError> {
+    // Create HTTP request we'll send
+    let mut http_message = create_http_message(&req, true)?;
+
+    // Plain http goes straight through the proxy, which needs the full URL to know where to send it
+    if options.proxy.is_some() && req.uri().scheme_str() != Some("https") {
+        http_message.abs_path = req.uri().to_string();
+        add_proxy_authorization(&mut http_message.headers, options)?;
+    }
+
+    apply_target_form(&mut http_message, req.uri(), options);
+
+    let (message, body) = http_message.to_parts(&RequestStyles::default())?;
+
+    // Kept with the response too, for --timing
+    let (recorder, events) = EventHook::recorder();
+    let options = &RequestOptions {
+        events: [options.events.as_slice(), &[recorder]].concat(),
+        safe_fetch: options.safe_fetch || req.extensions().get::<SafeFetch>().is_some(),
+        ..options.clone()
+    };
+
+    // Reuse a connection if we have one, otherwise connect to server via TCP, using TLS for https
+    let key = pool_key(req.uri(), options);
+    let pooled = if options.fresh_connect {
+        None
+    } else {
+        pool::checkout(&key, options.max_conn_age)
+    };
+
+    let authority = get_authority(req.uri());
+    let start = Instant::now();
+    let (mut connection, mut connected) = match pooled {
+        Some(connection) => {
+            connection.deadline.start_request(options.timeouts.total);
+            (connection, None)
+        }
+        None => (connect(req.uri(), options)?, Some(start.elapsed())),
+    };
+
+    count_request(&authority);
+    let (mut response, arrived) = match send(
+        &mut connection,
+        &authority,
+        req.method(),
+        &message,
+        &body,
+        options,
+    ) {
+        // The server might have closed the idle connection on us, try again with a new one. Only
+        // if none of the response arrived (so the server can't have answered), and only if
+        // sending the request twice is harmless
+        Err(err)
+            if connection.reused && err.is::<NothingReceived>() && is_idempotent(req.method()) =>
+        {
+            if let Ok(mut events) = events.lock() {
+                events.clear();
+            }
+
+            connection = connect(req.uri(), options)?;
+            connected = Some(start.elapsed());
+            count_request(&authority);
+            send(
+                &mut connection,
+                &authority,
+                req.method(),
+                &message,
+                &body,
+                options,
+            )?
+        }
+        result => result?,
+    };
+
+    response.extensions_mut().insert(Timings {
+        connect: connected,
+        early_hints: arrived
+            .early_hints
+            .map(|early_hints| early_hints.duration_since(start)),
+        first_byte: arrived.headers.duration_since(start),
+        total: start.elapsed(),
+    });
+    response
+        .extensions_mut()
+        .insert(http_message.sent(&message));
+
+    if let Ok(mut events) = events.lock() {
+        let events = ConnectionEvents(std::mem::take(&mut *events));
+        response.extensions_mut().insert(events);
+    }
+
+    if can_reuse(&http_message, &response) {
+        pool::checkin(&key, connection);
+    }
+
+    Ok(response)
+}
+
+/// Print `req` the way [`http_request`] (or [`http_upgrade`]) sends it, defaults like Host and the
+/// proxy's full URL included, if very verbose
+pub fn print_request(
+    req: &Request<Option<&[u8]>>,
+    options: &RequestOptions,
+) -> Result<(), RequestError> {
+    let mut http_message = create_http_message(req, true)?;
+
+    if options.proxy.is_some() && req.uri().scheme_str() != Some("https") {
+        http_message.abs_path = req.uri().to_string();
+        add_proxy_authorization(&mut http_message.headers, options)?;
+    }
+
+    apply_target_form(&mut http_message, req.uri(), options);
+    print_http_message(&http_message, options)
+}
+
+/// Send `req` on a new connection to switch protocols (like to WebSocket), returning the
+/// response and, if it was a `101 Switching Protocols`, the connection to use the new protocol on
+///
+/// The request's timeouts stop applying once the response arrives, the new protocol is on its own
+pub fn http_upgrade(
+    req: Request<Option<&[u8]>>,
+    options: &RequestOptions,
+) -> Result<(Response<Vec<u8>>, Option<Connection>), RequestError> {
+    let mut http_message = create_http_message(&req, true)?;
+
+    if options.proxy.is_some() && req.uri().scheme_str() != Some("https") {
+        http_message.abs_path = req.uri().to_string();
+        add_proxy_authorization(&mut http_message.headers, options)?;
+    }
+
+    apply_target_form(&mut http_message, req.uri(), options);
+
+    let (message, body) = http_message.to_parts(&RequestStyles::default())?;
+
+    let authority = get_authority(req.uri());
+    let mut connection = connect(req.uri(), options)?;
+    count_request(&authority);
+    let (mut response, _) = send(
+        &mut connection,
+        &authority,
+        req.method(),
+        &message,
+        &body,
+        options,
+    )?;
+    response
+        .extensions_mut()
+        .insert(http_message.sent(&message));
+
+    if response.status() != StatusCode::SWITCHING_PROTOCOLS {
+        return Ok((response, None));
+    }
+
+    connection.deadline.start_request(None);
+    Ok((response, Some(connection)))
+}
+
+/// Replace the request-target with `--request-target`, or the `--target-form` one
+fn apply_target_form(http_message: &mut HttpMessage, uri: &Uri, options: &RequestOptions) {
+    if let Some(target) = &options.request_target {
+        http_message.abs_path = target.clone();
+        return;
+    }
+
+    let Some(form) = options.target_form else {
+        return;
+    };
+
+    if form == TargetForm::Asterisk && http_message.method != Method::OPTIONS.as_str() {
+        warn(
+            WarningKind::Usage,
+            format!(
+                "Sending a {} with the asterisk-form, which is only meant for OPTIONS",
+                http_message.method
+            ),
+        );
+    }
+
+    http_message.abs_path = form.target(uri);
+}
+
+/// Basic auth for the proxy, from `--proxy-user` or the proxy URL's `user:password@`
+fn proxy_credentials(options: &RequestOptions) -> Result<Option<String>, RequestError> {
+    if options.proxy_user.is_some() {
+        return Ok(options.proxy_user.clone());
+    }
+
+    match &options.proxy {
+        Some(proxy) => Ok(split_userinfo(proxy)?.1),
+        None => Ok(None),
+    }
+}
+
+/// Add the proxy's `Proxy-Authorization`, unless one was given with -h (or by --ntlm)
+fn add_proxy_authorization(
+    headers: &mut HeaderMap,
+    options: &RequestOptions,
+) -> Result<(), RequestError> {
+    if headers.contains_key(header::PROXY_AUTHORIZATION) {
+        return Ok(());
+    }
+
+    if let Some(user) = proxy_credentials(options)? {
+        headers.insert(header::PROXY_AUTHORIZATION, basic_auth(&user).parse()?);
+    }
+
+    Ok(())
+}
+
+/// Open a new connection for a request, through the proxy if there is one
+fn connect(uri: &Uri, options: &RequestOptions) -> Result<Connection, RequestError> {
+    let https = uri.scheme_str() == Some("https");
+    let deadline = Deadline::default();
+    deadline.start_request(options.timeouts.total);
+
+    // The proxy resolves the host itself, so there's no telling where it'd actually connect
+    if options.safe_fetch && options.proxy.is_some() {
+        return Err(
+            "--safe-fetch (and --redirect-no-private) can't check where a proxy connects to, so it can't use one".into(),
+        );
+    }
+
+    let stream = match &options.proxy {
+        Some(proxy) if https => proxy_tunnel(proxy, &get_authority(uri), options, &deadline)?,
+        Some(proxy) => tcp_connect(proxy, options, &deadline)?,
+        None => tcp_connect(uri, options, &deadline)?,
+    };
+
+    let peer = stream.peer_addr().ok();
+    let mut stream: Box<dyn ReadAndWrite> = match https {
+        true => tls_connect(uri, stream, options, &deadline)?,
+        false => Box::new(stream),
+    };
+
+    stream = Box::new(Counted::new(stream, &get_authority(uri)));
+
+    if let Some(trace) = &options.trace {
+        stream = Box::new(trace.wrap(stream, &pool_key(uri, options)));
+    }
+
+    if let Some(rate) = options.limit_rate.filter(|rate| *rate > 0) {
+        stream = Box::new(Throttled::new(stream, rate));
+    }
+
+    Ok(Connection::new(stream, deadline, peer))
+}
+
+/// When the parts of a response's head arrived
+struct Arrived {
+    /// The first `103 Early Hints`, if there was one
+    early_hints: Option<Instant>,
+    /// The final response's headers
+    headers: Instant,
+}
+
+/// Send a request, then read & parse the response, also returning when its head arrived
+fn send(
+    connection: &mut Connection,
+    authority: &str,
+    method: &Method,
+    message: &str,
+    body: &[u8],
+    options: &RequestOptions,
+) -> Result<(Response<Vec<u8>>, Arrived), RequestError> {
+    let deadline = &connection.deadline;
+    let peer = connection.peer;
+    deadline.start(
+        "Waiting for response headers",
+        options.timeouts.response_header,
+    );
+
+    let nothing_received = |err: io::Error| -> RequestError {
+        match is_closed(&err) {
+            true => Box::new(NothingReceived(err)),
+            false => err.into(),
+        }
+    };
+
+    let writing = Instant::now();
+    let stream = connection.stream.get_mut();
+    stream
+        .write_all(message.as_bytes())
+        .map_err(nothing_received)?;
+    stream.write_all(body).map_err(nothing_received)?;
+
+    let written = Instant::now();
+    emit(
+        &options.events,
+        EventKind::RequestWritten {
+            bytes: (message.len() + body.len()) as u64,
+        },
+        authority,
+        peer,
+        written - writing,
+    );
+
+    // Wait for the response to start, so a closed connection can be told apart from one that
+    // closed partway through the response
+    if connection
+        .stream
+        .fill_buf()
+        .map_err(nothing_received)?
+        .is_empty()
+    {
+        return Err(nothing_received(io::ErrorKind::UnexpectedEof.into()));
+    }
+
+    // Once the headers are in, only the read & total timeouts are left
+    let mut first_byte = None;
+    let mut headers_at = None;
+    let mut early_hints = None;
+    let response = parse_response(
+        &mut connection.stream,
+        method,
+        &mut |status| {
+            if first_byte.is_none() {
+                let now = Instant::now();
+                first_byte = Some(now);
+                emit(
+                    &options.events,
+                    EventKind::FirstByte,
+                    authority,
+                    peer,
+                    now - written,
+                );
+            }
+
+            if status.as_u16() == 103 {
+                early_hints = early_hints.or_else(|| Some(Instant::now()));
+            } else if !status.is_informational() {
+                deadline.end_phase();
+                headers_at = Some(Instant::now());
+            }
+        },
+        options.stream.as_deref(),
+        options.max_memory_body,
+        options.max_body,
+    )?;
+
+    emit(
+        &options.events,
+        EventKind::BodyComplete {
+            bytes: body_len(&response),
+        },
+        authority,
+        peer,
+        first_byte.map_or_else(Duration::default, |first_byte| first_byte.elapsed()),
+    );
+
+    let arrived = Arrived {
+        early_hints,
+        headers: headers_at.unwrap_or_else(Instant::now),
+    };
+    Ok((response, arrived))
+}
+
+/// Connections are pooled per scheme + authority (+ proxy), so http and https never get mixed up.
+/// Anything else that changes how a connection is made is part of it too, requests with
+/// different settings (like in a collection) can't end up on each other's connections
+fn pool_key(uri: &Uri, options: &RequestOptions) -> String {
+    let mut key = format!(
+        "{}://{}",
+        uri.scheme_str().unwrap_or("http"),
+        get_authority(uri)
+    );
+
+    if let Some(proxy) = &options.proxy {
+        key = format!("{} via {}", key, get_authority(proxy));
+    }
+
+    let mut settings = vec![];
+
+    if let Some(family) = options.ip_family {
+        settings.push(family.to_string());
+    }
+
+    if options.insecure {
+        settings.push("insecure".to_string());
+    }
+
+    // Connections made without checking where they went can't be reused by ones that check
+    if options.safe_fetch {
+        settings.push("safe".to_string());
+    }
+
+    if let Some(ca_cert) = &options.ca_cert {
+        settings.push(format!("cacert {}", ca_cert));
+    }
+
+    if let Some(tag) = &options.connection_tag {
+        settings.push(format!("tag {}", tag));
+    }
+
+    if settings.is_empty() {
+        key
+    } else {
+        format!("{} ({})", key, settings.join(", "))
+    }
+}
+
+/// Check if the connection can be used for another request after this response
+///
+/// Both sides need to be fine with keeping it open, and we need to know exactly where the
+/// body ended (otherwise we might have left part of it unread)
+fn can_reuse(http_message: &HttpMessage, response: &Response<Vec<u8>>) -> bool {
+    let wants_close = |headers: &HeaderMap| {
+        headers
+            .get_all(header::CONNECTION)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .any(|value| value.to_ascii_lowercase().contains("close"))
+    };
+
+    let keep_alive = match response.version() {
+        // HTTP/1.0 closes unless explicitly told otherwise
+        Version::HTTP_10 => response
+            .headers()
+            .get(header::CONNECTION)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.eq_ignore_ascii_case("keep-alive")),
+        _ => !wants_close(response.headers()),
+    };
+
+    let framed = response.headers().contains_key(header::CONTENT_LENGTH)
+        || response
+            .headers()
+            .get(header::TRANSFER_ENCODING)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.to_ascii_lowercase().contains("chunked"));
+
+    keep_alive && framed && !wants_close(&http_message.headers)
+}
+
+/// How far a chunked body that's still arriving has gotten, so each read only looks at what's new
+#[cfg(feature = "async")]
+#[derive(Default)]
+struct ChunkProgress {
+    /// Where the next chunk starts
+    at: usize,
+    /// The size of the chunks before it
+    received: u64,
+}
+
+#[cfg(feature = "async")]
+impl ChunkProgress {
+    /// Go over the chunks that have arrived in `body`. Returns if there's nothing more to read,
+    /// since the body and its trailers are all there, or more than `max_body` of it is (or it's
+    /// invalid, which the parser will say)
+    fn advance(&mut self, body: &[u8], max_body: Option<u64>) -> bool {
+        let line_end = |data: &[u8]| data.windows(2).position(|pair| pair == b"\r\n");
+
+        loop {
+            let rest = &body[self.at..];
+            let Some(size_end) = line_end(rest) else {
+                return false;
+            };
+
+            let size = rest[..size_end].split(|byte| *byte == b';').next();
+            let Some(size) = size
+                .and_then(|size| from_utf8(size).ok())
+                .and_then(|size| u64::from_str_radix(size.trim(), 16).ok())
+            else {
+                return true;
+            };
+
+            let data = &rest[size_end + 2..];
+
+            if size == 0 {
+                // The trailers end with an empty line
+                return data.starts_with(b"\r\n") || data.windows(4).any(|w| w == b"\r\n\r\n");
+            }
+
+            let arrived = self.received + size.min(data.len() as u64);
+            if max_body.is_some_and(|max| arrived > max) {
+                return true;
+            }
+
+            match usize::try_from(size)
+                .ok()
+                .and_then(|size| size.checked_add(2))
+            {
+                Some(chunk) if data.len() >= chunk => {
+                    self.at += size_end + 2 + chunk;
+                    self.received += size;
+                }
+                _ => return false,
+            }
+        }
+    }
+}
+
+/// Execute an HTTP 1.1 request asynchronously, then parse the response
+///
+/// This builds and parses messages exactly like [`http_request`], only the I/O is done with tokio.
+/// `limit_rate` and `timeouts` aren't supported here, throttling & timeouts belong to whoever
+/// drives the future. Neither are `strict_tls` (a missing close_notify is always a normal close),
+/// `keylog` and `events`, and nothing is counted for `--summary`
+#[cfg(feature = "async")]
+pub async fn http_request_async(
+    req: Request<Option<&[u8]>>,
+    options: &RequestOptions,
+) -> Result<Response<Vec<u8>>, RequestError> {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let http_message = create_http_message(&req, false)?;
+    print_http_message(&http_message, options)?;
+
+    if options.proxy.is_some() {
+        return Err("Proxies aren't supported by http_request_async yet".into());
+    }
+
+    let mut stream = tcp_connect_async(req.uri(), options).await?;
+
+    let (message, body) = http_message.to_parts(&RequestStyles::default())?;
+    stream.write_all(message.as_bytes()).await?;
+    stream.write_all(body.as_slice()).await?;
+    stream.flush().await?;
+
+    let mut response: Vec<u8> = vec![];
+    let mut chunk = [0; 8192];
+
+    // Read until the head is all there, only parsing it once a blank line arrives (it's parsed
+    // again if that was the end of a 1xx response's head)
+    let (head, head_length) = loop {
+        let read = stream.read(&mut chunk).await?;
+        let searched = response.len().saturating_sub(3);
+        response.extend_from_slice(&chunk[..read]);
+
+        if read > 0 && !response[searched..].windows(4).any(|w| w == b"\r\n\r\n") {
+            continue;
+        }
+
+        let mut rest = response.as_slice();
+        match read_head(
+            &mut (&mut rest).bytes(),
+            req.method(),
+            &mut |_| {},
+            options.max_body,
+        ) {
+            Err(err) if read > 0 && err.is::<IncompleteResponse>() => continue,
+            result => break (result?, response.len() - rest.len()),
+        }
+    };
+
+    // Then the body, until its framing says it's done (or the server closes)
+    let mut chunks = ChunkProgress::default();
+    loop {
+        let body = &response[head_length..];
+        let done = match head.framing {
+            Framing::Length(length) => body.len() >= length,
+            Framing::Chunked => chunks.advance(body, options.max_body),
+            Framing::Close => options.max_body.is_some_and(|max| body.len() as u64 > max),
+        };
+
+        if done {
+            break;
+        }
+
+        let read = stream.read(&mut chunk).await?;
+        if read == 0 {
+            break;
+        }
+        response.extend_from_slice(&chunk[..read]);
+    }
+
+    let mut response = read_response_body(
+        &mut response[head_length..].bytes(),
+        head,
+        None,
+        None,
+        options.max_body,
+    )?;
+    response
+        .extensions_mut()
+        .insert(http_message.sent(&message));
+
+    Ok(response)
+}
+
+/// Print the message we're about to send, if very verbose
+fn print_http_message(
+    http_message: &HttpMessage,
+    options: &RequestOptions,
+) -> Result<(), RequestError> {
+    if options.verbosity >= VERY_VERBOSE {
+        let (message, body) = http_message.to_parts(&RequestStyles::colorized())?;
+        let display_body = if !body.is_empty() {
+            match from_utf8(body.as_slice()) {
+                Ok(body) => format!("{}\n\n", body),
+                Err(_) => String::from("[Invalid UTF-8]"),
+            }
+        } else {
+            String::new()
+        };
+
+        print!(
+            "{}\n{}{}",
+            "→ Sending".out_color(|t| t.yellow()),
+            message,
+            display_body,
+        );
+    }
+
+    Ok(())
+}
+
+pub trait ReadAndWrite: io::Read + io::Write + Send {}
+
+impl<T: io::Read + io::Write + Send> ReadAndWrite for T {}
+
+/// Connects to a server via TCP
+///
+/// `fresh_connect` skips the DNS cache
+pub(crate) fn tcp_connect(
+    uri: &Uri,
+    options: &RequestOptions,
+    deadline: &Deadline,
+) -> Result<TimedStream, RequestError> {
+    let authority = get_authority(uri);
+    let timeouts = &options.timeouts;
+
+    let dns_budget = phase_budget(timeouts.dns, deadline)?;
+    let looking_up = Instant::now();
+    let addresses = pool::resolve(&authority, options.fresh_connect, dns_budget)?;
+    let addresses = IpFamily::filter(options.ip_family, &authority, addresses)?;
+    emit(
+        &options.events,
+        EventKind::DnsResolved {
+            addresses: addresses.clone(),
+        },
+        &authority,
+        None,
+        looking_up.elapsed(),
+    );
+
+    if options.safe_fetch {
+        check_safe(&authority, &addresses)?;
+    }
+
+    let connecting = Instant::now();
+    let stream = match phase_budget(timeouts.connect, deadline)? {
+        Some(budget) => connect_timeout(&addresses, budget)
+            .map_err(|err| format!("Couldn't connect to {}: {}", authority, err))?,
+        None => TcpStream::connect(addresses.as_slice())?,
+    };
+    emit(
+        &options.events,
+        EventKind::TcpConnected {
+            local: stream.local_addr().ok(),
+        },
+        &authority,
+        stream.peer_addr().ok(),
+        connecting.elapsed(),
+    );
+
+    Ok(TimedStream::new(stream, deadline.clone(), timeouts.read))
+}
+
+/// Make sure none of the addresses a host resolved to are private, so a public name can't point
+/// somewhere internal
+fn check_safe(authority: &str, addresses: &[SocketAddr]) -> Result<(), RequestError> {
+    match addresses.iter().find(|address| is_private(address.ip())) {
+        Some(address) => Err(format!(
+            "Refusing to connect to {} ({}), it's a private address",
+            authority,
+            address.ip()
+        )
+        .into()),
+        None => Ok(()),
+    }
+}
+
+/// Try connecting to each address in turn, giving each one `budget`
+fn connect_timeout(addresses: &[SocketAddr], budget: Duration) -> io::Result<TcpStream> {
+    let mut last_err = io::Error::new(io::ErrorKind::NotFound, "no addresses to connect to");
+
+    for address in addresses {
+        match TcpStream::connect_timeout(address, budget) {
+            Ok(stream) => return Ok(stream),
+            Err(err) if err.kind() == io::ErrorKind::TimedOut => {
+                last_err = io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!("connecting timed out after {:?}", budget),
+                )
+            }
+            Err(err) => last_err = err,
+        }
+    }
+
+    Err(last_err)
+}
+
+/// How long a phase can take: its own timeout, or less if the whole request runs out first
+fn phase_budget(
+    timeout: Option<Duration>,
+    deadline: &Deadline,
+) -> Result<Option<Duration>, RequestError> {
+    Ok(match (timeout, deadline.remaining()?) {
+        (Some(timeout), Some(remaining)) => Some(timeout.min(remaining)),
+        (timeout, remaining) => timeout.or(remaining),
+    })
+}
+
+/// Start TLS over a stream to the server for `uri`
+fn tls_connect(
+    uri: &Uri,
+    stream: TimedStream,
+    options: &RequestOptions,
+    deadline: &Deadline,
+) -> Result<Box<dyn ReadAndWrite>, RequestError> {
+    let host = uri.host().ok_or("URL has no host to connect to")?;
+    deadline.start("TLS handshake", options.timeouts.tls);
+    let handshake = Instant::now();
+    let peer = stream.peer_addr().ok();
+
+    let stream = EofIsUnexpected(stream);
+    let stream: Box<dyn ReadAndWrite> = match &options.keylog {
+        Some(keylog) => keylog_connect(host, stream, options, keylog)?,
+        None => {
+            let stream = tls_connector(options)?.connect(host, stream)?;
+
+            if let (Some(within), Some(certificate)) =
+                (options.cert_expiry_check, stream.peer_certificate()?)
+            {
+                check_expiry(host, &certificate.to_der()?, within);
+            }
+
+            Box::new(stream)
+        }
+    };
+
+    emit(
+        &options.events,
+        EventKind::TlsNegotiated,
+        &get_authority(uri),
+        peer,
+        handshake.elapsed(),
+    );
+
+    Ok(Box::new(CloseNotify {
+        inner: stream,
+        strict: options.strict_tls,
+    }))
+}
+
+/// Servers should end TLS with a close_notify alert, so nobody can cut a response short by
+/// closing the connection on us. Plenty don't bother, so it's only an error with `strict`
+///
+/// A close_notify reads as the end of the stream, the connection closing without one is the
+/// [`EofIsUnexpected`] error from under the TLS stream
+struct CloseNotify<S> {
+    inner: S,
+    strict: bool,
+}
+
+impl<S: Read> CloseNotify<S> {
+    fn missing(&self) -> io::Result<usize> {
+        match self.strict {
+            true => Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "TLS connection closed without a close_notify, the response might be cut short",
+            )),
+            false => Ok(0),
+        }
+    }
+}
+
+impl<S: Read> Read for CloseNotify<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.inner.read(buf) {
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => self.missing(),
+            result => result,
+        }
+    }
+}
+
+impl<S: io::Write> io::Write for CloseNotify<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// The connection under a TLS stream, where it closing is an [`io::ErrorKind::UnexpectedEof`]
+///
+/// TLS libraries stop reading once they get a close_notify, so if the connection itself ends
+/// the server never sent one. OpenSSL hands our error back as is, where it'd otherwise make up
+/// its own (that only says what happened in its message)
+#[derive(Debug)]
+struct EofIsUnexpected<S>(S);
+
+impl<S: Read> Read for EofIsUnexpected<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.0.read(buf)? {
+            0 if !buf.is_empty() => Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "Connection closed partway through TLS",
+            )),
+            read => Ok(read),
+        }
+    }
+}
+
+impl<S: io::Write> io::Write for EofIsUnexpected<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+/// Build the TLS connector for a request's options
+///
+/// I am not implementing crypto myself, so this uses native_tls
+/// Which binds to native implementations for us
+/// (openssl on linux, schannel on windows, security-framework on macos)
+pub(crate) fn tls_connector(options: &RequestOptions) -> Result<TlsConnector, RequestError> {
+    let mut builder = TlsConnector::builder();
+    builder.danger_accept_invalid_certs(options.insecure);
+    builder.danger_accept_invalid_hostnames(options.insecure);
 
-    if verbosity >= VERY_VERBOSE {
-        let (message, body) = http_message.to_parts(&RequestStyles::colorized())?;
-        let display_body = if !body.is_empty() {
-            match from_utf8(body.as_slice()) {
-                Ok(body) => format!("{}\n\n", body),
-                Err(_) => String::from("[Invalid UTF-8]"),
-            }
-        } else {
-            String::new()
+    if let Some(file) = &options.ca_cert {
+        let pem = std::fs::read(expand_home(file))
+            .map_err(|err| format!("Can't read --cacert {}: {}", file, err))?;
+        builder.add_root_certificate(Certificate::from_pem(&pem)?);
+    }
+
+    Ok(builder.build()?)
+}
+
+/// The certificate the server for an https `uri` presents (as DER), connecting just for it
+pub fn server_certificate(uri: &Uri, options: &RequestOptions) -> Result<Vec<u8>, RequestError> {
+    let host = uri.host().ok_or("URL has no host to connect to")?;
+    let deadline = Deadline::default();
+    deadline.start_request(options.timeouts.total);
+
+    let stream = match &options.proxy {
+        Some(proxy) => proxy_tunnel(proxy, &get_authority(uri), options, &deadline)?,
+        None => tcp_connect(uri, options, &deadline)?,
+    };
+
+    deadline.start("TLS handshake", options.timeouts.tls);
+    let stream = tls_connector(options)?.connect(host, stream)?;
+    let certificate = stream
+        .peer_certificate()?
+        .ok_or("The server didn't send a certificate")?;
+
+    Ok(certificate.to_der()?)
+}
+
+/// Open a tunnel to `authority` through an HTTP proxy with CONNECT
+pub fn proxy_tunnel(
+    proxy: &Uri,
+    authority: &str,
+    options: &RequestOptions,
+    deadline: &Deadline,
+) -> Result<TimedStream, RequestError> {
+    let mut stream = tcp_connect(proxy, options, deadline)?;
+    deadline.start("Proxy CONNECT", options.timeouts.response_header);
+
+    let request = Request::builder()
+        .method(Method::CONNECT)
+        .uri(authority)
+        .header(header::HOST, authority)
+        .body(None)?;
+    let mut http_message = create_http_message(&request, true)?;
+    add_proxy_authorization(&mut http_message.headers, options)?;
+    print_http_message(&http_message, options)?;
+
+    let (message, _) = http_message.to_parts(&RequestStyles::default())?;
+    io::Write::write_all(&mut stream, message.as_bytes())?;
+
+    // Once it's connected the server might speak first, so don't buffer past the response
+    let response = parse_response(
+        &mut BufReader::with_capacity(1, &mut stream),
+        &Method::CONNECT,
+        &mut |_| {},
+        None,
+        None,
+        None,
+    )?;
+
+    if response.status() == StatusCode::PROXY_AUTHENTICATION_REQUIRED {
+        let hint = match proxy_credentials(options)? {
+            Some(_) => "it rejected the credentials",
+            None => "give them with --proxy-user USER:PASSWORD",
         };
 
-        print!(
-            "{}\n{}{}",
-            "→ Sending".out_color(|t| t.yellow()),
-            message,
-            display_body,
-        );
+        return Err(format!(
+            "Proxy {} needs authentication to connect to {} ({})",
+            get_authority(proxy),
+            authority,
+            hint
+        )
+        .into());
     }
 
-    // Connect to server via TCP, using TLS for https
-    let mut stream = tcp_connect(req.uri())?;
-
-    // Send request
-    let (message, body) = http_message.to_parts(&RequestStyles::default())?;
-    stream.write_all(message.as_bytes())?;
-    stream.write_all(body.as_slice())?;
+    if !response.status().is_success() {
+        return Err(format!(
+            "Proxy {} refused to connect to {}: {}",
+            get_authority(proxy),
+            authority,
+            response.status()
+        )
+        .into());
+    }
 
-    // Read & Parse response
-    let buf_reader = BufReader::new(stream);
-    parse_http_response(buf_reader)
+    Ok(stream)
 }
 
-trait ReadAndWrite: io::Read + io::Write {}
+#[cfg(feature = "async")]
+trait AsyncReadAndWrite: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin {}
 
-impl<T: io::Read + io::Write> ReadAndWrite for T {}
+#[cfg(feature = "async")]
+impl<T: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin> AsyncReadAndWrite for T {}
 
-/// Connects to a server via TCP, using TLS for https
-fn tcp_connect(uri: &Uri) -> Result<Box<dyn ReadAndWrite>, RequestError> {
+/// Connects to a server via TCP, using TLS for https, but async
+#[cfg(feature = "async")]
+async fn tcp_connect_async(
+    uri: &Uri,
+    options: &RequestOptions,
+) -> Result<Box<dyn AsyncReadAndWrite>, RequestError> {
     let authority = get_authority(uri);
-    let addresses: Vec<SocketAddr> = authority.to_socket_addrs()?.collect();
-    let stream = TcpStream::connect(addresses.as_slice())?;
+    let addresses = tokio::net::lookup_host(&authority)
+        .await?
+        .collect::<Vec<_>>();
+    let addresses = IpFamily::filter(options.ip_family, &authority, addresses)?;
+
+    if options.safe_fetch {
+        check_safe(&authority, &addresses)?;
+    }
+
+    let stream = tokio::net::TcpStream::connect(addresses.as_slice()).await?;
 
     if uri.scheme_str() == Some("https") {
-        // We need to setup a TLS connector to handle HTTPS for us
-        // I am not implementing crypto myself, so this uses native_tls
-        // Which binds to native implementations for us
-        // (openssl on linux, schannel on windows, security-framework on macos)
-        let connector = TlsConnector::new().unwrap();
-        let stream = connector.connect(uri.host().unwrap(), stream)?;
+        let connector = tokio_native_tls::TlsConnector::from(tls_connector(options)?);
+        let host = uri.host().unwrap();
+        let stream = connector.connect(host, stream).await?;
+
+        if let (Some(within), Some(certificate)) = (
+            options.cert_expiry_check,
+            stream.get_ref().peer_certificate()?,
+        ) {
+            check_expiry(host, &certificate.to_der()?, within);
+        }
+
         Ok(Box::new(stream))
     } else {
         Ok(Box::new(stream))
@@ -143,12 +1333,28 @@ impl HttpMessage {
 
         Ok((message, self.body.clone().unwrap_or_default()))
     }
+
+    /// What was sent, given the unstyled `head` from [`HttpMessage::to_parts`]
+    fn sent(&self, head: &str) -> SentRequest {
+        SentRequest {
+            method: self.method.clone(),
+            target: self.abs_path.clone(),
+            headers: self.headers.clone(),
+            head: head.to_string(),
+        }
+    }
 }
 
 impl From<&Request<Option<&[u8]>>> for HttpMessage {
     fn from(req: &Request<Option<&[u8]>>) -> Self {
         let method = req.method().to_string();
-        let abs_path = req.uri().path_and_query().unwrap().to_string();
+        // CONNECT's target is just the authority, so there isn't a path to use. Any other URL
+        // without a path (like one put together from parts) is asking for the root
+        let abs_path = match req.uri().path_and_query() {
+            Some(path) if !path.as_str().is_empty() => path.to_string(),
+            _ if req.uri().scheme().is_some() => "/".to_string(),
+            _ => req.uri().to_string(),
+        };
         let version = format!("{:?}", req.version());
         let headers = req.headers().to_owned();
         let body = req.body().map(|b| b.to_vec());
@@ -168,16 +1374,20 @@ impl From<&Request<Option<&[u8]>>> for HttpMessage {
 /// This will add any missing required/"strongly suggested" headers (Host, User-Agent, Connection, Content-Length) if not already defined
 /// and then build an HttpMessage that can be turned into a string (to send) or a colored string (to display)
 ///
+/// `keep_alive` is if we're going to try to reuse the connection afterwards
+///
 /// Note: Rust uses UTF-8 as default string encodings, so Header/Values are encoded as UTF-8.
 /// In most cases you're likely using ASCII-compatible characters, so this is fine, but you might run into
 /// oddities if you start sending UTF-8 characters in your headers
-fn create_http_message(req: &Request<Option<&[u8]>>) -> Result<HttpMessage, RequestError> {
-    let authority = get_authority(req.uri());
+fn create_http_message(
+    req: &Request<Option<&[u8]>>,
+    keep_alive: bool,
+) -> Result<HttpMessage, RequestError> {
     let mut added_headers = HeaderMap::new();
 
     // Host: www.example.com
     if !req.headers().contains_key(header::HOST) {
-        added_headers.insert(header::HOST, authority.parse()?);
+        added_headers.insert(header::HOST, host_header(req.uri()).parse()?);
     }
 
     // Set a default UA
@@ -189,9 +1399,15 @@ fn create_http_message(req: &Request<Option<&[u8]>>) -> Result<HttpMessage, Requ
     }
 
     // Set a default connection header
-    // We don't reuse the connection, so just tell the server to close
+    // If we won't reuse the connection, just tell the server to close
+    // TE is hop-by-hop, so it needs to be listed too (RFC 9110 10.1.4)
     if !req.headers().contains_key(header::CONNECTION) {
-        added_headers.insert(header::CONNECTION, "close".parse()?);
+        let connection = if keep_alive { "keep-alive" } else { "close" };
+        let connection = match req.headers().contains_key(header::TE) {
+            true => format!("{}, TE", connection),
+            false => connection.to_string(),
+        };
+        added_headers.insert(header::CONNECTION, connection.parse()?);
     }
 
     // Calculate content-length
@@ -202,18 +1418,432 @@ fn create_http_message(req: &Request<Option<&[u8]>>) -> Result<HttpMessage, Requ
         }
     }
 
+    if let Some(removed) = req.extensions().get::<RemovedHeaders>() {
+        for name in &removed.0 {
+            added_headers.remove(name);
+        }
+    }
+
     let mut message = HttpMessage::from(req);
     message.headers.extend(added_headers);
 
     Ok(message)
 }
 
+/// The headers a request is sent with: its own, plus the defaults [`create_http_message`] adds
+/// for any it doesn't have
+pub fn resolved_headers(req: &Request<Option<&[u8]>>) -> Result<HeaderMap, RequestError> {
+    Ok(create_http_message(req, true)?.headers)
+}
+
+/// Get the next byte of the response, erroring if the connection ended early
+fn next_byte<T: Read>(bytes: &mut io::Bytes<T>) -> Result<u8, RequestError> {
+    match bytes.next() {
+        Some(Ok(byte)) => Ok(byte),
+        // A missing close_notify doesn't matter much if we know the response was cut short
+        Some(Err(err)) if err.kind() == io::ErrorKind::UnexpectedEof => {
+            Err(Box::new(IncompleteResponse::default()))
+        }
+        Some(Err(err)) => Err(err.into()),
+        None => Err(Box::new(IncompleteResponse::default())),
+    }
+}
+
+/// Strip any leading UTF-8 BOMs, CRs, and LFs from what should be the status line
+fn trim_status_line_garbage(mut line: &[u8]) -> &[u8] {
+    loop {
+        if let Some(rest) = line.strip_prefix(b"\xEF\xBB\xBF") {
+            line = rest;
+        } else if let Some(rest) = line
+            .strip_prefix(b"\r")
+            .or_else(|| line.strip_prefix(b"\n"))
+        {
+            line = rest;
+        } else {
+            return line;
+        }
+    }
+}
+
+/// How the end of a response body is found
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Framing {
+    /// Content-Length
+    Length(usize),
+    /// Transfer-Encoding: chunked
+    Chunked,
+    /// Neither, so the body is everything until the server closes the connection
+    Close,
+}
+
+/// Where the body goes as it's read: kept for the response, passed on to a [`StreamHandler`] a
+/// line at a time, or written to a [`Spool`] once it's too big to keep
+struct Body<'a> {
+    data: Vec<u8>,
+    received: usize,
+    stream: Option<MutexGuard<'a, dyn StreamHandler + 'static>>,
+    /// Past this many bytes, the body goes to `spool` instead
+    spool_at: Option<u64>,
+    spool: Option<Spool>,
+    /// Past this many bytes, stop with [`BodyTooBig`]
+    max: Option<u64>,
+}
+
+impl Body<'_> {
+    fn push(&mut self, byte: u8) -> io::Result<()> {
+        self.data.push(byte);
+        self.received += 1;
+
+        if let Some(limit) = self.max.filter(|max| self.received as u64 > *max) {
+            return Err(io::Error::other(BodyTooBig {
+                limit,
+                length: None,
+            }));
+        }
+
+        if self.stream.is_some() {
+            return match byte {
+                b'\n' => self.flush(),
+                _ => Ok(()),
+            };
+        }
+
+        match (&mut self.spool, self.spool_at) {
+            (Some(_), _) if self.data.len() >= 8192 => self.flush()?,
+            (None, Some(max)) if self.data.len() as u64 > max => {
+                let mut spool = Spool::create()?;
+                spool.write(&std::mem::take(&mut self.data))?;
+                self.spool = Some(spool);
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.data.is_empty() {
+            return Ok(());
+        }
+
+        if let Some(stream) = &mut self.stream {
+            stream.data(&self.data)?;
+            self.data.clear();
+        } else if let Some(spool) = &mut self.spool {
+            spool.write(&self.data)?;
+            self.data.clear();
+        }
+
+        Ok(())
+    }
+}
+
+/// Read a response body into `body` (and any trailers after a chunked one into `trailers`),
+/// erroring with [`IncompleteResponse`] if the connection closes before it's done
+fn read_body<T: Read>(
+    byte_iter: &mut io::Bytes<T>,
+    framing: Framing,
+    body: &mut Body,
+    trailers: &mut HeaderMap,
+) -> Result<(), RequestError> {
+    match framing {
+        Framing::Length(content_length) => {
+            // Parse the body, reading bytes until we meet content-length
+            while body.received < content_length {
+                body.push(next_byte(byte_iter)?)?;
+            }
+        }
+        Framing::Close => {
+            for byte in byte_iter {
+                body.push(byte?)?;
+            }
+        }
+        Framing::Chunked => loop {
+            // Read the chunk "head"
+            // [hex octets]*(;ext-name=ext-val)\r\n
+            // We need the num of octects in the chunk, but can ignore the chunk-ext
+            // We don't recognize any chunk extensions, so we MUST ignore them
+
+            // Read octets
+            let mut octets: Vec<u8> = vec![];
+            loop {
+                let byte = next_byte(byte_iter)?;
+                if byte == b';' || byte == b'\r' {
+                    break;
+                }
+                octets.push(byte);
+            }
+
+            // Read until end of line
+            loop {
+                let byte = next_byte(byte_iter)?;
+                if byte == b'\n' {
+                    break;
+                }
+            }
+
+            let octets = usize::from_str_radix(from_utf8(&octets).unwrap(), 16)?;
+
+            if octets == 0 {
+                // We've reached the end of the chunked body, the trailers follow until an empty
+                // line, like headers
+                loop {
+                    let mut line: Vec<u8> = vec![];
+                    while !line.ends_with(b"\r\n") {
+                        line.push(next_byte(byte_iter)?);
+                    }
+
+                    if line == b"\r\n" {
+                        break;
+                    }
+
+                    let (name, value) = from_utf8(&line)
+                        .ok()
+                        .and_then(|line| line.split_once(':'))
+                        .ok_or_else(|| {
+                            format!(
+                                "Invalid trailer: '{}'",
+                                String::from_utf8_lossy(&line).trim()
+                            )
+                        })?;
+
+                    trailers.append(
+                        name.trim().parse::<HeaderName>()?,
+                        value.trim().parse::<HeaderValue>()?,
+                    );
+                }
+
+                break;
+            }
+
+            // Read the chunk
+            for _ in 0..octets {
+                body.push(next_byte(byte_iter)?)?;
+            }
+
+            body.flush()?;
+
+            // Read the chunk end
+            loop {
+                let byte = next_byte(byte_iter)?;
+                if byte == b'\r' && next_byte(byte_iter)? == b'\n' {
+                    break;
+                }
+            }
+        },
+    }
+
+    body.flush()?;
+    Ok(())
+}
+
+/// The most that's allocated for a body before any of it arrives, the rest is grown into as it
+/// does
+const MAX_PREALLOCATED_BODY: usize = 1024 * 1024;
+
+/// Parse a Content-Length, erroring if it isn't a number or disagrees with a `previous` one
+///
+/// A list of the same length (`5, 5`) is allowed, from proxies that merged repeated headers
+/// ```
+/// # use httpc::http_request::parse_content_length;
+/// assert_eq!(parse_content_length("42", None).unwrap(), 42);
+/// assert_eq!(parse_content_length("42, 42", Some(42)).unwrap(), 42);
+/// assert!(parse_content_length("42, 43", None).is_err());
+/// assert!(parse_content_length("42", Some(43)).is_err());
+/// assert!(parse_content_length("+42", None).is_err());
+/// assert!(parse_content_length("99999999999999999999999", None).is_err());
+/// ```
+pub fn parse_content_length(value: &str, previous: Option<usize>) -> Result<usize, String> {
+    let mut length = previous;
+
+    for part in value.split(',').map(str::trim) {
+        let parsed = match part.bytes().all(|byte| byte.is_ascii_digit()) && !part.is_empty() {
+            true => part
+                .parse::<usize>()
+                .map_err(|_| format!("Content-Length {} is too big", part))?,
+            false => return Err(format!("Invalid Content-Length: '{}'", value)),
+        };
+
+        match length {
+            Some(length) if length != parsed => {
+                return Err(format!(
+                    "Conflicting Content-Lengths: {} and {}",
+                    length, parsed
+                ))
+            }
+            _ => length = Some(parsed),
+        }
+    }
+
+    Ok(length.unwrap_or_default())
+}
+
 /// Parse an HTTP response into a rust Response
-fn parse_http_response<T: Read>(reader: BufReader<T>) -> Result<Response<Vec<u8>>, RequestError> {
+///
+/// Any 1xx responses before the final one are skipped over, and kept as [`Informational`]. Only
+/// the response is read from `reader`, anything after it is left alone
+pub fn parse_http_response<T: Read>(
+    reader: &mut BufReader<T>,
+) -> Result<Response<Vec<u8>>, RequestError> {
+    parse_response(reader, &Method::GET, &mut |_| {}, None, None, None)
+}
+
+/// Parse an HTTP/1.x request into a rust Request, the other way around from
+/// [`parse_http_response`] (for `httpc serve`)
+///
+/// Returns `None` if the connection was closed before a request started. A chunked body's
+/// trailers are kept as [`Trailers`]
+pub fn parse_http_request<T: Read>(
+    reader: &mut BufReader<T>,
+) -> Result<Option<Request<Vec<u8>>>, RequestError> {
+    if reader.fill_buf()?.is_empty() {
+        return Ok(None);
+    }
+
+    let mut request_builder = Request::builder();
+    let mut started = false;
+    let mut content_length = None;
+    let mut chunked = false;
+
+    let mut byte_iter = reader.bytes();
+
+    loop {
+        let mut line: Vec<u8> = vec![];
+        while !line.ends_with(b"\r\n") {
+            line.push(next_byte(&mut byte_iter)?);
+        }
+
+        if !started {
+            // Like with status lines, empty lines before the request line are ignored
+            let request_line = trim_status_line_garbage(&line);
+
+            if request_line.is_empty() {
+                continue;
+            }
+
+            let invalid = || {
+                format!(
+                    "Invalid request line: '{}'",
+                    String::from_utf8_lossy(request_line).trim()
+                )
+            };
+            let mut parts = from_utf8(request_line)
+                .map_err(|_| invalid())?
+                .split_whitespace();
+            let (Some(method), Some(target), Some(version)) =
+                (parts.next(), parts.next(), parts.next())
+            else {
+                return Err(invalid().into());
+            };
+
+            request_builder = request_builder
+                .method(method.parse::<Method>()?)
+                .uri(target.parse::<Uri>()?)
+                .version(match version {
+                    "HTTP/1.0" => Version::HTTP_10,
+                    _ => Version::HTTP_11,
+                });
+            started = true;
+        } else if line == b"\r\n" {
+            break;
+        } else {
+            let (name, value) = from_utf8(&line)
+                .ok()
+                .and_then(|line| line.split_once(':'))
+                .ok_or_else(|| {
+                    format!(
+                        "Invalid header: '{}'",
+                        String::from_utf8_lossy(&line).trim()
+                    )
+                })?;
+            let (name, value) = (name.trim(), value.trim());
+
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = Some(parse_content_length(value, content_length)?);
+            }
+
+            if name.eq_ignore_ascii_case("transfer-encoding")
+                && value.to_ascii_lowercase().contains("chunked")
+            {
+                chunked = true;
+            }
+
+            request_builder =
+                request_builder.header(name.parse::<HeaderName>()?, value.parse::<HeaderValue>()?);
+        }
+    }
+
+    // Requests without either don't have a body, they can't be ended by closing the connection
+    let framing = if chunked {
+        Framing::Chunked
+    } else {
+        Framing::Length(content_length.unwrap_or_default())
+    };
+
+    let mut body = Body {
+        data: vec![],
+        received: 0,
+        stream: None,
+        spool_at: None,
+        spool: None,
+        max: None,
+    };
+    let mut trailers = HeaderMap::new();
+    read_body(&mut byte_iter, framing, &mut body, &mut trailers)?;
+
+    let mut request = request_builder.body(body.data)?;
+
+    if !trailers.is_empty() {
+        request.extensions_mut().insert(Trailers(trailers));
+    }
+
+    Ok(Some(request))
+}
+
+/// [`parse_http_response`] for a response to `method`, calling `on_headers` with the status
+/// once each head (1xx ones too) has been received and offering the body to `stream`. Bodies
+/// bigger than `spool_at` are written to a temp file, ones bigger than `max_body` are a
+/// [`BodyTooBig`]
+fn parse_response<T: Read>(
+    reader: &mut BufReader<T>,
+    method: &Method,
+    on_headers: &mut dyn FnMut(StatusCode),
+    stream: Option<&Mutex<dyn StreamHandler>>,
+    spool_at: Option<u64>,
+    max_body: Option<u64>,
+) -> Result<Response<Vec<u8>>, RequestError> {
+    let mut byte_iter = reader.bytes();
+    let head = read_head(&mut byte_iter, method, on_headers, max_body)?;
+    read_response_body(&mut byte_iter, head, stream, spool_at, max_body)
+}
+
+/// The final response's head, and the 1xx ones before it
+struct Head {
+    builder: http::response::Builder,
+    status: StatusCode,
+    version: Version,
+    content_length: Option<usize>,
+    chunked: bool,
+    /// A response to HEAD, or a status that never has a body
+    bodyless: bool,
+    framing: Framing,
+    informational: Vec<(StatusCode, HeaderMap)>,
+}
+
+/// Read up to the end of the final (not 1xx) response's head, erroring with [`BodyTooBig`] if
+/// its Content-Length is over `max_body`
+fn read_head<T: Read>(
+    byte_iter: &mut io::Bytes<T>,
+    method: &Method,
+    on_headers: &mut dyn FnMut(StatusCode),
+    max_body: Option<u64>,
+) -> Result<Head, RequestError> {
     // Store the HTTP status code, also serves as a signal that we should parse headers
     let mut status_code: Option<u16> = None;
+    // We only need to tell 1.0 apart, since it changes how connections are kept alive
+    let mut version = Version::HTTP_11;
     // Length of body in bytes (from 'Content-Length' header)
-    let mut content_length = 0;
+    let mut content_length = None;
     // Is the content body chunked
     let mut chunked = false;
 
@@ -222,7 +1852,7 @@ fn parse_http_response<T: Read>(reader: BufReader<T>) -> Result<Response<Vec<u8>
         .headers_mut()
         .expect("Failed to get mut ref to headers");
 
-    let mut byte_iter = reader.bytes();
+    let mut informational = vec![];
 
     // Parse the metadata: status code & headers
     loop {
@@ -231,22 +1861,60 @@ fn parse_http_response<T: Read>(reader: BufReader<T>) -> Result<Response<Vec<u8>
         let mut line: Vec<u8> = vec![];
         loop {
             // We won't deal with invalid bytes
-            let byte = byte_iter.next().unwrap()?;
+            let byte = next_byte(byte_iter)?;
             line.push(byte);
             if line.ends_with(b"\r\n") {
                 break;
             }
         }
 
-        if line == b"\r\n" {
-            // We've reached the end of the HTTP headers
-            break;
-        } else if status_code.is_none() {
+        if status_code.is_none() {
             // First line is status code
-            let status_code_str = from_utf8(&line).unwrap();
-            let status_code_str = status_code_str.split_whitespace().nth(1).unwrap();
+            // RFC 9112 says we SHOULD ignore empty lines before the status line,
+            // and some middleboxes also like to stick a BOM or bare LFs in front of it
+            let status_line = trim_status_line_garbage(&line);
+
+            if status_line.is_empty() {
+                continue;
+            }
+
+            if status_line.len() < line.len() {
+                warn(
+                    WarningKind::ParseViolation,
+                    "Ignored a BOM or line breaks before the status line",
+                );
+            }
+
+            let status_code_str = from_utf8(status_line)
+                .ok()
+                .and_then(|status_line| status_line.split_whitespace().nth(1))
+                .ok_or_else(|| {
+                    format!(
+                        "Invalid status line: '{}'",
+                        String::from_utf8_lossy(status_line)
+                    )
+                })?;
             let status_code_u16 = status_code_str.parse::<u16>()?;
             status_code = Some(status_code_u16);
+
+            if status_line.starts_with(b"HTTP/1.0") {
+                version = Version::HTTP_10;
+            }
+        } else if line == b"\r\n" {
+            // We've reached the end of the HTTP headers, of an interim response if it's 1xx
+            // (except for 101, which means the connection isn't HTTP anymore)
+            match status_code {
+                Some(code @ 100..=199) if code != 101 => {
+                    let status = StatusCode::from_u16(code)?;
+                    on_headers(status);
+                    informational.push((status, std::mem::take(response_headers)));
+                    status_code = None;
+                    version = Version::HTTP_11;
+                    content_length = None;
+                    chunked = false;
+                }
+                _ => break,
+            }
         } else {
             // Other lines are headers
             let header = from_utf8(&line).unwrap();
@@ -255,7 +1923,7 @@ fn parse_http_response<T: Read>(reader: BufReader<T>) -> Result<Response<Vec<u8>
             let header_value = header.1.trim();
 
             if header_name.to_lowercase() == "content-length" {
-                content_length = header_value.parse::<usize>()?;
+                content_length = Some(parse_content_length(header_value, content_length)?);
             }
 
             if header_name.to_lowercase() == "transfer-encoding"
@@ -264,7 +1932,8 @@ fn parse_http_response<T: Read>(reader: BufReader<T>) -> Result<Response<Vec<u8>
                 chunked = true;
             }
 
-            response_headers.insert(
+            // Headers like Set-Cookie can be repeated, so keep every one of them
+            response_headers.append(
                 header_name.parse::<HeaderName>()?,
                 header_value.parse::<HeaderValue>()?,
             );
@@ -279,72 +1948,136 @@ fn parse_http_response<T: Read>(reader: BufReader<T>) -> Result<Response<Vec<u8>
         return Err("No status code found".into());
     }
 
-    // The body we've received
-    let mut body: Vec<u8> = Vec::with_capacity(content_length);
+    let status = StatusCode::from_u16(status_code.unwrap())?;
+    on_headers(status);
 
-    if !chunked {
-        if content_length > 0 {
-            // Parse the body, reading bytes until we meet content-length or end of stream
-            for byte in byte_iter {
-                body.push(byte.unwrap());
-                if body.len() >= content_length {
-                    break;
-                }
-            }
+    // Responses to HEAD and 1xx/204/304 never have a body, even if the headers describe one
+    // (RFC 9112 6.3), after a 101 or a 2xx to CONNECT it's a different protocol
+    let bodyless = method == Method::HEAD
+        || matches!(status_code, Some(100..=199 | 204 | 304))
+        || (method == Method::CONNECT && matches!(status_code, Some(200..=299)));
+    if chunked && content_length.is_some() && !bodyless {
+        warn(
+            WarningKind::ParseViolation,
+            "The response has both Content-Length and Transfer-Encoding: chunked, going by chunked",
+        );
+    }
+
+    if let Some(limit) = max_body.filter(|_| !bodyless) {
+        if let Some(length) = content_length.filter(|length| *length as u64 > limit) {
+            return Err(Box::new(BodyTooBig {
+                limit,
+                length: Some(length as u64),
+            }));
         }
+    }
+
+    let framing = if bodyless {
+        Framing::Length(0)
+    } else if chunked {
+        Framing::Chunked
     } else {
-        loop {
-            // Read the chunk "head"
-            // [hex octets]*(;ext-name=ext-val)\r\n
-            // We need the num of octects in the chunk, but can ignore the chunk-ext
-            // We don't recognize any chunk extensions, so we MUST ignore them
+        content_length.map_or(Framing::Close, Framing::Length)
+    };
 
-            // Read octets
-            let mut octets: Vec<u8> = vec![];
-            loop {
-                let byte = byte_iter.next().unwrap()?;
-                if byte == b';' || byte == b'\r' {
-                    break;
-                }
-                octets.push(byte);
-            }
+    Ok(Head {
+        builder: response_builder,
+        status,
+        version,
+        content_length,
+        chunked,
+        bodyless,
+        framing,
+        informational,
+    })
+}
 
-            // Read until end of line
-            loop {
-                let byte = byte_iter.next().unwrap()?;
-                if byte == b'\n' {
-                    break;
-                }
-            }
+/// Read the body `head` describes, and put the response together
+fn read_response_body<T: Read>(
+    byte_iter: &mut io::Bytes<T>,
+    head: Head,
+    stream: Option<&Mutex<dyn StreamHandler>>,
+    spool_at: Option<u64>,
+    max_body: Option<u64>,
+) -> Result<Response<Vec<u8>>, RequestError> {
+    let Head {
+        builder: mut response_builder,
+        status,
+        version,
+        content_length,
+        chunked,
+        bodyless,
+        framing,
+        informational,
+    } = head;
 
-            let octets = usize::from_str_radix(from_utf8(&octets).unwrap(), 16)?;
+    let stream = match stream.filter(|_| !bodyless) {
+        Some(handler) => {
+            let mut handler = handler.lock().map_err(|_| "Stream handler panicked")?;
+            let headers = response_builder
+                .headers_ref()
+                .expect("Failed to get headers");
+            handler.start(status, headers).then_some(handler)
+        }
+        None => None,
+    };
 
-            if octets == 0 {
-                // We've reached the end of the chunked body
-                // Technically there's trailing headers, but since we don't send "TE: trailers"
-                // the server knows we might just discard the trailers
-                // so we can just discard the trailers and still respect the spec 😎
-                break;
-            }
+    // The body we've received (or are passing on). The Content-Length is only a hint for how
+    // much to allocate up front, the server could be lying about it
+    let mut body = Body {
+        data: Vec::with_capacity(
+            content_length
+                .filter(|length| {
+                    stream.is_none() && spool_at.is_none_or(|max| *length as u64 <= max)
+                })
+                .unwrap_or_default()
+                .min(MAX_PREALLOCATED_BODY),
+        ),
+        received: 0,
+        stream,
+        spool_at,
+        spool: None,
+        max: max_body,
+    };
+    let mut trailers = HeaderMap::new();
 
-            // Read the chunk
-            for _ in 0..octets {
-                body.push(byte_iter.next().unwrap()?);
-            }
+    read_body(byte_iter, framing, &mut body, &mut trailers).map_err(|err| -> RequestError {
+        if err.is::<IncompleteResponse>() {
+            return Box::new(IncompleteResponse {
+                body: Some((body.received, content_length.filter(|_| !chunked))),
+            });
+        }
 
-            // Read the chunk end
-            loop {
-                let byte = byte_iter.next().unwrap()?;
-                if byte == b'\r' && byte_iter.next().unwrap()? == b'\n' {
-                    break;
-                }
+        // Body::push can only give back an io::Error, so take the BodyTooBig out of it
+        match err.downcast::<io::Error>() {
+            Ok(err) if err.get_ref().is_some_and(|inner| inner.is::<BodyTooBig>()) => {
+                err.into_inner().expect("Checked above")
             }
+            Ok(err) => err,
+            Err(err) => err,
         }
+    })?;
+
+    if body.stream.is_some() {
+        response_builder = response_builder.extension(Streamed);
+    }
+
+    if let Some(spool) = body.spool.take() {
+        response_builder = response_builder.extension(spool.finish()?);
+    }
+
+    if !informational.is_empty() {
+        response_builder = response_builder.extension(Informational(informational));
+    }
+
+    if !trailers.is_empty() {
+        response_builder = response_builder.extension(Trailers(trailers));
     }
 
     // Then we can just finalize the response and return it
     Ok(response_builder
-        .status(status_code.unwrap())
-        .body(body)
+        .status(status)
+        .version(version)
+        .body(body.data)
         .expect("Failed to construct response"))
 }
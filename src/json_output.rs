@@ -0,0 +1,120 @@
+//! `--format json`, the whole response as one JSON object (per line) for other tools
+//!
+//! ```text
+//! {"url": "...", "status": 200, "reason": "OK", "http_version": "HTTP/1.1",
+//!  "headers": [{"name": "...", "value": "..."}], "trailers": [{"name": "...", "value": "..."}],
+//!  "redirects": [{"status": 301, "url": "..."}],
+//!  "request": {"method": "GET", "target": "/", "headers": [{"name": "...", "value": "..."}], "head": "..."},
+//!  "timings": {"connect": 0.01, "early_hints": null, "first_byte": 0.05, "total": 0.06},
+//!  "server_timing": [{"name": "db", "duration": 0.0532, "description": "Database"}],
+//!  "body": "...", "body_encoding": "utf-8",
+//!  "warnings": [{"kind": "parse-violation", "message": "..."}]}
+//! ```
+//!
+//! Times are in seconds (even `server_timing`'s, which the header gives in milliseconds),
+//! `connect` is null for a reused connection and `timings` is null for a cached response. `request` is the last request exactly as it was sent (`head` is its raw
+//! text), also null for a cached response. Bodies that aren't valid UTF-8 are base64 encoded,
+//! `body` and `body_encoding` are null for bodies too big to keep in memory (`--max-memory-body`).
+//! `warnings` are added by the CLI, see [`crate::warnings`]
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use http::{HeaderMap, Response};
+use serde_json::{json, Value};
+
+use crate::http_request::{SentRequest, Trailers};
+use crate::server_timing::server_timing;
+use crate::spool::Spooled;
+use crate::write_out::{EffectiveUrl, Redirects, Timings};
+
+/// The response as a JSON object
+/// ```
+/// # use httpc::json_output::response_json;
+/// let response = http::Response::builder().header("a", "1").header("a", "2").body(vec![0xff]).unwrap();
+/// let json = response_json(&response);
+/// assert_eq!(json["status"], 200);
+/// assert_eq!(json["headers"][1]["value"], "2");
+/// assert_eq!((json["body"].as_str(), json["body_encoding"].as_str()), (Some("/w=="), Some("base64")));
+/// assert_eq!(json["trailers"].as_array().map(Vec::len), Some(0));
+/// assert!(json["request"].is_null());
+/// ```
+pub fn response_json(response: &Response<Vec<u8>>) -> Value {
+    let extensions = response.extensions();
+
+    let trailers = extensions
+        .get::<Trailers>()
+        .map(|trailers| headers_json(&trailers.0))
+        .unwrap_or_default();
+
+    let redirects = extensions
+        .get::<Redirects>()
+        .map(|redirects| {
+            redirects
+                .0
+                .iter()
+                .map(|(status, url)| json!({"status": status.as_u16(), "url": url.to_string()}))
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    let timings = extensions.get::<Timings>().map(|timings| {
+        json!({
+            "connect": timings.connect.map(|time| time.as_secs_f64()),
+            "early_hints": timings.early_hints.map(|time| time.as_secs_f64()),
+            "first_byte": timings.first_byte.as_secs_f64(),
+            "total": timings.total.as_secs_f64(),
+        })
+    });
+
+    let request = extensions.get::<SentRequest>().map(|sent| {
+        json!({
+            "method": sent.method,
+            "target": sent.target,
+            "headers": headers_json(&sent.headers),
+            "head": sent.head,
+        })
+    });
+
+    let server_timing = server_timing(response)
+        .into_iter()
+        .map(|metric| {
+            json!({
+                "name": metric.name,
+                "duration": metric.duration.map(|millis| millis / 1000.0),
+                "description": metric.description,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let (body, encoding) = match std::str::from_utf8(response.body()) {
+        _ if extensions.get::<Spooled>().is_some() => (None, None),
+        Ok(text) => (Some(text.to_string()), Some("utf-8")),
+        Err(_) => (Some(BASE64.encode(response.body())), Some("base64")),
+    };
+
+    json!({
+        "url": extensions.get::<EffectiveUrl>().map(|url| url.0.to_string()),
+        "status": response.status().as_u16(),
+        "reason": response.status().canonical_reason(),
+        "http_version": format!("{:?}", response.version()),
+        "headers": headers_json(response.headers()),
+        "trailers": trailers,
+        "redirects": redirects,
+        "request": request,
+        "timings": timings,
+        "server_timing": server_timing,
+        "body": body,
+        "body_encoding": encoding,
+    })
+}
+
+pub(crate) fn headers_json(headers: &HeaderMap) -> Vec<Value> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            json!({
+                "name": name.as_str(),
+                "value": String::from_utf8_lossy(value.as_bytes()),
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,128 @@
+//! A small subset of JSONPath, enough to point at a single value in a response
+//!
+//! Paths start at `$`, and go down with `.key`, `["key"]` (or `['key']`) and `[index]`. `[*]`
+//! (or `.*`) goes into every item of an array or value of an object, for [`select_all`]
+
+use serde_json::Value;
+
+/// One step down into a JSON value
+#[derive(Debug, PartialEq, Eq)]
+enum Step {
+    Key(String),
+    Index(usize),
+    /// Every item or value
+    All,
+}
+
+/// Get the value at `path` in `value`
+/// ```
+/// # use httpc::jsonpath::select;
+/// let value = serde_json::json!({"data": {"users": [{"name": "a"}, {"name": "b"}]}});
+/// assert_eq!(select(&value, "$.data.users[1].name").unwrap(), "b");
+/// assert_eq!(select(&value, "$['data'][\"users\"][0]").unwrap(), &serde_json::json!({"name": "a"}));
+/// assert!(select(&value, "$.data.nope").is_err());
+/// ```
+pub fn select<'a>(value: &'a Value, path: &str) -> Result<&'a Value, String> {
+    match select_all(value, path)?.as_slice() {
+        [one] => Ok(one),
+        _ => Err(format!(
+            "{} is more than one value, it can't have [*]",
+            path
+        )),
+    }
+}
+
+/// Get every value at `path` in `value`, which can be more than one with `[*]`
+///
+/// A `[*]` of an empty array is no values, but a key or index that isn't there is an error
+/// ```
+/// # use httpc::jsonpath::select_all;
+/// # use serde_json::json;
+/// let value = json!({"users": [{"id": 1}, {"id": 2}], "by_name": {"a": 3, "b": 4}});
+/// assert_eq!(select_all(&value, "$.users[*].id").unwrap(), vec![&json!(1), &json!(2)]);
+/// assert_eq!(select_all(&value, "$.by_name.*").unwrap(), vec![&json!(3), &json!(4)]);
+/// assert_eq!(select_all(&value, "$.users[0].id").unwrap(), vec![&json!(1)]);
+/// assert!(select_all(&value, "$.users[*].name").is_err());
+/// assert!(select_all(&value, "$.users[0].id[*]").is_err());
+/// ```
+pub fn select_all<'a>(value: &'a Value, path: &str) -> Result<Vec<&'a Value>, String> {
+    let mut current = vec![value];
+
+    for step in parse_path(path)? {
+        let mut next = vec![];
+
+        for value in current {
+            match &step {
+                Step::All => match value {
+                    Value::Array(items) => next.extend(items),
+                    Value::Object(values) => next.extend(values.values()),
+                    _ => return Err(format!("Nothing to go into with [*] at {}", path)),
+                },
+                Step::Key(key) => next.push(
+                    value
+                        .get(key)
+                        .ok_or_else(|| format!("No key '{}' at {} in the JSON", key, path))?,
+                ),
+                Step::Index(index) => next.push(
+                    value
+                        .get(index)
+                        .ok_or_else(|| format!("No index [{}] at {} in the JSON", index, path))?,
+                ),
+            }
+        }
+
+        current = next;
+    }
+
+    Ok(current)
+}
+
+/// Turn a value into text for putting in a header, URL or body: strings lose their quotes,
+/// everything else is written as JSON
+pub fn to_text(value: &Value) -> String {
+    match value {
+        Value::String(string) => string.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn parse_path(path: &str) -> Result<Vec<Step>, String> {
+    let invalid = || format!("Invalid JSON path '{}'", path);
+    let mut rest = path.trim().strip_prefix('$').ok_or_else(invalid)?;
+    let mut steps = vec![];
+
+    while !rest.is_empty() {
+        if let Some(after_dot) = rest.strip_prefix('.') {
+            let end = after_dot.find(['.', '[']).unwrap_or(after_dot.len());
+
+            if end == 0 {
+                return Err(invalid());
+            }
+
+            steps.push(match &after_dot[..end] {
+                "*" => Step::All,
+                key => Step::Key(key.to_string()),
+            });
+            rest = &after_dot[end..];
+        } else if let Some(after_bracket) = rest.strip_prefix('[') {
+            let end = after_bracket.find(']').ok_or_else(invalid)?;
+            let inside = after_bracket[..end].trim();
+
+            let quoted = inside
+                .strip_prefix('"')
+                .and_then(|key| key.strip_suffix('"'))
+                .or_else(|| inside.strip_prefix('\'')?.strip_suffix('\''));
+
+            steps.push(match quoted {
+                Some(key) => Step::Key(key.to_string()),
+                None if inside == "*" => Step::All,
+                None => Step::Index(inside.parse().map_err(|_| invalid())?),
+            });
+            rest = &after_bracket[end + 1..];
+        } else {
+            return Err(invalid());
+        }
+    }
+
+    Ok(steps)
+}
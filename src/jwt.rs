@@ -0,0 +1,33 @@
+//! Just enough JWT to know when one expires, the signature isn't checked since it's not ours to
+//! trust or not
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as BASE64, Engine};
+
+/// When a JWT expires (its `exp` claim), or `None` if it isn't a JWT or doesn't expire
+/// ```
+/// # use httpc::jwt::jwt_expiry;
+/// # use std::time::{Duration, UNIX_EPOCH};
+/// // {"alg":"none"}.{"sub":"me","exp":1700000000}.
+/// let token = "eyJhbGciOiJub25lIn0.eyJzdWIiOiJtZSIsImV4cCI6MTcwMDAwMDAwMH0.";
+/// assert_eq!(jwt_expiry(token), Some(UNIX_EPOCH + Duration::from_secs(1700000000)));
+/// assert_eq!(jwt_expiry("not-a-jwt"), None);
+/// // {"alg":"none"}.{"exp":1e30}., too far off to be a time
+/// assert_eq!(jwt_expiry("eyJhbGciOiJub25lIn0.eyJleHAiOjFlMzB9."), None);
+/// ```
+pub fn jwt_expiry(token: &str) -> Option<SystemTime> {
+    let mut parts = token.trim().split('.');
+    let (Some(_header), Some(payload), Some(_signature), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return None;
+    };
+
+    let payload = BASE64.decode(payload.trim_end_matches('=')).ok()?;
+    let claims: serde_json::Value = serde_json::from_slice(&payload).ok()?;
+    let exp = claims.get("exp")?.as_f64()?;
+
+    // Negative or too far off to be a time, which is as good as never expiring
+    UNIX_EPOCH.checked_add(Duration::try_from_secs_f64(exp).ok()?)
+}
@@ -0,0 +1,88 @@
+//! `--keylog`/`SSLKEYLOGFILE`: log each TLS connection's secrets to a file (in the NSS key log
+//! format), so a capture of the traffic can be decrypted in Wireshark
+//!
+//! native-tls has no way to get at the secrets, so these connections are made with OpenSSL
+//! directly. That's only possible where OpenSSL is what native-tls uses anyways, not on Windows
+//! or macOS/iOS
+
+use std::{
+    fmt::Debug,
+    io::{Read, Write},
+};
+
+use crate::http_request::{ReadAndWrite, RequestError, RequestOptions};
+
+/// If this platform's TLS can log its secrets
+pub const SUPPORTED: bool = cfg!(not(any(target_os = "windows", target_vendor = "apple")));
+
+/// Start TLS over `stream` to `host` like native-tls would (same certificate checks), appending
+/// the secrets to the `keylog` file
+#[cfg(not(any(target_os = "windows", target_vendor = "apple")))]
+pub fn keylog_connect<S: Read + Write + Send + Debug + 'static>(
+    host: &str,
+    stream: S,
+    options: &RequestOptions,
+    keylog: &str,
+) -> Result<Box<dyn ReadAndWrite>, RequestError> {
+    use std::{fs::OpenOptions, sync::Mutex};
+
+    use openssl::{
+        ssl::{SslConnector, SslMethod, SslVerifyMode},
+        x509::X509,
+    };
+
+    use crate::helpers::expand_home;
+
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(expand_home(keylog))
+        .map_err(|err| format!("Can't open keylog file {}: {}", keylog, err))?;
+    let file = Mutex::new(file);
+
+    let mut builder = SslConnector::builder(SslMethod::tls())?;
+
+    if options.insecure {
+        builder.set_verify(SslVerifyMode::NONE);
+    }
+
+    if let Some(path) = &options.ca_cert {
+        let pem = std::fs::read(expand_home(path))
+            .map_err(|err| format!("Can't read --cacert {}: {}", path, err))?;
+
+        for cert in X509::stack_from_pem(&pem)? {
+            builder.cert_store_mut().add_cert(cert)?;
+        }
+    }
+
+    builder.set_keylog_callback(move |_, line| {
+        if let Ok(mut file) = file.lock() {
+            // Nothing to do about it mid-handshake, the connection itself is still fine
+            let _ = writeln!(file, "{}", line);
+        }
+    });
+
+    let stream = builder
+        .build()
+        .configure()?
+        .verify_hostname(!options.insecure)
+        .connect(host, stream)?;
+
+    if let (Some(within), Some(certificate)) =
+        (options.cert_expiry_check, stream.ssl().peer_certificate())
+    {
+        crate::cert_expiry::check_expiry(host, &certificate.to_der()?, within);
+    }
+
+    Ok(Box::new(stream))
+}
+
+#[cfg(any(target_os = "windows", target_vendor = "apple"))]
+pub fn keylog_connect<S: Read + Write + Send + Debug + 'static>(
+    _host: &str,
+    _stream: S,
+    _options: &RequestOptions,
+    _keylog: &str,
+) -> Result<Box<dyn ReadAndWrite>, RequestError> {
+    Err("--keylog needs OpenSSL, which isn't the TLS library used on this platform".into())
+}
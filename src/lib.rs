@@ -0,0 +1,71 @@
+//! httpc is a curl-like HTTP 1.1 client, building and parsing HTTP messages by hand
+//!
+//! The `httpc` binary is a thin CLI over these modules, but they can be used on their own:
+//! [`http_request::http_request`] sends a [`http::Request`] and returns the parsed [`http::Response`]
+
+pub mod assertions;
+pub mod batch;
+pub mod bench;
+pub mod cache;
+pub mod cassette;
+pub mod cert_expiry;
+pub mod cli;
+pub mod collection;
+pub mod conditional;
+pub mod config;
+pub mod content_digest;
+pub mod cookies;
+pub mod cors;
+pub mod curl;
+pub mod diff;
+pub mod early_hints;
+pub mod events;
+pub mod export_code;
+pub mod features;
+pub mod file_url;
+pub mod ftp;
+pub mod grep;
+pub mod har;
+pub mod helpers;
+pub mod hmac_sign;
+pub mod http_request;
+pub mod json_output;
+pub mod jsonpath;
+pub mod jwt;
+pub mod keylog;
+pub mod middleware;
+pub mod multipart;
+pub mod normalize;
+pub mod ntlm;
+pub mod oauth2;
+pub mod pace;
+pub mod pager;
+pub mod parallel;
+pub mod pool;
+pub mod presign;
+pub mod pretty;
+pub mod range;
+pub mod redirect;
+pub mod remote_name;
+pub mod render;
+pub mod request_log;
+pub mod response_renderer;
+pub mod resume;
+pub mod serve;
+pub mod server_timing;
+pub mod session;
+pub mod sigv4;
+pub mod snippet;
+pub mod spool;
+pub mod sse;
+pub mod template;
+pub mod throttle;
+pub mod timeout;
+pub mod trace;
+pub mod transfer;
+pub mod tunnel;
+pub mod user_agent;
+pub mod warnings;
+pub mod watch;
+pub mod write_out;
+pub mod ws;
@@ -1,97 +1,2070 @@
-use std::{error::Error, str::FromStr};
+use std::{
+    error::Error,
+    fs::{File, OpenOptions},
+    io::{self, IsTerminal, Read, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use clap::Parser;
+
+use http::{header, uri::Scheme, HeaderValue, Method, Request, Response, StatusCode, Uri, Version};
+use httpc::{
+    assertions::format_failures,
+    batch::{build_batch, print_batch},
+    bench::{format_stats, stats_json, Sample, Stats},
+    cache::Cache,
+    cassette::Cassette,
+    cert_expiry::{describe_expiry, not_after, seconds_until},
+    cli::{
+        expand_args, split_next, CertExpiryOptions, Cli, Commands, CommonOptions, CookiesCommand,
+        HarCommand, HarImportOptions, OutputFormat, PresignTarget, RunOptions, SnippetCommand,
+        VERBOSE, VERY_VERBOSE,
+    },
+    collection::{collection_vars, parse_collection, Collection, CollectionRequest},
+    conditional::{read_etag, save_etag},
+    config::{load_profile, Config},
+    content_digest::{digest_header, verify_response_digests},
+    cookies::{format_table, Cookie},
+    cors::{format_policy, Preflight},
+    curl::curl_command,
+    diff::{diff_bodies, diff_responses},
+    early_hints::{format_report, preload_hints, Prefetched},
+    export_code::export_code,
+    features::{check_options, features, format_features},
+    file_url::{file_path, file_response, is_file_url},
+    ftp::{ftp_response, is_ftp_url},
+    grep::Grep,
+    har::{read_har, rewrite_host, HarRecorder, HarRequest},
+    helpers::{
+        append_query, basic_auth, expand_home, expand_output_template, guess_content_type,
+        has_header, host_matches, parse_header_lines, parse_headers, random_bytes, removed_headers,
+        resolve_url, response_head, split_userinfo, MColorize,
+    },
+    http_request::{
+        http_request, http_upgrade, print_request, server_certificate, BodyTooBig, RemovedHeaders,
+        RequestError, RequestOptions, SafeFetch, SentRequest, Streamed, TargetForm,
+    },
+    jsonpath::{select_all, to_text},
+    middleware::{Client, Exchange, FollowRedirects, Middleware, SharedOutput, VerboseRequests},
+    normalize::normalize_url,
+    ntlm::{
+        auth_token, authenticate_message, negotiate_message, parse_challenge, ConnectionAuth,
+        NtlmCredentials,
+    },
+    oauth2::{
+        cache_token, cached_token, client_auth, parse_token_response, token_failure,
+        TOKEN_REQUEST_BODY,
+    },
+    pace::{retry_wait, Pacer, MAX_RETRIES},
+    pager::page,
+    parallel::{run_pool, run_pool_limited},
+    presign::presign_s3,
+    range::{is_byteranges, print_byteranges},
+    remote_name::{disposition_file_name, url_file_name},
+    render::Renderers,
+    request_log::RequestLog,
+    response_renderer::{RawRenderer, ResponseRenderer, TerminalRenderer},
+    resume::{check_resumed_response, Resume},
+    serve::run_serve,
+    server_timing::format_timing,
+    session::{Session, StoreCookies},
+    sigv4::{Credentials, Signer},
+    snippet::{delete_snippet, format_snippet, load_snippet, save_snippet, snippet_names},
+    spool::{body_len, body_prefix, copy_body, Spooled},
+    sse::SsePrinter,
+    template::substitute,
+    trace::Trace,
+    transfer::{format_summary, snapshot, summary_json, total},
+    tunnel::run_tunnel,
+    warnings::{print_warnings, warn, warning_count, WarningKind},
+    watch::{format_poll, Watch},
+    write_out::{write_out, EffectiveUrl, Redirects, Timings},
+    ws::{self, WebSocket},
+};
+use owo_colors::{OwoColorize, Stream, Style};
+
+fn main() {
+    // A broken config is reported by whatever loads it for real, it just means no aliases here
+    let aliases = Config::load()
+        .map(|config| config.aliases)
+        .unwrap_or_default();
+    // Everything's parsed before anything's sent, so a typo in the last request doesn't leave
+    // the earlier ones half done
+    let commands = split_next(std::env::args_os().collect())
+        .into_iter()
+        .map(|args| match expand_args(args, &aliases) {
+            Ok(args) => Cli::parse_from(args),
+            Err(err) => {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
+        })
+        .collect::<Vec<_>>();
+
+    // --color and --warnings-as-errors go for the whole run, wherever they're given
+    let warnings_as_errors = commands.iter().any(|args| args.warnings_as_errors);
+    commands[0].color.init();
+
+    let mut failed = None;
+
+    for args in commands {
+        // Like curl, one request failing doesn't stop the ones after it
+        if let Err(err) = run_command(args.command) {
+            // oh no
+            eprintln!("{}", err);
+            failed = Some(exit_code(&err));
+        }
+    }
+
+    print_warnings();
+
+    if let Some(code) = failed {
+        std::process::exit(code);
+    }
+
+    if warnings_as_errors && warning_count() > 0 {
+        eprintln!(
+            "{} warning(s), failing because of --warnings-as-errors",
+            warning_count()
+        );
+        std::process::exit(1);
+    }
+}
+
+/// A 4xx or 5xx response, with --fail
+#[derive(Debug)]
+struct FailedStatus(StatusCode);
+
+impl std::fmt::Display for FailedStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "The server responded with {}", self.0)
+    }
+}
+
+impl Error for FailedStatus {}
+
+/// Exit with 22 for --fail and 63 when a body was too big (like curl), so scripts can tell them
+/// apart from other errors
+fn exit_code(err: &RequestError) -> i32 {
+    if err.is::<FailedStatus>() {
+        22
+    } else if err.is::<BodyTooBig>() {
+        63
+    } else {
+        1
+    }
+}
+
+/// Exit with `code`, showing any warnings first
+fn exit(code: i32) -> ! {
+    print_warnings();
+    std::process::exit(code);
+}
+
+fn run_command(command: Commands) -> Result<(), RequestError> {
+    match command {
+        Commands::Get {
+            mut options,
+            exists,
+        } => {
+            prepare_options(&mut options)?;
+
+            if exists {
+                let result = all_exist(&options);
+                save_session(&options)?;
+
+                if !result? {
+                    exit(1);
+                }
+
+                return Ok(());
+            }
+
+            // OPTIONS is the only method the asterisk-form means anything for
+            let method = match options.target_form {
+                Some(TargetForm::Asterisk) => Method::OPTIONS,
+                _ => Method::GET,
+            };
+
+            let result = run_request(method, None, &options);
+            print_transferred(&options);
+            save_session(&options).and(result)
+        }
+
+        Commands::Post {
+            mut options,
+            data,
+            file,
+            batch,
+        } => {
+            prepare_options(&mut options)?;
+            let result = run_post(&mut options, data, file, batch);
+            print_transferred(&options);
+            save_session(&options).and(result)
+        }
+
+        Commands::Run(run) => {
+            if !run_collection(&run)? {
+                exit(1);
+            }
+
+            Ok(())
+        }
+
+        Commands::Snippet { command } => run_snippet(command),
+
+        Commands::Har {
+            command: HarCommand::Import(import),
+        } => {
+            if !run_har_import(&import)? {
+                exit(1);
+            }
+
+            Ok(())
+        }
+
+        Commands::Cookies {
+            command: CookiesCommand::List { session },
+        } => {
+            let session = Session::load(&session)?;
+
+            if !session.path.exists() {
+                return Err(format!("No session saved at {}", session.path.display()).into());
+            }
+
+            print_cookies(&session.cookies.cookies);
+            Ok(())
+        }
+
+        Commands::Cookies {
+            command: CookiesCommand::Show { mut options, .. },
+        } => {
+            prepare_options(&mut options)?;
+            let result = show_cookies(&options);
+            save_session(&options).and(result)
+        }
+
+        Commands::Ws {
+            mut options,
+            protocol,
+        } => {
+            prepare_options(&mut options)?;
+            let result = run_websocket(&options, &protocol);
+            print_transferred(&options);
+            save_session(&options).and(result)
+        }
+
+        Commands::Bench {
+            mut options,
+            requests,
+            concurrency,
+            data,
+        } => {
+            prepare_options(&mut options)?;
+            let result = run_bench(&mut options, requests, concurrency, data);
+            print_transferred(&options);
+            save_session(&options).and(result)
+        }
+
+        Commands::Diff {
+            mut options,
+            data,
+            ignore_header,
+        } => {
+            prepare_options(&mut options)?;
+            let result = run_diff(&mut options, data, &ignore_header);
+            print_transferred(&options);
+
+            if !save_session(&options).and(result)? {
+                exit(1);
+            }
+
+            Ok(())
+        }
+
+        Commands::Tunnel(tunnel) => run_tunnel(&tunnel),
+
+        Commands::Serve(serve) => run_serve(&serve),
+
+        Commands::CertExpiry(check) => {
+            if !run_cert_expiry(&check)? {
+                exit(1);
+            }
+
+            Ok(())
+        }
+
+        Commands::Features => {
+            print!("{}", format_features(&features()));
+            Ok(())
+        }
+
+        Commands::Presign {
+            target: PresignTarget::S3(presign),
+        } => {
+            let (method, url) = presign_s3(&presign)?;
+
+            if !presign.send {
+                println!("{}", url);
+                return Ok(());
+            }
+
+            let mut options = CommonOptions::try_parse_from(["httpc", url.as_str()])?;
+            options.output = presign.output;
+            options.verbosity = presign.verbosity;
+
+            let body = presign.file.map(std::fs::read).transpose()?;
+            run_request(method, body.as_deref(), &options)
+        }
+    }
+}
+
+/// Send a POST with a body from -d/-f, or a batch of sub-requests
+fn run_post(
+    options: &mut CommonOptions,
+    data: Option<String>,
+    file: Option<PathBuf>,
+    batch: Vec<String>,
+) -> Result<(), RequestError> {
+    if !batch.is_empty() {
+        let (content_type, body) = build_batch(&batch)?;
+
+        if !has_header(&options.header, header::CONTENT_TYPE.as_str()) {
+            options
+                .header
+                .push(format!("Content-Type: {}", content_type));
+        }
+
+        let url = match collect_urls(options)?.as_slice() {
+            [url] => url.clone(),
+            _ => return Err("Batch requests can only be sent to a single URL".into()),
+        };
+
+        if options.print_curl || options.export_code.is_some() {
+            return print_code(&Method::POST, &[url], Some(&body), options);
+        }
+
+        let response = do_request(Method::POST, &url, Some(&body), options)?;
+
+        return if options.output.is_some() {
+            output_response(&response, options, false, &mut io::stdout())
+        } else {
+            print_batch(&response, options.verbosity)
+        };
+    }
+
+    let data_given = data.is_some();
+    let body: Option<Vec<u8>> = match (data, file) {
+        // -d '{"data": "here"}'
+        (Some(data), None) => Some(substitute(&data, &options.vars())?.into_bytes()),
+        // -f ./file.txt
+        (None, Some(file)) => Some(
+            std::fs::read(&file)
+                .map_err(|err| format!("Can't read {}: {}", file.display(), err))?,
+        ),
+        // -d '{"data": "here"}' -f ./file.txt
+        (Some(_), Some(_)) => {
+            return Err(Box::<dyn Error>::from(
+                "File and data cannot be used together",
+            ))
+        }
+        _ => None,
+    };
+
+    if let Some(body) = &body {
+        default_content_type(options, body, data_given);
+    }
+
+    run_request(Method::POST, body.as_deref(), options)
+}
+
+/// Add a Content-Type for a body that doesn't have one, if it's `typed` out text (-d) that looks
+/// like JSON, XML or a form. Otherwise warn, servers might not know what to do with it
+fn default_content_type(options: &mut CommonOptions, body: &[u8], typed: bool) {
+    let content_type = header::CONTENT_TYPE.as_str();
+
+    if body.is_empty() || has_header(&options.header, content_type) {
+        return;
+    }
+
+    // Only -d is text someone typed out, a file could be anything
+    let guessed = if typed {
+        std::str::from_utf8(body).ok().and_then(guess_content_type)
+    } else {
+        None
+    };
+
+    match guessed {
+        Some(guessed) => options
+            .header
+            .push(format!("Content-Type: {}", guessed)),
+        None => warn(
+            WarningKind::Usage,
+            "Sending a body without a Content-Type, the server might not know what to do with it (add one with -h 'Content-Type: ...')",
+        ),
+    }
+}
+
+/// Send the same request to both URLs and print how their responses differ
+/// Returns if both URLs sent back the same response
+fn run_diff(
+    options: &mut CommonOptions,
+    data: Option<String>,
+    ignored_headers: &[String],
+) -> Result<bool, RequestError> {
+    let [old_url, new_url] = <[String; 2]>::try_from(collect_urls(options)?)
+        .map_err(|_| "httpc diff compares exactly two URLs")?;
+
+    let body = data
+        .map(|data| substitute(&data, &options.vars()))
+        .transpose()?
+        .map(String::into_bytes);
+    let method = match &body {
+        Some(body) => {
+            default_content_type(options, body, true);
+            Method::POST
+        }
+        None => Method::GET,
+    };
+
+    // Both responses are needed in full to compare them, the diff is the only output
+    let mut diff_options = options.clone();
+    diff_options.stream = None;
+    diff_options.output = None;
+    diff_options.dump_header = None;
+
+    let old = do_request(method.clone(), &old_url, body.as_deref(), &diff_options)?;
+    let new = do_request(method, &new_url, body.as_deref(), &diff_options)?;
+    let (diff, different) = diff_responses((&old_url, &old), (&new_url, &new), ignored_headers)?;
+
+    println!("{}", diff);
+    Ok(!different)
+}
+
+/// Send the request `requests` times, `concurrency` at once, and report how fast it was
+fn run_bench(
+    options: &mut CommonOptions,
+    requests: u32,
+    concurrency: u16,
+    data: Option<String>,
+) -> Result<(), RequestError> {
+    let [url] = <[String; 1]>::try_from(collect_urls(options)?)
+        .map_err(|_| "httpc bench benchmarks one URL at a time")?;
+
+    let body = data
+        .map(|data| substitute(&data, &options.vars()))
+        .transpose()?
+        .map(String::into_bytes);
+    let method = match &body {
+        Some(body) => {
+            default_content_type(options, body, true);
+            Method::POST
+        }
+        None => Method::GET,
+    };
+
+    // Every request has to actually go to the server, and nothing gets printed along the way
+    let mut bench_options = options.clone();
+    bench_options.verbosity = 0;
+    bench_options.stream = None;
+    bench_options.cache_dir = None;
+    bench_options.output = None;
+    bench_options.dump_header = None;
+
+    let runs = vec![(); requests as usize];
+    let mut samples = vec![None; runs.len()];
+    let before = total(&snapshot());
+    let start = Instant::now();
+
+    run_pool(
+        &runs,
+        concurrency,
+        |_, _| {
+            let start = Instant::now();
+            let result = do_request(method.clone(), &url, body.as_deref(), &bench_options)
+                .map(|response| response.status())
+                .map_err(|err| err.to_string());
+
+            Sample {
+                took: start.elapsed(),
+                result,
+            }
+        },
+        |i, sample| samples[i] = Some(sample),
+    );
+
+    let took = start.elapsed();
+    let samples = samples.into_iter().flatten().collect::<Vec<_>>();
+    let transferred = total(&snapshot()).since(&before);
+    let stats = Stats::new(&samples, concurrency, took, transferred);
+
+    if options.output_format == OutputFormat::Json {
+        println!("{}", stats_json(&stats));
+    } else {
+        print!("{}", format_stats(&stats, &url));
+    }
+
+    match stats.failures() {
+        0 => Ok(()),
+        failures => Err(format!("{} of {} requests failed", failures, stats.requests).into()),
+    }
+}
+
+/// Print when each URL's certificate expires, returning whether they're all good for `--within`
+fn run_cert_expiry(check: &CertExpiryOptions) -> Result<bool, RequestError> {
+    let options = RequestOptions {
+        insecure: check.insecure,
+        ca_cert: check.cacert.clone(),
+        proxy: check.proxy.clone(),
+        proxy_user: check.proxy_user.clone(),
+        ..Default::default()
+    };
+
+    let mut all_good = true;
+
+    for url in &check.url {
+        let uri = request_uri(url)?;
+
+        if uri.scheme_str() != Some("https") {
+            return Err(format!("{} isn't an https URL, so it has no certificate", url).into());
+        }
+
+        let expires = server_certificate(&uri, &options).and_then(|certificate| {
+            not_after(&certificate).ok_or_else(|| "Can't read the certificate's expiry date".into())
+        });
+
+        let expires = match expires {
+            Ok(expires) => expires,
+            Err(err) => {
+                println!("{} {}: {}", "✗".out_color(|t| t.red()), url, err);
+                all_good = false;
+                continue;
+            }
+        };
+
+        let left = seconds_until(expires);
+        let described = describe_expiry(expires);
+
+        if left < 0 {
+            println!(
+                "{} {} expired {}",
+                "✗".out_color(|t| t.red()),
+                url,
+                described
+            );
+        } else if left < check.within.as_secs() as i64 {
+            println!(
+                "{} {} expires {}",
+                "!".out_color(|t| t.yellow()),
+                url,
+                described
+            );
+        } else {
+            println!(
+                "{} {} expires {}",
+                "✓".out_color(|t| t.green()),
+                url,
+                described
+            );
+            continue;
+        }
+
+        all_good = false;
+    }
+
+    Ok(all_good)
+}
+
+/// Run every request in a `.http` collection in order, returning whether they all succeeded
+///
+/// Values captured from a response become variables for the requests after it
+fn run_collection(run: &RunOptions) -> Result<bool, RequestError> {
+    let collection = parse_collection(&std::fs::read_to_string(&run.file)?)?;
+    let mut vars = collection_vars(&collection, &run.var)?;
+
+    let total = collection.requests.len();
+    let mut failures = 0;
+
+    for (i, request) in collection.requests.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+
+        println!(
+            "{}",
+            format!("── Request {}/{}: {} ──", i + 1, total, request.title())
+                .out_color(|t| t.bright_black())
+        );
+
+        let reason = match run_collection_request(request, &mut vars, run) {
+            Ok(status) if status.is_client_error() || status.is_server_error() => {
+                status.to_string()
+            }
+            Ok(_) => continue,
+            Err(err) => err.to_string(),
+        };
+
+        eprintln!(
+            "{} {}: {}",
+            "✗".if_supports_color(Stream::Stderr, |t| t.red()),
+            request.title(),
+            reason
+        );
+        failures += 1;
+
+        if !run.keep_going {
+            break;
+        }
+    }
+
+    Ok(failures == 0)
+}
+
+fn run_snippet(command: SnippetCommand) -> Result<(), RequestError> {
+    match command {
+        SnippetCommand::Save {
+            name,
+            url,
+            method,
+            header,
+            data,
+            var,
+            ..
+        } => {
+            let method = method.to_ascii_uppercase().parse::<Method>()?;
+            let text = format_snippet(&name, &method, &url, &header, data.as_deref(), &var);
+            let path = save_snippet(&name, &text)?;
+
+            println!("Saved {} to {}", name, path.display());
+            Ok(())
+        }
+
+        SnippetCommand::Run {
+            name,
+            verbosity,
+            var,
+            location,
+        } => {
+            let (path, _) = load_snippet(&name)?;
+            let run = RunOptions {
+                file: path,
+                verbosity,
+                var,
+                location,
+                keep_going: false,
+            };
+
+            if !run_collection(&run)? {
+                exit(1);
+            }
+
+            Ok(())
+        }
+
+        SnippetCommand::List => {
+            for name in snippet_names()? {
+                let requests = match load_snippet(&name) {
+                    Ok((_, collection)) => collection
+                        .requests
+                        .iter()
+                        .map(|request| format!("{} {}", request.method, request.url))
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                    Err(err) => err.to_string(),
+                };
+
+                println!("{}  {}", name.out_color(|t| t.cyan()), requests);
+            }
+
+            Ok(())
+        }
+
+        SnippetCommand::Show { name } => {
+            let (path, _) = load_snippet(&name)?;
+            print!("{}", std::fs::read_to_string(path)?);
+            Ok(())
+        }
+
+        SnippetCommand::Delete { name } => delete_snippet(&name),
+    }
+}
+
+/// Send the requests in a HAR file in order (or list them), returning whether they all succeeded
+fn run_har_import(import: &HarImportOptions) -> Result<bool, RequestError> {
+    let har = read_har(Path::new(&import.file))?;
+    let entries = &har.log.entries;
+
+    if let Some(index) = import.index.iter().find(|&&i| i as usize > entries.len()) {
+        return Err(format!(
+            "There's no entry {}, {} only has {}",
+            index,
+            import.file.display(),
+            entries.len()
+        )
+        .into());
+    }
+
+    let selected = entries
+        .iter()
+        .enumerate()
+        .map(|(i, entry)| (i + 1, &entry.request))
+        .filter(|(n, _)| import.index.is_empty() || import.index.contains(&(*n as u32)))
+        .collect::<Vec<_>>();
+
+    if import.list {
+        for (n, request) in selected {
+            println!(
+                "{} {} {}",
+                format!("{:>3}", n).out_color(|t| t.bright_black()),
+                request.method.to_ascii_uppercase().out_color(|t| t.cyan()),
+                request.url
+            );
+        }
+
+        return Ok(true);
+    }
+
+    let mut failures = 0;
+
+    for (i, (n, request)) in selected.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+
+        let url = match &import.host {
+            Some(host) => rewrite_host(&request.url, host)?,
+            None => request.url.clone(),
+        };
+
+        println!(
+            "{}",
+            format!(
+                "── Entry {}/{}: {} {} ──",
+                n,
+                entries.len(),
+                request.method.to_ascii_uppercase(),
+                url
+            )
+            .out_color(|t| t.bright_black())
+        );
+
+        let result = replay_har_request(request, &url, import);
+
+        if let Err(err) = result {
+            eprintln!(
+                "{} Entry {}: {}",
+                "✗".if_supports_color(Stream::Stderr, |t| t.red()),
+                n,
+                err
+            );
+            failures += 1;
+
+            if !import.keep_going {
+                break;
+            }
+        }
+    }
+
+    Ok(failures == 0)
+}
+
+/// Send a request from a HAR file to `url` and print the response
+fn replay_har_request(
+    request: &HarRequest,
+    url: &str,
+    import: &HarImportOptions,
+) -> Result<(), RequestError> {
+    let mut options = CommonOptions::try_parse_from(["httpc", url])?;
+    options.verbosity = import.verbosity;
+    options.location = import.location;
+    options.insecure = import.insecure;
+    options.header = request.headers();
+
+    let body = request.body();
+
+    if let (Some(_), Some(content_type)) = (&body, request.content_type()) {
+        if !has_header(&options.header, header::CONTENT_TYPE.as_str()) {
+            options
+                .header
+                .push(format!("Content-Type: {}", content_type));
+        }
+    }
+
+    prepare_options(&mut options)?;
+    let result = run_request(
+        request.method()?,
+        body.as_deref().map(str::as_bytes),
+        &options,
+    );
+    save_session(&options).and(result)
+}
+
+/// Send one request from a collection and print the response, adding its captures to `vars`
+fn run_collection_request(
+    request: &CollectionRequest,
+    vars: &mut Vec<(String, String)>,
+    run: &RunOptions,
+) -> Result<StatusCode, RequestError> {
+    let url = substitute(&request.url, vars)?;
+    let body = request
+        .body
+        .as_deref()
+        .map(|body| substitute(body, vars))
+        .transpose()?;
+
+    let mut options = CommonOptions::try_parse_from(["httpc", url.as_str()])?;
+    options.verbosity = run.verbosity;
+    options.location = run.location;
+    options.header = request.headers.clone();
+    options.var = vars.clone();
+    prepare_options(&mut options)?;
+    request.settings.apply(&mut options);
+
+    let response = do_request(
+        request.method.clone(),
+        &url,
+        body.as_deref().map(str::as_bytes),
+        &options,
+    )?;
+    print_response(&response, &options, &mut io::stdout())?;
+
+    let status = response.status();
+
+    // No point capturing out of an error response
+    if !(status.is_client_error() || status.is_server_error()) {
+        for capture in &request.captures {
+            vars.push((capture.name.clone(), capture.extract(&response)?));
+        }
+    }
+
+    Ok(status)
+}
+
+/// GET every URL and show the cookies each response sets, warning about the ones we'd reject
+fn show_cookies(options: &CommonOptions) -> Result<(), RequestError> {
+    for (i, url) in collect_urls(options)?.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+
+        let response = do_request(Method::GET, url, None, options)?;
+        let uri = response
+            .extensions()
+            .get::<EffectiveUrl>()
+            .map_or_else(|| response_uri(url), |url| url.0.clone());
+
+        let mut cookies = vec![];
+
+        for set_cookie in response.headers().get_all(header::SET_COOKIE) {
+            let set_cookie = String::from_utf8_lossy(set_cookie.as_bytes());
+
+            match Cookie::try_parse(&set_cookie, &uri) {
+                Ok(cookie) => cookies.push(cookie),
+                Err(reason) => warn(
+                    WarningKind::Cookie,
+                    format!("Rejected '{}': {}", set_cookie, reason),
+                ),
+            }
+        }
+
+        if cookies.is_empty() {
+            println!(
+                "{}",
+                format!("No cookies set by {}", uri).out_color(|t| t.bright_black())
+            );
+        } else {
+            print_cookies(&cookies);
+        }
+    }
+
+    Ok(())
+}
+
+/// Print a table of cookies, with the header row in bold
+fn print_cookies(cookies: &[Cookie]) {
+    let table = format_table(cookies);
+    let (header, rows) = table.split_once('\n').unwrap_or((&table, ""));
+
+    println!("{}", header.out_color(|t| t.bold()));
+
+    if !rows.is_empty() {
+        println!("{}", rows);
+    }
+}
+
+/// Check if every URL exists (responds 2xx/3xx to a HEAD), stopping at the first one that doesn't
+fn all_exist(options: &CommonOptions) -> Result<bool, RequestError> {
+    for url in collect_urls(options)? {
+        let status = probe(&url, options)?.status();
+
+        if !(status.is_success() || status.is_redirection()) {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+/// HEAD a URL, falling back to a GET (throwing away the body) if the server doesn't support HEAD
+fn probe(url: &str, options: &CommonOptions) -> Result<Response<Vec<u8>>, RequestError> {
+    let response = do_request(Method::HEAD, url, None, options)?;
+
+    if !matches!(
+        response.status(),
+        StatusCode::METHOD_NOT_ALLOWED | StatusCode::NOT_IMPLEMENTED
+    ) {
+        return Ok(response);
+    }
+
+    if options.verbosity >= VERBOSE {
+        println!(
+            "{}",
+            format!("HEAD got {}, retrying with GET\n", response.status())
+                .out_color(|t| t.bright_black())
+        );
+    }
+
+    let mut response = do_request(Method::GET, url, None, options)?;
+    response.body_mut().clear();
+    Ok(response)
+}
+
+/// Load the `--session` (if any) and then the profile into the options, add the `--ua` headers,
+/// then fill in any `{{VAR}}`s in the headers
+///
+/// Explicit flags win over the session, which wins over the profile
+fn prepare_options(options: &mut CommonOptions) -> Result<(), RequestError> {
+    check_options(options)?;
+
+    // Like they were given with -h, but after them so -h wins
+    for path in &options.headers_from {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|err| format!("Can't read {}: {}", path.display(), err))?;
+        let headers = parse_header_lines(&contents)
+            .map_err(|err| format!("Invalid header in {}, {}", path.display(), err))?;
+
+        for header in headers {
+            let name = header.split([':', ';']).next().unwrap_or_default().trim();
+
+            if !has_header(&options.header, name) {
+                options.header.push(header);
+            }
+        }
+    }
+
+    let mut config = Config::load()?;
+    let profile = load_profile(&mut config, options)?;
+    options.renderers = Renderers::new(&config.renderers);
+
+    if options.session.is_none() {
+        options.session = profile.as_ref().and_then(|profile| profile.session.clone());
+    }
+
+    if let Some(name) = &options.session {
+        let mut session = Session::load(name)?;
+        session.apply(options);
+        options.loaded_session = Some(Arc::new(Mutex::new(session)));
+    }
+
+    if let Some(profile) = &profile {
+        profile.apply(options)?;
+    }
+
+    options.loaded_profile = profile.map(Arc::new);
+
+    if let Some(path) = &options.trace {
+        options.opened_trace = Some(Trace::open(path, false)?);
+    } else if let Some(path) = &options.trace_ascii {
+        options.opened_trace = Some(Trace::open(path, true)?);
+    }
+
+    if let Some(path) = &options.har {
+        options.opened_har = Some(HarRecorder::create(path)?);
+    }
+
+    if let Some(path) = &options.log_file {
+        options.opened_log = Some(RequestLog::open(path, options.log_format)?);
+    }
+
+    options.pacer = Pacer::from_options(options.rps, options.delay);
+
+    log_in(options, false)?;
+
+    if let Some(token) = oauth2_token(options)? {
+        options
+            .header
+            .push(format!("Authorization: Bearer {}", token));
+    }
+
+    // --ua replaces any User-Agent, the rest of its headers only fill in what's missing
+    if let Some(ua) = &options.ua {
+        options
+            .header
+            .retain(|header| !has_header(std::slice::from_ref(header), "user-agent"));
+
+        for header in ua.headers(options.ua_headers) {
+            let name = header.split_once(':').map_or("", |(name, _)| name);
+
+            if !has_header(&options.header, name) {
+                options.header.push(header);
+            }
+        }
+    }
+
+    options.raw_headers = options.header.clone();
+    options.header = substitute_headers(&options.raw_headers, &options.vars())?;
+
+    Ok(())
+}
+
+fn substitute_headers(
+    headers: &[String],
+    vars: &[(String, String)],
+) -> Result<Vec<String>, RequestError> {
+    headers
+        .iter()
+        .map(|header| substitute(header, vars))
+        .collect()
+}
+
+/// Treat a login as expired this long before it actually does, so it doesn't expire mid-request
+const LOGIN_LEEWAY: Duration = Duration::from_secs(30);
+
+/// Log the session in with the profile's bootstrap collection if it isn't yet, or if `force`d
+///
+/// Expired logins are refreshed with the profile's refresh collection if it has one, falling back
+/// to the bootstrap. Returns if anything was run
+fn log_in(options: &CommonOptions, force: bool) -> Result<bool, RequestError> {
+    let Some(profile) = &options.loaded_profile else {
+        return Ok(false);
+    };
+    let Some(bootstrap) = &profile.bootstrap else {
+        return Ok(false);
+    };
+    let Some(session) = &options.loaded_session else {
+        let hint = "set 'session' in the profile or pass --session";
+        return Err(format!(
+            "The profile's bootstrap needs a session to log into, {}",
+            hint
+        )
+        .into());
+    };
+
+    let (path, bootstrap) = read_login_collection(bootstrap)?;
+    let captures = bootstrap
+        .requests
+        .iter()
+        .flat_map(|request| &request.captures)
+        .map(|capture| capture.name.as_str())
+        .collect::<Vec<_>>();
+
+    let (logged_in, expired) = {
+        let session = session
+            .lock()
+            .map_err(|_| "Session was poisoned by a panic")?;
+
+        let logged_in = match captures.is_empty() {
+            false => captures
+                .iter()
+                .all(|name| session.variables.contains_key(*name)),
+            true => !session.cookies.cookies.is_empty(),
+        };
+
+        (logged_in, session.login_expired(&captures, LOGIN_LEEWAY))
+    };
+
+    if logged_in && !expired && !force {
+        return Ok(false);
+    }
+
+    if let (true, Some(refresh)) = (logged_in, &profile.refresh) {
+        let (refresh_path, refresh) = read_login_collection(refresh)?;
+        print_login(&format!(
+            "Refreshing the login with {}",
+            refresh_path.display()
+        ));
+
+        match run_login_collection(options, &refresh) {
+            Ok(()) => return Ok(true),
+            Err(err) => warn(
+                WarningKind::Login,
+                format!("Refreshing failed, logging in again: {}", err),
+            ),
+        }
+    }
+
+    print_login(&format!("Logging in with {}", path.display()));
+    run_login_collection(options, &bootstrap)?;
+    Ok(true)
+}
+
+/// The --oauth2-token-url access token to send, from the cache or a new token request. `None` if
+/// there isn't one to get, or an Authorization header was already given
+fn oauth2_token(options: &CommonOptions) -> Result<Option<String>, RequestError> {
+    let (Some(token_url), Some(client_id), Some(client_secret)) = (
+        &options.oauth2_token_url,
+        &options.client_id,
+        &options.client_secret,
+    ) else {
+        return Ok(None);
+    };
+
+    if has_header(&options.header, "authorization") {
+        return Ok(None);
+    }
+
+    if let Some(token) = cached_token(token_url, client_id, client_secret, LOGIN_LEEWAY) {
+        return Ok(Some(token.access_token));
+    }
+
+    print_login(&format!("Getting an access token from {}", token_url));
+
+    // Same connection settings, but only the token request's own headers
+    let mut token_options = options.clone();
+    token_options.header = vec![
+        format!("Authorization: {}", client_auth(client_id, client_secret)),
+        "Content-Type: application/x-www-form-urlencoded".to_string(),
+        "Accept: application/json".to_string(),
+    ];
+    token_options.user = None;
+    token_options.aws_sigv4 = None;
+    token_options.cache_dir = None;
+
+    let response = do_request(
+        Method::POST,
+        token_url,
+        Some(TOKEN_REQUEST_BODY.as_bytes()),
+        &token_options,
+    )?;
+    let status = response.status();
+
+    if !status.is_success() {
+        let reason = token_failure(response.body()).unwrap_or_else(|| status.to_string());
+        return Err(format!("Getting an access token failed: {}", reason).into());
+    }
+
+    let token = parse_token_response(response.body(), SystemTime::now())
+        .map_err(|err| format!("Getting an access token failed: {}", err))?;
+    cache_token(token_url, client_id, client_secret, &token)?;
+
+    Ok(Some(token.access_token))
+}
+
+fn read_login_collection(path: &str) -> Result<(PathBuf, Collection), RequestError> {
+    let path = expand_home(path);
+    let text = std::fs::read_to_string(&path)
+        .map_err(|err| format!("Can't read {}: {}", path.display(), err))?;
+    let collection = parse_collection(&text)?;
+    Ok((path, collection))
+}
+
+fn print_login(message: &str) {
+    eprintln!(
+        "{}",
+        message.if_supports_color(Stream::Stderr, |t| t.bright_black())
+    );
+}
+
+/// Run a bootstrap or refresh collection, saving everything it captures in the session
+///
+/// An `expires_in` capture (in seconds, like OAuth's) is also saved as when the login expires
+fn run_login_collection(
+    options: &CommonOptions,
+    collection: &Collection,
+) -> Result<(), RequestError> {
+    let Some(session) = &options.loaded_session else {
+        return Ok(());
+    };
+    let mut vars = collection_vars(collection, &options.vars())?;
+
+    // Only known again if this login says so
+    session
+        .lock()
+        .map_err(|_| "Session was poisoned by a panic")?
+        .expires = None;
+
+    for request in &collection.requests {
+        let mut url = substitute(&request.url, &vars)?;
+        let body = request
+            .body
+            .as_deref()
+            .map(|body| substitute(body, &vars))
+            .transpose()?;
+
+        if let (Some(base_url), true) = (&options.base_url, url.starts_with('/')) {
+            url = format!("{}{}", base_url.trim_end_matches('/'), url);
+        }
+
+        // Same connection settings & session, but only the collection's own headers, and no
+        // logging in again if it gets a 401
+        let mut request_options = options.clone();
+        request_options.header = substitute_headers(&request.headers, &vars)?;
+        request_options.loaded_profile = None;
+        request.settings.apply(&mut request_options);
+
+        let response = do_request(
+            request.method.clone(),
+            &url,
+            body.as_deref().map(str::as_bytes),
+            &request_options,
+        )?;
+        let status = response.status();
+
+        if status.is_client_error() || status.is_server_error() {
+            return Err(format!("Request '{}' failed: {}", request.title(), status).into());
+        }
+
+        for capture in &request.captures {
+            let value = capture.extract(&response)?;
+            let mut session = session
+                .lock()
+                .map_err(|_| "Session was poisoned by a panic")?;
+
+            if capture.name == "expires_in" {
+                let expires_in = value
+                    .parse::<u64>()
+                    .map_err(|_| format!("expires_in should be in seconds, got '{}'", value))?;
+                let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+                session.expires = Some(now.saturating_add(expires_in));
+            }
+
+            session
+                .variables
+                .insert(capture.name.clone(), value.clone());
+            vars.push((capture.name.clone(), value));
+        }
+    }
+
+    save_session(options)
+}
+
+/// Save the session (and whatever cookies it picked up) for next time
+fn save_session(options: &CommonOptions) -> Result<(), RequestError> {
+    match &options.loaded_session {
+        Some(session) => session
+            .lock()
+            .map_err(|_| "Session was poisoned by a panic")?
+            .save(),
+        None => Ok(()),
+    }
+}
+
+/// Every URL to request, from the command line and `--url-file`
+fn collect_urls(options: &CommonOptions) -> Result<Vec<String>, RequestError> {
+    let mut urls = options.url.clone();
+
+    if let Some(file) = &options.url_file {
+        let contents = std::fs::read_to_string(file)?;
+        urls.extend(
+            contents
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(String::from),
+        );
+    }
+
+    if urls.is_empty() {
+        return Err("No URLs to request".into());
+    }
+
+    resolve_urls(urls, options)
+}
+
+/// Fill in the `{{VAR}}`s, --base-url and --query of each URL
+fn resolve_urls(
+    mut urls: Vec<String>,
+    options: &CommonOptions,
+) -> Result<Vec<String>, RequestError> {
+    for url in urls.iter_mut() {
+        *url = substitute(url, &options.vars())?;
+    }
+
+    if let Some(base_url) = &options.base_url {
+        for url in urls.iter_mut().filter(|url| url.starts_with('/')) {
+            *url = format!("{}{}", base_url.trim_end_matches('/'), url);
+        }
+    }
+
+    // Filled in before encoding, or the braces would be encoded too
+    let vars = options.vars();
+    let query = options
+        .query
+        .iter()
+        .map(|(key, value)| Ok((substitute(key, &vars)?, substitute(value, &vars)?)))
+        .collect::<Result<Vec<_>, RequestError>>()?;
+
+    for url in urls.iter_mut() {
+        *url = append_query(url, &query);
+    }
+
+    Ok(urls)
+}
+
+/// Request every URL given, printing (or saving) each response
+///
+/// A single URL behaves exactly like it always has, multiple URLs are fetched
+/// `--parallel` at a time and finish with a summary of what failed
+fn run_request(
+    method: Method,
+    body: Option<&[u8]>,
+    options: &CommonOptions,
+) -> Result<(), RequestError> {
+    let urls = collect_urls(options)?;
+    let urls = match options.repeat {
+        1 => urls,
+        repeat => urls
+            .iter()
+            .cycle()
+            .take(urls.len() * repeat as usize)
+            .cloned()
+            .collect(),
+    };
+
+    if options.sse && (options.output_format == OutputFormat::Json || urls.len() > 1) {
+        return Err(
+            "--sse prints events as they arrive, so it only works for one URL and text output"
+                .into(),
+        );
+    }
+
+    if options.prefetch_hints && options.output_format == OutputFormat::Json {
+        return Err(
+            "--prefetch-hints reports what it fetched as text, so it needs text output".into(),
+        );
+    }
+
+    if let Some(origin) = &options.cors {
+        return run_cors(origin, method, &urls, options);
+    }
+
+    if options.print_curl || options.export_code.is_some() {
+        return print_code(&method, &urls, body, options);
+    }
+
+    if !options.mirror.is_empty() {
+        let [url] = urls.as_slice() else {
+            return Err("--mirror is for one URL, give its mirrors with it".into());
+        };
+
+        let mut candidates = vec![url.clone()];
+        candidates.extend(resolve_urls(options.mirror.clone(), options)?);
+        return fetch_from_mirrors(method, &candidates, body, options);
+    }
+
+    if let Some(watch) = options.watch() {
+        let [url] = urls.as_slice() else {
+            return Err("--watch repeats one request, give it one URL".into());
+        };
+
+        return run_watch(&watch, method, url, body, options);
+    }
+
+    if let [url] = urls.as_slice() {
+        // Event streams are printed as they arrive, which needs to go straight to the terminal
+        let streams = options.output_format == OutputFormat::Text
+            && !options.hex
+            && !options.pager
+            && options.output.is_none()
+            && !options.remote_name
+            && options.cache_dir.is_none()
+            && options.checksums().is_empty()
+            && options.grep.is_none()
+            && options.assert_body_contains.is_empty();
+
+        if options.sse || streams {
+            let mut options = options.clone();
+            let printer = SsePrinter::new(options.sse, options.verbosity, options.pretty);
+            options.stream = Some(Arc::new(Mutex::new(printer)));
+            fetch_one(method, url, body, &options, &mut io::stdout())?;
+        } else if options.pager && io::stdout().is_terminal() {
+            let output = SharedOutput::default();
+            let mut options = options.clone();
+            options.redirect_output = Some(output.clone());
+            let result = fetch_one(method, url, body, &options, &mut output.clone());
+            page(&output.take())?;
+            result?;
+        } else {
+            fetch_one(method, url, body, options, &mut io::stdout())?;
+        }
+
+        return Ok(());
+    }
+
+    // Each response would overwrite the file the last one was saved to
+    if let Some(output) = options.output.as_deref().filter(|output| *output != "-") {
+        let unique = &urls[..urls.len() / options.repeat as usize];
+        let mut files = unique
+            .iter()
+            .map(|url| expand_output_template(output, &response_uri(url)))
+            .collect::<Vec<_>>();
+        files.sort();
+        files.dedup();
+
+        if files.len() < unique.len() {
+            return Err(format!(
+                "-o {} is the same file for more than one URL, put %{{host}} or %{{path}} in it to save each to its own file",
+                output
+            )
+            .into());
+        }
+    }
+
+    let failures = fetch_all(method, body, &urls, options);
+
+    if failures > 0 {
+        return Err(format!("{} of {} requests failed", failures, urls.len()).into());
+    }
+
+    Ok(())
+}
+
+/// Send the request every `watch.interval` and print a line for each response, with how the body
+/// changed since the last one, until what `watch` waits for comes back (or forever without it)
+fn run_watch(
+    watch: &Watch,
+    method: Method,
+    url: &str,
+    body: Option<&[u8]>,
+    options: &CommonOptions,
+) -> Result<(), RequestError> {
+    if options.output_format == OutputFormat::Json {
+        return Err("--watch prints a line for each response, so it needs text output".into());
+    }
+
+    // Every request has to go to the server, and the body's only shown as what changed
+    let mut watch_options = options.clone();
+    watch_options.stream = None;
+    watch_options.cache_dir = None;
+
+    let start = Instant::now();
+    let mut last: Option<Response<Vec<u8>>> = None;
+
+    for number in 1.. {
+        let sent = Instant::now();
+        let result = do_request(method.clone(), url, body, &watch_options);
+        let took = sent.elapsed();
+
+        match result {
+            Ok(response) => {
+                let (changed, changes) = match &last {
+                    Some(last) => {
+                        let changes = diff_bodies(last, &response)?;
+                        (
+                            changes.is_some() || last.status() != response.status(),
+                            changes,
+                        )
+                    }
+                    None => (false, None),
+                };
+
+                println!(
+                    "{}",
+                    format_poll(number, start.elapsed(), Ok((&response, took)), changed)
+                );
+
+                if let Some(changes) = changes {
+                    println!("{}", changes);
+                }
+
+                if watch.is_done(&response)? {
+                    println!(
+                        "{}",
+                        format!("Done after {} request(s)", number).out_color(|t| t.bold())
+                    );
+                    return Ok(());
+                }
+
+                last = Some(response);
+            }
+            Err(err) => println!(
+                "{}",
+                format_poll(number, start.elapsed(), Err(&err.to_string()), false)
+            ),
+        }
+
+        std::thread::sleep(watch.interval.saturating_sub(sent.elapsed()));
+    }
+
+    Ok(())
+}
+
+/// Send each URL the CORS preflight a browser at `origin` would before this request, and show
+/// what the server allows instead of making the request itself
+fn run_cors(
+    origin: &str,
+    method: Method,
+    urls: &[String],
+    options: &CommonOptions,
+) -> Result<(), RequestError> {
+    if options.output_format == OutputFormat::Json {
+        return Err("--cors shows the server's policy as text, so it needs text output".into());
+    }
+
+    let credentials = options.user.is_some() || options.session.is_some();
+    let preflight = Preflight::new(origin, method, &options.header, credentials)?;
+
+    // Browsers send preflights without cookies, auth or the request's own headers, and don't
+    // follow redirects for them
+    let mut preflight_options = options.clone();
+    preflight_options.header = preflight.request_headers();
+    preflight_options.user = None;
+    preflight_options.session = None;
+    preflight_options.loaded_session = None;
+    preflight_options.location = false;
+    preflight_options.cache_dir = None;
+    preflight_options.stream = None;
+
+    let mut blocked = 0;
+    let mut out = io::stdout();
+
+    for (i, url) in urls.iter().enumerate() {
+        if i > 0 {
+            writeln!(out)?;
+        }
+
+        if urls.len() > 1 {
+            writeln!(out, "{}", url.out_color(|t| t.bold()))?;
+        }
+
+        let response = do_request(Method::OPTIONS, url, None, &preflight_options)?;
+
+        if options.verbosity >= VERBOSE {
+            print_response(&response, options, &mut out)?;
+            writeln!(out)?;
+        }
+
+        if !preflight.problems(&response).is_empty() {
+            blocked += 1;
+        }
+
+        write!(out, "{}", format_policy(&preflight, &response))?;
+    }
+
+    match blocked {
+        0 => Ok(()),
+        _ if urls.len() == 1 => {
+            Err(format!("A browser at {} would block this request", preflight.origin).into())
+        }
+        _ => Err(format!(
+            "A browser at {} would block {} of {} requests",
+            preflight.origin,
+            blocked,
+            urls.len()
+        )
+        .into()),
+    }
+}
+
+/// Open a WebSocket to the one URL given, then send & print messages until it's closed
+fn run_websocket(options: &CommonOptions, protocols: &[String]) -> Result<(), RequestError> {
+    let [url] = <[String; 1]>::try_from(collect_urls(options)?)
+        .map_err(|_| "httpc ws connects to one URL at a time")?;
+
+    let uri = request_uri(&ws::http_url(&url))?;
+    let mut request = build_request(&Method::GET, &uri, None, options)?;
+    let key = ws::add_handshake_headers(request.headers_mut(), protocols)?;
+
+    let request_options = RequestOptions::from(options);
+    print_request(&request, &request_options)?;
+
+    let (response, connection) = http_upgrade(request, &request_options)?;
+    store_cookies(&uri, &response, options);
+
+    if options.verbosity >= VERBOSE || connection.is_none() {
+        print_response(&response, options, &mut io::stdout())?;
+    }
+
+    let socket = WebSocket::new(&response, connection, &key)?;
+    ws::interact(socket, options.verbosity, options.hex)
+}
+
+/// Request a single URL and print (or save) the response, resuming the download if asked to
+fn fetch_one(
+    method: Method,
+    url: &str,
+    body: Option<&[u8]>,
+    options: &CommonOptions,
+    out: &mut dyn Write,
+) -> Result<StatusCode, RequestError> {
+    let mut options = options.clone();
+
+    if options.remote_name {
+        options.output = Some(match is_file_url(url) {
+            true => url_file_name(&Uri::from_str(&file_path(url)?.to_string_lossy())?)?,
+            false => url_file_name(&response_uri(url))?,
+        });
+    }
+
+    let resume_from = match (options.continue_at, &options.output) {
+        (Some(continue_at), Some(file)) if file != "-" => continue_at.offset(file)?,
+        _ => 0,
+    };
+
+    add_request_headers(&mut options, resume_from)?;
+
+    // A file:// URL reads local files, and an FTP server picks where the data connection goes
+    if options.safe_fetch && (is_file_url(url) || is_ftp_url(url)) {
+        return Err(format!(
+            "--safe-fetch refuses {}:// URLs",
+            url.split("://").next().unwrap_or(url)
+        )
+        .into());
+    }
+
+    let response = if is_file_url(url) {
+        file_response(&method, url, options.max_filesize)?
+    } else if is_ftp_url(url) {
+        ftp_response(&method, url, resume_from, &RequestOptions::from(&options))?
+    } else if method == Method::GET && options.prefer_head {
+        probe(url, &options)?
+    } else {
+        paced_request(method, url, body, &options)?
+    };
+
+    if let Some(file) = &options.etag_save {
+        save_etag(&response, file)?;
+    }
+
+    if options.remote_header_name {
+        let suggested = response
+            .headers()
+            .get(header::CONTENT_DISPOSITION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(disposition_file_name);
+
+        if let Some(file) = suggested {
+            // The server picked the name, so don't let it clobber anything
+            if Path::new(&file).exists() {
+                return Err(
+                    format!("Not overwriting {}, the server's suggested name", file).into(),
+                );
+            }
+
+            options.output = Some(file);
+        }
+    }
+
+    if options.fail && (response.status().is_client_error() || response.status().is_server_error())
+    {
+        return Err(Box::new(FailedStatus(response.status())));
+    }
+
+    // Before anything's written, so a corrupted download doesn't end up in the -o file
+    if !options.checksums().is_empty() {
+        verify_checksums(&response, &options, resume_from)?;
+    }
+
+    match options.dump_header.as_deref() {
+        Some("-") => out.write_all(&response_head(&response))?,
+        Some(file) => std::fs::write(file, response_head(&response))?,
+        None => {}
+    }
+
+    // Already printed as it arrived
+    if response.extensions().get::<Streamed>().is_some() {
+        return Ok(response.status());
+    }
+
+    if options.output_format == OutputFormat::Json {
+        let mut json = vec![];
+        options.response_renderer().render(&response, &mut json)?;
+
+        match options.output.as_deref() {
+            Some("-") | None => out.write_all(&json)?,
+            Some(file) => std::fs::write(file, json)?,
+        }
+
+        check_assertions(&response, &options)?;
+        return Ok(response.status());
+    }
+
+    let conditional = options.etag_compare.is_some() || options.time_cond.is_some();
+
+    if conditional && response.status() == StatusCode::NOT_MODIFIED {
+        if options.verbosity >= VERBOSE {
+            print_response(&response, &options, out)?;
+            writeln!(
+                out,
+                "\n{}",
+                "Not modified, nothing written".out_color(|t| t.bright_black())
+            )?;
+        }
+    } else if options.range.is_some() {
+        if response.status() != StatusCode::PARTIAL_CONTENT && response.status().is_success() {
+            warn(
+                WarningKind::Response,
+                "Server ignored the range request and sent the whole body",
+            );
+        }
+
+        if is_byteranges(&response) && options.output.is_none() {
+            print_byteranges(&response, options.verbosity, out)?;
+        } else {
+            output_response(&response, &options, false, out)?;
+        }
+    } else if resume_from > 0 {
+        match check_resumed_response(&response, resume_from)? {
+            Resume::Append => output_response(&response, &options, true, out)?,
+            Resume::AlreadyComplete => writeln!(
+                out,
+                "{}",
+                "Download already complete, nothing to resume".out_color(|t| t.bright_black())
+            )?,
+        }
+    } else {
+        output_response(&response, &options, false, out)?;
+    }
+
+    let format = match (&options.write_out, options.show_final_url) {
+        (Some(format), _) => Some(format.as_str()),
+        (None, true) => Some("%{url_effective}\n"),
+        (None, false) => None,
+    };
+
+    if let Some(format) = format {
+        write!(out, "{}", write_out(format, &response))?;
+    }
+
+    if options.timing {
+        write!(out, "\n{}", format_timing(&response))?;
+    }
+
+    if options.prefetch_hints {
+        prefetch_hints(&response, &options, out)?;
+    }
+
+    check_assertions(&response, &options)?;
+    Ok(response.status())
+}
+
+/// [`do_request`], waiting its turn with --rps/--delay, and waiting out 429s (and 503s with
+/// Retry-After) before trying again while pacing
+fn paced_request(
+    method: Method,
+    url: &str,
+    body: Option<&[u8]>,
+    options: &CommonOptions,
+) -> Result<Response<Vec<u8>>, RequestError> {
+    let Some(pacer) = &options.pacer else {
+        return do_request(method, url, body, options);
+    };
+
+    let mut attempt = 0;
+
+    loop {
+        pacer.wait();
+        let response = do_request(method.clone(), url, body, options)?;
+
+        let Some(wait) = retry_wait(&response, attempt, SystemTime::now()) else {
+            return Ok(response);
+        };
+
+        attempt += 1;
+        eprintln!(
+            "{} {} from {}, trying again in {:.1}s ({} of {})",
+            "↻".if_supports_color(Stream::Stderr, |t| t.yellow()),
+            response.status(),
+            url,
+            wait.as_secs_f64(),
+            attempt,
+            MAX_RETRIES
+        );
+        pacer.pause(wait);
+    }
+}
+
+/// Check the response against the --assert-* flags, after it's been shown so there's something
+/// to go on when they fail
+fn check_assertions(
+    response: &Response<Vec<u8>>,
+    options: &CommonOptions,
+) -> Result<(), RequestError> {
+    let assertions = options.assertions();
+
+    if assertions.is_empty() {
+        return Ok(());
+    }
+
+    match assertions.check(response)?.as_slice() {
+        [] => Ok(()),
+        failures => Err(format!(
+            "{} assertion(s) failed:\n{}",
+            failures.len(),
+            format_failures(failures).trim_end()
+        )
+        .into()),
+    }
+}
 
-use clap::Parser;
+/// Check the body against --expect-sha256 and co. A resumed download is checked as a whole: the
+/// `resume_from` bytes already in the -o file, then what was just received
+fn verify_checksums(
+    response: &Response<Vec<u8>>,
+    options: &CommonOptions,
+    resume_from: u64,
+) -> Result<(), RequestError> {
+    if response.extensions().get::<Streamed>().is_some() {
+        return Err("Can't check the hash of a body that was printed as it arrived".into());
+    }
 
-use cli::{Cli, Commands, VERBOSE};
-use helpers::{format_response, parse_headers};
-use http::{header, Method, Request, Response, Uri, Version};
-use http_request::{http_request, RequestError};
-use owo_colors::{OwoColorize, Style};
+    for checksum in options.checksums() {
+        let body: Box<dyn Read> = match response.extensions().get::<Spooled>() {
+            Some(spooled) => Box::new(spooled.open()?),
+            None => Box::new(response.body().as_slice()),
+        };
 
-use crate::{
-    cli::VERY_VERBOSE,
-    helpers::{resolve_url, should_redirect, MColorize},
-};
+        match options.output.as_deref().filter(|_| resume_from > 0) {
+            // Nothing left to resume, the file's all there is
+            Some(file) if response.status() == StatusCode::RANGE_NOT_SATISFIABLE => {
+                checksum.verify(File::open(file)?)?
+            }
+            Some(file) => checksum.verify(File::open(file)?.take(resume_from).chain(body))?,
+            None => checksum.verify(body)?,
+        }
+    }
 
-mod cli;
-mod helpers;
-mod http_request;
+    Ok(())
+}
 
-fn main() {
-    let args = Cli::parse();
-    args.color.init();
+/// Add the headers asked for by --range (or resuming from `resume_from`), --etag-compare and
+/// --time-cond
+fn add_request_headers(options: &mut CommonOptions, resume_from: u64) -> Result<(), RequestError> {
+    if let Some(range) = &options.range {
+        options.header.push(format!("Range: bytes={}", range));
+    } else if resume_from > 0 {
+        options
+            .header
+            .push(format!("Range: bytes={}-", resume_from));
+    }
 
-    let res = run_command(args.command);
+    if let Some(etag) = options.etag_compare.as_deref().map(read_etag).transpose()? {
+        options
+            .header
+            .extend(etag.map(|etag| format!("If-None-Match: {}", etag)));
+    }
 
-    if res.is_err() {
-        // oh no
-        eprintln!("{}", res.unwrap_err());
-        std::process::exit(1);
+    if let Some(time_cond) = options.time_cond {
+        options.header.push(time_cond.header());
     }
+
+    Ok(())
 }
 
-fn run_command(command: Commands) -> Result<(), RequestError> {
-    match command {
-        Commands::Get { options } => do_request(
-            Method::GET,
-            &options.url,
-            options.header,
-            None,
-            options.verbosity,
-            options.output,
-            options.location,
-        ),
+/// Fetch everything `response` hinted at preloading, like a browser would (up to 6 at once from
+/// each host), and report how it went
+fn prefetch_hints(
+    response: &Response<Vec<u8>>,
+    options: &CommonOptions,
+    out: &mut dyn Write,
+) -> Result<(), RequestError> {
+    let base = match response.extensions().get::<EffectiveUrl>() {
+        Some(url) => url.0.clone(),
+        None => return Ok(()),
+    };
+    let hints = preload_hints(response, &base);
 
-        Commands::Post {
-            options,
-            data,
-            file,
-        } => {
-            let body: Option<Vec<u8>> = match (data, file) {
-                // -d '{"data": "here"}'
-                (Some(data), None) => Some(data.into_bytes()),
-                // -f ./file.txt
-                (None, Some(file)) => Some(std::fs::read(file).unwrap()),
-                // -d '{"data": "here"}' -f ./file.txt
-                (Some(_), Some(_)) => {
-                    return Err(Box::<dyn Error>::from(
-                        "File and data cannot be used together",
-                    ))
+    if hints.is_empty() {
+        let message = "No preload hints (103 Early Hints or Link: rel=preload) to prefetch";
+        writeln!(out, "\n{}", message.out_color(|t| t.bright_black()))?;
+        return Ok(());
+    }
+
+    // Same connection settings, session & headers, but nothing printed or saved
+    let mut fetch_options = options.clone();
+    fetch_options.verbosity = 0;
+    fetch_options.stream = None;
+    fetch_options.cache_dir = None;
+
+    let mut results = vec![None; hints.len()];
+    let start = Instant::now();
+
+    run_pool_limited(
+        &hints,
+        hints.len().min(u16::MAX as usize) as u16,
+        Some(6),
+        |hint| response_uri(&hint.url).host().map(str::to_ascii_lowercase),
+        |_, hint| {
+            let start = Instant::now();
+            do_request(Method::GET, &hint.url, None, &fetch_options)
+                .map(|response| Prefetched {
+                    status: response.status(),
+                    size: body_len(&response) as usize,
+                    took: start.elapsed(),
+                })
+                .map_err(|err| err.to_string())
+        },
+        |i, result| results[i] = Some(result),
+    );
+
+    let results = results.into_iter().flatten().collect::<Vec<_>>();
+    let timings = response.extensions().get::<Timings>();
+    let report = format_report(&hints, &results, timings, start.elapsed());
+    write!(out, "\n{}", report)?;
+
+    Ok(())
+}
+
+/// Fetch many URLs concurrently, returning how many failed
+fn fetch_all(
+    method: Method,
+    body: Option<&[u8]>,
+    urls: &[String],
+    options: &CommonOptions,
+) -> usize {
+    let mut results: Vec<Option<Result<StatusCode, String>>> = vec![None; urls.len()];
+
+    run_pool_limited(
+        urls,
+        options.parallel,
+        options.max_per_host,
+        |url| {
+            response_uri(url)
+                .host()
+                .map(|host| host.to_ascii_lowercase())
+        },
+        |_, url| {
+            // Buffer all the output so responses finishing at the same time don't get mixed up,
+            // the redirects -v shows included
+            let output = SharedOutput::default();
+            let mut options = options.clone();
+            options.redirect_output = Some(output.clone());
+            options.output = options
+                .output
+                .as_deref()
+                .map(|template| expand_output_template(template, &response_uri(url)));
+            options.dump_header = options
+                .dump_header
+                .as_deref()
+                .map(|template| expand_output_template(template, &response_uri(url)));
+
+            let result = fetch_one(method.clone(), url, body, &options, &mut output.clone());
+
+            (output.take(), result.map_err(|err| err.to_string()))
+        },
+        |i, (output, result)| {
+            let mut stdout = io::stdout().lock();
+            // Nothing useful to do if stdout is gone
+            let _ = stdout.write_all(&output);
+            let _ = stdout.flush();
+            results[i] = Some(result);
+        },
+    );
+
+    print_summary(urls, &results)
+}
+
+/// Try the same request against each of `urls` in turn until one succeeds (isn't an error or a
+/// 4xx/5xx), and say which one it was. Only the successful response is output
+fn fetch_from_mirrors(
+    method: Method,
+    urls: &[String],
+    body: Option<&[u8]>,
+    options: &CommonOptions,
+) -> Result<(), RequestError> {
+    for url in urls {
+        let mut output = vec![];
+        let reason = match fetch_one(method.clone(), url, body, options, &mut output) {
+            Ok(status) if status.is_client_error() || status.is_server_error() => {
+                status.to_string()
+            }
+            Ok(_) => {
+                io::stdout().write_all(&output)?;
+
+                if url != &urls[0] || options.verbosity >= VERBOSE {
+                    eprintln!(
+                        "{} {}",
+                        "Served by:".if_supports_color(Stream::Stderr, |t| t.bold()),
+                        url
+                    );
                 }
-                _ => None,
-            };
 
-            do_request(
-                Method::POST,
-                &options.url,
-                options.header,
-                body.as_deref(),
-                options.verbosity,
-                options.output,
-                options.location,
-            )
+                return Ok(());
+            }
+            Err(err) => err.to_string(),
+        };
+
+        eprintln!(
+            "{} {}: {}",
+            "✗".if_supports_color(Stream::Stderr, |t| t.red()),
+            url,
+            reason
+        );
+    }
+
+    Err(format!("The URL and all {} of its mirrors failed", urls.len() - 1).into())
+}
+
+/// With --summary, print how much was sent to & received from each host, even if something failed
+fn print_transferred(options: &CommonOptions) {
+    if !options.summary {
+        return;
+    }
+
+    let hosts = snapshot();
+
+    if options.output_format == OutputFormat::Json {
+        println!("{}", summary_json(&hosts));
+    } else {
+        eprint!("\n{}", format_summary(&hosts));
+    }
+}
+
+fn response_uri(url: &str) -> Uri {
+    Uri::from_str(&normalize_url(&ensure_starts_with_schema(url))).unwrap_or_default()
+}
+
+/// Print which requests succeeded & failed to stderr, returning the number of failures
+fn print_summary(urls: &[String], results: &[Option<Result<StatusCode, String>>]) -> usize {
+    let mut failures = vec![];
+
+    for (url, result) in urls.iter().zip(results) {
+        match result {
+            Some(Ok(status)) if status.is_client_error() || status.is_server_error() => {
+                failures.push((url, status.to_string()))
+            }
+            Some(Ok(_)) => {}
+            Some(Err(err)) => failures.push((url, err.clone())),
+            None => failures.push((url, "Request never finished".to_string())),
         }
     }
+
+    eprintln!(
+        "\n{} {} succeeded, {} failed",
+        "Summary:".if_supports_color(Stream::Stderr, |t| t.bold()),
+        urls.len() - failures.len(),
+        failures.len()
+    );
+
+    for (url, reason) in &failures {
+        eprintln!(
+            "  {} {}: {}",
+            "✗".if_supports_color(Stream::Stderr, |t| t.red()),
+            url,
+            reason
+        );
+    }
+
+    failures.len()
 }
 
 fn ensure_starts_with_schema(uri: &str) -> String {
-    if uri.starts_with("http://") || uri.starts_with("https://") {
+    if uri.starts_with("http://") || uri.starts_with("https://") || is_ftp_url(uri) {
         uri.to_string()
     } else {
         format!("http://{}", uri)
     }
 }
 
-fn do_request(
-    method: Method,
-    uri: &str,
-    headers: Vec<String>,
-    body: Option<&[u8]>,
-    verbosity: u8,
-    output: Option<String>,
-    location: bool,
-) -> Result<(), RequestError> {
-    let uri = ensure_starts_with_schema(uri);
+/// Parse a URL given on the command line, with `.` and `..` in the path resolved
+fn request_uri(uri: &str) -> Result<Uri, RequestError> {
+    let uri = normalize_url(&ensure_starts_with_schema(uri));
     // Parse out path
     let uri = Uri::from_str(uri.as_str())?;
     // Resolve . and .. in path
@@ -101,78 +2074,700 @@ fn do_request(
         uri.query().map_or(String::new(), |q| format!("?{}", q))
     );
     // Parse back to uri
-    let uri = Uri::from_str(&uri)?;
+    Ok(Uri::from_str(&uri)?)
+}
 
+/// Build the request with the headers from the options, the session's cookies and auth
+fn build_request<'a>(
+    method: &Method,
+    uri: &Uri,
+    body: Option<&'a [u8]>,
+    options: &CommonOptions,
+) -> Result<Request<Option<&'a [u8]>>, RequestError> {
     let mut request = Request::builder()
         .version(Version::HTTP_11)
-        .method(&method)
-        .uri(&uri);
+        .method(method)
+        .uri(uri);
 
     let req_headers = request.headers_mut().unwrap();
 
-    for (name, value) in parse_headers(&headers)? {
+    for (name, value) in parse_headers(&options.header)? {
         req_headers.append(name, value);
     }
 
-    let request = request.body(body)?;
-    let response = http_request(request, verbosity)?;
+    // Checked for every request, so a redirect to another host doesn't take them along
+    let host = uri.host().unwrap_or_default();
+    let mut host_headers = options
+        .host_header
+        .iter()
+        .filter(|(pattern, _)| host_matches(pattern, host))
+        .map(|(_, header)| header.clone())
+        .collect::<Vec<_>>();
+
+    if !host_headers.is_empty() {
+        host_headers = substitute_headers(&host_headers, &options.vars())?;
+    }
+
+    for (name, value) in parse_headers(&host_headers)? {
+        if !has_header(&options.header, name.as_str()) {
+            req_headers.append(name, value);
+        }
+    }
+
+    if let Some(session) = &options.loaded_session {
+        let cookie = session.lock().ok().and_then(|s| s.cookies.header_for(uri));
+
+        if let Some(cookie) = cookie.filter(|_| !req_headers.contains_key(header::COOKIE)) {
+            req_headers.insert(header::COOKIE, cookie.parse()?);
+        }
+    }
+
+    if let Some(user) = options
+        .user
+        .as_ref()
+        .filter(|_| options.aws_sigv4.is_none() && options.connection_auth().is_none())
+    {
+        if !req_headers.contains_key(header::AUTHORIZATION) {
+            req_headers.insert(header::AUTHORIZATION, basic_auth(user).parse()?);
+        }
+    }
+
+    if let Some(referer) = options.referer() {
+        if !req_headers.contains_key(header::REFERER) {
+            req_headers.insert(header::REFERER, referer.parse()?);
+        }
+    }
+
+    if options.trailers && !req_headers.contains_key(header::TE) {
+        req_headers.insert(header::TE, HeaderValue::from_static("trailers"));
+    }
+
+    if let (Some(algorithm), Some(body)) = (options.send_digest, body) {
+        let (name, value) = digest_header(algorithm, body)?;
 
-    // Follow redirects
-    if location && should_redirect(&response.status()) {
-        if let Some(header_location) = response.headers().get(header::LOCATION) {
-            let header_location = header_location.to_str()?;
-            let header_location = resolve_url(&uri, header_location);
+        if !req_headers.contains_key(&name) {
+            req_headers.insert(name, value);
+        }
+    }
+
+    // -h 'Name:' takes out whatever was added above too, before anything gets signed
+    let removed = removed_headers(&options.header);
+    for name in &removed {
+        req_headers.remove(name);
+    }
+
+    options
+        .sign
+        .sign(method, uri, req_headers, body, SystemTime::now())?;
+
+    // Last, since it signs the headers
+    if let Some(scope) = &options.aws_sigv4 {
+        let credentials = Credentials::from_user_or_env(options.user.as_deref())?;
+        let signer = Signer {
+            credentials: &credentials,
+            region: &scope.region,
+            service: &scope.service,
+            time: SystemTime::now(),
+        };
+        signer.sign(method, uri, req_headers, body)?;
+    }
+
+    Ok(request.extension(RemovedHeaders(removed)).body(body)?)
+}
+
+/// The request for a URL, with any credentials in it sent as Basic auth instead
+fn request_for<'a>(
+    method: &Method,
+    uri: &str,
+    body: Option<&'a [u8]>,
+    options: &CommonOptions,
+) -> Result<Request<Option<&'a [u8]>>, RequestError> {
+    let (uri, url_user) = split_userinfo(&request_uri(uri)?)?;
+
+    // Only for this request, redirects don't get to keep them
+    let request = match url_user {
+        Some(user) if options.user.is_none() => {
+            warn(WarningKind::Usage, "Sending the credentials in the URL with Basic auth, use -u to keep them out of the URL (and your shell history)");
+            let mut with_user = options.clone();
+            with_user.user = Some(user);
+            build_request(method, &uri, body, &with_user)?
+        }
+        Some(_) => {
+            warn(
+                WarningKind::Usage,
+                "Ignoring the credentials in the URL, -u was given",
+            );
+            build_request(method, &uri, body, options)?
+        }
+        None => build_request(method, &uri, body, options)?,
+    };
+
+    Ok(request)
+}
+
+/// Print the curl command (or --export-code code) for each URL instead of requesting them
+fn print_code(
+    method: &Method,
+    urls: &[String],
+    body: Option<&[u8]>,
+    options: &CommonOptions,
+) -> Result<(), RequestError> {
+    for url in urls {
+        // curl gets -C instead of a Range, so it appends to the file
+        let mut options = options.clone();
+        add_request_headers(&mut options, 0)?;
+
+        let request = request_for(method, url, body, &options)?;
+
+        match options.export_code {
+            Some(language) => println!("{}", export_code(language, &request, &options)?),
+            None => println!("{}", curl_command(&request, &options)),
+        }
+    }
 
-            if verbosity >= VERBOSE {
-                // Print response between redirect if verbose
-                print_response(&response, verbosity)?;
+    Ok(())
+}
+
+/// Save the cookies a response sets in the session, if there is one
+fn store_cookies(uri: &Uri, response: &Response<Vec<u8>>, options: &CommonOptions) {
+    if let Some(session) = &options.loaded_session {
+        if let Ok(mut session) = session.lock() {
+            session.cookies.store(uri, response.headers());
+        }
+    }
+}
 
+/// Send the request (or answer it from --replay or --cache-dir)
+fn send_request(
+    request: Request<Option<&[u8]>>,
+    options: &CommonOptions,
+    request_options: &RequestOptions,
+) -> Result<Response<Vec<u8>>, RequestError> {
+    match (&options.replay, &options.cache_dir) {
+        (Some(dir), _) => {
+            let (method, uri) = (request.method(), request.uri());
+            let replayed = Cassette::open(dir)?.replay(method, uri, *request.body())?;
+
+            if options.verbosity >= VERBOSE {
                 println!(
-                    "\n{} {}\n",
-                    "↪ Redirecting to:".out_color(|t| t.blue()),
-                    header_location.out_color(|t| t.style(Style::new().blue().underline()))
+                    "{}",
+                    format!("Replayed {} {} from {}\n", method, uri, dir)
+                        .out_color(|t| t.bright_black())
                 );
             }
 
-            return do_request(
-                method,
-                &header_location,
-                headers,
-                body,
-                verbosity,
-                output,
-                location,
-            );
+            Ok(replayed)
         }
+        (None, Some(dir)) => Cache::open(dir)?.request(request, request_options),
+        (None, None) => http_request(request, request_options),
     }
+}
+
+/// The client to send requests with, with the middleware the options ask for
+fn client(options: &CommonOptions) -> Result<Client, RequestError> {
+    let transport_options = options.clone();
+    let mut client =
+        Client::new(RequestOptions::from(options)).transport(move |request, request_options| {
+            send_request(request, &transport_options, request_options)
+        });
 
-    // If we don't redirect, we can finally print (or output to file) the response
+    if let Some(log) = &options.opened_log {
+        client = client.with(log.clone());
+    }
 
-    if let Some(file) = &output {
-        std::fs::write(&file, response.body())?;
+    if let Some(dir) = &options.record {
+        client = client.with(Cassette::open(dir)?);
+    }
 
-        if verbosity >= VERBOSE {
-            print_response(&response, verbosity)?;
-            println!(
+    if let Some(har) = &options.opened_har {
+        client = client.with(har.clone());
+    }
+
+    if let Some(session) = &options.loaded_session {
+        client = client.with(StoreCookies(session.clone()));
+    }
+
+    if options.verbosity >= VERY_VERBOSE {
+        client = client.with(VerboseRequests);
+    }
+
+    if options.location || options.follow_meta_refresh {
+        let show = (options.verbosity >= VERBOSE)
+            .then(|| Arc::new(ShownResponse(options.clone())) as Arc<_>);
+
+        client = client.with(CliRedirects {
+            follow: FollowRedirects {
+                policy: options.redirect.clone(),
+                location: options.location,
+                meta_refresh: options.follow_meta_refresh,
+                auto_referer: options.auto_referer(),
+                show,
+                out: options.redirect_output.clone(),
+            },
+            options: options.clone(),
+        });
+    }
+
+    Ok(client)
+}
+
+fn do_request(
+    method: Method,
+    uri: &str,
+    body: Option<&[u8]>,
+    options: &CommonOptions,
+) -> Result<Response<Vec<u8>>, RequestError> {
+    let request = request_for(&method, uri, body, options)?;
+    let mut response = client(options)?.send(request)?;
+
+    // A redirect's last request is a GET without a body if it was a meta refresh
+    let final_method = response
+        .extensions()
+        .get::<SentRequest>()
+        .and_then(|sent| Method::from_str(&sent.method).ok())
+        .unwrap_or_else(|| method.clone());
+    let body = body.filter(|_| final_method == method);
+
+    let redirects = response.extensions_mut().remove::<Redirects>();
+
+    // Redirected to another origin, which the credentials weren't meant for
+    let crossed_origin = match (&redirects, response.extensions().get::<EffectiveUrl>()) {
+        (Some(Redirects(previous)), Some(EffectiveUrl(last))) => previous
+            .iter()
+            .map(|(_, uri)| uri)
+            .chain([last])
+            .collect::<Vec<_>>()
+            .windows(2)
+            .any(|pair| options.redirect.strips_credentials(pair[0], pair[1])),
+        _ => false,
+    };
+
+    let mut response = check_response(final_method, body, response, crossed_origin, options)?;
+
+    // Logging in again (or authenticating) sends another request, which comes after the redirects
+    if let Some(mut redirects) = redirects {
+        if let Some(later) = response.extensions_mut().remove::<Redirects>() {
+            redirects.0.extend(later.0);
+        }
+
+        response.extensions_mut().insert(redirects);
+    }
+
+    Ok(response)
+}
+
+/// Deal with what the response (after any redirects) says about auth: log in again or
+/// authenticate if it wants that, and warn about a proxy's rejected credentials. Then check its
+/// digests with --verify-digest
+///
+/// Neither logging in again nor authenticating happens once the redirects `crossed_origin`, since
+/// both would send the credentials to the other origin
+fn check_response(
+    method: Method,
+    body: Option<&[u8]>,
+    mut response: Response<Vec<u8>>,
+    crossed_origin: bool,
+    options: &CommonOptions,
+) -> Result<Response<Vec<u8>>, RequestError> {
+    let uri = match response.extensions().get::<EffectiveUrl>() {
+        Some(EffectiveUrl(uri)) => uri.clone(),
+        None => return Err("The response doesn't say which URL it came from".into()),
+    };
+
+    // The login might've been revoked or expired early, so log in again and retry (only once)
+    if response.status() == StatusCode::UNAUTHORIZED && !crossed_origin && log_in(options, true)? {
+        let mut retry = options.clone();
+        retry.header = substitute_headers(&options.raw_headers, &options.vars())?;
+        retry.loaded_profile = None;
+
+        return do_request(method, uri.to_string().as_str(), body, &retry);
+    }
+
+    if let Some(auth) = options.connection_auth().filter(|_| !crossed_origin) {
+        if challenge_header(&response)
+            .is_some_and(|name| auth_token(response.headers(), name.as_str(), auth).is_some())
+        {
+            response = connection_auth_handshake(auth, &method, &uri, body, response, options)?;
+        }
+    }
+
+    // Only warned about when the credentials are ours, a Proxy-Authorization from -h (or from
+    // --ntlm's handshake) is the user's business
+    if response.status() == StatusCode::PROXY_AUTHENTICATION_REQUIRED
+        && !has_header(&options.header, header::PROXY_AUTHORIZATION.as_str())
+    {
+        let has_credentials = options.proxy_user.is_some()
+            || options
+                .proxy
+                .as_ref()
+                .is_some_and(|proxy| proxy.authority().is_some_and(|a| a.as_str().contains('@')));
+
+        warn(
+            WarningKind::Auth,
+            match has_credentials {
+                true => "The proxy rejected its credentials",
+                false => "The proxy wants credentials, give them with --proxy-user USER:PASSWORD",
+            },
+        );
+    }
+
+    if options.verify_digest && verify_response_digests(&response)? == 0 {
+        warn(
+            WarningKind::Response,
+            "--verify-digest: response has no Content-Digest or Content-MD5 to verify",
+        );
+    }
+
+    Ok(response)
+}
+
+/// `-l` and `--follow-meta-refresh`, building each redirect's request from the options again like
+/// the first one, so it gets the session's cookies for where it's going, --host-header's,
+/// signatures and all
+struct CliRedirects {
+    follow: FollowRedirects,
+    options: CommonOptions,
+}
+
+impl Middleware for CliRedirects {
+    fn follow_up<'a>(
+        &self,
+        exchange: &Exchange<'_, 'a>,
+        response: &Response<Vec<u8>>,
+    ) -> Result<Option<Request<Option<&'a [u8]>>>, RequestError> {
+        let Some(hop) = self.follow.next_hop(exchange, response)? else {
+            return Ok(None);
+        };
+
+        self.follow.announce(exchange, response, &hop)?;
+
+        let uri = exchange.request.uri();
+        let mut options = self.options.clone();
+
+        if self.follow.auto_referer {
+            // Like browsers, an https URL isn't given away to plain http
+            let downgraded =
+                uri.scheme() == Some(&Scheme::HTTPS) && hop.target.scheme() == Some(&Scheme::HTTP);
+
+            options.referer = Some(match downgraded {
+                true => ";auto".to_string(),
+                false => format!("{};auto", uri),
+            });
+        }
+
+        // Once another origin is involved, credentials meant for the first one aren't sent anymore
+        if self.follow.strips_credentials(exchange, &hop.target) && options.strip_credentials() {
+            // Only said the first time
+            if options.verbosity >= VERBOSE && !self.follow.strips_credentials(exchange, uri) {
+                println!(
+                    "{}",
+                    "Not sending credentials to a different origin (--location-trusted would)\n"
+                        .out_color(|t| t.bright_black())
+                );
+            }
+        }
+
+        let body = match hop.keep_body {
+            true => *exchange.request.body(),
+            false => None,
+        };
+
+        let mut next = request_for(&hop.method, &hop.target.to_string(), body, &options)?;
+
+        // Checked again on connecting, the host could resolve somewhere else by then
+        if self.follow.policy.no_private {
+            next.extensions_mut().insert(SafeFetch);
+        }
+
+        Ok(Some(next))
+    }
+}
+
+/// Shows the responses that redirected like [`print_response`]
+struct ShownResponse(CommonOptions);
+
+impl ResponseRenderer for ShownResponse {
+    fn render(
+        &self,
+        response: &Response<Vec<u8>>,
+        out: &mut dyn Write,
+    ) -> Result<(), RequestError> {
+        print_response(response, &self.0, out)
+    }
+}
+
+/// Where a 401 (or a proxy's 407) says which auth it wants
+fn challenge_header(response: &Response<Vec<u8>>) -> Option<header::HeaderName> {
+    match response.status() {
+        StatusCode::UNAUTHORIZED => Some(header::WWW_AUTHENTICATE),
+        StatusCode::PROXY_AUTHENTICATION_REQUIRED => Some(header::PROXY_AUTHENTICATE),
+        _ => None,
+    }
+}
+
+/// Authenticate with NTLM after the `response` from `uri` asked for it, returning the response
+/// to the authenticated request, see [`httpc::ntlm`]
+///
+/// Each leg goes through [`do_request`] with its own connection tag, so they share a connection
+/// no other request can take. Proxies can only be authenticated this way with plain HTTP, an
+/// https URL's CONNECT happens before any of this
+fn connection_auth_handshake(
+    auth: ConnectionAuth,
+    method: &Method,
+    uri: &Uri,
+    body: Option<&[u8]>,
+    response: Response<Vec<u8>>,
+    options: &CommonOptions,
+) -> Result<Response<Vec<u8>>, RequestError> {
+    static HANDSHAKES: AtomicUsize = AtomicUsize::new(0);
+
+    let Some(challenge_header) = challenge_header(&response) else {
+        return Ok(response);
+    };
+    let auth_header = match response.status() {
+        StatusCode::PROXY_AUTHENTICATION_REQUIRED => header::PROXY_AUTHORIZATION,
+        _ => header::AUTHORIZATION,
+    };
+
+    if options.verbosity >= VERBOSE {
+        print_response(&response, options, &mut io::stdout())?;
+        println!(
+            "\n{}\n",
+            format!("↪ Authenticating with {}", auth.scheme()).out_color(|t| t.blue())
+        );
+    }
+
+    // No redirects or logging in in between, and -u isn't sent as Basic auth
+    let mut leg = options.clone();
+    leg.ntlm = false;
+    leg.negotiate = false;
+    leg.user = None;
+    leg.loaded_profile = None;
+    leg.location = false;
+    leg.follow_meta_refresh = false;
+    leg.connection_tag = Some(format!(
+        "{} {}",
+        auth.scheme(),
+        HANDSHAKES.fetch_add(1, Ordering::Relaxed)
+    ));
+    leg.header.retain(|line| {
+        !line
+            .split_once(':')
+            .is_some_and(|(name, _)| name.trim().eq_ignore_ascii_case(auth_header.as_str()))
+    });
+
+    // The body only needs to be sent once we're authenticated
+    let mut negotiate = leg.clone();
+    negotiate.verify_digest = false;
+    negotiate.header.push(format!(
+        "{}: {}",
+        auth_header,
+        auth.header_value(&negotiate_message())
+    ));
+    let challenged = do_request(method.clone(), &uri.to_string(), None, &negotiate)?;
+
+    let token = auth_token(challenged.headers(), challenge_header.as_str(), auth)
+        .filter(|token| !token.is_empty());
+    let Some(token) = token else {
+        warn(
+            WarningKind::Auth,
+            format!(
+                "The server didn't answer with a {} challenge, it got {}",
+                auth.scheme(),
+                challenged.status()
+            ),
+        );
+        return Ok(challenged);
+    };
+
+    if options.verbosity >= VERBOSE {
+        print_response(&challenged, options, &mut io::stdout())?;
+        println!();
+    }
+
+    let credentials = NtlmCredentials::parse(options.user.as_deref().unwrap_or_default());
+    let message = authenticate_message(
+        &credentials,
+        &parse_challenge(&token)?,
+        random_bytes(),
+        SystemTime::now(),
+    );
+
+    // Has to be the connection the challenge came on
+    leg.fresh_connect = false;
+    leg.header
+        .push(format!("{}: {}", auth_header, auth.header_value(&message)));
+    let authenticated = do_request(method.clone(), &uri.to_string(), body, &leg)?;
+
+    if authenticated.status() == response.status() {
+        warn(
+            WarningKind::Auth,
+            format!(
+                "{} authentication failed, check -u's domain, user and password",
+                auth.scheme()
+            ),
+        );
+    }
+
+    Ok(authenticated)
+}
+
+/// Print (or output to file) the final response
+///
+/// `append` adds the body to the end of the output file instead of replacing it
+fn output_response(
+    response: &Response<Vec<u8>>,
+    options: &CommonOptions,
+    append: bool,
+    out: &mut dyn Write,
+) -> Result<(), RequestError> {
+    let verbosity = options.verbosity;
+    let head = if options.include {
+        response_head(response)
+    } else {
+        vec![]
+    };
+
+    // Piping the body (or -o -) passes it through untouched, so binary data survives
+    let raw = options.output.as_deref() == Some("-")
+        || options.output_format == OutputFormat::Raw
+        || (verbosity < VERBOSE && !options.hex && !io::stdout().is_terminal());
+
+    if let Some(file) = options.output.as_ref().filter(|file| *file != "-") {
+        let mut output = if append {
+            OpenOptions::new().append(true).create(true).open(file)?
+        } else {
+            std::fs::File::create(file)?
+        };
+
+        output.write_all(&head)?;
+        copy_body(response, &mut output)?;
+
+        if let Some(grep) = options.grep() {
+            print_matches(response, &grep, out)?;
+        } else if let Some(path) = &options.extract {
+            print_extracted(response, path, out)?;
+        } else if verbosity >= VERBOSE {
+            print_response(response, options, out)?;
+            writeln!(
+                out,
                 "\n{} {}",
                 "Output written to:".out_color(|t| t.bright_black()),
                 file.out_color(|t| t.style(Style::new().blue().underline()))
-            );
+            )?;
         }
+    } else if let Some(grep) = options.grep() {
+        out.write_all(&head)?;
+        print_matches(response, &grep, out)?;
+    } else if let Some(path) = &options.extract {
+        out.write_all(&head)?;
+        print_extracted(response, path, out)?;
+    } else if raw {
+        RawRenderer {
+            include: options.include,
+        }
+        .render(response, out)?;
     } else {
-        print_response(&response, verbosity)?;
+        // -v already shows the headers
+        if verbosity < VERBOSE {
+            out.write_all(&head)?;
+        }
+
+        match options.max_display_bytes {
+            Some(max) if body_len(response) > max => {
+                print_truncated(response, max as usize, options, out)?
+            }
+            _ => print_response(response, options, out)?,
+        }
+    }
+
+    Ok(())
+}
+
+/// Print the lines of the body --grep matched, erroring if there weren't any
+fn print_matches(
+    response: &Response<Vec<u8>>,
+    grep: &Grep,
+    out: &mut dyn Write,
+) -> Result<(), RequestError> {
+    let lines = grep.matching_lines(response)?;
+
+    for line in &lines {
+        writeln!(out, "{}", line)?;
+    }
+
+    match lines.is_empty() {
+        true => Err(format!("No lines of the body have '{}' in them", grep.pattern).into()),
+        false => Ok(()),
+    }
+}
+
+/// Print the values at --extract's JSONPath in the body, one per line
+fn print_extracted(
+    response: &Response<Vec<u8>>,
+    path: &str,
+    out: &mut dyn Write,
+) -> Result<(), RequestError> {
+    let body = body_prefix(response, body_len(response))?;
+    let json = serde_json::from_slice::<serde_json::Value>(&body)
+        .map_err(|err| format!("Can't --extract from a body that isn't JSON: {}", err))?;
+
+    for value in select_all(&json, path)? {
+        writeln!(out, "{}", to_text(value))?;
     }
 
     Ok(())
 }
 
-fn print_response(response: &Response<Vec<u8>>, verbosity: u8) -> Result<(), RequestError> {
-    let formatted = format_response(response, verbosity)?;
+/// Print the response with only the first `max` bytes of the body, saying how much was left out
+fn print_truncated(
+    response: &Response<Vec<u8>>,
+    max: usize,
+    options: &CommonOptions,
+    out: &mut dyn Write,
+) -> Result<(), RequestError> {
+    // One more byte than is shown, to tell if the last one starts a character
+    let body = body_prefix(response, max as u64 + 1)?;
+    let mut cut = max;
 
-    if verbosity >= VERY_VERBOSE {
-        println!("{}", "← Received".out_color(|t| t.green()))
+    // Don't split a UTF-8 character in half
+    while cut > 0 && body[cut] & 0b1100_0000 == 0b1000_0000 {
+        cut -= 1;
     }
 
-    println!("{}", formatted);
+    let mut shown = Response::new(body[..cut].to_vec());
+    *shown.status_mut() = response.status();
+    *shown.version_mut() = response.version();
+    *shown.headers_mut() = response.headers().clone();
+
+    print_response(&shown, options, out)?;
+    writeln!(
+        out,
+        "{}",
+        format!(
+            "… {} more bytes not shown, use -o to save the whole body",
+            body_len(response) - cut as u64
+        )
+        .out_color(|t| t.bright_black())
+    )?;
+
     Ok(())
 }
+
+fn print_response(
+    response: &Response<Vec<u8>>,
+    options: &CommonOptions,
+    out: &mut dyn Write,
+) -> Result<(), RequestError> {
+    // Responses shown along the way (redirects, auth challenges, -v) are always text
+    let renderer = match options.output_format {
+        OutputFormat::Text | OutputFormat::Plain => options.response_renderer(),
+        OutputFormat::Json | OutputFormat::Raw => Box::new(TerminalRenderer::from(options)),
+    };
+
+    if options.verbosity >= VERY_VERBOSE {
+        writeln!(out, "{}", "← Received".out_color(|t| t.green()))?;
+    }
+
+    renderer.render(response, out)
+}
@@ -0,0 +1,509 @@
+//! A [`Client`] that sends requests through a chain of [`Middleware`], so a program using httpc as
+//! a library can log, add auth, or retry without touching [`http_request`] itself
+//!
+//! Each middleware sees every request before it's sent (in the order they were added), then every
+//! response (or error) as it arrives (in reverse), and can ask for a follow-up request to be sent
+//! instead of returning the response, like a redirect or a retry. The CLI is built the same way:
+//! `-vv` is [`VerboseRequests`], `-l` is built on [`FollowRedirects`], `--log-file` is a
+//! [`crate::request_log::RequestLog`] and `--har` is a [`crate::har::HarRecorder`]
+//!
+//! ```no_run
+//! use httpc::{
+//!     http_request::{RequestError, RequestOptions},
+//!     middleware::{Client, FollowRedirects, Middleware},
+//! };
+//!
+//! /// Send a token with every request
+//! struct BearerAuth(String);
+//!
+//! impl Middleware for BearerAuth {
+//!     fn before_send(
+//!         &self,
+//!         request: &mut http::Request<Option<&[u8]>>,
+//!         _: &RequestOptions,
+//!     ) -> Result<(), RequestError> {
+//!         let token = format!("Bearer {}", self.0).parse()?;
+//!         request.headers_mut().insert(http::header::AUTHORIZATION, token);
+//!         Ok(())
+//!     }
+//! }
+//!
+//! let client = Client::new(RequestOptions::default())
+//!     .with(BearerAuth("hunter2".to_string()))
+//!     .with(FollowRedirects::default());
+//! let request = http::Request::get("http://example.com/api/me").body(None).unwrap();
+//! let response = client.send(request).unwrap();
+//! ```
+
+use std::{
+    io::{self, Write},
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime},
+};
+
+use http::{header, uri::Scheme, Method, Request, Response, StatusCode, Uri};
+use owo_colors::{OwoColorize, Style};
+
+use crate::{
+    helpers::{should_redirect, MColorize},
+    http_request::{
+        http_request, print_request, RemovedHeaders, RequestError, RequestOptions, SafeFetch,
+    },
+    redirect::{meta_refresh, RedirectPolicy},
+    response_renderer::ResponseRenderer,
+    warnings::{warn, WarningKind},
+    write_out::{EffectiveUrl, Redirects},
+};
+
+/// How many follow-up requests (redirects, retries) one request can lead to, like curl's
+/// `--max-redirs` default
+pub const MAX_FOLLOW_UPS: usize = 50;
+
+/// Sends a request and gets its response, [`http_request`] unless the [`Client`] was given another
+pub type Transport = dyn Fn(Request<Option<&[u8]>>, &RequestOptions) -> Result<Response<Vec<u8>>, RequestError>
+    + Send
+    + Sync;
+
+/// A request that was sent, with when and how long it took
+pub struct Exchange<'r, 'a> {
+    pub request: &'r Request<Option<&'a [u8]>>,
+    pub options: &'r RequestOptions,
+    pub started: SystemTime,
+    pub took: Duration,
+    /// The responses that led to this request (like redirects), and the URLs they came from
+    pub previous: &'r [(StatusCode, Uri)],
+}
+
+/// Hooks around every request a [`Client`] sends. They all do nothing unless implemented
+pub trait Middleware: Send + Sync {
+    /// Change a request before it's sent, like adding headers
+    fn before_send(
+        &self,
+        _request: &mut Request<Option<&[u8]>>,
+        _options: &RequestOptions,
+    ) -> Result<(), RequestError> {
+        Ok(())
+    }
+
+    /// Look at (or change) a response as it arrives
+    fn after_receive(
+        &self,
+        _exchange: &Exchange,
+        _response: &mut Response<Vec<u8>>,
+    ) -> Result<(), RequestError> {
+        Ok(())
+    }
+
+    /// Called when a request fails before there's a response, the error is returned afterwards
+    fn on_error(&self, _exchange: &Exchange, _err: &RequestError) {}
+
+    /// A request to send next instead of returning the response, like a redirect or a retry. The
+    /// last middleware added to ask for one gets it
+    fn follow_up<'a>(
+        &self,
+        _exchange: &Exchange<'_, 'a>,
+        _response: &Response<Vec<u8>>,
+    ) -> Result<Option<Request<Option<&'a [u8]>>>, RequestError> {
+        Ok(None)
+    }
+}
+
+/// Sends requests through its [`Middleware`]
+#[derive(Clone)]
+pub struct Client {
+    pub options: RequestOptions,
+    middleware: Vec<Arc<dyn Middleware>>,
+    transport: Option<Arc<Transport>>,
+}
+
+impl Client {
+    pub fn new(options: RequestOptions) -> Self {
+        Self {
+            options,
+            middleware: vec![],
+            transport: None,
+        }
+    }
+
+    /// Add a middleware, which sees requests after the ones added before it
+    pub fn with(mut self, middleware: impl Middleware + 'static) -> Self {
+        self.middleware.push(Arc::new(middleware));
+        self
+    }
+
+    /// Send requests with this instead of [`http_request`], like to answer them from a cache
+    pub fn transport(
+        mut self,
+        transport: impl Fn(Request<Option<&[u8]>>, &RequestOptions) -> Result<Response<Vec<u8>>, RequestError>
+            + Send
+            + Sync
+            + 'static,
+    ) -> Self {
+        self.transport = Some(Arc::new(transport));
+        self
+    }
+
+    /// Send a request, and any follow-ups the middleware asks for. The response has the
+    /// [`EffectiveUrl`] it came from, and the [`Redirects`] if there were follow-ups
+    pub fn send(
+        &self,
+        mut request: Request<Option<&[u8]>>,
+    ) -> Result<Response<Vec<u8>>, RequestError> {
+        let mut previous = vec![];
+
+        loop {
+            for middleware in &self.middleware {
+                middleware.before_send(&mut request, &self.options)?;
+            }
+
+            let started = SystemTime::now();
+            let sent = Instant::now();
+            let result = match &self.transport {
+                Some(transport) => transport(copy_request(&request), &self.options),
+                None => http_request(copy_request(&request), &self.options),
+            };
+
+            let exchange = Exchange {
+                request: &request,
+                options: &self.options,
+                started,
+                took: sent.elapsed(),
+                previous: &previous,
+            };
+
+            let mut response = match result {
+                Ok(response) => response,
+                Err(err) => {
+                    for middleware in self.middleware.iter().rev() {
+                        middleware.on_error(&exchange, &err);
+                    }
+
+                    return Err(err);
+                }
+            };
+
+            for middleware in self.middleware.iter().rev() {
+                middleware.after_receive(&exchange, &mut response)?;
+            }
+
+            let mut next = None;
+
+            for middleware in self.middleware.iter().rev() {
+                next = middleware.follow_up(&exchange, &response)?;
+
+                if next.is_some() {
+                    break;
+                }
+            }
+
+            let Some(next) = next else {
+                response
+                    .extensions_mut()
+                    .insert(EffectiveUrl(request.uri().clone()));
+
+                if !previous.is_empty() {
+                    response.extensions_mut().insert(Redirects(previous));
+                }
+
+                return Ok(response);
+            };
+
+            if previous.len() >= MAX_FOLLOW_UPS {
+                return Err(format!(
+                    "Gave up after {} redirects (or other follow-up requests)",
+                    MAX_FOLLOW_UPS
+                )
+                .into());
+            }
+
+            previous.push((response.status(), request.uri().clone()));
+            request = next;
+        }
+    }
+}
+
+/// The same request again, for sending it while keeping the original for the middleware
+pub fn copy_request<'a>(request: &Request<Option<&'a [u8]>>) -> Request<Option<&'a [u8]>> {
+    let mut copy = Request::new(*request.body());
+    *copy.method_mut() = request.method().clone();
+    *copy.uri_mut() = request.uri().clone();
+    *copy.version_mut() = request.version();
+    *copy.headers_mut() = request.headers().clone();
+
+    if let Some(removed) = request.extensions().get::<RemovedHeaders>() {
+        copy.extensions_mut().insert(removed.clone());
+    }
+
+    if let Some(safe) = request.extensions().get::<SafeFetch>() {
+        copy.extensions_mut().insert(*safe);
+    }
+
+    copy
+}
+
+/// A buffer that's written to from more than one place, like a `--parallel` job's output that
+/// [`FollowRedirects`] shows the redirects in, so they come out in order with the rest of it
+#[derive(Clone, Debug, Default)]
+pub struct SharedOutput(Arc<Mutex<Vec<u8>>>);
+
+impl SharedOutput {
+    /// Everything written so far, leaving it empty
+    pub fn take(&self) -> Vec<u8> {
+        self.0
+            .lock()
+            .map(|mut output| std::mem::take(&mut *output))
+            .unwrap_or_default()
+    }
+}
+
+impl Write for SharedOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0
+            .lock()
+            .map_err(|_| io::Error::other("Output was poisoned by a panic"))?
+            .write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Print each request the way it's sent (with the defaults like Host added), for `-vv`
+#[derive(Clone, Copy, Debug, Default)]
+pub struct VerboseRequests;
+
+impl Middleware for VerboseRequests {
+    fn before_send(
+        &self,
+        request: &mut Request<Option<&[u8]>>,
+        options: &RequestOptions,
+    ) -> Result<(), RequestError> {
+        print_request(request, options)
+    }
+}
+
+/// Where a redirect goes, and how
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Hop {
+    pub method: Method,
+    pub target: Uri,
+    /// Send the same body again, a meta refresh is a GET without one
+    pub keep_body: bool,
+}
+
+/// Follow redirects (Location headers, and HTML meta refreshes if asked to) within a
+/// [`RedirectPolicy`], for `-l`
+///
+/// The request is sent again to where it redirects with the same method and body. Authorization
+/// and Cookie headers are left out once another origin is involved (unless the policy trusts
+/// redirects), and the Referer can be set to where it came from
+#[derive(Clone)]
+pub struct FollowRedirects {
+    pub policy: RedirectPolicy,
+    /// Follow Location headers, which is the point unless only following meta refreshes
+    pub location: bool,
+    /// Follow `<meta http-equiv="refresh">` in HTML too
+    pub meta_refresh: bool,
+    /// Send the URL each redirect came from as the Referer
+    pub auto_referer: bool,
+    /// Show the responses that redirected with this, followed by where they redirected to
+    pub show: Option<Arc<dyn ResponseRenderer + Send + Sync>>,
+    /// Where `show` writes, stdout if `None`
+    pub out: Option<SharedOutput>,
+}
+
+impl Default for FollowRedirects {
+    fn default() -> Self {
+        Self {
+            policy: RedirectPolicy::default(),
+            location: true,
+            meta_refresh: false,
+            auto_referer: false,
+            show: None,
+            out: None,
+        }
+    }
+}
+
+impl FollowRedirects {
+    /// Where `response` redirects to, if it does and the policy allows it (it's warned about when
+    /// it doesn't)
+    pub fn next_hop(
+        &self,
+        exchange: &Exchange,
+        response: &Response<Vec<u8>>,
+    ) -> Result<Option<Hop>, RequestError> {
+        let uri = exchange.request.uri();
+
+        if self.location && should_redirect(&response.status()) {
+            match response.headers().get(header::LOCATION) {
+                Some(location) => {
+                    match self
+                        .policy
+                        .check(response.status(), uri, location.to_str()?)
+                    {
+                        Ok(target) => {
+                            return Ok(Some(Hop {
+                                method: exchange.request.method().clone(),
+                                target: Uri::from_str(&target)?,
+                                keep_body: true,
+                            }))
+                        }
+                        Err(reason) => warn(
+                            WarningKind::Redirect,
+                            format!("Not following the redirect: {}", reason),
+                        ),
+                    }
+                }
+                // 201 doesn't need one, and 304 isn't really a redirect
+                None if response.status().is_redirection()
+                    && response.status() != StatusCode::NOT_MODIFIED =>
+                {
+                    warn(
+                        WarningKind::Redirect,
+                        format!(
+                            "Can't follow the {} redirect, the response has no Location header",
+                            response.status()
+                        ),
+                    )
+                }
+                None => {}
+            }
+        }
+
+        // Some old apps redirect with HTML instead, which browsers follow with a GET
+        let html = response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.to_ascii_lowercase().contains("html"));
+
+        if self.meta_refresh && response.status().is_success() && html {
+            if let Some(target) = meta_refresh(&String::from_utf8_lossy(response.body())) {
+                match self.policy.check(response.status(), uri, &target) {
+                    // Refreshing the same page would go on forever
+                    Ok(target) if target == uri.to_string() => {}
+                    Ok(target) => {
+                        return Ok(Some(Hop {
+                            method: Method::GET,
+                            target: Uri::from_str(&target)?,
+                            keep_body: false,
+                        }))
+                    }
+                    Err(reason) => warn(
+                        WarningKind::Redirect,
+                        format!("Not following the meta refresh: {}", reason),
+                    ),
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Show the response that redirected with [`FollowRedirects::show`] (if there is one), and warn
+    /// if it went from HTTPS to plain HTTP
+    pub fn announce(
+        &self,
+        exchange: &Exchange,
+        response: &Response<Vec<u8>>,
+        hop: &Hop,
+    ) -> Result<(), RequestError> {
+        if let Some(show) = &self.show {
+            let mut out: Box<dyn Write> = match &self.out {
+                Some(out) => Box::new(out.clone()),
+                None => Box::new(io::stdout()),
+            };
+            show.render(response, &mut out)?;
+            writeln!(
+                out,
+                "\n{} {}\n",
+                "↪ Redirecting to:".out_color(|t| t.blue()),
+                hop.target
+                    .out_color(|t| t.style(Style::new().blue().underline()))
+            )?;
+        }
+
+        if is_downgrade(exchange.request.uri(), &hop.target) {
+            warn(
+                WarningKind::InsecureRedirect,
+                format!(
+                    "Followed a redirect from HTTPS to plain HTTP: {}",
+                    hop.target
+                ),
+            );
+        }
+
+        Ok(())
+    }
+
+    /// If Authorization and Cookie headers shouldn't be sent to `target`, since another origin
+    /// was involved on the way there
+    pub fn strips_credentials(&self, exchange: &Exchange, target: &Uri) -> bool {
+        let chain = exchange
+            .previous
+            .iter()
+            .map(|(_, uri)| uri)
+            .chain([exchange.request.uri(), target])
+            .collect::<Vec<_>>();
+
+        chain
+            .windows(2)
+            .any(|pair| self.policy.strips_credentials(pair[0], pair[1]))
+    }
+}
+
+/// If going from `from` to `to` goes from HTTPS to plain HTTP
+fn is_downgrade(from: &Uri, to: &Uri) -> bool {
+    from.scheme() == Some(&Scheme::HTTPS) && to.scheme() == Some(&Scheme::HTTP)
+}
+
+impl Middleware for FollowRedirects {
+    fn follow_up<'a>(
+        &self,
+        exchange: &Exchange<'_, 'a>,
+        response: &Response<Vec<u8>>,
+    ) -> Result<Option<Request<Option<&'a [u8]>>>, RequestError> {
+        let Some(hop) = self.next_hop(exchange, response)? else {
+            return Ok(None);
+        };
+
+        self.announce(exchange, response, &hop)?;
+        let mut next = copy_request(exchange.request);
+        *next.uri_mut() = hop.target.clone();
+        *next.method_mut() = hop.method;
+
+        // Host is worked out from the URL again
+        next.headers_mut().remove(header::HOST);
+
+        if !hop.keep_body {
+            *next.body_mut() = None;
+            next.headers_mut().remove(header::CONTENT_LENGTH);
+            next.headers_mut().remove(header::CONTENT_TYPE);
+        }
+
+        if self.strips_credentials(exchange, &hop.target) {
+            next.headers_mut().remove(header::AUTHORIZATION);
+            next.headers_mut().remove(header::COOKIE);
+        }
+
+        // Checked again on connecting, the host could resolve somewhere else by then
+        if self.policy.no_private {
+            next.extensions_mut().insert(SafeFetch);
+        }
+
+        if self.auto_referer {
+            next.headers_mut().remove(header::REFERER);
+
+            // Like browsers, an https URL isn't given away to plain http
+            if !is_downgrade(exchange.request.uri(), &hop.target) {
+                next.headers_mut()
+                    .insert(header::REFERER, exchange.request.uri().to_string().parse()?);
+            }
+        }
+
+        Ok(Some(next))
+    }
+}
@@ -0,0 +1,160 @@
+use std::{
+    process,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use http::{header::HeaderName, HeaderMap, HeaderValue};
+
+use crate::http_request::RequestError;
+
+/// A single part of a multipart body
+#[derive(Debug, Default)]
+pub struct Part {
+    pub headers: HeaderMap,
+    pub body: Vec<u8>,
+}
+
+/// Generate a boundary that's (very) unlikely to show up in any of the parts
+///
+/// We don't need real randomness, just something that won't collide with the content
+pub fn generate_boundary() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+
+    format!("httpc_{:x}{:x}", nanos, process::id())
+}
+
+/// Extract the `boundary` parameter from a multipart Content-Type
+/// ```
+/// # use httpc::multipart::boundary_from_content_type;
+/// assert_eq!(boundary_from_content_type("multipart/mixed; boundary=abc"), Some("abc".to_string()));
+/// assert_eq!(boundary_from_content_type("multipart/mixed; boundary=\"a b\""), Some("a b".to_string()));
+/// assert_eq!(boundary_from_content_type("application/json"), None);
+/// ```
+pub fn boundary_from_content_type(content_type: &str) -> Option<String> {
+    if !content_type
+        .trim_start()
+        .to_ascii_lowercase()
+        .starts_with("multipart/")
+    {
+        return None;
+    }
+
+    content_type
+        .split(';')
+        .skip(1)
+        .filter_map(|param| param.split_once('='))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("boundary"))
+        .map(|(_, value)| value.trim().trim_matches('"').to_string())
+}
+
+/// Encode parts into a multipart body using the given boundary
+pub fn encode(boundary: &str, parts: &[Part]) -> Vec<u8> {
+    let mut body = Vec::new();
+
+    for part in parts {
+        body.extend_from_slice(format!("--{}\r\n", boundary).as_bytes());
+
+        for (name, value) in &part.headers {
+            body.extend_from_slice(name.as_str().as_bytes());
+            body.extend_from_slice(b": ");
+            body.extend_from_slice(value.as_bytes());
+            body.extend_from_slice(b"\r\n");
+        }
+
+        body.extend_from_slice(b"\r\n");
+        body.extend_from_slice(&part.body);
+        body.extend_from_slice(b"\r\n");
+    }
+
+    body.extend_from_slice(format!("--{}--\r\n", boundary).as_bytes());
+    body
+}
+
+/// Split a multipart body into its parts
+///
+/// The preamble (before the first boundary) and epilogue (after the last one) are ignored.
+/// Both CRLF and bare LF line endings are accepted, since plenty of servers get this wrong
+pub fn split(body: &[u8], boundary: &str) -> Result<Vec<Part>, RequestError> {
+    let delimiter = format!("--{}", boundary);
+    let delimiter = delimiter.as_bytes();
+
+    let mut parts = vec![];
+    let mut rest = match find(body, delimiter) {
+        Some(start) => &body[start + delimiter.len()..],
+        None => return Err(format!("Multipart boundary '{}' not found in body", boundary).into()),
+    };
+
+    loop {
+        // The delimiter is either followed by "--" (last one) or the end of its line
+        if rest.starts_with(b"--") {
+            break;
+        }
+
+        rest = skip_line(rest);
+
+        let end = match find(rest, delimiter) {
+            Some(end) => end,
+            None => return Err("Multipart body ended without a closing boundary".into()),
+        };
+
+        // The CRLF before the delimiter belongs to the delimiter, not the part
+        let mut content = &rest[..end];
+        content = content.strip_suffix(b"\n").unwrap_or(content);
+        content = content.strip_suffix(b"\r").unwrap_or(content);
+
+        parts.push(parse_part(content)?);
+        rest = &rest[end + delimiter.len()..];
+    }
+
+    Ok(parts)
+}
+
+/// Parse the headers + body of a single part
+fn parse_part(mut content: &[u8]) -> Result<Part, RequestError> {
+    let mut part = Part::default();
+
+    loop {
+        let line_end = content
+            .iter()
+            .position(|b| *b == b'\n')
+            .unwrap_or(content.len());
+        let line = &content[..line_end];
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        content = &content[(line_end + 1).min(content.len())..];
+
+        if line.is_empty() {
+            // Empty line means the headers are done
+            break;
+        }
+
+        let line = std::str::from_utf8(line)?;
+        let (name, value) = line
+            .split_once(':')
+            .ok_or_else(|| format!("Invalid multipart header: '{}'", line))?;
+
+        part.headers.append(
+            name.trim().parse::<HeaderName>()?,
+            value.trim().parse::<HeaderValue>()?,
+        );
+    }
+
+    part.body = content.to_vec();
+    Ok(part)
+}
+
+fn skip_line(bytes: &[u8]) -> &[u8] {
+    match bytes.iter().position(|b| *b == b'\n') {
+        Some(pos) => &bytes[pos + 1..],
+        None => &[],
+    }
+}
+
+/// Find the first position of `needle` in `haystack`
+pub fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
@@ -0,0 +1,204 @@
+//! Turning URLs the way people write them (spaces, unicode paths, internationalized domain names)
+//! into ones [`http::Uri`] will accept: illegal characters in the path & query are
+//! percent-encoded and unicode host names are converted to punycode
+//!
+//! This isn't full IDNA (there's no UTS #46 mapping table), labels are only lowercased and
+//! encoded, which covers the domains people actually type
+
+use crate::helpers::percent_encode;
+
+/// Normalize a URL with a scheme, leaving what's already valid (like existing `%XX` escapes) alone
+/// ```
+/// # use httpc::normalize::normalize_url;
+/// assert_eq!(
+///     normalize_url("https://Bücher.example/a b/ü?q=x y#top"),
+///     "https://xn--bcher-kva.example/a%20b/%C3%BC?q=x%20y#top"
+/// );
+/// assert_eq!(normalize_url("http://example.com/a%20b?c=d"), "http://example.com/a%20b?c=d");
+/// assert_eq!(normalize_url("http://u:p@[::1]:8080/é"), "http://u:p@[::1]:8080/%C3%A9");
+/// ```
+pub fn normalize_url(url: &str) -> String {
+    let Some((scheme, rest)) = url.split_once("://") else {
+        return encode_illegal(url);
+    };
+
+    let authority_end = rest.find(['/', '?', '#']).unwrap_or(rest.len());
+    let (authority, path) = rest.split_at(authority_end);
+
+    // user:password@host:port, only the host can be internationalized
+    let (userinfo, host_port) = match authority.rsplit_once('@') {
+        Some((userinfo, host_port)) => (Some(userinfo), host_port),
+        None => (None, authority),
+    };
+    // The colons in an IPv6 address ([::1]) aren't a port
+    let (host, port) = match host_port.rfind(':') {
+        Some(colon) if !host_port[colon..].contains(']') => {
+            (&host_port[..colon], Some(&host_port[colon + 1..]))
+        }
+        _ => (host_port, None),
+    };
+
+    let mut normalized = format!("{}://", scheme);
+
+    if let Some(userinfo) = userinfo {
+        normalized.push_str(&encode_illegal(userinfo));
+        normalized.push('@');
+    }
+
+    normalized.push_str(&domain_to_ascii(host));
+
+    if let Some(port) = port {
+        normalized.push(':');
+        normalized.push_str(port);
+    }
+
+    normalized.push_str(&encode_illegal(path));
+    normalized
+}
+
+/// Convert a unicode domain to its ASCII (`xn--`) form, lowercasing non-ASCII labels
+/// ```
+/// # use httpc::normalize::domain_to_ascii;
+/// assert_eq!(domain_to_ascii("München.DE"), "xn--mnchen-3ya.DE");
+/// assert_eq!(domain_to_ascii("例え。テスト"), "xn--r8jz45g.xn--zckzah");
+/// assert_eq!(domain_to_ascii("example.com"), "example.com");
+/// ```
+pub fn domain_to_ascii(domain: &str) -> String {
+    if domain.is_ascii() {
+        return domain.to_string();
+    }
+
+    // Ideographic full stops separate labels too
+    domain
+        .split(['.', '。', '．', '｡'])
+        .map(|label| {
+            if label.is_ascii() {
+                return label.to_string();
+            }
+
+            match punycode_encode(&label.to_lowercase()) {
+                Some(encoded) => format!("xn--{}", encoded),
+                None => label.to_string(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// Encode a label with [punycode](https://www.rfc-editor.org/rfc/rfc3492), `None` if it's
+/// too long to encode
+/// ```
+/// # use httpc::normalize::punycode_encode;
+/// assert_eq!(punycode_encode("bücher").as_deref(), Some("bcher-kva"));
+/// assert_eq!(punycode_encode("ü").as_deref(), Some("tda"));
+/// ```
+pub fn punycode_encode(label: &str) -> Option<String> {
+    const BASE: u32 = 36;
+    const T_MIN: u32 = 1;
+    const T_MAX: u32 = 26;
+    const SKEW: u32 = 38;
+    const DAMP: u32 = 700;
+    const INITIAL_BIAS: u32 = 72;
+    const INITIAL_N: u32 = 128;
+
+    fn adapt(mut delta: u32, points: u32, first: bool) -> u32 {
+        delta /= if first { DAMP } else { 2 };
+        delta += delta / points;
+
+        let mut k = 0;
+        while delta > ((BASE - T_MIN) * T_MAX) / 2 {
+            delta /= BASE - T_MIN;
+            k += BASE;
+        }
+
+        k + (BASE - T_MIN + 1) * delta / (delta + SKEW)
+    }
+
+    fn digit(value: u32) -> char {
+        match value {
+            0..=25 => (b'a' + value as u8) as char,
+            _ => (b'0' + (value - 26) as u8) as char,
+        }
+    }
+
+    let code_points: Vec<u32> = label.chars().map(u32::from).collect();
+    let mut output: String = label.chars().filter(char::is_ascii).collect();
+    let basic = output.len() as u32;
+    let mut handled = basic;
+
+    if basic > 0 {
+        output.push('-');
+    }
+
+    let mut n = INITIAL_N;
+    let mut delta: u32 = 0;
+    let mut bias = INITIAL_BIAS;
+
+    while (handled as usize) < code_points.len() {
+        // The smallest code point that hasn't been handled yet
+        let m = code_points.iter().copied().filter(|&c| c >= n).min()?;
+        delta = delta.checked_add((m - n).checked_mul(handled + 1)?)?;
+        n = m;
+
+        for &c in &code_points {
+            if c < n {
+                delta = delta.checked_add(1)?;
+            }
+
+            if c == n {
+                let mut q = delta;
+                let mut k = BASE;
+
+                loop {
+                    let t = if k <= bias {
+                        T_MIN
+                    } else if k >= bias + T_MAX {
+                        T_MAX
+                    } else {
+                        k - bias
+                    };
+
+                    if q < t {
+                        break;
+                    }
+
+                    output.push(digit(t + (q - t) % (BASE - t)));
+                    q = (q - t) / (BASE - t);
+                    k += BASE;
+                }
+
+                output.push(digit(q));
+                bias = adapt(delta, handled + 1, handled == basic);
+                delta = 0;
+                handled += 1;
+            }
+        }
+
+        delta = delta.checked_add(1)?;
+        n += 1;
+    }
+
+    Some(output)
+}
+
+/// Percent-encode whatever isn't allowed in a URL (non-ASCII, spaces, controls and a few
+/// symbols), leaving everything else as it is
+fn encode_illegal(text: &str) -> String {
+    let mut encoded = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        let illegal = matches!(
+            c,
+            ' ' | '"' | '<' | '>' | '\\' | '^' | '`' | '{' | '|' | '}'
+        ) || c.is_ascii_control()
+            || !c.is_ascii();
+
+        if illegal {
+            encoded.push_str(&percent_encode(c.encode_utf8(&mut [0; 4]), false));
+        } else {
+            encoded.push(c);
+        }
+    }
+
+    encoded
+}
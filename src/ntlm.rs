@@ -0,0 +1,466 @@
+//! NTLM authentication (MS-NLMP) for `--ntlm` and `--negotiate`, which corporate proxies and IIS
+//! servers like
+//!
+//! NTLM authenticates a connection instead of a request: once the server answers with a 401
+//! offering it, we send a negotiate message, the server answers (with another 401) with a
+//! challenge, and the request is sent again with our NTLMv2 response to it, all on the same
+//! connection. `--negotiate` sends the same messages in a `Negotiate` (SPNEGO) header, which
+//! Windows servers accept in place of Kerberos
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use hmac::{Hmac, Mac};
+use http::HeaderMap;
+use md5::Md5;
+
+use crate::http_request::RequestError;
+
+const SIGNATURE: &[u8; 8] = b"NTLMSSP\0";
+
+const NEGOTIATE_UNICODE: u32 = 0x0000_0001;
+const NEGOTIATE_OEM: u32 = 0x0000_0002;
+const REQUEST_TARGET: u32 = 0x0000_0004;
+const NEGOTIATE_NTLM: u32 = 0x0000_0200;
+const NEGOTIATE_ALWAYS_SIGN: u32 = 0x0000_8000;
+const NEGOTIATE_EXTENDED_SESSIONSECURITY: u32 = 0x0008_0000;
+const NEGOTIATE_TARGET_INFO: u32 = 0x0080_0000;
+
+/// The flags we ask for, and accept back in the authenticate message
+const FLAGS: u32 = NEGOTIATE_UNICODE
+    | REQUEST_TARGET
+    | NEGOTIATE_NTLM
+    | NEGOTIATE_ALWAYS_SIGN
+    | NEGOTIATE_EXTENDED_SESSIONSECURITY
+    | NEGOTIATE_TARGET_INFO;
+
+/// The AV pair (MS-NLMP 2.2.2.1) with the server's time, if its target info has one
+const MSV_AV_TIMESTAMP: u16 = 7;
+
+/// Seconds between 1601 (when Windows' FILETIMEs start) and 1970
+const FILETIME_UNIX_EPOCH: u64 = 11_644_473_600;
+
+/// Which header the NTLM messages go in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConnectionAuth {
+    Ntlm,
+    Negotiate,
+}
+
+impl ConnectionAuth {
+    /// The scheme in `WWW-Authenticate` & `Authorization`
+    pub fn scheme(&self) -> &'static str {
+        match self {
+            ConnectionAuth::Ntlm => "NTLM",
+            ConnectionAuth::Negotiate => "Negotiate",
+        }
+    }
+
+    /// The `Authorization` (or `Proxy-Authorization`) value sending an NTLM message
+    /// ```
+    /// # use httpc::ntlm::ConnectionAuth;
+    /// assert_eq!(ConnectionAuth::Negotiate.header_value(b"hi"), "Negotiate aGk=");
+    /// ```
+    pub fn header_value(&self, message: &[u8]) -> String {
+        format!("{} {}", self.scheme(), BASE64.encode(message))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NtlmCredentials {
+    pub domain: String,
+    pub user: String,
+    pub password: String,
+}
+
+impl NtlmCredentials {
+    /// Credentials from `-u DOMAIN\user:password` (or `user@domain:password`, which is sent as the
+    /// user name as-is)
+    /// ```
+    /// # use httpc::ntlm::NtlmCredentials;
+    /// let credentials = NtlmCredentials::parse(r"CORP\me:pass:word");
+    /// assert_eq!(
+    ///     (credentials.domain.as_str(), credentials.user.as_str(), credentials.password.as_str()),
+    ///     ("CORP", "me", "pass:word")
+    /// );
+    /// assert_eq!(NtlmCredentials::parse("me@corp.example.com:pw").domain, "");
+    /// ```
+    pub fn parse(user: &str) -> Self {
+        let (user, password) = user.split_once(':').unwrap_or((user, ""));
+        let (domain, user) = user.split_once(['\\', '/']).unwrap_or(("", user));
+
+        Self {
+            domain: domain.to_string(),
+            user: user.to_string(),
+            password: password.to_string(),
+        }
+    }
+}
+
+/// What the server sent in its challenge message
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Challenge {
+    pub flags: u32,
+    pub server_challenge: [u8; 8],
+    pub target_info: Vec<u8>,
+}
+
+/// The negotiate (type 1) message that starts the handshake
+/// ```
+/// # use httpc::ntlm::negotiate_message;
+/// let message = negotiate_message();
+/// assert_eq!((&message[..8], message[8], message.len()), (&b"NTLMSSP\0"[..], 1, 32));
+/// ```
+pub fn negotiate_message() -> Vec<u8> {
+    let mut message = SIGNATURE.to_vec();
+    message.extend(1u32.to_le_bytes());
+    message.extend((FLAGS | NEGOTIATE_OEM).to_le_bytes());
+    // No domain or workstation
+    message.extend([0; 16]);
+    message
+}
+
+/// The NTLM token for `auth`'s scheme in a 401's `WWW-Authenticate` (or a 407's
+/// `Proxy-Authenticate`) headers: `Some` empty one if the scheme is offered without a token,
+/// `None` if it isn't offered at all
+/// ```
+/// # use httpc::ntlm::{auth_token, ConnectionAuth};
+/// let mut headers = http::HeaderMap::new();
+/// headers.append("www-authenticate", "Negotiate, NTLM".parse().unwrap());
+/// assert_eq!(auth_token(&headers, "www-authenticate", ConnectionAuth::Ntlm), Some(vec![]));
+///
+/// headers.append("www-authenticate", "NTLM aGk=".parse().unwrap());
+/// assert_eq!(auth_token(&headers, "www-authenticate", ConnectionAuth::Ntlm), Some(b"hi".to_vec()));
+/// assert_eq!(auth_token(&headers, "proxy-authenticate", ConnectionAuth::Ntlm), None);
+/// ```
+pub fn auth_token(headers: &HeaderMap, header: &str, auth: ConnectionAuth) -> Option<Vec<u8>> {
+    let mut offered = None;
+
+    let challenges = headers
+        .get_all(header)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim);
+
+    for challenge in challenges {
+        let (scheme, token) = challenge.split_once(' ').unwrap_or((challenge, ""));
+
+        if !scheme.eq_ignore_ascii_case(auth.scheme()) {
+            continue;
+        }
+
+        match BASE64.decode(token.trim()) {
+            Ok(token) if !token.is_empty() => return Some(token),
+            _ => offered = Some(vec![]),
+        }
+    }
+
+    offered
+}
+
+/// Parse the server's challenge (type 2) message. It can also be wrapped in SPNEGO, like
+/// `Negotiate` responses can be
+pub fn parse_challenge(token: &[u8]) -> Result<Challenge, RequestError> {
+    let start = token
+        .windows(SIGNATURE.len())
+        .position(|window| window == SIGNATURE)
+        .ok_or("The NTLM challenge isn't an NTLM message")?;
+    let message = &token[start..];
+
+    if message.len() < 32 || u32_at(message, 8) != 2 {
+        return Err("The NTLM challenge isn't a challenge message".into());
+    }
+
+    let flags = u32_at(message, 20);
+
+    if flags & NEGOTIATE_UNICODE == 0 {
+        return Err("The server only supports OEM NTLM, which isn't supported".into());
+    }
+
+    let mut server_challenge = [0; 8];
+    server_challenge.copy_from_slice(&message[24..32]);
+
+    let target_info = if message.len() >= 48 && flags & NEGOTIATE_TARGET_INFO != 0 {
+        let length = u16::from_le_bytes([message[40], message[41]]) as usize;
+        let offset = u32_at(message, 44) as usize;
+        message
+            .get(offset..offset + length)
+            .ok_or("The NTLM challenge's target info is cut short")?
+            .to_vec()
+    } else {
+        vec![]
+    };
+
+    Ok(Challenge {
+        flags,
+        server_challenge,
+        target_info,
+    })
+}
+
+/// The authenticate (type 3) message answering the server's challenge with NTLMv2
+pub fn authenticate_message(
+    credentials: &NtlmCredentials,
+    challenge: &Challenge,
+    client_challenge: [u8; 8],
+    now: SystemTime,
+) -> Vec<u8> {
+    // Better to go by the server's clock when it says what time it is
+    let timestamp = av_pair(&challenge.target_info, MSV_AV_TIMESTAMP)
+        .and_then(|time| time.try_into().ok())
+        .map(u64::from_le_bytes);
+    let filetime = timestamp.unwrap_or_else(|| {
+        let since_1970 = now.duration_since(UNIX_EPOCH).unwrap_or_default();
+        (since_1970.as_secs() + FILETIME_UNIX_EPOCH) * 10_000_000
+            + u64::from(since_1970.subsec_nanos() / 100)
+    });
+
+    let (mut lm_response, nt_response) = ntlmv2_responses(
+        &ntowf_v2(credentials),
+        &challenge.server_challenge,
+        &client_challenge,
+        filetime,
+        &challenge.target_info,
+    );
+
+    // With the server's time, the LMv2 response is meant to be left empty (MS-NLMP 3.1.5.1.2)
+    if timestamp.is_some() {
+        lm_response = vec![0; 24];
+    }
+
+    let fields = [
+        lm_response,
+        nt_response,
+        utf16(&credentials.domain),
+        utf16(&credentials.user),
+        // Workstation and session key
+        vec![],
+        vec![],
+    ];
+
+    let mut message = SIGNATURE.to_vec();
+    message.extend(3u32.to_le_bytes());
+
+    let mut offset = 64;
+    for field in &fields {
+        message.extend((field.len() as u16).to_le_bytes());
+        message.extend((field.len() as u16).to_le_bytes());
+        message.extend((offset as u32).to_le_bytes());
+        offset += field.len();
+    }
+
+    message.extend((challenge.flags & FLAGS).to_le_bytes());
+
+    for field in fields {
+        message.extend(field);
+    }
+
+    message
+}
+
+/// The NTLMv2 hash of a user's password (NTOWFv2)
+/// ```
+/// # use httpc::ntlm::{ntowf_v2, NtlmCredentials};
+/// # use httpc::helpers::to_hex;
+/// // MS-NLMP 4.2.4.1.1
+/// let credentials = NtlmCredentials::parse(r"Domain\User:Password");
+/// assert_eq!(to_hex(&ntowf_v2(&credentials)), "0c868a403bfd7a93a3001ef22ef02e3f");
+/// ```
+pub fn ntowf_v2(credentials: &NtlmCredentials) -> [u8; 16] {
+    let nt_hash = md4(&utf16(&credentials.password));
+    let identity = utf16(&format!(
+        "{}{}",
+        credentials.user.to_uppercase(),
+        credentials.domain
+    ));
+
+    hmac_md5(&nt_hash, &[&identity])
+}
+
+/// The LMv2 and NTLMv2 responses to a server challenge
+/// ```
+/// # use httpc::ntlm::ntlmv2_responses;
+/// # use httpc::helpers::to_hex;
+/// // MS-NLMP 4.2.4, with the server's name & domain as the target info
+/// let ntowf = [
+///     0x0c, 0x86, 0x8a, 0x40, 0x3b, 0xfd, 0x7a, 0x93, 0xa3, 0x00, 0x1e, 0xf2, 0x2e, 0xf0, 0x2e, 0x3f,
+/// ];
+/// let target_info = [
+///     &[0x02, 0x00, 0x0c, 0x00][..], "Domain".encode_utf16().flat_map(u16::to_le_bytes).collect::<Vec<_>>().as_slice(),
+///     &[0x01, 0x00, 0x0c, 0x00][..], "Server".encode_utf16().flat_map(u16::to_le_bytes).collect::<Vec<_>>().as_slice(),
+///     &[0x00, 0x00, 0x00, 0x00][..],
+/// ]
+/// .concat();
+/// let server_challenge = [0x01, 0x23, 0x45, 0x67, 0x89, 0xab, 0xcd, 0xef];
+/// let (lm, nt) = ntlmv2_responses(&ntowf, &server_challenge, &[0xaa; 8], 0, &target_info);
+/// assert_eq!(to_hex(&lm), "86c35097ac9cec102554764a57cccc19aaaaaaaaaaaaaaaa");
+/// assert_eq!(to_hex(&nt[..16]), "68cd0ab851e51c96aabc927bebef6a1c");
+/// ```
+pub fn ntlmv2_responses(
+    ntowf: &[u8; 16],
+    server_challenge: &[u8; 8],
+    client_challenge: &[u8; 8],
+    filetime: u64,
+    target_info: &[u8],
+) -> (Vec<u8>, Vec<u8>) {
+    let mut blob = vec![1, 1, 0, 0, 0, 0, 0, 0];
+    blob.extend(filetime.to_le_bytes());
+    blob.extend(client_challenge);
+    blob.extend([0; 4]);
+    blob.extend(target_info);
+    blob.extend([0; 4]);
+
+    let nt_proof = hmac_md5(ntowf, &[server_challenge, &blob]);
+    let lm_proof = hmac_md5(ntowf, &[server_challenge, client_challenge]);
+
+    (
+        [&lm_proof[..], client_challenge].concat(),
+        [&nt_proof[..], &blob].concat(),
+    )
+}
+
+/// The value of an AV pair in a challenge's target info
+fn av_pair(target_info: &[u8], id: u16) -> Option<&[u8]> {
+    let mut rest = target_info;
+
+    while rest.len() >= 4 {
+        let av_id = u16::from_le_bytes([rest[0], rest[1]]);
+        let length = u16::from_le_bytes([rest[2], rest[3]]) as usize;
+        let value = rest.get(4..4 + length)?;
+
+        if av_id == id {
+            return Some(value);
+        }
+
+        // MsvAvEOL
+        if av_id == 0 {
+            return None;
+        }
+
+        rest = &rest[4 + length..];
+    }
+
+    None
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        bytes[offset],
+        bytes[offset + 1],
+        bytes[offset + 2],
+        bytes[offset + 3],
+    ])
+}
+
+fn utf16(text: &str) -> Vec<u8> {
+    text.encode_utf16().flat_map(u16::to_le_bytes).collect()
+}
+
+fn hmac_md5(key: &[u8], parts: &[&[u8]]) -> [u8; 16] {
+    let mut mac = Hmac::<Md5>::new_from_slice(key).expect("HMAC takes keys of any size");
+
+    for part in parts {
+        mac.update(part);
+    }
+
+    mac.finalize().into_bytes().into()
+}
+
+/// MD4 (RFC 1320), which is only still around for NTLM's password hash
+fn md4(message: &[u8]) -> [u8; 16] {
+    let mut state: [u32; 4] = [0x6745_2301, 0xefcd_ab89, 0x98ba_dcfe, 0x1032_5476];
+
+    let mut data = message.to_vec();
+    data.push(0x80);
+    while data.len() % 64 != 56 {
+        data.push(0);
+    }
+    data.extend((message.len() as u64).wrapping_mul(8).to_le_bytes());
+
+    let f = |x: u32, y: u32, z: u32| (x & y) | (!x & z);
+    let g = |x: u32, y: u32, z: u32| (x & y) | (x & z) | (y & z);
+    let h = |x: u32, y: u32, z: u32| x ^ y ^ z;
+
+    for block in data.chunks(64) {
+        let x = (0..16).map(|i| u32_at(block, i * 4)).collect::<Vec<_>>();
+        let [mut a, mut b, mut c, mut d] = state;
+
+        for i in [0, 4, 8, 12] {
+            a = a.wrapping_add(f(b, c, d)).wrapping_add(x[i]).rotate_left(3);
+            d = d
+                .wrapping_add(f(a, b, c))
+                .wrapping_add(x[i + 1])
+                .rotate_left(7);
+            c = c
+                .wrapping_add(f(d, a, b))
+                .wrapping_add(x[i + 2])
+                .rotate_left(11);
+            b = b
+                .wrapping_add(f(c, d, a))
+                .wrapping_add(x[i + 3])
+                .rotate_left(19);
+        }
+
+        for i in 0..4 {
+            let k = 0x5a82_7999u32;
+            a = a
+                .wrapping_add(g(b, c, d))
+                .wrapping_add(x[i])
+                .wrapping_add(k)
+                .rotate_left(3);
+            d = d
+                .wrapping_add(g(a, b, c))
+                .wrapping_add(x[i + 4])
+                .wrapping_add(k)
+                .rotate_left(5);
+            c = c
+                .wrapping_add(g(d, a, b))
+                .wrapping_add(x[i + 8])
+                .wrapping_add(k)
+                .rotate_left(9);
+            b = b
+                .wrapping_add(g(c, d, a))
+                .wrapping_add(x[i + 12])
+                .wrapping_add(k)
+                .rotate_left(13);
+        }
+
+        for i in [0, 2, 1, 3] {
+            let k = 0x6ed9_eba1u32;
+            a = a
+                .wrapping_add(h(b, c, d))
+                .wrapping_add(x[i])
+                .wrapping_add(k)
+                .rotate_left(3);
+            d = d
+                .wrapping_add(h(a, b, c))
+                .wrapping_add(x[i + 8])
+                .wrapping_add(k)
+                .rotate_left(9);
+            c = c
+                .wrapping_add(h(d, a, b))
+                .wrapping_add(x[i + 4])
+                .wrapping_add(k)
+                .rotate_left(11);
+            b = b
+                .wrapping_add(h(c, d, a))
+                .wrapping_add(x[i + 12])
+                .wrapping_add(k)
+                .rotate_left(15);
+        }
+
+        state = [
+            state[0].wrapping_add(a),
+            state[1].wrapping_add(b),
+            state[2].wrapping_add(c),
+            state[3].wrapping_add(d),
+        ];
+    }
+
+    let mut digest = [0; 16];
+    for (chunk, word) in digest.chunks_mut(4).zip(state) {
+        chunk.copy_from_slice(&word.to_le_bytes());
+    }
+    digest
+}
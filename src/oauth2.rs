@@ -0,0 +1,167 @@
+//! `--oauth2-token-url`: get an access token with OAuth2's client credentials grant
+//! (RFC 6749 4.4) and send it as a Bearer token
+//!
+//! Tokens are cached in `~/.config/httpc/tokens/`, one file per token URL & client, and used
+//! until they expire (from the token response's `expires_in`). Tokens without an `expires_in`
+//! aren't cached, since there's no telling when they stop working
+
+use std::{
+    fs, io,
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    config::config_dir,
+    helpers::{basic_auth, percent_encode, to_hex},
+    http_request::RequestError,
+};
+
+/// The body of a client credentials token request
+pub const TOKEN_REQUEST_BODY: &str = "grant_type=client_credentials";
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Token {
+    pub access_token: String,
+    /// When the token expires, in seconds since the Unix epoch
+    pub expires: Option<u64>,
+}
+
+impl Token {
+    /// If the token has expired, or will in the next `leeway`
+    pub fn expired(&self, leeway: Duration) -> bool {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+
+        self.expires
+            .is_some_and(|expires| now + leeway >= Duration::from_secs(expires))
+    }
+}
+
+/// The `Authorization` value to authenticate the client with, its id and secret are form-encoded
+/// first like RFC 6749 2.3.1 says
+/// ```
+/// # use httpc::oauth2::client_auth;
+/// assert_eq!(client_auth("my app", "s3cr:t"), "Basic bXklMjBhcHA6czNjciUzQXQ=");
+/// ```
+pub fn client_auth(client_id: &str, client_secret: &str) -> String {
+    basic_auth(&format!(
+        "{}:{}",
+        percent_encode(client_id, false),
+        percent_encode(client_secret, false)
+    ))
+}
+
+/// The token from a token response's JSON body, `now` being when it was received
+/// ```
+/// # use std::time::{Duration, UNIX_EPOCH};
+/// # use httpc::oauth2::parse_token_response;
+/// let body = br#"{"access_token": "abc", "token_type": "Bearer", "expires_in": 3600}"#;
+/// let token = parse_token_response(body, UNIX_EPOCH + Duration::from_secs(1000)).unwrap();
+/// assert_eq!((token.access_token.as_str(), token.expires), ("abc", Some(4600)));
+///
+/// let body = br#"{"access_token": "abc", "expires_in": 18446744073709551615}"#;
+/// let token = parse_token_response(body, UNIX_EPOCH + Duration::from_secs(1000)).unwrap();
+/// assert_eq!(token.expires, Some(u64::MAX));
+///
+/// let error = parse_token_response(br#"{"error": "invalid_client"}"#, UNIX_EPOCH);
+/// assert_eq!(error.unwrap_err().to_string(), "invalid_client");
+/// ```
+pub fn parse_token_response(body: &[u8], now: SystemTime) -> Result<Token, RequestError> {
+    let json: Value = serde_json::from_slice(body)
+        .map_err(|err| format!("The token response isn't JSON: {}", err))?;
+
+    if let Some(error) = token_error(&json) {
+        return Err(error.into());
+    }
+
+    let access_token = json["access_token"]
+        .as_str()
+        .ok_or("The token response has no access_token")?
+        .to_string();
+
+    // Some servers send it as a string
+    let expires_in = match &json["expires_in"] {
+        Value::Number(number) => number.as_u64(),
+        Value::String(text) => text.parse().ok(),
+        _ => None,
+    };
+    let now = now.duration_since(UNIX_EPOCH)?.as_secs();
+
+    Ok(Token {
+        access_token,
+        expires: expires_in.map(|expires_in| now.saturating_add(expires_in)),
+    })
+}
+
+/// The `error` (and `error_description`) of an error response, if it is one
+fn token_error(json: &Value) -> Option<String> {
+    let error = json["error"].as_str()?;
+
+    match json["error_description"].as_str() {
+        Some(description) => Some(format!("{}: {}", error, description)),
+        None => Some(error.to_string()),
+    }
+}
+
+/// Why a token request failed, from its body if it says
+pub fn token_failure(body: &[u8]) -> Option<String> {
+    token_error(&serde_json::from_slice(body).ok()?)
+}
+
+/// The file a token is cached in. The secret is part of the name so changing it gets a new token
+fn token_path(token_url: &str, client_id: &str, client_secret: &str) -> Option<PathBuf> {
+    let key = Sha256::digest(format!("{}\n{}\n{}", token_url, client_id, client_secret));
+    Some(
+        config_dir()?
+            .join("tokens")
+            .join(format!("{}.json", to_hex(&key))),
+    )
+}
+
+/// A cached token for this client, if there's one that hasn't expired (or won't in `leeway`)
+pub fn cached_token(
+    token_url: &str,
+    client_id: &str,
+    client_secret: &str,
+    leeway: Duration,
+) -> Option<Token> {
+    let path = token_path(token_url, client_id, client_secret)?;
+    let token: Token = serde_json::from_slice(&fs::read(path).ok()?).ok()?;
+
+    (!token.expired(leeway)).then_some(token)
+}
+
+/// Cache a token for next time, if it says when it expires
+pub fn cache_token(
+    token_url: &str,
+    client_id: &str,
+    client_secret: &str,
+    token: &Token,
+) -> Result<(), RequestError> {
+    if token.expires.is_none() {
+        return Ok(());
+    }
+
+    let path = token_path(token_url, client_id, client_secret)
+        .ok_or("Can't find a config directory to cache tokens in")?;
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    // It's a credential, so only for us to read
+    let mut file = fs::OpenOptions::new();
+    file.write(true).create(true).truncate(true);
+
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut file, 0o600);
+
+    io::Write::write_all(&mut file.open(&path)?, &serde_json::to_vec(token)?)
+        .map_err(|err| format!("Can't cache the token in {}: {}", path.display(), err).into())
+}
@@ -0,0 +1,170 @@
+//! `--rps` and `--delay`: space requests out so a batch doesn't trip a server's rate limits, and
+//! back off when it says to slow down anyway (a 429, or a 503 with Retry-After) before trying again
+//!
+//! The pace is shared by every request (and thread), so `--parallel` doesn't go any faster, it
+//! only lets slow responses overlap
+
+use std::{
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+use http::{header, Response, StatusCode};
+
+/// How many times a request is tried again after being told to slow down
+pub const MAX_RETRIES: u32 = 5;
+
+/// The longest a Retry-After is waited for, so a server can't hang a batch
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(5 * 60);
+
+/// The longest wait between tries without a Retry-After
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// When the next request can be sent, shared by every request (and thread)
+#[derive(Clone, Debug)]
+pub struct Pacer {
+    /// Time between requests
+    interval: Duration,
+    next: Arc<Mutex<Option<Instant>>>,
+}
+
+impl Pacer {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            next: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// The pacer for `--rps` and `--delay`, going by whichever is slower, if either was given
+    /// ```
+    /// # use httpc::pace::Pacer;
+    /// # use std::time::Duration;
+    /// assert!(Pacer::from_options(None, None).is_none());
+    /// assert_eq!(
+    ///     Pacer::from_options(Some(4.0), Some(Duration::from_millis(100))).unwrap().interval(),
+    ///     Duration::from_millis(250)
+    /// );
+    /// ```
+    pub fn from_options(rps: Option<f64>, delay: Option<Duration>) -> Option<Self> {
+        let per_request = rps.map(|rps| Duration::from_secs_f64(1.0 / rps));
+
+        match (per_request, delay) {
+            (None, None) => None,
+            (Some(interval), None) | (None, Some(interval)) => Some(Self::new(interval)),
+            (Some(a), Some(b)) => Some(Self::new(a.max(b))),
+        }
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Wait until it's this request's turn, then take it. The turn's checked again after
+    /// sleeping, since another request might've been told to slow down in the meantime
+    pub fn wait(&self) {
+        loop {
+            // The pace is best-effort, a poisoned lock just means not waiting
+            let Ok(mut next) = self.next.lock() else {
+                return;
+            };
+
+            let now = Instant::now();
+
+            match *next {
+                Some(at) if at > now => {
+                    drop(next);
+                    thread::sleep(at - now);
+                }
+                _ => {
+                    *next = Some(now + self.interval);
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Hold every request back for `wait`, after the server said to slow down
+    pub fn pause(&self, wait: Duration) {
+        if let Ok(mut next) = self.next.lock() {
+            let until = Instant::now() + wait;
+            *next = Some(next.map_or(until, |next| next.max(until)));
+        }
+    }
+}
+
+/// Parse `--rps`, which has to be more than 0
+/// ```
+/// # use httpc::pace::parse_rps;
+/// assert_eq!(parse_rps("2.5"), Ok(2.5));
+/// assert!(parse_rps("0").is_err());
+/// assert!(parse_rps("fast").is_err());
+/// ```
+pub fn parse_rps(rps: &str) -> Result<f64, String> {
+    match rps.parse::<f64>() {
+        Ok(rps) if rps > 0.0 && rps.is_finite() => Ok(rps),
+        _ => Err(format!(
+            "Expected a number of requests per second above 0, like 10 or 0.5, got '{}'",
+            rps
+        )),
+    }
+}
+
+/// How long to wait before trying `response`'s request again, or `None` if it shouldn't be: it
+/// wasn't told to slow down, or it's already been tried `attempt` ≥ [`MAX_RETRIES`] times.
+/// Retry-After is used when there is one (capped at 5 minutes), otherwise it's 1s, 2s, 4s…
+/// ```
+/// # use httpc::pace::retry_wait;
+/// # use std::time::{Duration, SystemTime};
+/// let response = |status, retry_after: Option<&str>| {
+///     let mut response = http::Response::builder().status(status);
+///     if let Some(retry_after) = retry_after {
+///         response = response.header("Retry-After", retry_after);
+///     }
+///     response.body(vec![]).unwrap()
+/// };
+/// let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+///
+/// assert_eq!(retry_wait(&response(429, Some("3")), 0, now), Some(Duration::from_secs(3)));
+/// assert_eq!(retry_wait(&response(429, None), 2, now), Some(Duration::from_secs(4)));
+/// assert_eq!(
+///     retry_wait(&response(503, Some("Sun, 09 Sep 2001 01:46:50 GMT")), 0, now),
+///     Some(Duration::from_secs(10))
+/// );
+/// assert_eq!(retry_wait(&response(503, None), 0, now), None);
+/// assert_eq!(retry_wait(&response(429, Some("3")), 5, now), None);
+/// assert_eq!(retry_wait(&response(200, None), 0, now), None);
+/// ```
+pub fn retry_wait(response: &Response<Vec<u8>>, attempt: u32, now: SystemTime) -> Option<Duration> {
+    let retry_after = response
+        .headers()
+        .get(header::RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| parse_retry_after(value.trim(), now));
+
+    let slow_down = match response.status() {
+        StatusCode::TOO_MANY_REQUESTS => true,
+        StatusCode::SERVICE_UNAVAILABLE => retry_after.is_some(),
+        _ => false,
+    };
+
+    if !slow_down || attempt >= MAX_RETRIES {
+        return None;
+    }
+
+    Some(match retry_after {
+        Some(retry_after) => retry_after.min(MAX_RETRY_AFTER),
+        None => Duration::from_secs(1 << attempt).min(MAX_BACKOFF),
+    })
+}
+
+/// A Retry-After of seconds or an HTTP date, as how long from `now`
+fn parse_retry_after(value: &str, now: SystemTime) -> Option<Duration> {
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let date = httpdate::parse_http_date(value).ok()?;
+    Some(date.duration_since(now).unwrap_or_default())
+}
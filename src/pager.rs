@@ -0,0 +1,51 @@
+use std::{
+    env,
+    io::{self, Write},
+    process::{Command, Stdio},
+};
+
+/// Terminal height to assume when `$LINES` isn't set
+const DEFAULT_HEIGHT: usize = 24;
+
+/// Print `output`, through `$PAGER` (or `less`) if it's taller than the terminal
+///
+/// If the pager can't be started, the output is printed as-is instead
+pub fn page(output: &[u8]) -> io::Result<()> {
+    let height = env::var("LINES")
+        .ok()
+        .and_then(|lines| lines.parse().ok())
+        .unwrap_or(DEFAULT_HEIGHT);
+
+    if output.split(|byte| *byte == b'\n').count() < height {
+        return io::stdout().write_all(output);
+    }
+
+    let pager = env::var("PAGER")
+        .ok()
+        .filter(|pager| !pager.trim().is_empty())
+        .unwrap_or_else(|| "less".to_string());
+    let mut args = pager.split_whitespace();
+
+    let mut command = Command::new(args.next().unwrap_or("less"));
+    command.args(args).stdin(Stdio::piped());
+
+    // Keep colors, and don't page if it does fit after all (same as git)
+    if env::var_os("LESS").is_none() {
+        command.env("LESS", "FRX");
+    }
+
+    let Ok(mut child) = command.spawn() else {
+        return io::stdout().write_all(output);
+    };
+
+    if let Some(mut stdin) = child.stdin.take() {
+        match stdin.write_all(output) {
+            // Quitting the pager early is fine
+            Err(err) if err.kind() == io::ErrorKind::BrokenPipe => {}
+            result => result?,
+        }
+    }
+
+    child.wait()?;
+    Ok(())
+}
@@ -0,0 +1,135 @@
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    sync::{mpsc, Condvar, Mutex},
+    thread,
+};
+
+/// Run `job` on every item using up to `workers` threads
+///
+/// `done` is called on the current thread as each job finishes (in whatever order they finish),
+/// with the index of the item it was for, so callers don't need to synchronize anything themselves
+pub fn run_pool<T, R, Job, Done>(items: &[T], workers: u16, job: Job, done: Done)
+where
+    T: Sync,
+    R: Send,
+    Job: Fn(usize, &T) -> R + Sync,
+    Done: FnMut(usize, R),
+{
+    run_pool_limited(items, workers, None, |_| (), job, done)
+}
+
+/// Like [`run_pool`], but with at most `per_key` jobs running at once for items with the same key
+///
+/// Items are still started in order where possible, but an item whose key is at its limit is
+/// skipped over (for now) in favour of the next one that can run
+pub fn run_pool_limited<T, K, R, Key, Job, Done>(
+    items: &[T],
+    workers: u16,
+    per_key: Option<u16>,
+    key: Key,
+    job: Job,
+    mut done: Done,
+) where
+    T: Sync,
+    K: Eq + Hash + Sync,
+    R: Send,
+    Key: Fn(&T) -> K,
+    Job: Fn(usize, &T) -> R + Sync,
+    Done: FnMut(usize, R),
+{
+    let keys: Vec<K> = items.iter().map(key).collect();
+    let per_key = per_key.map_or(usize::MAX, usize::from);
+    let state = Mutex::new(Scheduler {
+        started: vec![false; items.len()],
+        running: HashMap::new(),
+    });
+    let finished = Condvar::new();
+
+    let (sender, receiver) = mpsc::channel();
+    let workers = usize::from(workers).clamp(1, items.len().max(1));
+
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            let sender = sender.clone();
+            let (keys, state, finished, job) = (&keys, &state, &finished, &job);
+
+            scope.spawn(move || {
+                while let Some(i) = next_item(keys, per_key, state, finished) {
+                    let slot = Slot {
+                        key: &keys[i],
+                        state,
+                        finished,
+                    };
+                    let result = job(i, &items[i]);
+                    drop(slot);
+
+                    if sender.send((i, result)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+
+        // Only the workers should keep the channel open
+        drop(sender);
+
+        for (i, result) in receiver {
+            done(i, result);
+        }
+    });
+}
+
+/// Which items have been picked up, and how many jobs are running for each key
+struct Scheduler<'k, K> {
+    started: Vec<bool>,
+    running: HashMap<&'k K, usize>,
+}
+
+/// A job running for a key, which frees its spot once dropped. Even when the job panicked,
+/// otherwise the other workers would wait on it forever (and so would the scope, to re-panic)
+struct Slot<'s, 'k, K: Eq + Hash> {
+    key: &'k K,
+    state: &'s Mutex<Scheduler<'k, K>>,
+    finished: &'s Condvar,
+}
+
+impl<K: Eq + Hash> Drop for Slot<'_, '_, K> {
+    fn drop(&mut self) {
+        if let Ok(mut state) = self.state.lock() {
+            if let Some(running) = state.running.get_mut(self.key) {
+                *running -= 1;
+            }
+        }
+        self.finished.notify_all();
+    }
+}
+
+/// Claim the first item that hasn't been started and whose key is under the limit,
+/// waiting for a job to finish if everything left is blocked. `None` once nothing is left
+fn next_item<'k, K: Eq + Hash>(
+    keys: &'k [K],
+    per_key: usize,
+    state: &Mutex<Scheduler<'k, K>>,
+    finished: &Condvar,
+) -> Option<usize> {
+    let mut state = state.lock().ok()?;
+
+    loop {
+        if state.started.iter().all(|started| *started) {
+            return None;
+        }
+
+        let next = (0..keys.len()).find(|&i| {
+            !state.started[i] && state.running.get(&keys[i]).copied().unwrap_or(0) < per_key
+        });
+
+        if let Some(i) = next {
+            state.started[i] = true;
+            *state.running.entry(&keys[i]).or_insert(0) += 1;
+            return Some(i);
+        }
+
+        state = finished.wait(state).ok()?;
+    }
+}
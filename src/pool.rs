@@ -0,0 +1,127 @@
+use std::{
+    collections::HashMap,
+    io::{self, BufReader},
+    net::{SocketAddr, ToSocketAddrs},
+    sync::{mpsc, Mutex, OnceLock},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{http_request::ReadAndWrite, timeout::Deadline};
+
+/// An open connection to a server, kept around so later requests to the same place can reuse it
+pub struct Connection {
+    /// The buffered stream, since the buffer might be holding onto bytes of the next response
+    pub stream: BufReader<Box<dyn ReadAndWrite>>,
+    /// When the connection was first opened
+    pub created: Instant,
+    /// If this connection came from the pool (and so might have been closed by the server since)
+    pub reused: bool,
+    /// Deadline the stream's reads & writes have to finish by, moved along as the request goes
+    pub deadline: Deadline,
+    /// The address connected to (the proxy's when going through one)
+    pub peer: Option<SocketAddr>,
+}
+
+impl Connection {
+    pub fn new(
+        stream: Box<dyn ReadAndWrite>,
+        deadline: Deadline,
+        peer: Option<SocketAddr>,
+    ) -> Self {
+        Self {
+            stream: BufReader::new(stream),
+            created: Instant::now(),
+            reused: false,
+            deadline,
+            peer,
+        }
+    }
+}
+
+/// Idle connections, keyed by `scheme://authority`
+fn connections() -> &'static Mutex<HashMap<String, Vec<Connection>>> {
+    static CONNECTIONS: OnceLock<Mutex<HashMap<String, Vec<Connection>>>> = OnceLock::new();
+    CONNECTIONS.get_or_init(Default::default)
+}
+
+/// Resolved addresses, keyed by `host:port`
+fn dns_cache() -> &'static Mutex<HashMap<String, Vec<SocketAddr>>> {
+    static DNS_CACHE: OnceLock<Mutex<HashMap<String, Vec<SocketAddr>>>> = OnceLock::new();
+    DNS_CACHE.get_or_init(Default::default)
+}
+
+/// Take an idle connection out of the pool, if there's one young enough
+///
+/// Connections older than `max_age` are closed & discarded instead of being returned
+pub fn checkout(key: &str, max_age: Option<Duration>) -> Option<Connection> {
+    let mut connections = connections().lock().ok()?;
+    let idle = connections.get_mut(key)?;
+
+    while let Some(mut connection) = idle.pop() {
+        if max_age.is_some_and(|max_age| connection.created.elapsed() > max_age) {
+            // Dropping it closes it
+            continue;
+        }
+
+        connection.reused = true;
+        return Some(connection);
+    }
+
+    None
+}
+
+/// Put a connection back into the pool once we're done with it
+pub fn checkin(key: &str, connection: Connection) {
+    if let Ok(mut connections) = connections().lock() {
+        connections
+            .entry(key.to_string())
+            .or_default()
+            .push(connection);
+    }
+}
+
+/// Resolve `host:port` to addresses, remembering the result for later connections
+///
+/// `fresh` skips (and replaces) whatever was cached. The system resolver can't be cancelled,
+/// so with a `timeout` the lookup runs on its own thread and is abandoned if it takes too long
+pub fn resolve(
+    authority: &str,
+    fresh: bool,
+    timeout: Option<Duration>,
+) -> io::Result<Vec<SocketAddr>> {
+    if !fresh {
+        if let Some(addresses) = dns_cache()
+            .lock()
+            .ok()
+            .and_then(|cache| cache.get(authority).cloned())
+        {
+            return Ok(addresses);
+        }
+    }
+
+    let addresses: Vec<SocketAddr> = match timeout {
+        Some(timeout) => {
+            let (sender, receiver) = mpsc::channel();
+            let lookup = authority.to_string();
+
+            thread::spawn(move || {
+                let _ = sender.send(lookup.to_socket_addrs().map(Iterator::collect));
+            });
+
+            receiver.recv_timeout(timeout).map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!("DNS lookup of {} timed out after {:?}", authority, timeout),
+                )
+            })??
+        }
+        None => authority.to_socket_addrs()?.collect(),
+    };
+
+    if let Ok(mut cache) = dns_cache().lock() {
+        cache.insert(authority.to_string(), addresses.clone());
+    }
+
+    Ok(addresses)
+}
@@ -0,0 +1,79 @@
+use std::{str::FromStr, time::SystemTime};
+
+use http::{Method, Uri};
+
+use crate::{
+    cli::{PresignMethod, PresignS3Options},
+    helpers::percent_encode,
+    http_request::RequestError,
+    sigv4::{region_from_env, Credentials, Signer},
+};
+
+/// Create a presigned URL for an S3 object, returning the method it's for and the URL
+pub fn presign_s3(options: &PresignS3Options) -> Result<(Method, String), RequestError> {
+    let credentials = match (&options.access_key, &options.secret_key) {
+        (Some(access_key_id), Some(secret_access_key)) => Credentials {
+            access_key_id: access_key_id.clone(),
+            secret_access_key: secret_access_key.clone(),
+            session_token: None,
+        },
+        (None, None) => Credentials::from_env().ok_or(
+            "No credentials: use --access-key and --secret-key, or set AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY",
+        )?,
+        _ => return Err("--access-key and --secret-key must be used together".into()),
+    };
+
+    let region = options.region.clone().unwrap_or_else(region_from_env);
+    let uri = s3_object_uri(options, &region)?;
+
+    let method = match options.method {
+        PresignMethod::Get => Method::GET,
+        PresignMethod::Put => Method::PUT,
+        PresignMethod::Head => Method::HEAD,
+        PresignMethod::Delete => Method::DELETE,
+    };
+
+    let signer = Signer {
+        credentials: &credentials,
+        region: &region,
+        service: "s3",
+        time: SystemTime::now(),
+    };
+
+    let url = signer.presign(&method, &uri, options.expires.as_secs())?;
+    Ok((method, url))
+}
+
+/// Build the URL of an object, either virtual-hosted style (`bucket.s3.region.amazonaws.com/key`)
+/// or path style (`endpoint/bucket/key`)
+fn s3_object_uri(options: &PresignS3Options, region: &str) -> Result<Uri, RequestError> {
+    let key = percent_encode(options.key.trim_start_matches('/'), true);
+
+    let url = match &options.endpoint {
+        Some(endpoint) => {
+            let endpoint = Uri::from_str(endpoint)?;
+            let scheme = endpoint.scheme_str().unwrap_or("https");
+            let authority = endpoint
+                .authority()
+                .ok_or("The endpoint needs a host, e.g. https://minio.example.com")?;
+
+            if options.path_style {
+                format!("{}://{}/{}/{}", scheme, authority, options.bucket, key)
+            } else {
+                format!("{}://{}.{}/{}", scheme, options.bucket, authority, key)
+            }
+        }
+        None if options.path_style => {
+            format!(
+                "https://s3.{}.amazonaws.com/{}/{}",
+                region, options.bucket, key
+            )
+        }
+        None => format!(
+            "https://{}.s3.{}.amazonaws.com/{}",
+            options.bucket, region, key
+        ),
+    };
+
+    Ok(Uri::from_str(&url)?)
+}
@@ -0,0 +1,498 @@
+//! `--pretty`: reindenting and highlighting HTML, XML, CSS and JSON bodies for the terminal
+//!
+//! Only what's displayed is touched, saved (`-o`) and piped bodies are always left as they are.
+//! The formatters are forgiving rather than strict: anything they don't understand is passed
+//! through, since a slightly odd-looking body beats no body
+
+use clap::ArgEnum;
+use owo_colors::{OwoColorize, Style};
+
+use crate::helpers::MColorize;
+
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pretty {
+    /// Show the body as it was received
+    None,
+    /// Only highlight it
+    Colors,
+    /// Only reindent it
+    Format,
+    /// Reindent and highlight it
+    All,
+}
+
+/// Languages we know how to pretty-print
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Language {
+    Html,
+    Xml,
+    Css,
+    Json,
+}
+
+/// HTML elements that never have a closing tag
+const VOID_ELEMENTS: [&str; 14] = [
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source",
+    "track", "wbr",
+];
+
+/// HTML elements whose content is left exactly as-is
+const RAW_ELEMENTS: [&str; 4] = ["script", "style", "pre", "textarea"];
+
+/// Pretty-print a body of the given Content-Type, anything we don't know is returned as-is
+/// ```
+/// # use httpc::pretty::{prettify, Pretty};
+/// assert_eq!(
+///     prettify("text/html", "<ul><li>a</li><li><br>b</li></ul>", Pretty::Format),
+///     "<ul>\n  <li>a</li>\n  <li>\n    <br>\n    b\n  </li>\n</ul>"
+/// );
+/// assert_eq!(prettify("text/css", "a{color:red;top:0}", Pretty::Format), "a {\n  color:red;\n  top:0\n}");
+/// assert_eq!(prettify("application/json", r#"{"a":1}"#, Pretty::Format), "{\n  \"a\": 1\n}");
+/// assert_eq!(prettify("text/plain", "<b>", Pretty::All), "<b>");
+/// ```
+pub fn prettify(content_type: &str, text: &str, pretty: Pretty) -> String {
+    match language(content_type) {
+        Some(language) => prettify_as(language, text, pretty),
+        None => text.to_string(),
+    }
+}
+
+/// Pretty-print a body as `language`, whatever its Content-Type says
+pub fn prettify_as(language: Language, text: &str, pretty: Pretty) -> String {
+    let text = match pretty {
+        Pretty::Format | Pretty::All => match language {
+            Language::Html => format_markup(text, true),
+            Language::Xml => format_markup(text, false),
+            Language::Css => format_css(text),
+            Language::Json => format_json(text),
+        },
+        Pretty::None | Pretty::Colors => text.to_string(),
+    };
+
+    match pretty {
+        Pretty::Colors | Pretty::All => match language {
+            Language::Html => highlight_markup(&text, true),
+            Language::Xml => highlight_markup(&text, false),
+            Language::Css => highlight_css(&text),
+            Language::Json => highlight_json(&text),
+        },
+        Pretty::None | Pretty::Format => text,
+    }
+}
+
+fn language(content_type: &str) -> Option<Language> {
+    let essence = content_type.split(';').next()?.trim().to_ascii_lowercase();
+
+    Some(match essence.as_str() {
+        "text/html" | "application/xhtml+xml" => Language::Html,
+        "text/css" => Language::Css,
+        "text/xml" | "application/xml" => Language::Xml,
+        _ if essence.ends_with("+xml") => Language::Xml,
+        _ if essence.ends_with("json") => Language::Json,
+        _ => return None,
+    })
+}
+
+/// A piece of HTML/XML: a tag (or comment, doctype...) or the text between them
+#[derive(Debug, PartialEq, Eq)]
+enum Token<'a> {
+    Open(&'a str, &'a str),
+    Close(&'a str, &'a str),
+    /// Self-closing tags, void elements, comments, doctypes & processing instructions
+    Standalone(&'a str),
+    Text(&'a str),
+}
+
+/// Split markup into tags & text, keeping the content of `<script>` & co as one text token
+fn tokenize_markup(text: &str, html: bool) -> Vec<Token<'_>> {
+    let mut tokens = vec![];
+    let mut rest = text;
+
+    while !rest.is_empty() {
+        let Some(start) = rest.find('<') else {
+            tokens.push(Token::Text(rest));
+            break;
+        };
+
+        if start > 0 {
+            tokens.push(Token::Text(&rest[..start]));
+            rest = &rest[start..];
+        }
+
+        let terminator = if rest.starts_with("<!--") {
+            "-->"
+        } else if rest.starts_with("<![CDATA[") {
+            "]]>"
+        } else {
+            ">"
+        };
+
+        let Some(end) = rest.find(terminator).map(|end| end + terminator.len()) else {
+            // Unclosed tag, give up and keep the rest as-is
+            tokens.push(Token::Text(rest));
+            break;
+        };
+
+        let tag = &rest[..end];
+        rest = &rest[end..];
+
+        let name = tag
+            .trim_start_matches(['<', '/'])
+            .split(|c: char| c.is_whitespace() || c == '>' || c == '/')
+            .next()
+            .unwrap_or_default();
+
+        if tag.starts_with("</") {
+            tokens.push(Token::Close(tag, name));
+        } else if tag.starts_with("<!")
+            || tag.starts_with("<?")
+            || tag.ends_with("/>")
+            || (html && VOID_ELEMENTS.contains(&name.to_ascii_lowercase().as_str()))
+        {
+            tokens.push(Token::Standalone(tag));
+        } else {
+            tokens.push(Token::Open(tag, name));
+
+            if html && RAW_ELEMENTS.contains(&name.to_ascii_lowercase().as_str()) {
+                let closing = format!("</{}", name.to_ascii_lowercase());
+                let end = rest
+                    .to_ascii_lowercase()
+                    .find(&closing)
+                    .unwrap_or(rest.len());
+
+                if end > 0 {
+                    tokens.push(Token::Text(&rest[..end]));
+                }
+
+                rest = &rest[end..];
+            }
+        }
+    }
+
+    tokens
+}
+
+/// Put every tag & text on its own line, indented by how deeply it's nested.
+/// Elements with only text in them stay on one line
+fn format_markup(text: &str, html: bool) -> String {
+    let tokens = tokenize_markup(text, html);
+    let mut lines: Vec<String> = vec![];
+    let mut depth: usize = 0;
+    let mut i = 0;
+
+    let indent = |depth: usize| "  ".repeat(depth);
+
+    while i < tokens.len() {
+        match &tokens[i] {
+            Token::Open(tag, name) => {
+                // <a>text</a> fits on one line
+                if let (Some(Token::Text(content)), Some(Token::Close(close, close_name))) =
+                    (tokens.get(i + 1), tokens.get(i + 2))
+                {
+                    let raw = html && RAW_ELEMENTS.contains(&name.to_ascii_lowercase().as_str());
+
+                    if close_name.eq_ignore_ascii_case(name) && (raw || !content.contains('\n')) {
+                        let content = if raw { content } else { content.trim() };
+                        lines.push(format!("{}{}{}{}", indent(depth), tag, content, close));
+                        i += 3;
+                        continue;
+                    }
+                }
+
+                if let Some(Token::Close(close, close_name)) = tokens.get(i + 1) {
+                    if close_name.eq_ignore_ascii_case(name) {
+                        lines.push(format!("{}{}{}", indent(depth), tag, close));
+                        i += 2;
+                        continue;
+                    }
+                }
+
+                lines.push(format!("{}{}", indent(depth), tag));
+                depth += 1;
+            }
+            Token::Close(tag, _) => {
+                depth = depth.saturating_sub(1);
+                lines.push(format!("{}{}", indent(depth), tag));
+            }
+            Token::Standalone(tag) => lines.push(format!("{}{}", indent(depth), tag)),
+            Token::Text(content) => {
+                for line in content
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty())
+                {
+                    lines.push(format!("{}{}", indent(depth), line));
+                }
+            }
+        }
+
+        i += 1;
+    }
+
+    lines.join("\n")
+}
+
+/// Color tag names, attribute names & values, and dim comments
+fn highlight_markup(text: &str, html: bool) -> String {
+    let mut output = String::with_capacity(text.len());
+
+    for token in tokenize_markup(text, html) {
+        let tag = match token {
+            Token::Text(text) => {
+                output.push_str(text);
+                continue;
+            }
+            Token::Open(tag, _) | Token::Close(tag, _) | Token::Standalone(tag) => tag,
+        };
+
+        if tag.starts_with("<!") || tag.starts_with("<?") {
+            paint(&mut output, tag, Style::new().bright_black());
+            continue;
+        }
+
+        // <name attr="value" ...>, colored piece by piece
+        let prefix = if tag.starts_with("</") { 2 } else { 1 };
+        let name_end = tag[prefix..]
+            .find(|c: char| c.is_whitespace() || c == '>' || c == '/')
+            .map_or(tag.len(), |end| end + prefix);
+
+        paint(&mut output, &tag[..name_end], Style::new().blue());
+
+        let mut rest = &tag[name_end..];
+
+        while !rest.is_empty() {
+            let c = rest.chars().next().unwrap_or_default();
+
+            if c == '"' || c == '\'' {
+                let end = rest[1..].find(c).map_or(rest.len(), |end| end + 2);
+                paint(&mut output, &rest[..end], Style::new().magenta());
+                rest = &rest[end..];
+            } else if c == '>' || c == '/' || c == '=' || c.is_whitespace() {
+                let end = c.len_utf8();
+                let piece = &rest[..end];
+
+                if c == '>' || c == '/' {
+                    paint(&mut output, piece, Style::new().blue());
+                } else {
+                    output.push_str(piece);
+                }
+
+                rest = &rest[end..];
+            } else {
+                let end = rest
+                    .find(|c: char| c.is_whitespace() || matches!(c, '=' | '>' | '/'))
+                    .unwrap_or(rest.len());
+                paint(&mut output, &rest[..end], Style::new().cyan());
+                rest = &rest[end..];
+            }
+        }
+    }
+
+    output
+}
+
+/// One declaration per line, with blocks indented
+fn format_css(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut depth: usize = 0;
+    let mut chars = text.chars().peekable();
+    let mut line = String::new();
+
+    let flush = |line: &mut String, depth: usize, output: &mut String| {
+        let trimmed = line.trim();
+
+        if !trimmed.is_empty() {
+            output.push_str(&"  ".repeat(depth));
+            output.push_str(trimmed);
+            output.push('\n');
+        }
+
+        line.clear();
+    };
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' | '\'' => {
+                line.push(c);
+
+                for next in chars.by_ref() {
+                    line.push(next);
+
+                    if next == c {
+                        break;
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                let mut comment = String::from("/");
+
+                while let Some(next) = chars.next() {
+                    comment.push(next);
+
+                    if next == '*' && chars.peek() == Some(&'/') {
+                        comment.push('/');
+                        chars.next();
+                        break;
+                    }
+                }
+
+                flush(&mut line, depth, &mut output);
+                line.push_str(&comment);
+                flush(&mut line, depth, &mut output);
+            }
+            '{' => {
+                line = format!("{} {{", line.trim());
+                flush(&mut line, depth, &mut output);
+                depth += 1;
+            }
+            ';' => {
+                line.push(';');
+                flush(&mut line, depth, &mut output);
+            }
+            '}' => {
+                flush(&mut line, depth, &mut output);
+                depth = depth.saturating_sub(1);
+                line.push('}');
+                flush(&mut line, depth, &mut output);
+            }
+            '\n' | '\r' => line.push(' '),
+            c => line.push(c),
+        }
+    }
+
+    flush(&mut line, depth, &mut output);
+    output.trim_end().to_string()
+}
+
+/// Color selectors, property names & values, and dim comments
+fn highlight_css(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut depth: usize = 0;
+    let mut in_value = false;
+    let mut chars = text.char_indices().peekable();
+    let mut start = 0;
+
+    // Color everything since the last piece we colored
+    let flush = |output: &mut String, piece: &str, depth: usize, in_value: bool| {
+        let style = if depth == 0 {
+            Style::new().blue()
+        } else if in_value {
+            Style::new().magenta()
+        } else {
+            Style::new().cyan()
+        };
+        paint(output, piece, style);
+    };
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            // Skip over strings, they might have any of the characters below in them
+            '"' | '\'' => {
+                for (_, next) in chars.by_ref() {
+                    if next == c {
+                        break;
+                    }
+                }
+            }
+            '/' if chars.peek().is_some_and(|(_, next)| *next == '*') => {
+                flush(&mut output, &text[start..i], depth, in_value);
+                let end = text[i + 2..]
+                    .find("*/")
+                    .map_or(text.len(), |end| i + end + 4);
+                paint(&mut output, &text[i..end], Style::new().bright_black());
+
+                while chars.peek().is_some_and(|(next, _)| *next < end) {
+                    chars.next();
+                }
+
+                start = end;
+            }
+            '{' | '}' | ';' | ':' if !(c == ':' && (depth == 0 || in_value)) => {
+                flush(&mut output, &text[start..i], depth, in_value);
+                output.push(c);
+                start = i + 1;
+
+                match c {
+                    '{' => {
+                        depth += 1;
+                        in_value = false;
+                    }
+                    '}' => {
+                        depth = depth.saturating_sub(1);
+                        in_value = false;
+                    }
+                    ';' => in_value = false,
+                    _ => in_value = true,
+                }
+            }
+            _ => {}
+        }
+    }
+
+    flush(&mut output, &text[start..], depth, in_value);
+    output
+}
+
+/// Reindent JSON, keeping the order of keys. Invalid JSON is left alone
+fn format_json(text: &str) -> String {
+    serde_json::from_str::<serde_json::Value>(text)
+        .and_then(|value| serde_json::to_string_pretty(&value))
+        .unwrap_or_else(|_| text.to_string())
+}
+
+/// Color keys, strings, and other values differently
+fn highlight_json(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(c) = rest.chars().next() {
+        let end = match c {
+            '"' => {
+                let mut escaped = false;
+                let end = rest[1..]
+                    .char_indices()
+                    .find(|(_, c)| {
+                        let closes = *c == '"' && !escaped;
+                        escaped = *c == '\\' && !escaped;
+                        closes
+                    })
+                    .map_or(rest.len(), |(end, _)| end + 2);
+
+                let is_key = rest[end..].trim_start().starts_with(':');
+                let string = &rest[..end];
+
+                if is_key {
+                    paint(&mut output, string, Style::new().cyan());
+                } else {
+                    paint(&mut output, string, Style::new().magenta());
+                }
+
+                end
+            }
+            c if c == '-' || c.is_ascii_alphanumeric() => {
+                let end = rest
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '+' | '.')))
+                    .unwrap_or(rest.len());
+                paint(&mut output, &rest[..end], Style::new().yellow());
+                end
+            }
+            c => {
+                output.push(c);
+                c.len_utf8()
+            }
+        };
+
+        rest = &rest[end..];
+    }
+
+    output
+}
+
+/// Add `text` to `output` in `style` (if stdout supports colors)
+fn paint(output: &mut String, text: &str, style: Style) {
+    if text.is_empty() {
+        return;
+    }
+
+    output.push_str(&text.out_color(|t| t.style(style)).to_string());
+}
@@ -0,0 +1,131 @@
+use std::io::Write;
+
+use http::{header, Response, StatusCode};
+use owo_colors::OwoColorize;
+
+use crate::{
+    helpers::{format_response, MColorize},
+    http_request::RequestError,
+    multipart,
+    pretty::Pretty,
+    render::Renderers,
+    resume::parse_content_range,
+    spool::{body_len, body_prefix},
+};
+
+/// Check a byte range spec like `0-1023`, `500-`, `-500`, or `0-99,200-299`
+///
+/// Used as a clap value parser, so it returns the spec back if it's valid
+/// ```
+/// # use httpc::range::parse_range;
+/// assert!(parse_range("0-1023").is_ok());
+/// assert!(parse_range("0-99, 200-").is_ok());
+/// assert!(parse_range("-500").is_ok());
+/// assert!(parse_range("100-50").is_err());
+/// assert!(parse_range("abc").is_err());
+/// ```
+pub fn parse_range(spec: &str) -> Result<String, String> {
+    let invalid = || format!("Invalid range: '{}', expected something like 0-1023", spec);
+    let mut ranges = vec![];
+
+    for range in spec.split(',') {
+        let (start, end) = range.trim().split_once('-').ok_or_else(invalid)?;
+        let start = (!start.is_empty())
+            .then(|| start.parse::<u64>())
+            .transpose()
+            .map_err(|_| invalid())?;
+        let end = (!end.is_empty())
+            .then(|| end.parse::<u64>())
+            .transpose()
+            .map_err(|_| invalid())?;
+
+        match (start, end) {
+            (None, None) => return Err(invalid()),
+            (Some(start), Some(end)) if start > end => return Err(invalid()),
+            _ => {}
+        }
+
+        ranges.push(range.trim().to_string());
+    }
+
+    Ok(ranges.join(","))
+}
+
+/// Check if a response is a multipart/byteranges body (a response to multiple ranges)
+pub fn is_byteranges(response: &Response<Vec<u8>>) -> bool {
+    response.status() == StatusCode::PARTIAL_CONTENT
+        && response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|ct| ct.to_str().ok())
+            .is_some_and(|ct| {
+                ct.trim_start()
+                    .to_ascii_lowercase()
+                    .starts_with("multipart/byteranges")
+            })
+}
+
+/// Split a multipart/byteranges response into a response per range
+pub fn split_byteranges(
+    response: &Response<Vec<u8>>,
+) -> Result<Vec<Response<Vec<u8>>>, RequestError> {
+    let boundary = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|ct| ct.to_str().ok())
+        .and_then(multipart::boundary_from_content_type)
+        .ok_or("multipart/byteranges response has no boundary")?;
+
+    // A spooled body is read back in, the parts are kept in memory either way
+    let body = body_prefix(response, body_len(response))?;
+
+    multipart::split(&body, &boundary)?
+        .into_iter()
+        .map(|part| {
+            if !part.headers.contains_key(header::CONTENT_RANGE) {
+                return Err("multipart/byteranges part is missing Content-Range".into());
+            }
+
+            let mut range = Response::new(part.body);
+            *range.status_mut() = StatusCode::PARTIAL_CONTENT;
+            *range.headers_mut() = part.headers;
+            Ok(range)
+        })
+        .collect()
+}
+
+/// Print every range of a multipart/byteranges response, one after the other
+pub fn print_byteranges(
+    response: &Response<Vec<u8>>,
+    verbosity: u8,
+    out: &mut dyn Write,
+) -> Result<(), RequestError> {
+    for (i, range) in split_byteranges(response)?.iter().enumerate() {
+        if i > 0 {
+            writeln!(out)?;
+        }
+
+        let content_range = range
+            .headers()
+            .get(header::CONTENT_RANGE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(parse_content_range);
+
+        let heading = match content_range {
+            Some((Some((start, end)), Some(total))) => {
+                format!("── bytes {}-{} of {} ──", start, end, total)
+            }
+            Some((Some((start, end)), None)) => format!("── bytes {}-{} ──", start, end),
+            _ => "── unknown range ──".to_string(),
+        };
+
+        writeln!(out, "{}", heading.out_color(|t| t.bright_black()))?;
+        writeln!(
+            out,
+            "{}",
+            format_response(range, verbosity, false, Pretty::None, &Renderers::default())?
+        )?;
+    }
+
+    Ok(())
+}
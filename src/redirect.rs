@@ -0,0 +1,328 @@
+//! Limits on which redirects `-l` follows, for when the URLs (or where they redirect to) can't be
+//! trusted
+//!
+//! Whatever the policy, redirects from HTTPS to plain HTTP aren't followed unless
+//! `--redirect-allow-downgrade` is given, and Authorization and Cookie headers (and `-u`) aren't
+//! sent on once a redirect goes to another origin, unless `--location-trusted` is
+//!
+//! ```toml
+//! [profiles.webhooks.redirect]
+//! same_origin = false
+//! no_private = true
+//! strip_query = true
+//! statuses = [301, 302, 307, 308]
+//! protocols = ["https"]
+//! ```
+
+use std::net::{IpAddr, ToSocketAddrs};
+
+use clap::Args;
+use http::{StatusCode, Uri};
+use serde::Deserialize;
+
+use crate::{helpers::resolve_url, normalize::normalize_url};
+
+#[derive(Args, Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RedirectPolicy {
+    /// With -l, only follow redirects to the same origin (scheme, host and port)
+    #[clap(long = "redirect-same-origin", requires = "location")]
+    pub same_origin: bool,
+
+    /// With -l, don't follow redirects to loopback, private or link-local addresses (checked after resolving the host, and again on connecting)
+    #[clap(long = "redirect-no-private", requires = "location")]
+    pub no_private: bool,
+
+    /// With -l, drop the query string when redirected to a different origin
+    #[clap(long = "redirect-strip-query", requires = "location")]
+    pub strip_query: bool,
+
+    /// With -l, only follow redirects with these statuses, e.g. 301,308
+    #[clap(
+        long = "redirect-status",
+        requires = "location",
+        value_name = "STATUS",
+        value_delimiter = ',',
+        value_parser = clap::value_parser!(u16).range(100..600)
+    )]
+    pub statuses: Vec<u16>,
+
+    /// With -l, only follow redirects to these protocols, e.g. https (http and https by default)
+    #[clap(
+        long = "proto-redir",
+        requires = "location",
+        value_name = "PROTOCOL",
+        value_delimiter = ',',
+        possible_values = &["http", "https"]
+    )]
+    pub protocols: Vec<String>,
+
+    /// With -l, follow redirects from HTTPS to plain HTTP instead of refusing them
+    #[clap(long = "redirect-allow-downgrade", requires = "location")]
+    pub allow_downgrade: bool,
+
+    /// With -l, keep sending Authorization and Cookie headers (and -u) when redirected to another origin
+    #[clap(long = "location-trusted", requires = "location")]
+    pub trusted: bool,
+}
+
+impl RedirectPolicy {
+    /// Fill in whatever isn't set from `other`, e.g. a profile's policy
+    pub fn merge(&mut self, other: &RedirectPolicy) {
+        self.same_origin |= other.same_origin;
+        self.no_private |= other.no_private;
+        self.strip_query |= other.strip_query;
+        self.allow_downgrade |= other.allow_downgrade;
+        self.trusted |= other.trusted;
+
+        if self.statuses.is_empty() {
+            self.statuses = other.statuses.clone();
+        }
+
+        if self.protocols.is_empty() {
+            self.protocols = other.protocols.clone();
+        }
+    }
+
+    /// If credentials should be left out of the requests after redirecting from `from` to
+    /// `target`, which is when it's another origin and they aren't `--location-trusted`
+    /// ```
+    /// # use httpc::redirect::RedirectPolicy;
+    /// let from = "https://example.com/a".parse().unwrap();
+    /// let policy = RedirectPolicy::default();
+    /// assert!(!policy.strips_credentials(&from, &"https://example.com:443/b".parse().unwrap()));
+    /// assert!(policy.strips_credentials(&from, &"https://cdn.example.com/b".parse().unwrap()));
+    ///
+    /// let policy = RedirectPolicy { trusted: true, ..Default::default() };
+    /// assert!(!policy.strips_credentials(&from, &"https://cdn.example.com/b".parse().unwrap()));
+    /// ```
+    pub fn strips_credentials(&self, from: &Uri, target: &Uri) -> bool {
+        !self.trusted && origin(from) != origin(target)
+    }
+
+    /// Where to follow a `status` redirect from `from` to `location` (resolved against `from`),
+    /// or why it shouldn't be followed
+    /// ```
+    /// # use httpc::redirect::RedirectPolicy;
+    /// # use http::StatusCode;
+    /// let policy = RedirectPolicy { strip_query: true, statuses: vec![301], ..Default::default() };
+    /// let from = "https://example.com/a?token=1".parse().unwrap();
+    /// let to = policy.check(StatusCode::MOVED_PERMANENTLY, &from, "https://example.org/b?token=1");
+    /// assert_eq!(to.unwrap(), "https://example.org/b");
+    /// assert!(policy.check(StatusCode::FOUND, &from, "/b").is_err());
+    ///
+    /// let policy = RedirectPolicy { same_origin: true, ..Default::default() };
+    /// assert_eq!(policy.check(StatusCode::FOUND, &from, "/b?c").unwrap(), "https://example.com/b?c");
+    /// assert!(policy.check(StatusCode::FOUND, &from, "https://example.com:8443/").is_err());
+    ///
+    /// let policy = RedirectPolicy { no_private: true, ..Default::default() };
+    /// assert!(policy.check(StatusCode::FOUND, &from, "http://127.0.0.1/").is_err());
+    ///
+    /// assert!(RedirectPolicy::default().check(StatusCode::FOUND, &from, "http://example.com/").is_err());
+    /// let policy = RedirectPolicy { allow_downgrade: true, ..Default::default() };
+    /// assert!(policy.check(StatusCode::FOUND, &from, "http://example.com/").is_ok());
+    ///
+    /// let policy = RedirectPolicy { protocols: vec!["https".to_string()], ..Default::default() };
+    /// let plain = "http://example.com/".parse().unwrap();
+    /// assert!(policy.check(StatusCode::FOUND, &plain, "/b").is_err());
+    /// assert!(policy.check(StatusCode::FOUND, &plain, "https://example.com/b").is_ok());
+    /// ```
+    pub fn check(&self, status: StatusCode, from: &Uri, location: &str) -> Result<String, String> {
+        if !self.statuses.is_empty() && !self.statuses.contains(&status.as_u16()) {
+            return Err(format!(
+                "{} isn't an allowed redirect status",
+                status.as_u16()
+            ));
+        }
+
+        let target = normalize_url(&resolve_url(from, location));
+        let uri = target
+            .parse::<Uri>()
+            .map_err(|err| format!("Invalid redirect URL '{}': {}", target, err))?;
+
+        let scheme = origin(&uri).0;
+
+        if !self.protocols.is_empty() && !self.protocols.contains(&scheme) {
+            return Err(format!("{} isn't an allowed redirect protocol", scheme));
+        }
+
+        if !self.allow_downgrade && origin(from).0 == "https" && scheme == "http" {
+            return Err(format!(
+                "{} would downgrade HTTPS to plain HTTP (allow it with --redirect-allow-downgrade)",
+                target
+            ));
+        }
+
+        let cross_origin = origin(&uri) != origin(from);
+
+        if self.same_origin && cross_origin {
+            return Err(format!("{} is a different origin", target));
+        }
+
+        if self.no_private {
+            let host = uri.host().unwrap_or_default();
+            let port = origin(&uri).2;
+
+            if resolves_to_private(host, port)? {
+                return Err(format!("{} is a private address", host));
+            }
+        }
+
+        match (self.strip_query && cross_origin, target.split_once('?')) {
+            (true, Some((without_query, _))) => Ok(without_query.to_string()),
+            _ => Ok(target),
+        }
+    }
+}
+
+/// Where an HTML `<meta http-equiv="refresh" content="5; url=...">` redirects to, if anywhere
+/// ```
+/// # use httpc::redirect::meta_refresh;
+/// let html = r#"<head><META HTTP-EQUIV=Refresh CONTENT="0; URL='/new?a=1&amp;b=2'"></head>"#;
+/// assert_eq!(meta_refresh(html).as_deref(), Some("/new?a=1&b=2"));
+/// assert_eq!(meta_refresh(r#"<meta http-equiv="refresh" content="30">"#), None);
+/// assert_eq!(meta_refresh(r#"<meta name="refresh" content="0; url=/x">"#), None);
+/// ```
+pub fn meta_refresh(html: &str) -> Option<String> {
+    // ASCII lowercasing keeps the offsets the same
+    let lower = html.to_ascii_lowercase();
+
+    lower.match_indices("<meta").find_map(|(start, tag)| {
+        let end = start + lower[start..].find('>')?;
+        let attributes = tag_attributes(&html[start + tag.len()..end]);
+
+        let refresh = attributes.iter().any(|(name, value)| {
+            name.eq_ignore_ascii_case("http-equiv") && value.eq_ignore_ascii_case("refresh")
+        });
+        let (_, content) = attributes
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case("content"))?;
+
+        refresh.then(|| refresh_url(content)).flatten()
+    })
+}
+
+/// The `name=value` attributes of a tag, without the quotes around values
+fn tag_attributes(mut tag: &str) -> Vec<(&str, &str)> {
+    let mut attributes = vec![];
+    tag = tag.trim_start();
+
+    while !tag.is_empty() {
+        let name_end = tag
+            .find(|c: char| c.is_whitespace() || c == '=' || c == '/')
+            .unwrap_or(tag.len());
+        let name = &tag[..name_end];
+        let mut value = "";
+        tag = tag[name_end..].trim_start();
+
+        if let Some(rest) = tag.strip_prefix('=') {
+            let rest = rest.trim_start();
+
+            match rest.chars().next() {
+                Some(quote @ ('"' | '\'')) => {
+                    let close = rest[1..].find(quote).map_or(rest.len(), |index| index + 1);
+                    value = &rest[1..close];
+                    tag = rest.get(close + 1..).unwrap_or_default();
+                }
+                _ => {
+                    let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+                    value = &rest[..end];
+                    tag = &rest[end..];
+                }
+            }
+        } else if name.is_empty() {
+            // The / of a self-closing tag
+            tag = &tag[1..];
+        }
+
+        if !name.is_empty() {
+            attributes.push((name, value));
+        }
+
+        tag = tag.trim_start();
+    }
+
+    attributes
+}
+
+/// The URL in a refresh's content (after the delay), like `5; url='/next'`
+fn refresh_url(content: &str) -> Option<String> {
+    let rest = content
+        .trim_start()
+        .trim_start_matches(|c: char| c.is_ascii_digit() || c == '.')
+        .trim_start();
+    let rest = rest
+        .strip_prefix(|c| c == ';' || c == ',')
+        .unwrap_or(rest)
+        .trim_start();
+
+    let url = rest
+        .get(..3)
+        .filter(|url| url.eq_ignore_ascii_case("url"))
+        .and_then(|_| rest[3..].trim_start().strip_prefix('='))
+        .map_or(rest, str::trim_start);
+    let url = url.trim_matches(|c| c == '"' || c == '\'').trim();
+
+    (!url.is_empty()).then(|| url.replace("&amp;", "&"))
+}
+
+/// Scheme, host & port (with the default filled in)
+fn origin(uri: &Uri) -> (String, String, u16) {
+    let scheme = uri.scheme_str().unwrap_or("http").to_ascii_lowercase();
+    let port = uri
+        .port_u16()
+        .unwrap_or(if scheme == "https" { 443 } else { 80 });
+    let host = uri.host().unwrap_or_default().to_ascii_lowercase();
+
+    (scheme, host, port)
+}
+
+/// If any address the host resolves to is loopback, private, link-local or unspecified
+fn resolves_to_private(host: &str, port: u16) -> Result<bool, String> {
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let mut addresses = (host, port)
+        .to_socket_addrs()
+        .map_err(|err| format!("Can't resolve {}: {}", host, err))?;
+
+    Ok(addresses.any(|address| is_private(address.ip())))
+}
+
+/// Loopback, private (RFC 1918, unique local), link-local, carrier-grade NAT or unspecified
+/// ```
+/// # use httpc::redirect::is_private;
+/// assert!(is_private("10.1.2.3".parse().unwrap()));
+/// assert!(is_private("169.254.169.254".parse().unwrap()));
+/// assert!(is_private("::ffff:127.0.0.1".parse().unwrap()));
+/// assert!(is_private("fd00::1".parse().unwrap()));
+/// assert!(!is_private("93.184.216.34".parse().unwrap()));
+/// assert!(!is_private("2606:2800:220:1::".parse().unwrap()));
+/// ```
+pub fn is_private(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+
+            ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                // 100.64.0.0/10
+                || (a == 100 && (64..128).contains(&b))
+                // 0.0.0.0/8
+                || a == 0
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_private(IpAddr::V4(ip)),
+            None => {
+                let first = ip.segments()[0];
+
+                ip.is_loopback()
+                    || ip.is_unspecified()
+                    // fc00::/7 and fe80::/10
+                    || (first & 0xfe00) == 0xfc00
+                    || (first & 0xffc0) == 0xfe80
+            }
+        },
+    }
+}
@@ -0,0 +1,127 @@
+//! `-O`/`-J`: picking the output file name from the URL or the server's `Content-Disposition`
+//!
+//! Names only ever come out as a single file name in the current directory, anything path-like
+//! (`../`, `/etc/`, `C:\`) is stripped so a server can't write outside of it
+
+use http::Uri;
+
+use crate::{helpers::percent_decode, http_request::RequestError};
+
+/// The file name for `-O`, the last segment of the URL's path
+/// ```
+/// # use httpc::remote_name::url_file_name;
+/// let uri = "https://example.com/files/report%202024.pdf?download=1".parse().unwrap();
+/// assert_eq!(url_file_name(&uri).unwrap(), "report 2024.pdf");
+/// assert!(url_file_name(&"https://example.com/files/".parse().unwrap()).is_err());
+/// ```
+pub fn url_file_name(uri: &Uri) -> Result<String, RequestError> {
+    let segment = uri.path().rsplit('/').next().unwrap_or_default();
+    let segment = String::from_utf8_lossy(&percent_decode(segment)).into_owned();
+
+    sanitize_file_name(&segment)
+        .ok_or_else(|| format!("Can't get a file name from {}, use -o to give one", uri).into())
+}
+
+/// The file name a `Content-Disposition` header suggests, preferring `filename*` (RFC 6266)
+/// ```
+/// # use httpc::remote_name::disposition_file_name;
+/// assert_eq!(disposition_file_name("attachment; filename=\"a b.txt\"").unwrap(), "a b.txt");
+/// assert_eq!(
+///     disposition_file_name("attachment; filename=a.txt; filename*=UTF-8''%E2%82%AC.txt").unwrap(),
+///     "€.txt"
+/// );
+/// assert_eq!(disposition_file_name("attachment; filename=\"../../.bashrc\"").unwrap(), "bashrc");
+/// assert_eq!(disposition_file_name("inline"), None);
+/// ```
+pub fn disposition_file_name(value: &str) -> Option<String> {
+    let mut plain = None;
+    let mut extended = None;
+
+    for param in split_params(value).into_iter().skip(1) {
+        let Some((key, value)) = param.split_once('=') else {
+            continue;
+        };
+
+        match key.trim().to_ascii_lowercase().as_str() {
+            "filename" => plain = Some(unquote(value.trim())),
+            // charset'language'percent-encoded-value, only UTF-8 (and its ASCII subset) in practice
+            "filename*" => {
+                extended = value
+                    .trim()
+                    .splitn(3, '\'')
+                    .nth(2)
+                    .map(|name| String::from_utf8_lossy(&percent_decode(name)).into_owned())
+            }
+            _ => {}
+        }
+    }
+
+    extended
+        .and_then(|name| sanitize_file_name(&name))
+        .or_else(|| plain.and_then(|name| sanitize_file_name(&name)))
+}
+
+/// Make a name from the server safe to use as a file name: only what's after the last path
+/// separator, without control characters or leading dots
+/// ```
+/// # use httpc::remote_name::sanitize_file_name;
+/// assert_eq!(sanitize_file_name("..\\..\\evil.exe").unwrap(), "evil.exe");
+/// assert_eq!(sanitize_file_name("/etc/passwd").unwrap(), "passwd");
+/// assert_eq!(sanitize_file_name(".."), None);
+/// ```
+pub fn sanitize_file_name(name: &str) -> Option<String> {
+    let name = name.rsplit(['/', '\\']).next().unwrap_or_default();
+    // Drive letters like C:
+    let name = name.rsplit(':').next().unwrap_or_default();
+
+    let name = name.chars().filter(|c| !c.is_control()).collect::<String>();
+    let name = name.trim().trim_start_matches('.').trim();
+
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+/// Split a header value on `;`, except inside quotes
+fn split_params(value: &str) -> Vec<&str> {
+    let mut params = vec![];
+    let mut start = 0;
+    let mut quoted = false;
+    let mut escaped = false;
+
+    for (i, c) in value.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            ';' if !quoted => {
+                params.push(&value[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+
+    params.push(&value[start..]);
+    params
+}
+
+/// Remove the quotes around a quoted-string, and its `\` escapes
+fn unquote(value: &str) -> String {
+    let Some(inner) = value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+    else {
+        return value.to_string();
+    };
+
+    let mut unquoted = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unquoted.extend(chars.next()),
+            c => unquoted.push(c),
+        }
+    }
+
+    unquoted
+}
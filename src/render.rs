@@ -0,0 +1,385 @@
+//! How response bodies are shown in the terminal: each Content-Type gets a [`Renderer`], picked
+//! from the `[renderers]` in `~/.config/httpc/config.toml` and then the built-in ones
+//!
+//! ```toml
+//! [renderers]
+//! "application/vnd.api+json" = "json"
+//! "application/x-ndjson" = "text"
+//! "text/*" = "hex"
+//! ```
+//!
+//! Types can be exact (`text/html`), a whole type (`image/*`), a suffix (`*+json`, `*json`) or
+//! `*/*`, the most specific match wins. Bodies without a Content-Type are sniffed instead, and so
+//! are ones with a generic type (`application/octet-stream`, `text/plain`) that the config doesn't
+//! have a renderer for, since plenty of servers send JSON, XML or HTML as those
+
+use std::{
+    collections::HashMap,
+    io::{self, IsTerminal},
+    str::from_utf8,
+};
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use http::{header::CONTENT_TYPE, HeaderMap};
+use serde::Deserialize;
+
+use crate::{
+    helpers::hexdump,
+    pretty::{prettify_as, Language, Pretty},
+    warnings::{warn, WarningKind},
+};
+
+/// A way to show a body
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Renderer {
+    Json,
+    Html,
+    Xml,
+    Css,
+    /// As it was received, no formatting or highlighting
+    Text,
+    /// Inline in terminals that can show images (kitty, iTerm2, WezTerm), otherwise its format
+    /// and size
+    Image,
+    Hex,
+    /// Not shown at all, only how big it is
+    Binary,
+}
+
+/// The built-in renderers, for anything the config doesn't pick one for
+const DEFAULT_RENDERERS: [(&str, Renderer); 13] = [
+    ("text/html", Renderer::Html),
+    ("application/xhtml+xml", Renderer::Html),
+    ("text/css", Renderer::Css),
+    ("text/xml", Renderer::Xml),
+    ("application/xml", Renderer::Xml),
+    ("*+xml", Renderer::Xml),
+    ("*xml", Renderer::Text),
+    ("*json", Renderer::Json),
+    ("text/*", Renderer::Text),
+    ("application/javascript", Renderer::Text),
+    ("application/x-www-form-urlencoded", Renderer::Text),
+    ("image/*", Renderer::Image),
+    ("*/*", Renderer::Binary),
+];
+
+/// Types servers send when they don't say what a body is, so it's checked for what it really is
+const GENERIC_TYPES: [&str; 2] = ["application/octet-stream", "text/plain"];
+
+/// Which renderer each Content-Type gets
+#[derive(Clone, Debug, Default)]
+pub struct Renderers {
+    /// From the config, most specific first
+    configured: Vec<(String, Renderer)>,
+}
+
+impl Renderers {
+    /// The built-in renderers, with `configured` ones taking priority
+    pub fn new(configured: &HashMap<String, Renderer>) -> Self {
+        let mut configured = configured
+            .iter()
+            .map(|(pattern, renderer)| (pattern.trim().to_ascii_lowercase(), *renderer))
+            .collect::<Vec<_>>();
+        configured.sort_by_key(|(pattern, _)| std::cmp::Reverse(specificity(pattern)));
+
+        Self { configured }
+    }
+
+    /// The renderer for a body
+    /// ```
+    /// # use httpc::render::{Renderer, Renderers};
+    /// let configured = [("application/vnd.custom".to_string(), Renderer::Json)].into();
+    /// let renderers = Renderers::new(&configured);
+    /// let mut headers = http::HeaderMap::new();
+    /// assert_eq!(renderers.renderer_for(&headers, b"hello"), Renderer::Text);
+    /// assert_eq!(renderers.renderer_for(&headers, b"GIF89a\x10\0\x20\0"), Renderer::Image);
+    /// headers.insert("content-type", "application/vnd.custom; charset=utf-8".parse().unwrap());
+    /// assert_eq!(renderers.renderer_for(&headers, b"{}"), Renderer::Json);
+    /// headers.insert("content-type", "image/svg+xml".parse().unwrap());
+    /// assert_eq!(renderers.renderer_for(&headers, b"<svg/>"), Renderer::Xml);
+    /// headers.insert("content-type", "application/octet-stream".parse().unwrap());
+    /// assert_eq!(renderers.renderer_for(&headers, b"hello"), Renderer::Binary);
+    /// assert_eq!(renderers.renderer_for(&headers, b"<!DOCTYPE html><p>hi"), Renderer::Html);
+    /// ```
+    pub fn renderer_for(&self, headers: &HeaderMap, body: &[u8]) -> Renderer {
+        self.choose(headers, body).0
+    }
+
+    /// The renderer for a body, and the Content-Type it was sent with if that was ignored because
+    /// the body is clearly something else
+    fn choose(&self, headers: &HeaderMap, body: &[u8]) -> (Renderer, Option<String>) {
+        let essence = headers
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split(';').next())
+            .map(|essence| essence.trim().to_ascii_lowercase())
+            .filter(|essence| !essence.is_empty());
+
+        let Some(essence) = essence else {
+            return (sniff(body), None);
+        };
+
+        let configured = self
+            .configured
+            .iter()
+            .find(|(pattern, _)| pattern_matches(pattern, &essence));
+
+        if let Some((_, renderer)) = configured {
+            return (*renderer, None);
+        }
+
+        if GENERIC_TYPES.contains(&essence.as_str()) {
+            if let Some(renderer) = detect(body) {
+                return (renderer, Some(essence));
+            }
+        }
+
+        let renderer = DEFAULT_RENDERERS
+            .iter()
+            .find(|(pattern, _)| pattern_matches(pattern, &essence))
+            .map_or(Renderer::Binary, |(_, renderer)| *renderer);
+
+        (renderer, None)
+    }
+
+    /// Show a body for the terminal, `hex` shows everything as a hexdump
+    pub fn render(&self, headers: &HeaderMap, body: &[u8], hex: bool, pretty: Pretty) -> String {
+        let renderer = if hex {
+            Renderer::Hex
+        } else {
+            let (renderer, ignored) = self.choose(headers, body);
+
+            if let Some(content_type) = ignored {
+                warn(
+                    WarningKind::MislabelledContentType,
+                    format!(
+                        "The body was sent as {} but looks like {}, showing it as that",
+                        content_type,
+                        renderer.name()
+                    ),
+                );
+            }
+
+            renderer
+        };
+
+        let text = || String::from_utf8_lossy(body);
+
+        match renderer {
+            Renderer::Json => prettify_as(Language::Json, &text(), pretty),
+            Renderer::Html => prettify_as(Language::Html, &text(), pretty),
+            Renderer::Xml => prettify_as(Language::Xml, &text(), pretty),
+            Renderer::Css => prettify_as(Language::Css, &text(), pretty),
+            Renderer::Text => text().into_owned(),
+            Renderer::Image => render_image(body),
+            Renderer::Hex => hexdump(body, 0),
+            Renderer::Binary => format!(
+                "Binary data ({} bytes), not displaying. Use --hex to see it, or -o to save it.",
+                body.len()
+            ),
+        }
+    }
+}
+
+impl Renderer {
+    /// What it shows, for messages
+    fn name(&self) -> &'static str {
+        match self {
+            Renderer::Json => "JSON",
+            Renderer::Html => "HTML",
+            Renderer::Xml => "XML",
+            Renderer::Css => "CSS",
+            Renderer::Text => "text",
+            Renderer::Image => "an image",
+            Renderer::Hex | Renderer::Binary => "binary data",
+        }
+    }
+}
+
+/// How specific a pattern is, more specific ones are tried first
+fn specificity(pattern: &str) -> u8 {
+    match pattern {
+        "*/*" => 0,
+        _ if pattern.ends_with("/*") => 1,
+        _ if pattern.starts_with('*') => 2,
+        _ => 3,
+    }
+}
+
+/// Whether a Content-Type (without parameters) matches a pattern
+fn pattern_matches(pattern: &str, essence: &str) -> bool {
+    match pattern {
+        "*/*" => true,
+        _ => match (pattern.strip_suffix("/*"), pattern.strip_prefix('*')) {
+            (Some(kind), _) => essence.split('/').next() == Some(kind),
+            (None, Some(suffix)) => essence.ends_with(suffix),
+            (None, None) => pattern == essence,
+        },
+    }
+}
+
+/// Guess a renderer from the body itself, for when there's no Content-Type
+fn sniff(body: &[u8]) -> Renderer {
+    if image_info(body).is_some() {
+        Renderer::Image
+    } else if body.contains(&0) || from_utf8(body).is_err() {
+        Renderer::Binary
+    } else {
+        Renderer::Text
+    }
+}
+
+/// What a body clearly is going by its content, if it's JSON, XML or HTML
+/// ```
+/// # use httpc::render::{detect, Renderer};
+/// assert_eq!(detect(br#" {"a": [1, 2]}"#), Some(Renderer::Json));
+/// assert_eq!(detect(b"<?xml version=\"1.0\"?><a/>"), Some(Renderer::Xml));
+/// assert_eq!(detect(b"<feed xmlns=\"x\"><entry/></feed>\n"), Some(Renderer::Xml));
+/// assert_eq!(detect(b"<!doctype html>\n<title>hi</title>"), Some(Renderer::Html));
+/// assert_eq!(detect(b"<b>hi</b> there"), None);
+/// assert_eq!(detect(b"{not json"), None);
+/// ```
+pub fn detect(body: &[u8]) -> Option<Renderer> {
+    let text = from_utf8(body).ok()?;
+    let text = text.trim_start_matches('\u{feff}').trim();
+
+    if text.starts_with(['{', '[']) && serde_json::from_str::<serde::de::IgnoredAny>(text).is_ok() {
+        return Some(Renderer::Json);
+    }
+
+    let start = text.get(..14).unwrap_or(text).to_ascii_lowercase();
+
+    if start.starts_with("<!doctype html") || start.starts_with("<html") {
+        return Some(Renderer::Html);
+    }
+
+    if start.starts_with("<?xml") {
+        return Some(Renderer::Xml);
+    }
+
+    // Otherwise only if the whole body is one element, like `<feed ...>...</feed>`
+    let root = text
+        .strip_prefix('<')?
+        .split(|c: char| c.is_whitespace() || c == '>' || c == '/')
+        .next()
+        .filter(|name| name.starts_with(|c: char| c.is_alphabetic()))?;
+
+    text.ends_with(&format!("</{}>", root))
+        .then_some(Renderer::Xml)
+}
+
+/// An image's format and its width & height, going by its header
+/// ```
+/// # use httpc::render::image_info;
+/// let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+/// png.extend_from_slice(&[0, 0, 2, 128, 0, 0, 1, 224]);
+/// assert_eq!(image_info(&png), Some(("PNG", 640, 480)));
+/// assert_eq!(image_info(b"GIF89a\x10\x00\x20\x00"), Some(("GIF", 16, 32)));
+/// assert_eq!(image_info(b"hello"), None);
+/// ```
+pub fn image_info(body: &[u8]) -> Option<(&'static str, u32, u32)> {
+    let be16 = |at: usize| Some(u16::from_be_bytes(body.get(at..at + 2)?.try_into().ok()?) as u32);
+    let le16 = |at: usize| Some(u16::from_le_bytes(body.get(at..at + 2)?.try_into().ok()?) as u32);
+    let be32 = |at: usize| Some(u32::from_be_bytes(body.get(at..at + 4)?.try_into().ok()?));
+    let le24 = |at: usize| {
+        let bytes = body.get(at..at + 3)?;
+        Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0]))
+    };
+
+    if body.starts_with(b"\x89PNG\r\n\x1a\n") {
+        return Some(("PNG", be32(16)?, be32(20)?));
+    }
+
+    if body.starts_with(b"GIF87a") || body.starts_with(b"GIF89a") {
+        return Some(("GIF", le16(6)?, le16(8)?));
+    }
+
+    if body.starts_with(b"RIFF") && body.get(8..12) == Some(b"WEBP") {
+        return match body.get(12..16)? {
+            b"VP8 " => Some(("WebP", le16(26)? & 0x3fff, le16(28)? & 0x3fff)),
+            b"VP8L" => {
+                let bits = u32::from_le_bytes(body.get(21..25)?.try_into().ok()?);
+                Some(("WebP", (bits & 0x3fff) + 1, ((bits >> 14) & 0x3fff) + 1))
+            }
+            b"VP8X" => Some(("WebP", le24(24)? + 1, le24(27)? + 1)),
+            _ => None,
+        };
+    }
+
+    if body.starts_with(b"\xff\xd8") {
+        // Walk the segments until a start of frame, which has the size
+        let mut at = 2;
+
+        while body.get(at) == Some(&0xff) {
+            let marker = *body.get(at + 1)?;
+
+            if (0xc0..=0xcf).contains(&marker) && ![0xc4, 0xc8, 0xcc].contains(&marker) {
+                return Some(("JPEG", be16(at + 7)?, be16(at + 5)?));
+            }
+
+            at += 2 + be16(at + 2)? as usize;
+        }
+    }
+
+    None
+}
+
+/// The image inline if the terminal can show it, otherwise what it is
+fn render_image(body: &[u8]) -> String {
+    let info = image_info(body);
+    let described = match info {
+        Some((format, width, height)) => {
+            format!(
+                "{} image, {}×{} ({} bytes)",
+                format,
+                width,
+                height,
+                body.len()
+            )
+        }
+        None => format!("Image ({} bytes)", body.len()),
+    };
+
+    let env = |name: &str| std::env::var(name).unwrap_or_default();
+    let kitty = env("TERM") == "xterm-kitty" || !env("KITTY_WINDOW_ID").is_empty();
+    let iterm = matches!(env("TERM_PROGRAM").as_str(), "iTerm.app" | "WezTerm")
+        || env("LC_TERMINAL") == "iTerm2";
+
+    if !io::stdout().is_terminal() || info.is_none() {
+        return format!("{}, not displaying. Use -o to save it.", described);
+    }
+
+    if iterm {
+        return format!(
+            "\x1b]1337;File=inline=1;size={}:{}\x07\n{}",
+            body.len(),
+            BASE64.encode(body),
+            described
+        );
+    }
+
+    // kitty only takes PNGs directly, sent in chunks of at most 4096 bytes
+    if kitty && body.starts_with(b"\x89PNG") {
+        let encoded = BASE64.encode(body);
+        let chunks = encoded.as_bytes().chunks(4096).collect::<Vec<_>>();
+        let mut escape = String::new();
+
+        for (i, chunk) in chunks.iter().enumerate() {
+            let more = u8::from(i + 1 < chunks.len());
+            let control = match i {
+                0 => format!("f=100,a=T,m={}", more),
+                _ => format!("m={}", more),
+            };
+            escape.push_str(&format!(
+                "\x1b_G{};{}\x1b\\",
+                control,
+                String::from_utf8_lossy(chunk)
+            ));
+        }
+
+        return format!("{}\n{}", escape, described);
+    }
+
+    format!("{}, not displaying. Use -o to save it.", described)
+}
@@ -0,0 +1,231 @@
+//! `--log-file`: append a record of every request (redirects included) to a file, whatever's
+//! shown on the terminal, to look through a long batch run afterwards
+//!
+//! Each record is one line: a JSON object with `--log-format json` (the default), or
+//! `key=value` pairs (logfmt) with `--log-format text`
+
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+use clap::ArgEnum;
+use http::{Method, Response, StatusCode, Uri};
+use serde_json::{json, Value};
+
+use crate::{
+    helpers::iso_date,
+    http_request::RequestError,
+    middleware::{Exchange, Middleware},
+    spool::body_len,
+    warnings::{warn, WarningKind},
+};
+
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogFormat {
+    /// One JSON object per line
+    Json,
+    /// key=value pairs, one request per line
+    Text,
+}
+
+/// How one request went
+#[derive(Clone, Debug, PartialEq)]
+pub struct LogEntry {
+    pub started: SystemTime,
+    pub method: Method,
+    pub url: String,
+    pub status: Option<StatusCode>,
+    /// Size of the body received
+    pub bytes: Option<u64>,
+    pub took: Duration,
+    pub error: Option<String>,
+}
+
+impl LogEntry {
+    pub fn new(
+        started: SystemTime,
+        method: &Method,
+        uri: &Uri,
+        result: Result<&Response<Vec<u8>>, &RequestError>,
+        took: Duration,
+    ) -> Self {
+        let response = result.ok();
+
+        Self {
+            started,
+            method: method.clone(),
+            url: uri.to_string(),
+            status: response.map(Response::status),
+            bytes: response.map(body_len),
+            took,
+            error: result.err().map(ToString::to_string),
+        }
+    }
+
+    /// The entry as a JSON object, with `null` for what's missing
+    /// ```
+    /// # use httpc::request_log::LogEntry;
+    /// # use std::time::{Duration, UNIX_EPOCH};
+    /// let entry = LogEntry {
+    ///     started: UNIX_EPOCH,
+    ///     method: http::Method::GET,
+    ///     url: "http://example.com/".to_string(),
+    ///     status: None,
+    ///     bytes: None,
+    ///     took: Duration::from_micros(1500),
+    ///     error: Some("Connection refused".to_string()),
+    /// };
+    /// assert_eq!(
+    ///     entry.to_json().to_string(),
+    ///     r#"{"timestamp":"1970-01-01T00:00:00.000Z","method":"GET","url":"http://example.com/","status":null,"bytes":null,"duration_ms":1.5,"error":"Connection refused"}"#
+    /// );
+    /// ```
+    pub fn to_json(&self) -> Value {
+        json!({
+            "timestamp": iso_date(self.started),
+            "method": self.method.as_str(),
+            "url": self.url,
+            "status": self.status.map(|status| status.as_u16()),
+            "bytes": self.bytes,
+            "duration_ms": self.took.as_micros() as f64 / 1000.0,
+            "error": self.error,
+        })
+    }
+
+    /// The entry as logfmt, leaving out what's missing
+    /// ```
+    /// # use httpc::request_log::LogEntry;
+    /// # use std::time::{Duration, UNIX_EPOCH};
+    /// let entry = LogEntry {
+    ///     started: UNIX_EPOCH,
+    ///     method: http::Method::GET,
+    ///     url: "http://example.com/".to_string(),
+    ///     status: Some(http::StatusCode::OK),
+    ///     bytes: Some(512),
+    ///     took: Duration::from_millis(20),
+    ///     error: None,
+    /// };
+    /// assert_eq!(
+    ///     entry.to_text(),
+    ///     "timestamp=1970-01-01T00:00:00.000Z method=GET url=http://example.com/ status=200 bytes=512 duration_ms=20.0"
+    /// );
+    /// ```
+    pub fn to_text(&self) -> String {
+        let mut fields = vec![
+            ("timestamp", iso_date(self.started)),
+            ("method", self.method.to_string()),
+            ("url", self.url.clone()),
+        ];
+
+        fields.extend(
+            self.status
+                .map(|status| ("status", status.as_u16().to_string())),
+        );
+        fields.extend(self.bytes.map(|bytes| ("bytes", bytes.to_string())));
+        fields.push((
+            "duration_ms",
+            format!("{:.1}", self.took.as_secs_f64() * 1000.0),
+        ));
+        fields.extend(self.error.as_ref().map(|error| ("error", error.clone())));
+
+        fields
+            .into_iter()
+            .map(|(key, value)| format!("{}={}", key, logfmt_value(&value)))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// A value quoted (with `"` and `\` escaped) if it has spaces or quotes, and on one line
+fn logfmt_value(value: &str) -> String {
+    if !value.is_empty() && !value.contains([' ', '"', '=', '\\', '\n', '\r']) {
+        return value.to_string();
+    }
+
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r");
+
+    format!("\"{}\"", escaped)
+}
+
+/// The file `--log-file` appends to, shared by every request (and thread)
+#[derive(Clone, Debug)]
+pub struct RequestLog {
+    file: Arc<Mutex<File>>,
+    format: LogFormat,
+}
+
+impl RequestLog {
+    /// Open the log to add to, creating it if it isn't there
+    pub fn open(path: &Path, format: LogFormat) -> Result<Self, RequestError> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|err| format!("Can't open {}: {}", path.display(), err))?;
+
+        Ok(Self {
+            file: Arc::new(Mutex::new(file)),
+            format,
+        })
+    }
+
+    /// Append an entry, as one line so entries from different threads don't get mixed up
+    pub fn record(&self, entry: &LogEntry) -> Result<(), RequestError> {
+        let line = match self.format {
+            LogFormat::Json => entry.to_json().to_string(),
+            LogFormat::Text => entry.to_text(),
+        };
+
+        let mut file = self
+            .file
+            .lock()
+            .map_err(|_| "--log-file was poisoned by a panic")?;
+
+        file.write_all(format!("{}\n", line).as_bytes())?;
+        Ok(())
+    }
+}
+
+impl Middleware for RequestLog {
+    fn after_receive(
+        &self,
+        exchange: &Exchange,
+        response: &mut Response<Vec<u8>>,
+    ) -> Result<(), RequestError> {
+        let request = exchange.request;
+
+        self.record(&LogEntry::new(
+            exchange.started,
+            request.method(),
+            request.uri(),
+            Ok(response),
+            exchange.took,
+        ))
+    }
+
+    fn on_error(&self, exchange: &Exchange, err: &RequestError) {
+        let request = exchange.request;
+        let entry = LogEntry::new(
+            exchange.started,
+            request.method(),
+            request.uri(),
+            Err(err),
+            exchange.took,
+        );
+
+        if let Err(err) = self.record(&entry) {
+            warn(
+                WarningKind::Usage,
+                format!("Couldn't write to --log-file: {}", err),
+            );
+        }
+    }
+}
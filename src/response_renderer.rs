@@ -0,0 +1,198 @@
+//! How a response is shown, picked with `--format`: colored for the terminal (`text`), the same
+//! without colors or images (`plain`), a JSON object for other tools (`json`), or the body exactly
+//! as it was received (`raw`)
+//!
+//! Anything that implements [`ResponseRenderer`] can show a response, so a program using httpc as
+//! a library can bring its own:
+//!
+//! ```
+//! use std::io::Write;
+//! use httpc::{http_request::RequestError, response_renderer::ResponseRenderer};
+//!
+//! /// Only the status code
+//! struct StatusOnly;
+//!
+//! impl ResponseRenderer for StatusOnly {
+//!     fn render(
+//!         &self,
+//!         response: &http::Response<Vec<u8>>,
+//!         out: &mut dyn Write,
+//!     ) -> Result<(), RequestError> {
+//!         writeln!(out, "{}", response.status().as_u16())?;
+//!         Ok(())
+//!     }
+//! }
+//!
+//! let mut out = vec![];
+//! let response = http::Response::builder().status(404).body(vec![]).unwrap();
+//! StatusOnly.render(&response, &mut out).unwrap();
+//! assert_eq!(out, b"404\n");
+//! ```
+
+use std::io::Write;
+
+use http::Response;
+
+use crate::{
+    cli::CommonOptions,
+    helpers::{format_response, response_head},
+    http_request::RequestError,
+    json_output::response_json,
+    pretty::Pretty,
+    render::Renderers,
+    spool::copy_body,
+    warnings::{take_warnings, warnings_json},
+};
+
+/// Something that can show a response
+pub trait ResponseRenderer {
+    /// Write `response` to `out`
+    fn render(&self, response: &Response<Vec<u8>>, out: &mut dyn Write)
+        -> Result<(), RequestError>;
+}
+
+/// Readable and colored (when the terminal supports it): the status and headers with -v, then the
+/// body reindented/highlighted, or as a hexdump or an image
+#[derive(Clone, Debug)]
+pub struct TerminalRenderer {
+    pub verbosity: u8,
+    pub hex: bool,
+    pub pretty: Pretty,
+    pub renderers: Renderers,
+}
+
+impl From<&CommonOptions> for TerminalRenderer {
+    fn from(options: &CommonOptions) -> Self {
+        Self {
+            verbosity: options.verbosity,
+            hex: options.hex,
+            pretty: options.pretty,
+            renderers: options.renderers.clone(),
+        }
+    }
+}
+
+impl ResponseRenderer for TerminalRenderer {
+    fn render(
+        &self,
+        response: &Response<Vec<u8>>,
+        out: &mut dyn Write,
+    ) -> Result<(), RequestError> {
+        let formatted = format_response(
+            response,
+            self.verbosity,
+            self.hex,
+            self.pretty,
+            &self.renderers,
+        )?;
+
+        writeln!(out, "{}", formatted)?;
+        Ok(())
+    }
+}
+
+/// What [`TerminalRenderer`] shows, without any colors (or inline images), for logs and
+/// terminals that don't handle escape codes
+#[derive(Clone, Debug)]
+pub struct PlainRenderer(pub TerminalRenderer);
+
+impl ResponseRenderer for PlainRenderer {
+    fn render(
+        &self,
+        response: &Response<Vec<u8>>,
+        out: &mut dyn Write,
+    ) -> Result<(), RequestError> {
+        let mut rendered = vec![];
+        self.0.render(response, &mut rendered)?;
+
+        out.write_all(strip_escapes(&String::from_utf8_lossy(&rendered)).as_bytes())?;
+        Ok(())
+    }
+}
+
+/// One JSON object with the status, headers, timings, redirects, body and any warnings so far
+/// (which then aren't shown again at the end), see [`response_json`]
+#[derive(Clone, Copy, Debug)]
+pub struct JsonRenderer;
+
+impl ResponseRenderer for JsonRenderer {
+    fn render(
+        &self,
+        response: &Response<Vec<u8>>,
+        out: &mut dyn Write,
+    ) -> Result<(), RequestError> {
+        let mut json = response_json(response);
+        json["warnings"] = warnings_json(&take_warnings()).into();
+
+        writeln!(out, "{}", json)?;
+        Ok(())
+    }
+}
+
+/// The body exactly as it was received, so binary data survives, after the status line and
+/// headers if `include`
+#[derive(Clone, Copy, Debug)]
+pub struct RawRenderer {
+    pub include: bool,
+}
+
+impl ResponseRenderer for RawRenderer {
+    fn render(
+        &self,
+        response: &Response<Vec<u8>>,
+        out: &mut dyn Write,
+    ) -> Result<(), RequestError> {
+        if self.include {
+            out.write_all(&response_head(response))?;
+        }
+
+        copy_body(response, out)?;
+        Ok(())
+    }
+}
+
+/// `text` without ANSI escape codes: colors and styles (CSI), and links and inline images (OSC
+/// and APC)
+/// ```
+/// # use httpc::response_renderer::strip_escapes;
+/// assert_eq!(strip_escapes("\x1b[36mcontent-type\x1b[0m: \x1b[1;35mtext/html\x1b[0m"), "content-type: text/html");
+/// assert_eq!(strip_escapes("a\x1b]1337;File=inline=1:AAAA\x07b\x1b_Gf=100;AAAA\x1b\\c"), "abc");
+/// ```
+pub fn strip_escapes(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            stripped.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            // CSI, up to a final byte from @ to ~
+            Some('[') => {
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            // OSC, APC and the like, up to BEL or ESC \
+            Some(']' | '_' | 'P' | '^') => {
+                while let Some(c) = chars.next() {
+                    if c == '\x07' {
+                        break;
+                    }
+
+                    if c == '\x1b' && chars.peek() == Some(&'\\') {
+                        chars.next();
+                        break;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    stripped
+}
@@ -0,0 +1,124 @@
+use std::{fs, io, str::FromStr};
+
+use http::{header, Response, StatusCode};
+
+use crate::http_request::RequestError;
+
+/// Where to resume a download from
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ContinueAt {
+    /// Continue from the end of the existing output file (`-C -`)
+    Auto,
+    /// Continue from a specific byte offset
+    Offset(u64),
+}
+
+impl FromStr for ContinueAt {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "-" => Ok(ContinueAt::Auto),
+            offset => offset
+                .parse::<u64>()
+                .map(ContinueAt::Offset)
+                .map_err(|_| format!("Invalid offset: '{}', expected a number or '-'", offset)),
+        }
+    }
+}
+
+impl ContinueAt {
+    /// Get the offset to resume from, given the file we're writing to
+    pub fn offset(self, file: &str) -> io::Result<u64> {
+        match self {
+            ContinueAt::Offset(offset) => Ok(offset),
+            ContinueAt::Auto => match fs::metadata(file) {
+                Ok(metadata) => Ok(metadata.len()),
+                Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(0),
+                Err(err) => Err(err),
+            },
+        }
+    }
+}
+
+/// What to do with the response to a range request
+#[derive(Debug, PartialEq, Eq)]
+pub enum Resume {
+    /// The server sent the rest of the file, append the body
+    Append,
+    /// There's nothing left to download
+    AlreadyComplete,
+}
+
+/// Make sure the server actually respected our `Range: bytes=<offset>-` request
+///
+/// Anything other than a matching 206 (or a 416 saying we already have it all) is an error,
+/// since appending the body would corrupt the file
+pub fn check_resumed_response(
+    response: &Response<Vec<u8>>,
+    offset: u64,
+) -> Result<Resume, RequestError> {
+    let content_range = response
+        .headers()
+        .get(header::CONTENT_RANGE)
+        .and_then(|value| value.to_str().ok())
+        .map(parse_content_range);
+
+    match response.status() {
+        StatusCode::PARTIAL_CONTENT => match content_range {
+            Some(Some((Some((start, _)), _))) if start == offset => Ok(Resume::Append),
+            Some(Some((Some((start, _)), _))) => Err(format!(
+                "Server resumed from byte {} but we asked for byte {}, not appending",
+                start, offset
+            )
+            .into()),
+            _ => Err(
+                "Server sent partial content without a valid Content-Range, not appending".into(),
+            ),
+        },
+        StatusCode::RANGE_NOT_SATISFIABLE => match content_range {
+            Some(Some((None, Some(total)))) if total == offset => Ok(Resume::AlreadyComplete),
+            _ => Err(format!(
+                "Server can't resume from byte {} (416 Range Not Satisfiable)",
+                offset
+            )
+            .into()),
+        },
+        status if status.is_success() => Err(format!(
+            "Server ignored the range request and sent the whole file ({}), not resuming",
+            status
+        )
+        .into()),
+        status => Err(format!("Can't resume download, server responded with {}", status).into()),
+    }
+}
+
+/// Parse a Content-Range header into `(range, complete length)`
+///
+/// ```
+/// # use httpc::resume::parse_content_range;
+/// assert_eq!(parse_content_range("bytes 100-199/500"), Some((Some((100, 199)), Some(500))));
+/// assert_eq!(parse_content_range("bytes 100-199/*"), Some((Some((100, 199)), None)));
+/// assert_eq!(parse_content_range("bytes */500"), Some((None, Some(500))));
+/// assert_eq!(parse_content_range("pages 1-2/3"), None);
+/// ```
+#[allow(clippy::type_complexity)]
+pub fn parse_content_range(value: &str) -> Option<(Option<(u64, u64)>, Option<u64>)> {
+    let range = value.trim().strip_prefix("bytes ")?;
+    let (range, total) = range.split_once('/')?;
+
+    let total = match total.trim() {
+        "*" => None,
+        total => Some(total.parse().ok()?),
+    };
+
+    let range = match range.trim() {
+        "*" => None,
+        range => {
+            let (start, end) = range.split_once('-')?;
+            Some((start.trim().parse().ok()?, end.trim().parse().ok()?))
+        }
+    };
+
+    Some((range, total))
+}
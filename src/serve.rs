@@ -0,0 +1,300 @@
+//! `httpc serve`: a tiny HTTP/1.1 server to try requests against, without installing a real one
+//!
+//! GET and HEAD requests for a file in the directory get the file (or a directory's
+//! `index.html`), anything else gets the request echoed back as JSON:
+//!
+//! ```text
+//! {"method": "POST", "target": "/things?a=1", "path": "/things", "query": "a=1",
+//!  "http_version": "HTTP/1.1", "headers": [{"name": "...", "value": "..."}],
+//!  "trailers": [], "body": "...", "body_encoding": "utf-8"}
+//! ```
+//!
+//! Bodies that aren't valid UTF-8 are base64 encoded, like `--format json`. It's meant for
+//! testing, not the internet: each connection gets a thread and files are read whole into memory
+
+use std::{
+    io::{BufReader, BufWriter, Write},
+    net::{TcpListener, TcpStream},
+    path::{Path, PathBuf},
+    sync::Arc,
+    thread,
+};
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use http::{header, HeaderValue, Method, Request, Response, StatusCode, Version};
+use owo_colors::{OwoColorize, Stream};
+use serde_json::{json, Value};
+
+use crate::{
+    cache::write_message,
+    cli::ServeOptions,
+    helpers::{expand_home, percent_decode},
+    http_request::{parse_http_request, RequestError, Trailers},
+    json_output::headers_json,
+};
+
+/// Content-Types of files, by extension
+const CONTENT_TYPES: [(&str, &str); 18] = [
+    ("html", "text/html; charset=utf-8"),
+    ("htm", "text/html; charset=utf-8"),
+    ("css", "text/css; charset=utf-8"),
+    ("js", "text/javascript; charset=utf-8"),
+    ("json", "application/json"),
+    ("xml", "application/xml"),
+    ("txt", "text/plain; charset=utf-8"),
+    ("csv", "text/csv; charset=utf-8"),
+    ("md", "text/markdown; charset=utf-8"),
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("gif", "image/gif"),
+    ("webp", "image/webp"),
+    ("svg", "image/svg+xml"),
+    ("ico", "image/x-icon"),
+    ("pdf", "application/pdf"),
+    ("wasm", "application/wasm"),
+];
+
+/// Serve until killed, logging every request to stderr
+pub fn run_serve(options: &ServeOptions) -> Result<(), RequestError> {
+    let root = expand_home(&options.dir);
+
+    if !root.is_dir() {
+        return Err(format!("{} isn't a directory", root.display()).into());
+    }
+
+    let listener = TcpListener::bind((options.bind, options.port))
+        .map_err(|err| format!("Can't listen on {}:{}: {}", options.bind, options.port, err))?;
+
+    eprintln!(
+        "{}",
+        format!(
+            "Serving {} on http://{}",
+            root.display(),
+            listener.local_addr()?
+        )
+        .if_supports_color(Stream::Stderr, |t| t.bold())
+    );
+
+    let root = Arc::new(root);
+
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else {
+            continue;
+        };
+
+        let root = root.clone();
+        thread::spawn(move || {
+            // The other end hanging up early isn't our problem
+            let _ = serve_connection(stream, &root);
+        });
+    }
+
+    Ok(())
+}
+
+/// Answer requests on a connection until either end wants it closed
+fn serve_connection(stream: TcpStream, root: &Path) -> Result<(), RequestError> {
+    let peer = stream.peer_addr()?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = BufWriter::new(stream);
+
+    loop {
+        let request = match parse_http_request(&mut reader) {
+            Ok(Some(request)) => request,
+            Ok(None) => return Ok(()),
+            Err(err) => {
+                let mut response = Response::new(format!("{}\n", err).into_bytes());
+                *response.status_mut() = StatusCode::BAD_REQUEST;
+                close(&mut response);
+                write_message(&response, &mut writer)?;
+                writer.flush()?;
+                return Ok(());
+            }
+        };
+
+        let mut response = respond(&request, root)?;
+        let keep_alive = keeps_alive(&request);
+
+        if !keep_alive {
+            close(&mut response);
+        } else if request.version() == Version::HTTP_10 {
+            response
+                .headers_mut()
+                .insert(header::CONNECTION, HeaderValue::from_static("keep-alive"));
+        }
+
+        log(&peer.to_string(), &request, &response);
+
+        let mut message = vec![];
+        write_message(&response, &mut message)?;
+
+        // HEAD gets the same headers as GET would, just not the body
+        if request.method() == Method::HEAD {
+            let head_end = message
+                .windows(4)
+                .position(|window| window == b"\r\n\r\n")
+                .map_or(message.len(), |at| at + 4);
+            message.truncate(head_end);
+        }
+
+        writer.write_all(&message)?;
+        writer.flush()?;
+
+        if !keep_alive {
+            return Ok(());
+        }
+    }
+}
+
+/// The file that was asked for, or the request echoed back
+fn respond(request: &Request<Vec<u8>>, root: &Path) -> Result<Response<Vec<u8>>, RequestError> {
+    let file = match *request.method() {
+        Method::GET | Method::HEAD => find_file(root, request.uri().path()),
+        _ => None,
+    };
+
+    let (body, content_type) = match file {
+        Some(file) => {
+            let body = std::fs::read(&file)?;
+            let content_type = content_type(&file, &body);
+            (body, content_type)
+        }
+        None => {
+            let mut body = serde_json::to_vec_pretty(&request_json(request))?;
+            body.push(b'\n');
+            (body, "application/json")
+        }
+    };
+
+    let response = Response::builder()
+        .header(header::CONTENT_TYPE, content_type)
+        .header(
+            header::SERVER,
+            format!("httpc/{}", env!("CARGO_PKG_VERSION")),
+        )
+        .body(body)?;
+
+    Ok(response)
+}
+
+/// The request as a JSON object, see the module docs
+/// ```
+/// # use httpc::serve::request_json;
+/// let request = http::Request::post("/things?a=1").header("a", "1").body(b"hi".to_vec()).unwrap();
+/// let json = request_json(&request);
+/// assert_eq!((json["method"].as_str(), json["path"].as_str(), json["query"].as_str()), (Some("POST"), Some("/things"), Some("a=1")));
+/// assert_eq!(json["headers"][0]["value"], "1");
+/// assert_eq!((json["body"].as_str(), json["body_encoding"].as_str()), (Some("hi"), Some("utf-8")));
+/// ```
+pub fn request_json(request: &Request<Vec<u8>>) -> Value {
+    let trailers = request
+        .extensions()
+        .get::<Trailers>()
+        .map(|trailers| headers_json(&trailers.0))
+        .unwrap_or_default();
+
+    let (body, encoding) = match std::str::from_utf8(request.body()) {
+        Ok(text) => (text.to_string(), "utf-8"),
+        Err(_) => (BASE64.encode(request.body()), "base64"),
+    };
+
+    json!({
+        "method": request.method().as_str(),
+        "target": request.uri().to_string(),
+        "path": request.uri().path(),
+        "query": request.uri().query(),
+        "http_version": format!("{:?}", request.version()),
+        "headers": headers_json(request.headers()),
+        "trailers": trailers,
+        "body": body,
+        "body_encoding": encoding,
+    })
+}
+
+/// The file a path points to in `root`, `None` if there isn't one or the path tries to leave it
+fn find_file(root: &Path, path: &str) -> Option<PathBuf> {
+    let path = String::from_utf8(percent_decode(path)).ok()?;
+    let mut file = root.to_path_buf();
+
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => return None,
+            segment => file.push(segment),
+        }
+    }
+
+    if file.is_dir() {
+        file.push("index.html");
+    }
+
+    file.is_file().then_some(file)
+}
+
+/// Going by the extension, or whether it looks like text if it's one we don't know
+/// ```
+/// # use std::path::Path;
+/// # use httpc::serve::content_type;
+/// assert_eq!(content_type(Path::new("a/b.JSON"), b"{}"), "application/json");
+/// assert_eq!(content_type(Path::new("notes"), b"hi"), "text/plain; charset=utf-8");
+/// ```
+pub fn content_type(file: &Path, body: &[u8]) -> &'static str {
+    let extension = file
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_ascii_lowercase());
+
+    CONTENT_TYPES
+        .iter()
+        .find(|(known, _)| extension.as_deref() == Some(*known))
+        .map(|(_, content_type)| *content_type)
+        .unwrap_or(match std::str::from_utf8(body) {
+            Ok(_) => "text/plain; charset=utf-8",
+            Err(_) => "application/octet-stream",
+        })
+}
+
+/// Whether the client wants the connection kept open after this request
+fn keeps_alive(request: &Request<Vec<u8>>) -> bool {
+    let connection = request
+        .headers()
+        .get(header::CONNECTION)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.to_ascii_lowercase())
+        .unwrap_or_default();
+
+    match request.version() {
+        Version::HTTP_10 => connection.contains("keep-alive"),
+        _ => !connection.contains("close"),
+    }
+}
+
+fn close(response: &mut Response<Vec<u8>>) {
+    response
+        .headers_mut()
+        .insert(header::CONNECTION, HeaderValue::from_static("close"));
+}
+
+fn log(peer: &str, request: &Request<Vec<u8>>, response: &Response<Vec<u8>>) {
+    let status = response.status();
+    let status = if status.is_success() {
+        status
+            .as_u16()
+            .if_supports_color(Stream::Stderr, |t| t.green())
+            .to_string()
+    } else {
+        status
+            .as_u16()
+            .if_supports_color(Stream::Stderr, |t| t.red())
+            .to_string()
+    };
+
+    eprintln!(
+        "{} {} {} {}",
+        peer.if_supports_color(Stream::Stderr, |t| t.bright_black()),
+        request.method(),
+        request.uri(),
+        status
+    );
+}
@@ -0,0 +1,173 @@
+//! The `Server-Timing` header (https://www.w3.org/TR/server-timing/), where servers say what
+//! they spent their time on, e.g. `db;dur=53.2;desc="Database", cache;desc="hit"`
+
+use http::{HeaderMap, Response};
+use owo_colors::OwoColorize;
+
+use crate::{
+    events::ConnectionEvents, helpers::MColorize, http_request::Trailers, write_out::Timings,
+};
+
+/// One metric from a `Server-Timing` header
+#[derive(Clone, Debug, PartialEq)]
+pub struct Metric {
+    pub name: String,
+    /// In milliseconds
+    pub duration: Option<f64>,
+    pub description: Option<String>,
+}
+
+/// Parse a `Server-Timing` header value, skipping anything malformed
+/// ```
+/// # use httpc::server_timing::parse_server_timing;
+/// let metrics = parse_server_timing(r#"db;dur=53.2;desc="Database; main", cache;desc=hit, total;dur=80"#);
+/// assert_eq!(metrics.len(), 3);
+/// assert_eq!((metrics[0].name.as_str(), metrics[0].duration), ("db", Some(53.2)));
+/// assert_eq!(metrics[0].description.as_deref(), Some("Database; main"));
+/// assert_eq!((metrics[1].duration, metrics[1].description.as_deref()), (None, Some("hit")));
+/// ```
+pub fn parse_server_timing(value: &str) -> Vec<Metric> {
+    split_unquoted(value, ',')
+        .iter()
+        .filter_map(|metric| {
+            let mut params = split_unquoted(metric, ';').into_iter();
+            let name = params.next()?.trim().to_string();
+
+            if name.is_empty() {
+                return None;
+            }
+
+            let mut metric = Metric {
+                name,
+                duration: None,
+                description: None,
+            };
+
+            for param in params {
+                let Some((key, value)) = param.split_once('=') else {
+                    continue;
+                };
+                let value = value.trim();
+                let value = match value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+                    Some(quoted) => quoted.replace("\\\"", "\"").replace("\\\\", "\\"),
+                    None => value.to_string(),
+                };
+
+                // Only the first of each counts
+                match key.trim().to_ascii_lowercase().as_str() {
+                    "dur" if metric.duration.is_none() => metric.duration = value.parse().ok(),
+                    "desc" if metric.description.is_none() => metric.description = Some(value),
+                    _ => {}
+                }
+            }
+
+            Some(metric)
+        })
+        .collect()
+}
+
+/// Every metric the response has, from its headers and then its trailers
+pub fn server_timing(response: &Response<Vec<u8>>) -> Vec<Metric> {
+    let mut metrics = header_metrics(response.headers());
+
+    if let Some(trailers) = response.extensions().get::<Trailers>() {
+        metrics.extend(header_metrics(&trailers.0));
+    }
+
+    metrics
+}
+
+fn header_metrics(headers: &HeaderMap) -> Vec<Metric> {
+    headers
+        .get_all("server-timing")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(parse_server_timing)
+        .collect()
+}
+
+/// Split on `separator`, except inside quoted strings
+fn split_unquoted(text: &str, separator: char) -> Vec<&str> {
+    let mut parts = vec![];
+    let mut start = 0;
+    let mut quoted = false;
+    let mut escaped = false;
+
+    for (i, c) in text.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            c if c == separator && !quoted => {
+                parts.push(&text[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+
+    parts.push(&text[start..]);
+    parts
+}
+
+/// How long each step of the request took on our end (from its [`ConnectionEvents`]), then what
+/// the server says it spent its time on, for `--timing`
+pub fn format_timing(response: &Response<Vec<u8>>) -> String {
+    let millis =
+        |duration: std::time::Duration| format!("{:.1}ms", duration.as_secs_f64() * 1000.0);
+
+    let client = match response.extensions().get::<ConnectionEvents>() {
+        Some(events) => {
+            let mut phases = events
+                .phases()
+                .into_iter()
+                .map(|(name, took)| format!("{} {}", name, millis(took)))
+                .collect::<Vec<_>>();
+
+            if let Some(timings) = response.extensions().get::<Timings>() {
+                phases.push(format!("total {}", millis(timings.total)));
+            }
+
+            let peer = events
+                .peer()
+                .map_or_else(String::new, |peer| format!(" ({})", peer));
+
+            match events.reused() {
+                true => format!("reused connection, {}{}", phases.join(", "), peer),
+                false => format!("{}{}", phases.join(", "), peer),
+            }
+        }
+        None => "cached, no request was made".to_string(),
+    };
+
+    let server = server_timing(response)
+        .iter()
+        .map(|metric| {
+            let mut formatted = metric.name.clone();
+
+            if let Some(duration) = metric.duration {
+                formatted.push_str(&format!(" {:.1}ms", duration));
+            }
+
+            if let Some(description) = &metric.description {
+                formatted.push_str(&format!(" ({})", description));
+            }
+
+            formatted
+        })
+        .collect::<Vec<_>>();
+
+    let server = if server.is_empty() {
+        "none sent".out_color(|t| t.bright_black()).to_string()
+    } else {
+        server.join(", ")
+    };
+
+    format!(
+        "{} {}\n{} {}\n",
+        "Client timing:".out_color(|t| t.cyan()),
+        client,
+        "Server timing:".out_color(|t| t.cyan()),
+        server
+    )
+}
@@ -0,0 +1,183 @@
+//! Named sessions that remember cookies, headers and auth between runs
+//!
+//! A session is a TOML file in `~/.config/httpc/sessions/<name>.toml` (or any path, if the name
+//! has a `/` in it). Headers & `-u` given while using a session are saved into it, and cookies
+//! the server sets are kept and sent back on later requests. Variables captured by a profile's
+//! bootstrap are kept too, for `{{NAME}}` placeholders, along with when that login expires
+
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use http::Response;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cli::CommonOptions,
+    config::config_dir,
+    cookies::CookieJar,
+    helpers::{expand_home, has_header},
+    http_request::RequestError,
+    jwt::jwt_expiry,
+    middleware::{Exchange, Middleware},
+};
+
+/// Headers that describe a single request, so there's no point remembering them
+const IGNORED_HEADERS: [&str; 9] = [
+    "content-type",
+    "content-length",
+    "content-digest",
+    "content-md5",
+    "cookie",
+    "if-none-match",
+    "if-modified-since",
+    "if-unmodified-since",
+    "range",
+];
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Session {
+    #[serde(skip)]
+    pub path: PathBuf,
+    /// Headers sent with every request, as `Name: value`
+    #[serde(default)]
+    pub headers: Vec<String>,
+    /// Basic auth credentials, same as `-u`
+    pub user: Option<String>,
+    /// Variables captured by the profile's bootstrap
+    #[serde(default)]
+    pub variables: BTreeMap<String, String>,
+    /// When the bootstrap's login expires, in seconds since the Unix epoch (from an `expires_in`
+    /// capture)
+    pub expires: Option<u64>,
+    #[serde(default)]
+    pub cookies: CookieJar,
+}
+
+impl Session {
+    /// The file a session is stored in
+    pub fn path(name: &str) -> Result<PathBuf, RequestError> {
+        if name.contains('/') || name.contains(std::path::MAIN_SEPARATOR) {
+            return Ok(expand_home(name));
+        }
+
+        let dir = config_dir().ok_or("Can't find a config directory to keep sessions in")?;
+        Ok(dir.join("sessions").join(format!("{}.toml", name)))
+    }
+
+    /// Load a session, which starts out empty if it doesn't exist yet
+    pub fn load(name: &str) -> Result<Self, RequestError> {
+        let path = Self::path(name)?;
+
+        let mut session: Session = match fs::read_to_string(&path) {
+            Ok(contents) => toml::from_str(&contents)
+                .map_err(|err| format!("Invalid session {}: {}", path.display(), err))?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Session::default(),
+            Err(err) => {
+                return Err(format!("Can't read session {}: {}", path.display(), err).into())
+            }
+        };
+
+        session.path = path;
+        Ok(session)
+    }
+
+    /// Remember the headers & auth given on the command line, then use the rest of the session's
+    /// headers & auth for anything that wasn't given
+    pub fn apply(&mut self, options: &mut CommonOptions) {
+        for header in &options.header {
+            let Some((name, _)) = header.split_once(':') else {
+                continue;
+            };
+            let name = name.trim();
+
+            if IGNORED_HEADERS.contains(&name.to_ascii_lowercase().as_str()) {
+                continue;
+            }
+
+            self.headers
+                .retain(|saved| !has_header(std::slice::from_ref(saved), name));
+            self.headers.push(header.clone());
+        }
+
+        let saved = self
+            .headers
+            .iter()
+            .filter(|saved| {
+                saved
+                    .split_once(':')
+                    .is_some_and(|(name, _)| !has_header(&options.header, name.trim()))
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        options.header.splice(0..0, saved);
+
+        match &options.user {
+            Some(user) => self.user = Some(user.clone()),
+            None => options.user = self.user.clone(),
+        }
+    }
+
+    /// If the login expires within `leeway`, going by [`Session::expires`] and any of
+    /// `variables` that are JWTs
+    pub fn login_expired(&self, variables: &[&str], leeway: Duration) -> bool {
+        let deadline = SystemTime::now() + leeway;
+
+        // Too far off to be a time doesn't expire
+        let expires = self
+            .expires
+            .and_then(|expires| UNIX_EPOCH.checked_add(Duration::from_secs(expires)));
+        let jwts = variables
+            .iter()
+            .filter_map(|name| self.variables.get(*name))
+            .filter_map(|value| jwt_expiry(value));
+
+        expires
+            .into_iter()
+            .chain(jwts)
+            .any(|expiry| expiry <= deadline)
+    }
+
+    /// Write the session back to its file, only readable by us since it's full of credentials
+    pub fn save(&self) -> Result<(), RequestError> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let contents = toml::to_string(self)?;
+
+        let mut file = fs::OpenOptions::new();
+        file.write(true).create(true).truncate(true);
+
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut file, 0o600);
+
+        io::Write::write_all(&mut file.open(&self.path)?, contents.as_bytes())
+            .map_err(|err| format!("Can't save session {}: {}", self.path.display(), err).into())
+    }
+}
+
+/// Keep the cookies every response sets (redirects included) in a session, before any follow-up
+/// request is made
+#[derive(Clone, Debug)]
+pub struct StoreCookies(pub Arc<Mutex<Session>>);
+
+impl Middleware for StoreCookies {
+    fn after_receive(
+        &self,
+        exchange: &Exchange,
+        response: &mut Response<Vec<u8>>,
+    ) -> Result<(), RequestError> {
+        if let Ok(mut session) = self.0.lock() {
+            session
+                .cookies
+                .store(exchange.request.uri(), response.headers());
+        }
+
+        Ok(())
+    }
+}
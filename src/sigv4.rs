@@ -0,0 +1,365 @@
+use std::{
+    env,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use hmac::{Hmac, Mac};
+use http::{header, HeaderMap, HeaderValue, Method, Uri};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    helpers::{civil_from_days, host_header, percent_encode, to_hex},
+    http_request::RequestError,
+};
+
+const ALGORITHM: &str = "AWS4-HMAC-SHA256";
+
+/// Presigned URLs can't be valid for longer than a week
+pub const MAX_PRESIGN_EXPIRES: u64 = 7 * 24 * 60 * 60;
+
+/// AWS (or S3-compatible) credentials to sign requests with
+#[derive(Clone, Debug)]
+pub struct Credentials {
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    pub session_token: Option<String>,
+}
+
+impl Credentials {
+    /// Read credentials from the standard `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY`,
+    /// and `AWS_SESSION_TOKEN` environment variables
+    pub fn from_env() -> Option<Self> {
+        Some(Self {
+            access_key_id: env::var("AWS_ACCESS_KEY_ID").ok()?,
+            secret_access_key: env::var("AWS_SECRET_ACCESS_KEY").ok()?,
+            session_token: env::var("AWS_SESSION_TOKEN").ok(),
+        })
+    }
+
+    /// Credentials from `-u ACCESS_KEY:SECRET_KEY`, or the environment without one
+    pub fn from_user_or_env(user: Option<&str>) -> Result<Self, RequestError> {
+        match user {
+            Some(user) => {
+                let (access_key_id, secret_access_key) = user
+                    .split_once(':')
+                    .ok_or("With --aws-sigv4, -u is ACCESS_KEY:SECRET_KEY")?;
+
+                Ok(Self {
+                    access_key_id: access_key_id.to_string(),
+                    secret_access_key: secret_access_key.to_string(),
+                    session_token: env::var("AWS_SESSION_TOKEN").ok(),
+                })
+            }
+            None => Self::from_env().ok_or_else(|| {
+                "No AWS credentials: use -u ACCESS_KEY:SECRET_KEY, or set AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY".into()
+            }),
+        }
+    }
+}
+
+/// The region and service to sign requests for, from `--aws-sigv4 REGION/SERVICE`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SigningScope {
+    pub region: String,
+    pub service: String,
+}
+
+/// Parse a `REGION/SERVICE` like `us-east-1/execute-api`
+/// ```
+/// # use httpc::sigv4::parse_signing_scope;
+/// let scope = parse_signing_scope("eu-west-1/s3").unwrap();
+/// assert_eq!((scope.region.as_str(), scope.service.as_str()), ("eu-west-1", "s3"));
+/// assert!(parse_signing_scope("s3").is_err());
+/// ```
+pub fn parse_signing_scope(scope: &str) -> Result<SigningScope, String> {
+    match scope.split_once('/') {
+        Some((region, service)) if !region.is_empty() && !service.is_empty() => Ok(SigningScope {
+            region: region.to_string(),
+            service: service.to_string(),
+        }),
+        _ => Err(format!(
+            "Invalid scope '{}', it's REGION/SERVICE like us-east-1/s3",
+            scope
+        )),
+    }
+}
+
+/// The region from `AWS_REGION` or `AWS_DEFAULT_REGION`, defaulting to us-east-1
+pub fn region_from_env() -> String {
+    env::var("AWS_REGION")
+        .or_else(|_| env::var("AWS_DEFAULT_REGION"))
+        .unwrap_or_else(|_| "us-east-1".to_string())
+}
+
+/// Signs requests with AWS Signature Version 4
+///
+/// https://docs.aws.amazon.com/IAM/latest/UserGuide/reference_aws-signing.html
+pub struct Signer<'a> {
+    pub credentials: &'a Credentials,
+    pub region: &'a str,
+    pub service: &'a str,
+    pub time: SystemTime,
+}
+
+impl Signer<'_> {
+    /// Create a presigned URL: all the auth goes into the query string, so anyone with the URL
+    /// can make this exact request (until it expires)
+    ///
+    /// Only the Host header is signed, and the payload is left unsigned, like the AWS SDKs do
+    pub fn presign(
+        &self,
+        method: &Method,
+        uri: &Uri,
+        expires: u64,
+    ) -> Result<String, RequestError> {
+        if expires == 0 || expires > MAX_PRESIGN_EXPIRES {
+            return Err(format!(
+                "Presigned URLs must expire in 1 to {} seconds",
+                MAX_PRESIGN_EXPIRES
+            )
+            .into());
+        }
+
+        let (date, datetime) = self.timestamps();
+        let scope = self.scope(&date);
+
+        let mut query = existing_query(uri);
+        let mut add = |key: &str, value: &str| {
+            query.push((percent_encode(key, false), percent_encode(value, false)))
+        };
+
+        add("X-Amz-Algorithm", ALGORITHM);
+        add(
+            "X-Amz-Credential",
+            &format!("{}/{}", self.credentials.access_key_id, scope),
+        );
+        add("X-Amz-Date", &datetime);
+        add("X-Amz-Expires", &expires.to_string());
+        add("X-Amz-SignedHeaders", "host");
+
+        if let Some(token) = &self.credentials.session_token {
+            add("X-Amz-Security-Token", token);
+        }
+
+        let canonical_query = canonical_query(&mut query);
+        let canonical_request = format!(
+            "{}\n{}\n{}\nhost:{}\n\nhost\nUNSIGNED-PAYLOAD",
+            method,
+            canonical_uri(uri),
+            canonical_query,
+            host_header(uri),
+        );
+
+        let signature = self.signature(&canonical_request, &date, &datetime);
+
+        Ok(format!(
+            "{}://{}{}?{}&X-Amz-Signature={}",
+            uri.scheme_str().unwrap_or("https"),
+            host_header(uri),
+            canonical_uri(uri),
+            canonical_query,
+            signature
+        ))
+    }
+
+    /// Sign a request by adding an Authorization header (and X-Amz-Date, plus the session token
+    /// and, for S3, the payload hash) to its headers
+    ///
+    /// Host, Content-Type and any X-Amz-* headers are signed, and the body is hashed into the
+    /// signature. Headers added later (like User-Agent) aren't, so they can't break it
+    /// ```
+    /// # use std::time::{Duration, UNIX_EPOCH};
+    /// # use httpc::sigv4::{Credentials, Signer};
+    /// let credentials = Credentials {
+    ///     access_key_id: "AKIDEXAMPLE".into(),
+    ///     secret_access_key: "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY".into(),
+    ///     session_token: None,
+    /// };
+    /// let signer = Signer {
+    ///     credentials: &credentials,
+    ///     region: "us-east-1",
+    ///     service: "service",
+    ///     time: UNIX_EPOCH + Duration::from_secs(1440938160),
+    /// };
+    /// let mut headers = http::HeaderMap::new();
+    /// let uri = "https://example.amazonaws.com/".parse().unwrap();
+    /// signer.sign(&http::Method::GET, &uri, &mut headers, None).unwrap();
+    /// assert_eq!(headers["x-amz-date"], "20150830T123600Z");
+    /// assert_eq!(
+    ///     headers["authorization"],
+    ///     "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, \
+    ///      SignedHeaders=host;x-amz-date, \
+    ///      Signature=5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31"
+    /// );
+    /// ```
+    pub fn sign(
+        &self,
+        method: &Method,
+        uri: &Uri,
+        headers: &mut HeaderMap,
+        body: Option<&[u8]>,
+    ) -> Result<(), RequestError> {
+        let (date, datetime) = self.timestamps();
+        let payload_hash = to_hex(&Sha256::digest(body.unwrap_or_default()));
+
+        headers.insert("x-amz-date", HeaderValue::from_str(&datetime)?);
+
+        if let Some(token) = &self.credentials.session_token {
+            headers.insert("x-amz-security-token", HeaderValue::from_str(token)?);
+        }
+
+        // Only S3 wants it, and needs it
+        if self.service == "s3" {
+            headers.insert(
+                "x-amz-content-sha256",
+                HeaderValue::from_str(&payload_hash)?,
+            );
+        }
+
+        let mut signed = vec![("host".to_string(), host_header(uri))];
+
+        for name in headers.keys() {
+            let name = name.as_str();
+
+            if name == "content-type" || name == "content-md5" || name.starts_with("x-amz-") {
+                let values = headers
+                    .get_all(name)
+                    .iter()
+                    .map(|value| {
+                        let value = String::from_utf8_lossy(value.as_bytes());
+                        value.split_whitespace().collect::<Vec<_>>().join(" ")
+                    })
+                    .collect::<Vec<_>>();
+                signed.push((name.to_string(), values.join(",")));
+            }
+        }
+
+        signed.sort();
+
+        let canonical_headers = signed
+            .iter()
+            .map(|(name, value)| format!("{}:{}\n", name, value))
+            .collect::<String>();
+        let signed_headers = signed
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>()
+            .join(";");
+
+        // Every service but S3 wants the path encoded a second time
+        let path = match self.service {
+            "s3" => canonical_uri(uri),
+            _ => percent_encode(&canonical_uri(uri), true),
+        };
+
+        let canonical_request = format!(
+            "{}\n{}\n{}\n{}\n{}\n{}",
+            method,
+            path,
+            canonical_query(&mut existing_query(uri)),
+            canonical_headers,
+            signed_headers,
+            payload_hash
+        );
+
+        let signature = self.signature(&canonical_request, &date, &datetime);
+        let authorization = format!(
+            "{} Credential={}/{}, SignedHeaders={}, Signature={}",
+            ALGORITHM,
+            self.credentials.access_key_id,
+            self.scope(&date),
+            signed_headers,
+            signature
+        );
+
+        headers.insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_str(&authorization)?,
+        );
+        Ok(())
+    }
+
+    /// `(YYYYMMDD, YYYYMMDD'T'HHMMSS'Z')` for the signing time, in UTC
+    fn timestamps(&self) -> (String, String) {
+        let seconds = self
+            .time
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default() as i64;
+
+        let (year, month, day) = civil_from_days(seconds.div_euclid(86400));
+        let time_of_day = seconds.rem_euclid(86400);
+
+        let date = format!("{:04}{:02}{:02}", year, month, day);
+        let datetime = format!(
+            "{}T{:02}{:02}{:02}Z",
+            date,
+            time_of_day / 3600,
+            time_of_day % 3600 / 60,
+            time_of_day % 60
+        );
+
+        (date, datetime)
+    }
+
+    /// The credential scope, `date/region/service/aws4_request`
+    fn scope(&self, date: &str) -> String {
+        format!("{}/{}/{}/aws4_request", date, self.region, self.service)
+    }
+
+    /// Hash the canonical request, build the string to sign, then sign it with the derived key
+    fn signature(&self, canonical_request: &str, date: &str, datetime: &str) -> String {
+        let string_to_sign = format!(
+            "{}\n{}\n{}\n{}",
+            ALGORITHM,
+            datetime,
+            self.scope(date),
+            to_hex(&Sha256::digest(canonical_request.as_bytes()))
+        );
+
+        let key = format!("AWS4{}", self.credentials.secret_access_key);
+        let key = hmac_sha256(key.as_bytes(), date.as_bytes());
+        let key = hmac_sha256(&key, self.region.as_bytes());
+        let key = hmac_sha256(&key, self.service.as_bytes());
+        let key = hmac_sha256(&key, b"aws4_request");
+
+        to_hex(&hmac_sha256(&key, string_to_sign.as_bytes()))
+    }
+}
+
+pub fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any size");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// The (already encoded) path, `/` if empty
+fn canonical_uri(uri: &Uri) -> String {
+    match uri.path() {
+        "" => "/".to_string(),
+        path => path.to_string(),
+    }
+}
+
+/// Query parameters already in the URI, kept encoded as-is
+fn existing_query(uri: &Uri) -> Vec<(String, String)> {
+    uri.query()
+        .unwrap_or_default()
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| match pair.split_once('=') {
+            Some((key, value)) => (key.to_string(), value.to_string()),
+            None => (pair.to_string(), String::new()),
+        })
+        .collect()
+}
+
+/// Sort the (encoded) parameters and join them back into a query string
+fn canonical_query(query: &mut [(String, String)]) -> String {
+    query.sort();
+    query
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect::<Vec<_>>()
+        .join("&")
+}
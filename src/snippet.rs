@@ -0,0 +1,170 @@
+//! `httpc snippet`: requests saved under a short name, to run again with `httpc snippet run NAME`
+//!
+//! A snippet is a one-request [collection](crate::collection) in
+//! `~/.config/httpc/snippets/<name>.http`, so it can use `{{NAME}}` placeholders anywhere, and
+//! `--var` given when saving becomes its default values:
+//!
+//! ```text
+//! @id = 1
+//!
+//! ### user
+//! GET https://api.example.com/users/{{id}}
+//! Accept: application/json
+//! ```
+//!
+//! `--var` when running overrides those. The file can be edited by hand for anything else a
+//! collection can do, like captures and connection settings
+
+use std::{fs, io, path::PathBuf};
+
+use http::Method;
+
+use crate::{
+    collection::{parse_collection, Collection},
+    config::config_dir,
+    http_request::RequestError,
+};
+
+/// The file a snippet is stored in
+pub fn snippet_path(name: &str) -> Result<PathBuf, RequestError> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+
+    if !valid {
+        return Err(format!(
+            "Invalid snippet name '{}', it can only have letters, numbers, - and _",
+            name
+        )
+        .into());
+    }
+
+    Ok(snippets_dir()?.join(format!("{}.http", name)))
+}
+
+fn snippets_dir() -> Result<PathBuf, RequestError> {
+    let dir = config_dir().ok_or("Can't find a config directory to keep snippets in")?;
+    Ok(dir.join("snippets"))
+}
+
+/// A snippet as the text of its `.http` file
+/// ```
+/// # use httpc::snippet::format_snippet;
+/// let snippet = format_snippet(
+///     "user",
+///     &http::Method::GET,
+///     "https://api.example.com/users/{{id}}",
+///     &["Accept: application/json".to_string()],
+///     None,
+///     &[("id".to_string(), "1".to_string())],
+/// );
+/// assert_eq!(
+///     snippet,
+///     "@id = 1\n\n### user\nGET https://api.example.com/users/{{id}}\nAccept: application/json\n"
+/// );
+/// ```
+pub fn format_snippet(
+    name: &str,
+    method: &Method,
+    url: &str,
+    headers: &[String],
+    body: Option<&str>,
+    vars: &[(String, String)],
+) -> String {
+    let mut text = String::new();
+
+    for (key, value) in vars {
+        text.push_str(&format!("@{} = {}\n", key, value));
+    }
+
+    if !vars.is_empty() {
+        text.push('\n');
+    }
+
+    text.push_str(&format!("### {}\n{} {}\n", name, method, url));
+
+    for header in headers {
+        text.push_str(&format!("{}\n", header));
+    }
+
+    if let Some(body) = body {
+        text.push_str(&format!("\n{}\n", body));
+    }
+
+    text
+}
+
+/// Save a snippet, replacing any with the same name. Errors if it isn't a valid collection
+///
+/// Only readable by us, since it can have tokens and passwords in its headers
+pub fn save_snippet(name: &str, text: &str) -> Result<PathBuf, RequestError> {
+    parse_collection(text)?;
+
+    let path = snippet_path(name)?;
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let mut file = fs::OpenOptions::new();
+    file.write(true).create(true).truncate(true);
+
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut file, 0o600);
+
+    io::Write::write_all(&mut file.open(&path)?, text.as_bytes())?;
+    Ok(path)
+}
+
+/// Load a snippet, erroring if there isn't one with that name
+pub fn load_snippet(name: &str) -> Result<(PathBuf, Collection), RequestError> {
+    let path = snippet_path(name)?;
+
+    match fs::read_to_string(&path) {
+        Ok(text) => {
+            let collection = parse_collection(&text)
+                .map_err(|err| format!("Invalid snippet {}: {}", path.display(), err))?;
+            Ok((path, collection))
+        }
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Err(format!(
+            "No snippet named '{}', save one with httpc snippet save",
+            name
+        )
+        .into()),
+        Err(err) => Err(format!("Can't read snippet {}: {}", path.display(), err).into()),
+    }
+}
+
+/// Delete a snippet, erroring if there isn't one with that name
+pub fn delete_snippet(name: &str) -> Result<(), RequestError> {
+    match fs::remove_file(snippet_path(name)?) {
+        Ok(()) => Ok(()),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            Err(format!("No snippet named '{}'", name).into())
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// The names of every saved snippet, sorted
+pub fn snippet_names() -> Result<Vec<String>, RequestError> {
+    let entries = match fs::read_dir(snippets_dir()?) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(err) => return Err(err.into()),
+    };
+
+    let mut names = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "http")
+        })
+        .filter_map(|path| Some(path.file_stem()?.to_str()?.to_string()))
+        .collect::<Vec<_>>();
+
+    names.sort();
+    Ok(names)
+}
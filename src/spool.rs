@@ -0,0 +1,119 @@
+//! `--max-memory-body`: bodies bigger than this are written to a temp file as they arrive, so an
+//! unexpectedly huge response can't run us out of memory
+//!
+//! A spooled response has an empty body and a [`Spooled`] extension instead, the helpers here
+//! read from whichever one it has. The file is deleted once the response is dropped
+
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use http::Response;
+
+/// Where a response's body went instead of memory
+#[derive(Debug)]
+pub struct Spooled {
+    path: PathBuf,
+    pub size: u64,
+}
+
+impl Spooled {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn open(&self) -> io::Result<File> {
+        File::open(&self.path)
+    }
+}
+
+impl Drop for Spooled {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// The temp file a body is being written to
+pub(crate) struct Spool {
+    file: BufWriter<File>,
+    spooled: Spooled,
+}
+
+impl Spool {
+    /// A new, empty temp file
+    pub(crate) fn create() -> io::Result<Self> {
+        static SPOOLS: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "httpc-{}-{}.body",
+            std::process::id(),
+            SPOOLS.fetch_add(1, Ordering::Relaxed)
+        ));
+
+        // Responses can have anything in them, so only for us to read
+        let mut file = File::options();
+        file.write(true).create_new(true);
+
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut file, 0o600);
+
+        let file = file.open(&path)?;
+
+        Ok(Self {
+            file: BufWriter::new(file),
+            spooled: Spooled { path, size: 0 },
+        })
+    }
+
+    pub(crate) fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        self.file.write_all(data)?;
+        self.spooled.size += data.len() as u64;
+        Ok(())
+    }
+
+    pub(crate) fn finish(mut self) -> io::Result<Spooled> {
+        self.file.flush()?;
+        Ok(self.spooled)
+    }
+}
+
+/// The size of a response's body, spooled or not
+pub fn body_len(response: &Response<Vec<u8>>) -> u64 {
+    match response.extensions().get::<Spooled>() {
+        Some(spooled) => spooled.size,
+        None => response.body().len() as u64,
+    }
+}
+
+/// Write a response's whole body to `out`, spooled or not
+pub fn copy_body(response: &Response<Vec<u8>>, out: &mut dyn Write) -> io::Result<()> {
+    match response.extensions().get::<Spooled>() {
+        Some(spooled) => {
+            io::copy(&mut spooled.open()?, out)?;
+        }
+        None => out.write_all(response.body())?,
+    }
+
+    Ok(())
+}
+
+/// Up to the first `max` bytes of a response's body, spooled or not
+/// ```
+/// # use httpc::spool::body_prefix;
+/// let response = http::Response::new(b"hello world".to_vec());
+/// assert_eq!(body_prefix(&response, 5).unwrap(), b"hello");
+/// ```
+pub fn body_prefix(response: &Response<Vec<u8>>, max: u64) -> io::Result<Vec<u8>> {
+    let mut prefix = vec![];
+
+    match response.extensions().get::<Spooled>() {
+        Some(spooled) => {
+            spooled.open()?.take(max).read_to_end(&mut prefix)?;
+        }
+        None => prefix.extend_from_slice(&response.body()[..body_len(response).min(max) as usize]),
+    }
+
+    Ok(prefix)
+}
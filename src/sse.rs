@@ -0,0 +1,224 @@
+//! Server-Sent Events (`text/event-stream`), printed one event at a time as they arrive instead
+//! of after the server closes the connection
+//!
+//! Only `data` is shown, with the `event` type if it isn't the default `message` (and the `id` and
+//! `retry` too with -v). Piped output gets the stream untouched, just without the wait
+
+use std::io::{self, IsTerminal, Write};
+
+use http::{header::CONTENT_TYPE, HeaderMap, Response, StatusCode};
+use owo_colors::OwoColorize;
+
+use crate::{
+    cli::{VERBOSE, VERY_VERBOSE},
+    helpers::{format_response, MColorize},
+    http_request::StreamHandler,
+    pretty::{prettify, Pretty},
+    render::Renderers,
+};
+
+/// One event from the stream
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Event {
+    /// The `event` type, `None` for the default (`message`)
+    pub event: Option<String>,
+    /// Every `data` line, joined with newlines
+    pub data: String,
+    pub id: Option<String>,
+    /// How long to wait before reconnecting, in milliseconds
+    pub retry: Option<u64>,
+}
+
+/// Splits a stream into [`Event`]s, following the
+/// [spec](https://html.spec.whatwg.org/multipage/server-sent-events.html#event-stream-interpretation)
+/// ```
+/// # use httpc::sse::EventParser;
+/// let mut parser = EventParser::default();
+/// assert!(parser.feed(b": ping\r\nevent: update\r\ndata: {\"a\"").is_empty());
+/// let events = parser.feed(b": 1}\r\ndata:two\r\n\r\nid: 7\ndata\n\n");
+/// assert_eq!(events[0].event.as_deref(), Some("update"));
+/// assert_eq!(events[0].data, "{\"a\": 1}\ntwo");
+/// assert_eq!((events[1].id.as_deref(), events[1].data.as_str()), (Some("7"), ""));
+/// ```
+#[derive(Debug, Default)]
+pub struct EventParser {
+    line: Vec<u8>,
+    event: Event,
+    /// If any `data` was given, events without any aren't dispatched
+    has_data: bool,
+    /// A CR ended the last line, so an LF right after it doesn't end another
+    after_cr: bool,
+    started: bool,
+}
+
+impl EventParser {
+    /// Parse the next part of the stream, returning the events it finished
+    pub fn feed(&mut self, data: &[u8]) -> Vec<Event> {
+        let mut events = vec![];
+
+        for &byte in data {
+            match byte {
+                b'\n' if self.after_cr => self.after_cr = false,
+                b'\r' | b'\n' => {
+                    self.after_cr = byte == b'\r';
+                    events.extend(self.end_line());
+                }
+                _ => {
+                    self.after_cr = false;
+                    self.line.push(byte);
+                }
+            }
+        }
+
+        events
+    }
+
+    fn end_line(&mut self) -> Option<Event> {
+        let mut line = String::from_utf8_lossy(&std::mem::take(&mut self.line)).into_owned();
+
+        if !self.started {
+            self.started = true;
+
+            if let Some(rest) = line.strip_prefix('\u{FEFF}') {
+                line = rest.to_string();
+            }
+        }
+
+        if line.is_empty() {
+            let event = std::mem::take(&mut self.event);
+            return std::mem::take(&mut self.has_data).then_some(event);
+        }
+
+        let (field, value) = line.split_once(':').unwrap_or((&line, ""));
+        let value = value.strip_prefix(' ').unwrap_or(value);
+
+        match field {
+            // A comment, usually a keep-alive
+            "" => {}
+            "event" => {
+                self.event.event =
+                    Some(value.to_string()).filter(|event| !event.is_empty() && event != "message")
+            }
+            "data" => {
+                if self.has_data {
+                    self.event.data.push('\n');
+                }
+
+                self.event.data.push_str(value);
+                self.has_data = true;
+            }
+            "id" if !value.contains('\0') => self.event.id = Some(value.to_string()),
+            "retry" => self.event.retry = value.parse().ok().or(self.event.retry),
+            _ => {}
+        }
+
+        None
+    }
+}
+
+/// Prints event stream responses (or every successful one, with `force`) to stdout as they arrive
+#[derive(Debug)]
+pub struct SsePrinter {
+    force: bool,
+    verbosity: u8,
+    pretty: Pretty,
+    /// Piped, so the stream is passed on untouched
+    raw: bool,
+    parser: EventParser,
+}
+
+impl SsePrinter {
+    pub fn new(force: bool, verbosity: u8, pretty: Pretty) -> Self {
+        Self {
+            force,
+            verbosity,
+            pretty,
+            raw: verbosity < VERBOSE && !io::stdout().is_terminal(),
+            parser: EventParser::default(),
+        }
+    }
+
+    fn print_event(&self, out: &mut impl Write, event: &Event) -> io::Result<()> {
+        let mut fields = vec![];
+
+        if let Some(event) = &event.event {
+            fields.push(("event", event.clone()));
+        }
+
+        if self.verbosity >= VERBOSE {
+            fields.extend(event.id.clone().map(|id| ("id", id)));
+            fields.extend(event.retry.map(|retry| ("retry", retry.to_string())));
+        }
+
+        for (name, value) in fields {
+            writeln!(
+                out,
+                "{}: {}",
+                name.out_color(|t| t.cyan()),
+                value.out_color(|t| t.magenta())
+            )?;
+        }
+
+        let content_type = match serde_json::from_str::<serde_json::Value>(&event.data) {
+            Ok(_) => "application/json",
+            Err(_) => "text/plain",
+        };
+
+        writeln!(
+            out,
+            "{}\n",
+            prettify(content_type, &event.data, self.pretty)
+        )
+    }
+}
+
+impl StreamHandler for SsePrinter {
+    fn start(&mut self, status: StatusCode, headers: &HeaderMap) -> bool {
+        let event_stream = headers
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.to_ascii_lowercase().starts_with("text/event-stream"));
+
+        if !status.is_success() || !(event_stream || self.force) {
+            return false;
+        }
+
+        // The rest of the response can take forever, so the head can't wait for it
+        if self.verbosity >= VERBOSE {
+            let mut head = Response::new(vec![]);
+            *head.status_mut() = status;
+            *head.headers_mut() = headers.clone();
+
+            let mut out = io::stdout().lock();
+            if self.verbosity >= VERY_VERBOSE {
+                let _ = writeln!(out, "{}", "← Received".out_color(|t| t.green()));
+            }
+
+            if let Ok(formatted) = format_response(
+                &head,
+                self.verbosity,
+                false,
+                self.pretty,
+                &Renderers::default(),
+            ) {
+                let _ = writeln!(out, "{}\n", formatted);
+            }
+        }
+
+        true
+    }
+
+    fn data(&mut self, data: &[u8]) -> io::Result<()> {
+        let mut out = io::stdout().lock();
+
+        if self.raw {
+            out.write_all(data)?;
+        } else {
+            for event in self.parser.feed(data) {
+                self.print_event(&mut out, &event)?;
+            }
+        }
+
+        out.flush()
+    }
+}
@@ -0,0 +1,59 @@
+use crate::http_request::RequestError;
+
+/// Parse a `--var KEY=VALUE`
+/// ```
+/// # use httpc::template::parse_var;
+/// assert_eq!(parse_var("env=prod"), Ok(("env".to_string(), "prod".to_string())));
+/// assert!(parse_var("env").is_err());
+/// ```
+pub fn parse_var(var: &str) -> Result<(String, String), String> {
+    match var.split_once('=') {
+        Some((key, value)) if !key.trim().is_empty() => {
+            Ok((key.trim().to_string(), value.to_string()))
+        }
+        _ => Err(format!("Expected KEY=VALUE, got '{}'", var)),
+    }
+}
+
+/// Replace every `{{NAME}}` in `text` with the value of `NAME` from `vars`, or the environment
+///
+/// `vars` wins over the environment, and a variable that's in neither is an error
+/// ```
+/// # use httpc::template::substitute;
+/// let vars = vec![("host".to_string(), "example.com".to_string())];
+/// assert_eq!(substitute("https://{{ host }}/a", &vars).unwrap(), "https://example.com/a");
+/// assert_eq!(substitute("no vars {here}", &vars).unwrap(), "no vars {here}");
+/// assert!(substitute("{{HTTPC_SURELY_UNDEFINED}}", &vars).is_err());
+/// ```
+pub fn substitute(text: &str, vars: &[(String, String)]) -> Result<String, RequestError> {
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find("{{") {
+        output.push_str(&rest[..start]);
+
+        let Some(end) = rest[start..].find("}}") else {
+            return Err(format!("Unclosed '{{{{' in '{}'", text).into());
+        };
+
+        let name = rest[start + 2..start + end].trim();
+        let value = vars
+            .iter()
+            .rev()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.clone())
+            .or_else(|| std::env::var(name).ok())
+            .ok_or_else(|| {
+                format!(
+                    "Variable '{}' isn't set, pass it with --var {}=... or as an environment variable",
+                    name, name
+                )
+            })?;
+
+        output.push_str(&value);
+        rest = &rest[start + end + 2..];
+    }
+
+    output.push_str(rest);
+    Ok(output)
+}
@@ -0,0 +1,80 @@
+use std::{
+    io::{self, Read, Write},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// A stream wrapper that paces reads and writes to stay under a bytes/second limit
+///
+/// Upload and download are tracked separately, so a slow upload doesn't "use up" the download budget
+pub struct Throttled<S> {
+    inner: S,
+    /// Max bytes per second, in each direction
+    rate: u64,
+    read: Progress,
+    written: Progress,
+}
+
+/// How many bytes have gone in one direction since the first transfer
+#[derive(Default)]
+struct Progress {
+    start: Option<Instant>,
+    bytes: u64,
+}
+
+impl Progress {
+    /// Record `bytes` transferred, then sleep until we're back under `rate`
+    fn record(&mut self, bytes: usize, rate: u64) {
+        let start = *self.start.get_or_insert_with(Instant::now);
+        self.bytes += bytes as u64;
+
+        // How long it *should* have taken to transfer everything so far
+        let expected = Duration::from_secs_f64(self.bytes as f64 / rate as f64);
+        let elapsed = start.elapsed();
+
+        if expected > elapsed {
+            thread::sleep(expected - elapsed);
+        }
+    }
+}
+
+impl<S> Throttled<S> {
+    pub fn new(inner: S, rate: u64) -> Self {
+        Self {
+            inner,
+            rate,
+            read: Progress::default(),
+            written: Progress::default(),
+        }
+    }
+
+    /// Limit the size of a single read/write so we can pace smoothly
+    /// instead of doing one huge transfer then sleeping for ages
+    fn chunk_size(&self, len: usize) -> usize {
+        // ~10 chunks a second, but always make progress
+        let max = (self.rate / 10).max(1) as usize;
+        len.min(max)
+    }
+}
+
+impl<S: Read> Read for Throttled<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.chunk_size(buf.len());
+        let n = self.inner.read(&mut buf[..len])?;
+        self.read.record(n, self.rate);
+        Ok(n)
+    }
+}
+
+impl<S: Write> Write for Throttled<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = self.chunk_size(buf.len());
+        let n = self.inner.write(&buf[..len])?;
+        self.written.record(n, self.rate);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
@@ -0,0 +1,209 @@
+use std::{
+    io::{self, Read, Write},
+    net::{SocketAddr, TcpStream},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// How long each phase of a request is allowed to take, `None` for no limit
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Timeouts {
+    /// Resolving the host name
+    pub dns: Option<Duration>,
+    /// Opening the TCP connection (per address tried)
+    pub connect: Option<Duration>,
+    /// The TLS handshake
+    pub tls: Option<Duration>,
+    /// From sending the request until all the response headers have arrived
+    pub response_header: Option<Duration>,
+    /// Max time between receiving any two bits of data
+    pub read: Option<Duration>,
+    /// The whole request, from DNS until the end of the body
+    pub total: Option<Duration>,
+}
+
+/// When the current phase has to be done by, and what to call it when it isn't
+#[derive(Clone, Copy, Debug)]
+struct Phase {
+    at: Instant,
+    name: &'static str,
+    budget: Duration,
+}
+
+/// Deadlines shared between a connection's stream and whoever is driving the request,
+/// so they can move on to the next phase after the stream's been wrapped in TLS & co
+#[derive(Clone, Debug, Default)]
+pub struct Deadline(Arc<Mutex<Deadlines>>);
+
+#[derive(Debug, Default)]
+struct Deadlines {
+    phase: Option<Phase>,
+    total: Option<Phase>,
+}
+
+impl Deadline {
+    /// Start a new request, which has to be done within `total`. Every phase has to fit in it too
+    ///
+    /// A budget too far out to be represented is the same as none at all
+    /// ```
+    /// # use std::time::Duration;
+    /// # use httpc::timeout::Deadline;
+    /// let deadline = Deadline::default();
+    /// deadline.start_request(Some(Duration::from_secs(60)));
+    /// assert!(deadline.remaining().unwrap().is_some());
+    ///
+    /// deadline.start_request(Some(Duration::MAX));
+    /// assert_eq!(deadline.remaining().unwrap(), None);
+    /// ```
+    pub fn start_request(&self, total: Option<Duration>) {
+        if let Ok(mut deadlines) = self.0.lock() {
+            deadlines.phase = None;
+            deadlines.total = Phase::new("Request", total);
+        }
+    }
+
+    /// Start a new phase that has to be done within `budget` (or the request's deadline, if sooner)
+    pub fn start(&self, name: &'static str, budget: Option<Duration>) {
+        if let Ok(mut deadlines) = self.0.lock() {
+            deadlines.phase = Phase::new(name, budget);
+        }
+    }
+
+    /// Finish the current phase, leaving just the request's deadline
+    pub fn end_phase(&self) {
+        self.start("", None);
+    }
+
+    /// Time left until the current phase (or the request) runs out, erroring if it already has
+    pub fn remaining(&self) -> io::Result<Option<Duration>> {
+        let Some(earliest) = self.earliest() else {
+            return Ok(None);
+        };
+
+        match earliest.at.checked_duration_since(Instant::now()) {
+            Some(remaining) if !remaining.is_zero() => Ok(Some(remaining)),
+            _ => Err(earliest.timed_out()),
+        }
+    }
+
+    /// Whichever of the phase & request deadline comes first
+    fn earliest(&self) -> Option<Phase> {
+        self.0.lock().ok().and_then(|deadlines| {
+            [deadlines.phase, deadlines.total]
+                .into_iter()
+                .flatten()
+                .min_by_key(|phase| phase.at)
+        })
+    }
+}
+
+impl Phase {
+    fn new(name: &'static str, budget: Option<Duration>) -> Option<Self> {
+        let budget = budget?;
+
+        Some(Phase {
+            at: Instant::now().checked_add(budget)?,
+            name,
+            budget,
+        })
+    }
+
+    fn timed_out(&self) -> io::Error {
+        io::Error::new(
+            io::ErrorKind::TimedOut,
+            format!("{} timed out after {:?}", self.name, self.budget),
+        )
+    }
+}
+
+/// A TCP stream that gives up on reads & writes once its [`Deadline`] runs out,
+/// or when no data arrives for `idle` (the read timeout)
+#[derive(Debug)]
+pub struct TimedStream {
+    stream: TcpStream,
+    deadline: Deadline,
+    idle: Option<Duration>,
+}
+
+impl TimedStream {
+    pub fn new(stream: TcpStream, deadline: Deadline, idle: Option<Duration>) -> Self {
+        Self {
+            stream,
+            deadline,
+            idle,
+        }
+    }
+
+    /// The address of the other end
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.stream.peer_addr()
+    }
+
+    /// The TCP stream, without any timeouts
+    pub fn into_inner(self) -> io::Result<TcpStream> {
+        self.stream.set_read_timeout(None)?;
+        self.stream.set_write_timeout(None)?;
+        Ok(self.stream)
+    }
+
+    /// Set the socket timeout for the next operation: whichever of the idle timeout and deadline
+    /// is first. Returns the deadline's phase if that's the one that'll trigger
+    fn set_timeout(&self, read: bool) -> io::Result<Option<Phase>> {
+        let remaining = self.deadline.remaining()?;
+
+        let (timeout, phase) = match (remaining, self.idle) {
+            (Some(remaining), Some(idle)) if idle <= remaining => (Some(idle), None),
+            (Some(remaining), _) => (Some(remaining), self.deadline.earliest()),
+            (None, idle) => (idle, None),
+        };
+
+        if read {
+            self.stream.set_read_timeout(timeout)?;
+        } else {
+            self.stream.set_write_timeout(timeout)?;
+        }
+
+        Ok(phase)
+    }
+
+    /// Turn the socket timing out into an error that says which timeout it was
+    fn timed_out(&self, err: io::Error, phase: Option<Phase>) -> io::Error {
+        if !matches!(
+            err.kind(),
+            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+        ) {
+            return err;
+        }
+
+        match (phase, self.idle) {
+            (Some(phase), _) => phase.timed_out(),
+            (None, Some(idle)) => io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("No data received for {:?} (read timeout)", idle),
+            ),
+            (None, None) => err,
+        }
+    }
+}
+
+impl Read for TimedStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let phase = self.set_timeout(true)?;
+        self.stream
+            .read(buf)
+            .map_err(|err| self.timed_out(err, phase))
+    }
+}
+
+impl Write for TimedStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let phase = self.set_timeout(false)?;
+        self.stream
+            .write(buf)
+            .map_err(|err| self.timed_out(err, phase))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
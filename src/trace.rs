@@ -0,0 +1,147 @@
+//! `--trace`/`--trace-ascii`: a dump of every byte sent & received, for when the parser
+//! doesn't agree with what the server sent
+//!
+//! ```text
+//! == #1 Connected to http://localhost:8080
+//! => #1 Send 78 bytes (offset 0x0)
+//! 0000: 47 45 54 20 2f 20 48 54 54 50 2f 31 2e 31 0d 0a  GET / HTTP/1.1..
+//! <= #1 Recv 120 bytes (offset 0x0)
+//! ...
+//! ```
+//!
+//! Offsets count from the start of the connection, in each direction. Data is traced after TLS
+//! (so it's readable) and before the throttle, the proxy's CONNECT isn't traced
+
+use std::{
+    fmt,
+    fs::File,
+    io::{self, Read, Write},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use crate::helpers::{hexdump, printable};
+
+/// Where the trace goes, shared by every connection (and thread)
+#[derive(Clone)]
+pub struct Trace {
+    out: Arc<Mutex<Box<dyn Write + Send>>>,
+    /// Only show the data as text, instead of hex + text
+    ascii: bool,
+}
+
+impl fmt::Debug for Trace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Trace").field("ascii", &self.ascii).finish()
+    }
+}
+
+impl Trace {
+    /// Trace to a file (overwriting it), or to stderr for `-`
+    pub fn open(path: &str, ascii: bool) -> io::Result<Self> {
+        let out: Box<dyn Write + Send> = match path {
+            "-" => Box::new(io::stderr()),
+            path => Box::new(File::create(path)?),
+        };
+
+        Ok(Self {
+            out: Arc::new(Mutex::new(out)),
+            ascii,
+        })
+    }
+
+    /// Wrap a new connection's stream so everything going through it is traced
+    pub fn wrap<S>(&self, inner: S, target: &str) -> Traced<S> {
+        static CONNECTIONS: AtomicUsize = AtomicUsize::new(0);
+        let id = CONNECTIONS.fetch_add(1, Ordering::Relaxed) + 1;
+
+        self.write(&format!("== #{} Connected to {}\n", id, target));
+
+        Traced {
+            inner,
+            trace: self.clone(),
+            id,
+            sent: 0,
+            received: 0,
+        }
+    }
+
+    /// Tracing is best-effort, it's not worth failing the request over
+    fn write(&self, text: &str) {
+        if let Ok(mut out) = self.out.lock() {
+            let _ = out.write_all(text.as_bytes()).and_then(|_| out.flush());
+        }
+    }
+
+    fn dump(&self, id: usize, direction: &str, offset: usize, data: &[u8]) {
+        let mut text = format!(
+            "{} #{} {} {} bytes (offset {:#x})\n",
+            if direction == "Send" { "=>" } else { "<=" },
+            id,
+            direction,
+            data.len(),
+            offset
+        );
+
+        if self.ascii {
+            let mut line_offset = offset;
+
+            for line in data.split_inclusive(|byte| *byte == b'\n') {
+                let content = line.strip_suffix(b"\n").unwrap_or(line);
+                let content = content.strip_suffix(b"\r").unwrap_or(content);
+                text += &format!("{:04x}: {}\n", line_offset, printable(content));
+                line_offset += line.len();
+            }
+        } else {
+            text += &hexdump(data, offset);
+        }
+
+        self.write(&text);
+    }
+}
+
+/// A stream wrapper that writes everything read & written to a [`Trace`]
+pub struct Traced<S> {
+    inner: S,
+    trace: Trace,
+    /// Which connection this is, to tell them apart when several are traced
+    id: usize,
+    sent: usize,
+    received: usize,
+}
+
+impl<S: Read> Read for Traced<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf).map_err(|err| {
+            self.trace
+                .write(&format!("== #{} Read failed: {}\n", self.id, err));
+            err
+        })?;
+
+        if read == 0 {
+            self.trace
+                .write(&format!("== #{} Closed by the server\n", self.id));
+        } else {
+            self.trace
+                .dump(self.id, "Recv", self.received, &buf[..read]);
+            self.received += read;
+        }
+
+        Ok(read)
+    }
+}
+
+impl<S: Write> Write for Traced<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.trace.dump(self.id, "Send", self.sent, &buf[..written]);
+        self.sent += written;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
@@ -0,0 +1,236 @@
+//! How much data went over the network this run, per host and overall, for `--summary`
+//!
+//! Everything that goes through a connection counts: request & response heads, bodies, proxy
+//! CONNECTs, redirect hops and retries. TLS overhead doesn't (it's counted above the TLS layer),
+//! and neither do responses served from the cache
+
+use std::{
+    collections::BTreeMap,
+    io::{self, Read, Write},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, OnceLock,
+    },
+};
+
+use owo_colors::{OwoColorize, Stream};
+use serde_json::{json, Value};
+
+/// Running totals for one host
+#[derive(Debug, Default)]
+pub struct HostTotals {
+    pub requests: AtomicU64,
+    pub connections: AtomicU64,
+    pub sent: AtomicU64,
+    pub received: AtomicU64,
+}
+
+/// A snapshot of one host's totals
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Transferred {
+    pub requests: u64,
+    pub connections: u64,
+    pub sent: u64,
+    pub received: u64,
+}
+
+impl Transferred {
+    fn add(&mut self, other: &Transferred) {
+        self.requests += other.requests;
+        self.connections += other.connections;
+        self.sent += other.sent;
+        self.received += other.received;
+    }
+
+    /// What's been added since `earlier`, a snapshot of the same totals
+    pub fn since(&self, earlier: &Transferred) -> Transferred {
+        Transferred {
+            requests: self.requests.saturating_sub(earlier.requests),
+            connections: self.connections.saturating_sub(earlier.connections),
+            sent: self.sent.saturating_sub(earlier.sent),
+            received: self.received.saturating_sub(earlier.received),
+        }
+    }
+}
+
+/// Every host's totals added up
+pub fn total(hosts: &BTreeMap<String, Transferred>) -> Transferred {
+    let mut total = Transferred::default();
+
+    for transferred in hosts.values() {
+        total.add(transferred);
+    }
+
+    total
+}
+
+/// Totals, keyed by `host:port`
+fn hosts() -> &'static Mutex<BTreeMap<String, Arc<HostTotals>>> {
+    static HOSTS: OnceLock<Mutex<BTreeMap<String, Arc<HostTotals>>>> = OnceLock::new();
+    HOSTS.get_or_init(Default::default)
+}
+
+/// The totals for `authority`, starting them at 0 the first time
+pub fn host_totals(authority: &str) -> Arc<HostTotals> {
+    match hosts().lock() {
+        Ok(mut hosts) => hosts.entry(authority.to_string()).or_default().clone(),
+        // Counting is best-effort, a poisoned lock just means this host goes uncounted
+        Err(_) => Arc::default(),
+    }
+}
+
+/// Count a request being sent to `authority` (retries count again)
+pub fn count_request(authority: &str) {
+    host_totals(authority)
+        .requests
+        .fetch_add(1, Ordering::Relaxed);
+}
+
+/// Every host's totals so far, in order
+pub fn snapshot() -> BTreeMap<String, Transferred> {
+    let Ok(hosts) = hosts().lock() else {
+        return BTreeMap::new();
+    };
+
+    hosts
+        .iter()
+        .map(|(host, totals)| {
+            let transferred = Transferred {
+                requests: totals.requests.load(Ordering::Relaxed),
+                connections: totals.connections.load(Ordering::Relaxed),
+                sent: totals.sent.load(Ordering::Relaxed),
+                received: totals.received.load(Ordering::Relaxed),
+            };
+            (host.clone(), transferred)
+        })
+        .collect()
+}
+
+/// A stream wrapper adding everything read & written to a host's totals
+pub struct Counted<S> {
+    inner: S,
+    totals: Arc<HostTotals>,
+}
+
+impl<S> Counted<S> {
+    /// Wrap a new connection to `authority`
+    pub fn new(inner: S, authority: &str) -> Self {
+        let totals = host_totals(authority);
+        totals.connections.fetch_add(1, Ordering::Relaxed);
+        Self { inner, totals }
+    }
+}
+
+impl<S: Read> Read for Counted<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.totals.received.fetch_add(n as u64, Ordering::Relaxed);
+        Ok(n)
+    }
+}
+
+impl<S: Write> Write for Counted<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.totals.sent.fetch_add(n as u64, Ordering::Relaxed);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// A byte count the way people read them
+/// ```
+/// # use httpc::transfer::format_bytes;
+/// assert_eq!(format_bytes(512), "512 B");
+/// assert_eq!(format_bytes(1536), "1.5 KiB");
+/// assert_eq!(format_bytes(3 * 1024 * 1024 * 1024), "3.0 GiB");
+/// ```
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+/// The totals as a table for stderr, one row per host then the overall total
+pub fn format_summary(hosts: &BTreeMap<String, Transferred>) -> String {
+    let width = hosts.keys().map(String::len).max().unwrap_or(0).max(5);
+
+    let row = |name: &str, transferred: &Transferred| {
+        format!(
+            "  {:<width$}  {:>8}  {:>11}  {:>10}  {:>10}\n",
+            name,
+            transferred.requests,
+            transferred.connections,
+            format_bytes(transferred.sent),
+            format_bytes(transferred.received),
+            width = width
+        )
+    };
+
+    let mut summary = format!(
+        "{}\n  {:<width$}  {:>8}  {:>11}  {:>10}  {:>10}\n",
+        "Transferred:".if_supports_color(Stream::Stderr, |t| t.bold()),
+        "Host",
+        "Requests",
+        "Connections",
+        "Sent",
+        "Received",
+        width = width
+    );
+
+    for (host, transferred) in hosts {
+        summary.push_str(&row(host, transferred));
+    }
+
+    if hosts.len() != 1 {
+        let total = row("Total", &total(hosts));
+        summary.push_str(
+            &total
+                .if_supports_color(Stream::Stderr, |t| t.bold())
+                .to_string(),
+        );
+    }
+
+    summary
+}
+
+/// The totals as JSON, with sizes in bytes
+/// ```
+/// # use httpc::transfer::{summary_json, Transferred};
+/// let hosts = [("a:80".to_string(), Transferred { requests: 2, connections: 1, sent: 100, received: 900 })].into();
+/// let json = summary_json(&hosts);
+/// assert_eq!(json["transfer"]["hosts"]["a:80"]["received"], 900);
+/// assert_eq!(json["transfer"]["total"]["requests"], 2);
+/// ```
+pub fn summary_json(hosts: &BTreeMap<String, Transferred>) -> Value {
+    let to_json = |transferred: &Transferred| {
+        json!({
+            "requests": transferred.requests,
+            "connections": transferred.connections,
+            "sent": transferred.sent,
+            "received": transferred.received,
+        })
+    };
+
+    let per_host = hosts
+        .iter()
+        .map(|(host, transferred)| (host.clone(), to_json(transferred)))
+        .collect::<serde_json::Map<_, _>>();
+
+    json!({"transfer": {"hosts": per_host, "total": to_json(&total(hosts))}})
+}
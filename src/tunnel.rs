@@ -0,0 +1,89 @@
+//! `httpc tunnel`: a CONNECT tunnel through a proxy, hooked up to stdin/stdout like `nc` would be,
+//! for checking what a proxy lets through (or talking to whatever's behind it)
+
+use std::{
+    io::{self, Read, Write},
+    net::Shutdown,
+    thread,
+};
+
+use owo_colors::{OwoColorize, Stream};
+
+use crate::{
+    cli::{TunnelOptions, VERBOSE},
+    helpers::get_authority,
+    http_request::{proxy_tunnel, RequestError, RequestOptions},
+    timeout::{Deadline, Timeouts},
+};
+
+/// Open the tunnel, then pass stdin through it and what comes back to stdout until both sides
+/// are done
+pub fn run_tunnel(options: &TunnelOptions) -> Result<(), RequestError> {
+    if !options.target.contains(':') {
+        return Err(format!(
+            "'{}' needs a port, like {}:443",
+            options.target, options.target
+        )
+        .into());
+    }
+
+    let request_options = RequestOptions {
+        proxy: Some(options.proxy.clone()),
+        proxy_user: options.proxy_user.clone(),
+        timeouts: Timeouts {
+            connect: options.connect_timeout,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    let deadline = Deadline::default();
+    deadline.start_request(None);
+
+    let stream =
+        proxy_tunnel(&options.proxy, &options.target, &request_options, &deadline)?.into_inner()?;
+
+    print_status(
+        options.verbosity,
+        format!(
+            "Tunnel open to {} through {}",
+            options.target,
+            get_authority(&options.proxy)
+        ),
+    );
+
+    let mut upload = stream.try_clone()?;
+    thread::spawn(move || {
+        // Let the other end know we're done sending, it might still have more to say
+        let _ = io::copy(&mut io::stdin().lock(), &mut upload);
+        let _ = upload.shutdown(Shutdown::Write);
+    });
+
+    // Not io::copy, stdout would hold onto anything without a newline at the end
+    let mut download = &stream;
+    let mut stdout = io::stdout().lock();
+    let mut buf = [0; 8192];
+
+    loop {
+        let read = download.read(&mut buf)?;
+
+        if read == 0 {
+            break;
+        }
+
+        stdout.write_all(&buf[..read])?;
+        stdout.flush()?;
+    }
+
+    print_status(options.verbosity, "Tunnel closed by the other end");
+    Ok(())
+}
+
+fn print_status(verbosity: u8, message: impl std::fmt::Display) {
+    if verbosity >= VERBOSE {
+        eprintln!(
+            "{}",
+            message.if_supports_color(Stream::Stderr, |t| t.bright_black())
+        );
+    }
+}
@@ -0,0 +1,81 @@
+use std::str::FromStr;
+
+/// A `--ua`, either one of the browser presets or a User-Agent string to send as-is
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum UserAgent {
+    Chrome,
+    Firefox,
+    Safari,
+    /// Our own default, useful to override a profile's or session's User-Agent
+    Httpc,
+    Custom(String),
+}
+
+impl FromStr for UserAgent {
+    type Err = String;
+
+    /// `chrome`, `firefox`, `safari` or `httpc` (any case), anything else is a custom string
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.to_ascii_lowercase().as_str() {
+            "" => return Err("User-Agent can't be empty".to_string()),
+            "chrome" => UserAgent::Chrome,
+            "firefox" => UserAgent::Firefox,
+            "safari" => UserAgent::Safari,
+            "httpc" => UserAgent::Httpc,
+            _ => UserAgent::Custom(s.to_string()),
+        })
+    }
+}
+
+impl UserAgent {
+    pub fn value(&self) -> String {
+        match self {
+            UserAgent::Chrome => "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/129.0.0.0 Safari/537.36".to_string(),
+            UserAgent::Firefox => "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:131.0) Gecko/20100101 Firefox/131.0".to_string(),
+            UserAgent::Safari => "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/18.0 Safari/605.1.15".to_string(),
+            UserAgent::Httpc => format!("httpc/{}", env!("CARGO_PKG_VERSION")),
+            UserAgent::Custom(user_agent) => user_agent.clone(),
+        }
+    }
+
+    /// The header lines (`Name: value`) to send, with the rest of the headers the browser
+    /// would send for a page load if `bundle` is set
+    /// ```
+    /// # use httpc::user_agent::UserAgent;
+    /// let headers = "chrome".parse::<UserAgent>().unwrap().headers(true);
+    /// assert!(headers[0].starts_with("User-Agent: Mozilla/5.0"));
+    /// assert!(headers.iter().any(|header| header.starts_with("sec-ch-ua: ")));
+    /// assert_eq!("curl/8".parse::<UserAgent>().unwrap().headers(true), ["User-Agent: curl/8"]);
+    /// ```
+    pub fn headers(&self, bundle: bool) -> Vec<String> {
+        let mut headers = vec![format!("User-Agent: {}", self.value())];
+
+        if !bundle {
+            return headers;
+        }
+
+        let extra: &[&str] = match self {
+            UserAgent::Chrome => &[
+                "Accept: text/html,application/xhtml+xml,application/xml;q=0.9,image/avif,image/webp,image/apng,*/*;q=0.8,application/signed-exchange;v=b3;q=0.7",
+                "Accept-Language: en-US,en;q=0.9",
+                "sec-ch-ua: \"Google Chrome\";v=\"129\", \"Not=A?Brand\";v=\"8\", \"Chromium\";v=\"129\"",
+                "sec-ch-ua-mobile: ?0",
+                "sec-ch-ua-platform: \"Windows\"",
+                "Upgrade-Insecure-Requests: 1",
+            ],
+            UserAgent::Firefox => &[
+                "Accept: text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8",
+                "Accept-Language: en-US,en;q=0.5",
+                "Upgrade-Insecure-Requests: 1",
+            ],
+            UserAgent::Safari => &[
+                "Accept: text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8",
+                "Accept-Language: en-US,en;q=0.9",
+            ],
+            UserAgent::Httpc | UserAgent::Custom(_) => &[],
+        };
+
+        headers.extend(extra.iter().map(|header| header.to_string()));
+        headers
+    }
+}
@@ -0,0 +1,124 @@
+//! Non-fatal problems noticed along the way (a mislabelled body, a response the parser had to be
+//! lenient with, a redirect to plain HTTP, a certificate about to expire...), collected so they're
+//! shown together at the end instead of in the middle of the output
+//!
+//! With `--format json`, each response's object has the warnings raised since the one
+//! before it as `"warnings": [{"kind": "...", "message": "..."}]`, so only ones after the last
+//! response are printed. `--warnings-as-errors` makes httpc exit with 1 if there were any
+
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Mutex, OnceLock,
+};
+
+use owo_colors::{OwoColorize, Stream};
+use serde_json::{json, Value};
+
+/// What a warning is about, for tools reading the JSON output
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WarningKind {
+    /// Something about how httpc was asked to send the request
+    Usage,
+    /// A Set-Cookie that was rejected
+    Cookie,
+    /// Logging a profile in or refreshing its login
+    Login,
+    /// Authenticating with --ntlm, --negotiate or a proxy
+    Auth,
+    /// A redirect that wasn't followed
+    Redirect,
+    /// A redirect from HTTPS to plain HTTP that was followed
+    InsecureRedirect,
+    /// A server certificate that expires soon
+    CertExpiring,
+    /// A body that's clearly something other than its Content-Type says
+    MislabelledContentType,
+    /// Something in the response that breaks the spec, but could be worked around
+    ParseViolation,
+    /// A response that isn't what was asked for
+    Response,
+}
+
+impl WarningKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WarningKind::Usage => "usage",
+            WarningKind::Cookie => "cookie",
+            WarningKind::Login => "login",
+            WarningKind::Auth => "auth",
+            WarningKind::Redirect => "redirect",
+            WarningKind::InsecureRedirect => "insecure-redirect",
+            WarningKind::CertExpiring => "cert-expiring",
+            WarningKind::MislabelledContentType => "mislabelled-content-type",
+            WarningKind::ParseViolation => "parse-violation",
+            WarningKind::Response => "response",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Warning {
+    pub kind: WarningKind,
+    pub message: String,
+}
+
+/// Warnings that haven't been printed (or put in the JSON output) yet
+fn pending() -> &'static Mutex<Vec<Warning>> {
+    static PENDING: OnceLock<Mutex<Vec<Warning>>> = OnceLock::new();
+    PENDING.get_or_init(Default::default)
+}
+
+/// How many warnings there have been in total
+static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// Keep a warning to show at the end
+pub fn warn(kind: WarningKind, message: impl std::fmt::Display) {
+    COUNT.fetch_add(1, Ordering::Relaxed);
+
+    // Warnings are best-effort, a poisoned lock just means this one isn't shown
+    if let Ok(mut pending) = pending().lock() {
+        pending.push(Warning {
+            kind,
+            message: message.to_string(),
+        });
+    }
+}
+
+/// The warnings that haven't been shown yet, which won't be shown at the end anymore
+/// ```
+/// # use httpc::warnings::{take_warnings, warn, warning_count, WarningKind};
+/// warn(WarningKind::Usage, "careful");
+/// let warnings = take_warnings();
+/// assert_eq!((warnings[0].kind, warnings[0].message.as_str()), (WarningKind::Usage, "careful"));
+/// assert!(take_warnings().is_empty());
+/// assert_eq!(warning_count(), 1);
+/// ```
+pub fn take_warnings() -> Vec<Warning> {
+    pending()
+        .lock()
+        .map(|mut pending| std::mem::take(&mut *pending))
+        .unwrap_or_default()
+}
+
+/// How many warnings there have been, shown or not
+pub fn warning_count() -> usize {
+    COUNT.load(Ordering::Relaxed)
+}
+
+/// Print the warnings that haven't been shown yet to stderr
+pub fn print_warnings() {
+    for warning in take_warnings() {
+        eprintln!(
+            "{} {}",
+            "warning:".if_supports_color(Stream::Stderr, |t| t.yellow()),
+            warning.message
+        );
+    }
+}
+
+pub fn warnings_json(warnings: &[Warning]) -> Vec<Value> {
+    warnings
+        .iter()
+        .map(|warning| json!({"kind": warning.kind.as_str(), "message": warning.message}))
+        .collect()
+}
@@ -0,0 +1,120 @@
+//! `--watch`: send the same request every so often, printing a line for each response (and how
+//! the body changed since the last one), until `--until-status`/`--until-body-contains` hold,
+//! for watching a deployment come up without a shell loop
+
+use std::time::Duration;
+
+use http::Response;
+use owo_colors::OwoColorize;
+
+use crate::{
+    helpers::MColorize,
+    spool::{body_len, body_prefix},
+    transfer::format_bytes,
+};
+
+/// How often to repeat the request, and when to stop
+#[derive(Clone, Debug)]
+pub struct Watch {
+    pub interval: Duration,
+    /// Stop once the status is any of these, if there are any
+    pub until_statuses: Vec<u16>,
+    /// Stop once the body contains this
+    pub until_body_contains: Option<String>,
+}
+
+impl Watch {
+    /// If there's something to wait for, instead of watching until stopped with Ctrl-C
+    pub fn has_until(&self) -> bool {
+        !self.until_statuses.is_empty() || self.until_body_contains.is_some()
+    }
+
+    /// If `response` is what's being waited for, which needs every --until-* to hold
+    /// ```
+    /// # use httpc::watch::Watch;
+    /// # use std::time::Duration;
+    /// let watch = Watch {
+    ///     interval: Duration::from_secs(5),
+    ///     until_statuses: vec![200],
+    ///     until_body_contains: Some("\"ready\"".to_string()),
+    /// };
+    /// let response = |status, body: &str| {
+    ///     http::Response::builder().status(status).body(body.as_bytes().to_vec()).unwrap()
+    /// };
+    ///
+    /// assert!(!watch.is_done(&response(503, "\"ready\"")).unwrap());
+    /// assert!(!watch.is_done(&response(200, "\"starting\"")).unwrap());
+    /// assert!(watch.is_done(&response(200, "{\"status\": \"ready\"}")).unwrap());
+    /// ```
+    pub fn is_done(&self, response: &Response<Vec<u8>>) -> std::io::Result<bool> {
+        if !self.has_until() {
+            return Ok(false);
+        }
+
+        if !self.until_statuses.is_empty()
+            && !self.until_statuses.contains(&response.status().as_u16())
+        {
+            return Ok(false);
+        }
+
+        match &self.until_body_contains {
+            Some(text) => {
+                let body = body_prefix(response, body_len(response))?;
+                Ok(String::from_utf8_lossy(&body).contains(text.as_str()))
+            }
+            None => Ok(true),
+        }
+    }
+}
+
+/// How long since watching started, as `m:ss` (or `h:mm:ss`)
+/// ```
+/// # use httpc::watch::format_elapsed;
+/// # use std::time::Duration;
+/// assert_eq!(format_elapsed(Duration::from_millis(65_400)), "1:05");
+/// assert_eq!(format_elapsed(Duration::from_secs(3725)), "1:02:05");
+/// ```
+pub fn format_elapsed(elapsed: Duration) -> String {
+    let seconds = elapsed.as_secs();
+
+    match seconds / 3600 {
+        0 => format!("{}:{:02}", seconds / 60, seconds % 60),
+        hours => format!("{}:{:02}:{:02}", hours, seconds / 60 % 60, seconds % 60),
+    }
+}
+
+/// The line printed for each request: which one it was, when, and what came back (or the error)
+pub fn format_poll(
+    number: u64,
+    elapsed: Duration,
+    result: Result<(&Response<Vec<u8>>, Duration), &str>,
+    changed: bool,
+) -> String {
+    let prefix = format!("#{:<3} {:>5}", number, format_elapsed(elapsed))
+        .out_color(|t| t.bright_black())
+        .to_string();
+
+    let (response, took) = match result {
+        Ok(response) => response,
+        Err(err) => return format!("{}  {} {}", prefix, "✗".out_color(|t| t.red()), err),
+    };
+
+    let status = response.status();
+    let status = match status.as_u16() {
+        400.. => status.out_color(|t| t.red()).to_string(),
+        300.. => status.out_color(|t| t.yellow()).to_string(),
+        _ => status.out_color(|t| t.green()).to_string(),
+    };
+
+    format!(
+        "{}  {}  {}  {:.1}ms{}",
+        prefix,
+        status,
+        format_bytes(body_len(response)),
+        took.as_secs_f64() * 1000.0,
+        match changed {
+            true => format!("  {}", "changed".out_color(|t| t.cyan())),
+            false => String::new(),
+        }
+    )
+}
@@ -0,0 +1,123 @@
+use std::time::Duration;
+
+use http::{header, Response, StatusCode, Uri};
+
+use crate::{http_request::SentRequest, spool::body_len};
+
+/// The URL a response actually came from, after defaulting the schema, resolving `.`/`..`
+/// and following redirects. Stored in the response's extensions
+#[derive(Clone, Debug)]
+pub struct EffectiveUrl(pub Uri);
+
+/// How long the request took, from the start until each point. Stored in the response's
+/// extensions (unless it came from the cache)
+#[derive(Clone, Copy, Debug)]
+pub struct Timings {
+    /// Until the connection was ready (after TLS), `None` if a pooled connection was reused
+    pub connect: Option<Duration>,
+    /// Until the first `103 Early Hints` was received, if there was one
+    pub early_hints: Option<Duration>,
+    /// Until all the response headers were received
+    pub first_byte: Duration,
+    /// Until the whole response was received
+    pub total: Duration,
+}
+
+/// Every response that redirected us on the way to this one, in order.
+/// Stored in the response's extensions when following redirects
+#[derive(Clone, Debug, Default)]
+pub struct Redirects(pub Vec<(StatusCode, Uri)>);
+
+/// Expand a `--write-out` format for a response
+///
+/// Variables are written as `%{name}`, and `\n`, `\t` & `\\` are unescaped so formats are
+/// easy to pass from a shell. Unknown variables are left as they are
+/// ```
+/// # use httpc::write_out::{write_out, EffectiveUrl};
+/// let mut response = http::Response::new(b"hi".to_vec());
+/// response.extensions_mut().insert(EffectiveUrl("http://example.com/a".parse().unwrap()));
+/// assert_eq!(write_out("%{http_code} %{url_effective}\\n", &response), "200 http://example.com/a\n");
+/// assert_eq!(write_out("%{size_download} %{nope}", &response), "2 %{nope}");
+/// ```
+pub fn write_out(format: &str, response: &Response<Vec<u8>>) -> String {
+    let mut output = String::new();
+    let mut rest = format;
+
+    while let Some(start) = rest.find(['%', '\\']) {
+        output.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let escaped = match rest.get(..2) {
+            Some("\\n") => Some('\n'),
+            Some("\\t") => Some('\t'),
+            Some("\\\\") => Some('\\'),
+            _ => None,
+        };
+
+        if let Some(escaped) = escaped {
+            output.push(escaped);
+            rest = &rest[2..];
+            continue;
+        }
+
+        let variable = rest
+            .strip_prefix("%{")
+            .and_then(|variable| variable.split_once('}'))
+            .and_then(|(name, after)| Some((variable_value(name, response)?, after)));
+
+        match variable {
+            Some((value, after)) => {
+                output.push_str(&value);
+                rest = after;
+            }
+            None => {
+                output.push_str(&rest[..1]);
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    output.push_str(rest);
+    output
+}
+
+fn variable_value(name: &str, response: &Response<Vec<u8>>) -> Option<String> {
+    Some(match name {
+        "url_effective" => response
+            .extensions()
+            .get::<EffectiveUrl>()
+            .map(|url| url.0.to_string())
+            .unwrap_or_default(),
+        "http_code" => response.status().as_u16().to_string(),
+        "http_version" => format!("{:?}", response.version()),
+        "content_type" => response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .to_string(),
+        "size_download" => body_len(response).to_string(),
+        // As sent, but without the empty line that ends it
+        "request_headers" => response
+            .extensions()
+            .get::<SentRequest>()
+            .map(|sent| sent.head.trim_end().to_string())
+            .unwrap_or_default(),
+        "num_redirects" => response
+            .extensions()
+            .get::<Redirects>()
+            .map_or(0, |redirects| redirects.0.len())
+            .to_string(),
+        "time_connect" | "time_starttransfer" | "time_total" => {
+            let timings = response.extensions().get::<Timings>();
+            let time = match name {
+                "time_connect" => timings.and_then(|timings| timings.connect),
+                "time_starttransfer" => timings.map(|timings| timings.first_byte),
+                _ => timings.map(|timings| timings.total),
+            };
+
+            format!("{:.6}", time.unwrap_or_default().as_secs_f64())
+        }
+        _ => return None,
+    })
+}
@@ -0,0 +1,450 @@
+//! `httpc ws`: a WebSocket (RFC 6455) client. Each line of stdin is sent as a text message, and
+//! every message received is printed as it arrives. Pings are answered, and the connection is
+//! closed once stdin ends
+//!
+//! The handshake is a normal request (see [`http_upgrade`]), so headers, auth, cookies, proxies
+//! and TLS options all work like they do for any other request
+
+use std::{
+    io::{self, Read, Write},
+    sync::mpsc::{self, TryRecvError},
+    thread,
+    time::{Duration, Instant},
+};
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use http::{header, HeaderMap, HeaderValue, Response};
+use owo_colors::{OwoColorize, Stream};
+use sha1::{Digest, Sha1};
+
+use crate::{
+    cli::VERBOSE,
+    helpers::{hexdump, random_bytes, MColorize},
+    http_request::RequestError,
+    pool::Connection,
+};
+
+#[cfg(doc)]
+use crate::http_request::http_upgrade;
+
+/// Appended to the key before hashing it, so the server proves it speaks WebSocket
+const GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// How long to wait for frames before checking for more input to send
+const POLL: Duration = Duration::from_millis(50);
+
+/// How long to wait for the server to answer our close frame
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Opcode {
+    Continuation,
+    Text,
+    Binary,
+    Close,
+    Ping,
+    Pong,
+    Other(u8),
+}
+
+impl From<u8> for Opcode {
+    fn from(opcode: u8) -> Self {
+        match opcode {
+            0x0 => Opcode::Continuation,
+            0x1 => Opcode::Text,
+            0x2 => Opcode::Binary,
+            0x8 => Opcode::Close,
+            0x9 => Opcode::Ping,
+            0xA => Opcode::Pong,
+            other => Opcode::Other(other),
+        }
+    }
+}
+
+impl From<Opcode> for u8 {
+    fn from(opcode: Opcode) -> Self {
+        match opcode {
+            Opcode::Continuation => 0x0,
+            Opcode::Text => 0x1,
+            Opcode::Binary => 0x2,
+            Opcode::Close => 0x8,
+            Opcode::Ping => 0x9,
+            Opcode::Pong => 0xA,
+            Opcode::Other(other) => other,
+        }
+    }
+}
+
+/// A single frame, messages can be split over several (with all but the last not `fin`)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Frame {
+    pub fin: bool,
+    pub opcode: Opcode,
+    pub payload: Vec<u8>,
+}
+
+impl Frame {
+    /// Encode the frame, masking the payload with `mask` like clients have to
+    /// ```
+    /// # use httpc::ws::{Frame, Opcode};
+    /// let frame = Frame { fin: true, opcode: Opcode::Text, payload: b"Hello".to_vec() };
+    /// let encoded = frame.encode(Some([0x37, 0xfa, 0x21, 0x3d]));
+    /// assert_eq!(encoded, b"\x81\x85\x37\xfa\x21\x3d\x7f\x9f\x4d\x51\x58");
+    /// assert_eq!(Frame::decode(&encoded).unwrap(), Some((frame, 11)));
+    /// assert_eq!(Frame::decode(&encoded[..6]).unwrap(), None);
+    /// ```
+    pub fn encode(&self, mask: Option<[u8; 4]>) -> Vec<u8> {
+        let mut encoded = vec![u8::from(self.opcode) | if self.fin { 0x80 } else { 0 }];
+        let masked = if mask.is_some() { 0x80 } else { 0 };
+        let len = self.payload.len();
+
+        match len {
+            0..=125 => encoded.push(masked | len as u8),
+            126..=0xFFFF => {
+                encoded.push(masked | 126);
+                encoded.extend_from_slice(&(len as u16).to_be_bytes());
+            }
+            _ => {
+                encoded.push(masked | 127);
+                encoded.extend_from_slice(&(len as u64).to_be_bytes());
+            }
+        }
+
+        match mask {
+            Some(mask) => {
+                encoded.extend_from_slice(&mask);
+                encoded.extend(apply_mask(&self.payload, mask));
+            }
+            None => encoded.extend_from_slice(&self.payload),
+        }
+
+        encoded
+    }
+
+    /// Decode the frame at the start of `data`, returning it and how many bytes it took up, or
+    /// `None` if it's not all there yet
+    pub fn decode(data: &[u8]) -> Result<Option<(Frame, usize)>, RequestError> {
+        let [first, second, ..] = *data else {
+            return Ok(None);
+        };
+
+        if first & 0x70 != 0 {
+            return Err("The server used a WebSocket extension we didn't ask for".into());
+        }
+
+        let (len, mut at) = match second & 0x7F {
+            126 => match data.get(2..4) {
+                Some(len) => (u16::from_be_bytes([len[0], len[1]]) as u64, 4),
+                None => return Ok(None),
+            },
+            127 => match data.get(2..10).map(|len| len.try_into()) {
+                Some(Ok(len)) => (u64::from_be_bytes(len), 10),
+                _ => return Ok(None),
+            },
+            len => (len as u64, 2),
+        };
+
+        // Servers shouldn't mask their frames, but it's easy enough to undo
+        let mask = match second & 0x80 {
+            0 => None,
+            _ => match data.get(at..at + 4) {
+                Some(mask) => {
+                    at += 4;
+                    Some([mask[0], mask[1], mask[2], mask[3]])
+                }
+                None => return Ok(None),
+            },
+        };
+
+        let end = usize::try_from(len)
+            .ok()
+            .and_then(|len| at.checked_add(len))
+            .ok_or("WebSocket frame is too big")?;
+
+        let Some(payload) = data.get(at..end) else {
+            return Ok(None);
+        };
+
+        let frame = Frame {
+            fin: first & 0x80 != 0,
+            opcode: Opcode::from(first & 0x0F),
+            payload: match mask {
+                Some(mask) => apply_mask(payload, mask),
+                None => payload.to_vec(),
+            },
+        };
+
+        Ok(Some((frame, end)))
+    }
+}
+
+fn apply_mask(payload: &[u8], mask: [u8; 4]) -> Vec<u8> {
+    payload
+        .iter()
+        .zip(mask.iter().cycle())
+        .map(|(byte, mask)| byte ^ mask)
+        .collect()
+}
+
+/// The http(s) URL to send the handshake to for a ws(s) URL
+/// ```
+/// # use httpc::ws::http_url;
+/// assert_eq!(http_url("wss://example.com/chat"), "https://example.com/chat");
+/// assert_eq!(http_url("WS://example.com"), "http://example.com");
+/// assert_eq!(http_url("example.com"), "example.com");
+/// ```
+pub fn http_url(url: &str) -> String {
+    let scheme = url.split_once("://").map(|(scheme, _)| scheme);
+
+    match scheme.map(str::to_ascii_lowercase).as_deref() {
+        Some("wss") => format!("https{}", &url[3..]),
+        Some("ws") => format!("http{}", &url[2..]),
+        _ => url.to_string(),
+    }
+}
+
+/// Add the headers asking to switch to WebSocket (and for the `protocols`, if any), returning the
+/// key the server's answer needs to match
+pub fn add_handshake_headers(
+    headers: &mut HeaderMap,
+    protocols: &[String],
+) -> Result<String, RequestError> {
+    let key = BASE64.encode(random_bytes::<16>());
+
+    headers.insert(header::CONNECTION, HeaderValue::from_static("Upgrade"));
+    headers.insert(header::UPGRADE, HeaderValue::from_static("websocket"));
+    headers.insert(
+        header::SEC_WEBSOCKET_VERSION,
+        HeaderValue::from_static("13"),
+    );
+    headers.insert(header::SEC_WEBSOCKET_KEY, key.parse()?);
+
+    if !protocols.is_empty() {
+        headers.insert(
+            header::SEC_WEBSOCKET_PROTOCOL,
+            protocols.join(", ").parse()?,
+        );
+    }
+
+    Ok(key)
+}
+
+/// What the server has to answer a handshake `key` with
+/// ```
+/// # use httpc::ws::accept_key;
+/// assert_eq!(accept_key("dGhlIHNhbXBsZSBub25jZQ=="), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+/// ```
+pub fn accept_key(key: &str) -> String {
+    BASE64.encode(Sha1::digest(format!("{}{}", key, GUID)))
+}
+
+/// An open WebSocket connection
+pub struct WebSocket {
+    connection: Connection,
+    /// Received bytes that aren't a whole frame yet
+    pending: Vec<u8>,
+}
+
+impl WebSocket {
+    /// Check that the server agreed to the handshake for `key`, taking over its connection
+    pub fn new(
+        response: &Response<Vec<u8>>,
+        connection: Option<Connection>,
+        key: &str,
+    ) -> Result<Self, RequestError> {
+        let connection = connection.ok_or_else(|| {
+            format!(
+                "The server didn't switch to WebSocket, it responded with {}",
+                response.status()
+            )
+        })?;
+
+        let header = |name| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or_default()
+        };
+
+        if !header(header::UPGRADE).eq_ignore_ascii_case("websocket") {
+            return Err("The server switched to something other than WebSocket".into());
+        }
+
+        if header(header::SEC_WEBSOCKET_ACCEPT) != accept_key(key) {
+            return Err("The server's Sec-WebSocket-Accept doesn't match the key we sent".into());
+        }
+
+        Ok(Self {
+            connection,
+            pending: vec![],
+        })
+    }
+
+    /// Send a single (masked) frame
+    pub fn send(&mut self, opcode: Opcode, payload: &[u8]) -> io::Result<()> {
+        let frame = Frame {
+            fin: true,
+            opcode,
+            payload: payload.to_vec(),
+        };
+
+        self.connection.deadline.end_phase();
+        let stream = self.connection.stream.get_mut();
+        stream.write_all(&frame.encode(Some(random_bytes())))?;
+        stream.flush()
+    }
+
+    /// The next frame, or `None` if there wasn't one within `wait`
+    pub fn receive(&mut self, wait: Duration) -> Result<Option<Frame>, RequestError> {
+        loop {
+            if let Some((frame, len)) = Frame::decode(&self.pending)? {
+                self.pending.drain(..len);
+                return Ok(Some(frame));
+            }
+
+            let mut buf = [0; 8192];
+            self.connection
+                .deadline
+                .start("Waiting for a WebSocket frame", Some(wait));
+
+            match self.connection.stream.read(&mut buf) {
+                Ok(0) => {
+                    return Err("The server closed the connection without a close frame".into())
+                }
+                Ok(read) => self.pending.extend_from_slice(&buf[..read]),
+                Err(err)
+                    if matches!(
+                        err.kind(),
+                        io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock
+                    ) =>
+                {
+                    return Ok(None)
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+}
+
+/// Send stdin a line at a time and print what's received until either side closes the connection
+pub fn interact(mut socket: WebSocket, verbosity: u8, hex: bool) -> Result<(), RequestError> {
+    let (lines, input) = mpsc::channel();
+
+    thread::spawn(move || {
+        for line in io::stdin().lines() {
+            let Ok(line) = line else { break };
+
+            if lines.send(line).is_err() {
+                break;
+            }
+        }
+    });
+
+    // The close frame we sent, if we did
+    let mut closed_at = None;
+    // A message that's been split over several frames, until its last one
+    let mut message: Option<(Opcode, Vec<u8>)> = None;
+
+    loop {
+        while closed_at.is_none() {
+            match input.try_recv() {
+                Ok(line) => socket.send(Opcode::Text, line.as_bytes())?,
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    socket.send(Opcode::Close, &1000u16.to_be_bytes())?;
+                    closed_at = Some(Instant::now());
+                }
+            }
+        }
+
+        if closed_at.is_some_and(|at: Instant| at.elapsed() > CLOSE_TIMEOUT) {
+            return Err("The server didn't answer our close frame".into());
+        }
+
+        let Some(frame) = socket.receive(POLL)? else {
+            continue;
+        };
+
+        match frame.opcode {
+            Opcode::Ping => {
+                socket.send(Opcode::Pong, &frame.payload)?;
+                print_control(verbosity, "← Ping, answered with a pong");
+            }
+            Opcode::Pong => print_control(verbosity, "← Pong"),
+            Opcode::Close => {
+                let (code, reason) = match frame.payload.as_slice() {
+                    [a, b, reason @ ..] => (
+                        Some(u16::from_be_bytes([*a, *b])),
+                        String::from_utf8_lossy(reason).into_owned(),
+                    ),
+                    _ => (None, String::new()),
+                };
+
+                if closed_at.is_none() {
+                    socket.send(Opcode::Close, &frame.payload[..frame.payload.len().min(2)])?;
+                }
+
+                return match code {
+                    None | Some(1000) | Some(1001) => {
+                        print_control(verbosity, "← Closed");
+                        Ok(())
+                    }
+                    Some(code) => {
+                        Err(format!("The server closed the connection: {} {}", code, reason).into())
+                    }
+                };
+            }
+            Opcode::Text | Opcode::Binary if frame.fin => {
+                print_message(frame.opcode, &frame.payload, hex)?
+            }
+            Opcode::Text | Opcode::Binary => message = Some((frame.opcode, frame.payload)),
+            Opcode::Continuation => {
+                let (opcode, mut payload) = message
+                    .take()
+                    .ok_or("Got a continuation frame without a message to continue")?;
+                payload.extend_from_slice(&frame.payload);
+
+                match frame.fin {
+                    true => print_message(opcode, &payload, hex)?,
+                    false => message = Some((opcode, payload)),
+                }
+            }
+            Opcode::Other(opcode) => {
+                return Err(format!("Unknown WebSocket opcode {:#x}", opcode).into())
+            }
+        }
+    }
+}
+
+fn print_message(opcode: Opcode, payload: &[u8], hex: bool) -> io::Result<()> {
+    let mut out = io::stdout().lock();
+
+    if opcode == Opcode::Text && !hex {
+        writeln!(out, "{}", String::from_utf8_lossy(payload))?;
+    } else if hex {
+        write!(out, "{}", hexdump(payload, 0))?;
+    } else {
+        writeln!(
+            out,
+            "{}",
+            format!(
+                "Binary message ({} bytes), use --hex to see it",
+                payload.len()
+            )
+            .out_color(|t| t.bright_black())
+        )?;
+    }
+
+    out.flush()
+}
+
+/// Control frames are only shown with -v, on stderr so stdout is just the messages
+fn print_control(verbosity: u8, message: &str) {
+    if verbosity >= VERBOSE {
+        eprintln!(
+            "{}",
+            message.if_supports_color(Stream::Stderr, |t| t.bright_black())
+        );
+    }
+}
@@ -0,0 +1,111 @@
+//! The --cache-dir cache: serving fresh responses, going by Age, and revalidating stale ones
+
+mod common;
+
+use std::{
+    path::PathBuf,
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+    time::Duration,
+};
+
+use httpc::{cache::Cache, http_request::RequestOptions};
+
+/// A new, empty cache directory
+fn cache_dir() -> PathBuf {
+    static DIRS: AtomicUsize = AtomicUsize::new(0);
+    let dir = std::env::temp_dir().join(format!(
+        "httpc-cache-test-{}-{}",
+        std::process::id(),
+        DIRS.fetch_add(1, Ordering::Relaxed)
+    ));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+/// GET `/` through the cache, returning the status, the body and the Age header
+fn get(cache: &Cache, port: u16) -> (u16, String, Option<String>) {
+    let request = http::Request::get(format!("http://127.0.0.1:{}/", port))
+        .body(None)
+        .unwrap();
+    let response = cache.request(request, &RequestOptions::default()).unwrap();
+    let age = response
+        .headers()
+        .get(http::header::AGE)
+        .map(|age| age.to_str().unwrap().to_string());
+
+    (
+        response.status().as_u16(),
+        String::from_utf8(response.body().clone()).unwrap(),
+        age,
+    )
+}
+
+fn response(headers: &str, body: &str) -> String {
+    format!(
+        "HTTP/1.1 200 OK\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+        headers,
+        body.len(),
+        body
+    )
+}
+
+#[test]
+fn fresh_responses_come_from_the_cache() {
+    let (port, server) = common::serve(vec![response("Cache-Control: max-age=60\r\n", "one")]);
+    let cache = Cache::open(cache_dir().to_str().unwrap()).unwrap();
+
+    assert_eq!(get(&cache, port), (200, "one".to_string(), None));
+    // The server only answers once, so this has to be the cached one
+    assert_eq!(
+        get(&cache, port),
+        (200, "one".to_string(), Some("0".to_string()))
+    );
+    assert_eq!(server.join().unwrap().len(), 1);
+}
+
+#[test]
+fn age_counts_against_freshness() {
+    let (port, server) = common::serve(vec![
+        response("Cache-Control: max-age=60\r\nAge: 30\r\n", "young"),
+        response(
+            "Cache-Control: max-age=60\r\nAge: 18446744073709551615\r\n",
+            "old",
+        ),
+        response("Cache-Control: max-age=60\r\n", "new"),
+    ]);
+    let cache = Cache::open(cache_dir().to_str().unwrap()).unwrap();
+
+    assert_eq!(get(&cache, port).1, "young");
+    let (_, body, age) = get(&cache, port);
+    assert_eq!((body.as_str(), age.as_deref()), ("young", Some("30")));
+
+    // An Age too big to add the time it's been in the cache to is as stale as can be, not a
+    // panic
+    let cache = Cache::open(cache_dir().to_str().unwrap()).unwrap();
+    assert_eq!(get(&cache, port).1, "old");
+    thread::sleep(Duration::from_secs(1));
+    assert_eq!(get(&cache, port).1, "new");
+    assert_eq!(server.join().unwrap().len(), 3);
+}
+
+#[test]
+fn stale_responses_are_revalidated() {
+    let (port, server) = common::serve(vec![
+        response("Cache-Control: max-age=0\r\nETag: \"v1\"\r\n", "cached"),
+        "HTTP/1.1 304 Not Modified\r\nETag: \"v1\"\r\nCache-Control: max-age=60\r\nConnection: close\r\n\r\n".to_string(),
+    ]);
+    let cache = Cache::open(cache_dir().to_str().unwrap()).unwrap();
+
+    assert_eq!(get(&cache, port).1, "cached");
+    assert_eq!(
+        get(&cache, port),
+        (200, "cached".to_string(), Some("0".to_string()))
+    );
+    // And now it's fresh again, without asking the server
+    assert_eq!(get(&cache, port).1, "cached");
+
+    let heads = server.join().unwrap();
+    assert!(!heads[0].contains("if-none-match"));
+    assert!(heads[1].contains("if-none-match: \"v1\""));
+}
@@ -0,0 +1,113 @@
+//! --strict-tls: telling a TLS connection that ended with a close_notify from one that was cut off
+//!
+//! The server side needs OpenSSL, so these only run where that's the TLS library
+#![cfg(not(any(target_os = "windows", target_vendor = "apple")))]
+
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::TcpListener,
+    thread,
+};
+
+use httpc::http_request::{http_request, RequestOptions};
+use openssl::{
+    asn1::Asn1Time,
+    hash::MessageDigest,
+    pkey::PKey,
+    rsa::Rsa,
+    ssl::{SslAcceptor, SslMethod, SslVersion},
+    x509::X509,
+};
+
+/// Answer one request over TLS with a response that ends when the connection does,
+/// sending a close_notify first or not
+fn serve_tls(version: SslVersion, close_notify: bool) -> u16 {
+    let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+    let mut cert = X509::builder().unwrap();
+    cert.set_pubkey(&key).unwrap();
+    cert.set_not_before(&Asn1Time::days_from_now(0).unwrap())
+        .unwrap();
+    cert.set_not_after(&Asn1Time::days_from_now(1).unwrap())
+        .unwrap();
+    cert.sign(&key, MessageDigest::sha256()).unwrap();
+
+    let mut acceptor = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls()).unwrap();
+    acceptor.set_private_key(&key).unwrap();
+    acceptor.set_certificate(&cert.build()).unwrap();
+    acceptor.set_min_proto_version(Some(version)).unwrap();
+    acceptor.set_max_proto_version(Some(version)).unwrap();
+    let acceptor = acceptor.build();
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(acceptor.accept(stream).unwrap());
+
+        let mut line = String::new();
+        while line != "\r\n" {
+            line.clear();
+            reader.read_line(&mut line).unwrap();
+        }
+
+        let mut stream = reader.into_inner();
+        stream
+            .write_all(b"HTTP/1.1 200 OK\r\nConnection: close\r\n\r\nhello")
+            .unwrap();
+
+        if close_notify {
+            stream.shutdown().unwrap();
+        }
+        // Dropping it closes the TCP connection without a word otherwise
+    });
+
+    port
+}
+
+fn get(port: u16, strict_tls: bool) -> Result<String, String> {
+    let request = http::Request::get(format!("https://127.0.0.1:{}/", port))
+        .body(None)
+        .unwrap();
+    let options = RequestOptions {
+        insecure: true,
+        strict_tls,
+        ..Default::default()
+    };
+
+    http_request(request, &options)
+        .map(|response| String::from_utf8(response.into_body()).unwrap())
+        .map_err(|err| err.to_string())
+}
+
+const VERSIONS: [SslVersion; 2] = [SslVersion::TLS1_2, SslVersion::TLS1_3];
+
+#[test]
+fn close_notify_ends_the_response() {
+    for version in VERSIONS {
+        assert_eq!(get(serve_tls(version, true), true).as_deref(), Ok("hello"));
+    }
+}
+
+#[test]
+fn missing_close_notify_is_an_error_when_strict() {
+    for version in VERSIONS {
+        let err = get(serve_tls(version, false), true).unwrap_err();
+        assert!(
+            err.contains("without a close_notify"),
+            "{:?}: {}",
+            version,
+            err
+        );
+    }
+}
+
+#[test]
+fn missing_close_notify_is_the_end_otherwise() {
+    for version in VERSIONS {
+        assert_eq!(
+            get(serve_tls(version, false), false).as_deref(),
+            Ok("hello")
+        );
+    }
+}
@@ -0,0 +1,39 @@
+//! A server for the tests to send requests to, answering with canned responses
+
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::TcpListener,
+    thread,
+};
+
+/// Answer a request with each of `responses` in turn, one per connection. Returns the port it's
+/// listening on, and a handle that gives back the head of each request it got (lowercased)
+pub fn serve(responses: Vec<String>) -> (u16, thread::JoinHandle<Vec<String>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    let handle = thread::spawn(move || {
+        let mut heads = vec![];
+
+        for response in responses {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut head = String::new();
+
+            loop {
+                let mut line = String::new();
+                if reader.read_line(&mut line).unwrap() == 0 || line == "\r\n" {
+                    break;
+                }
+                head.push_str(&line);
+            }
+
+            reader.get_mut().write_all(response.as_bytes()).unwrap();
+            heads.push(head.to_ascii_lowercase());
+        }
+
+        heads
+    });
+
+    (port, handle)
+}
@@ -0,0 +1,84 @@
+//! The worker pool behind --parallel, and its per-host limit
+
+use std::{
+    panic,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use httpc::parallel::run_pool_limited;
+
+/// Run `f` on another thread, failing instead of hanging the test run if it never returns
+fn within<R: Send + 'static>(f: impl FnOnce() -> R + Send + 'static) -> R {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || sender.send(f()));
+    receiver
+        .recv_timeout(Duration::from_secs(10))
+        .expect("the pool hung")
+}
+
+#[test]
+fn jobs_for_a_key_stay_under_the_limit() {
+    let (results, most_at_once) = within(|| {
+        let items = ["a", "a", "a", "a", "b", "b", "b", "b"];
+        let running = [AtomicUsize::new(0), AtomicUsize::new(0)];
+        let most_at_once = [AtomicUsize::new(0), AtomicUsize::new(0)];
+        let mut results = Vec::new();
+
+        run_pool_limited(
+            &items,
+            8,
+            Some(2),
+            |item| *item,
+            |i, item| {
+                let key = usize::from(*item == "b");
+                let now = running[key].fetch_add(1, Ordering::SeqCst) + 1;
+                most_at_once[key].fetch_max(now, Ordering::SeqCst);
+                thread::sleep(Duration::from_millis(50));
+                running[key].fetch_sub(1, Ordering::SeqCst);
+                i
+            },
+            |i, result| results.push((i, result)),
+        );
+
+        results.sort_unstable();
+        (results, most_at_once.map(AtomicUsize::into_inner))
+    });
+
+    assert_eq!(results, (0..8).map(|i| (i, i)).collect::<Vec<_>>());
+    assert_eq!(most_at_once, [2, 2]);
+}
+
+#[test]
+fn a_panicking_job_frees_its_slot() {
+    let finished = within(|| {
+        let finished = Mutex::new(Vec::new());
+
+        let pool = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+            run_pool_limited(
+                &[0, 1, 2, 3],
+                2,
+                Some(1),
+                |_| (),
+                |i, _| {
+                    if i == 0 {
+                        panic!("job 0 failed");
+                    }
+                },
+                |i, ()| finished.lock().unwrap().push(i),
+            )
+        }));
+        assert!(
+            pool.is_err(),
+            "the job's panic should still reach the caller"
+        );
+
+        finished.into_inner().unwrap()
+    });
+
+    assert_eq!(finished, [1, 2, 3]);
+}
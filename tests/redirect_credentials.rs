@@ -0,0 +1,32 @@
+//! Following a redirect to another origin mustn't take the first origin's credentials along
+
+mod common;
+
+use std::process::Command;
+
+#[test]
+fn cross_origin_redirect_isnt_signed() {
+    let (second_port, followed) = common::serve(vec![
+        "HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok".to_string(),
+    ]);
+    let (first_port, redirected) = common::serve(vec![format!(
+        "HTTP/1.1 302 Found\r\nLocation: http://127.0.0.1:{}/b\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        second_port
+    )]);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_httpc"))
+        .args(["get", "-l", "--aws-sigv4", "us-east-1/s3"])
+        .arg(format!("http://127.0.0.1:{}/a", first_port))
+        .env("AWS_ACCESS_KEY_ID", "AKIDEXAMPLE")
+        .env("AWS_SECRET_ACCESS_KEY", "secret")
+        .env("AWS_SESSION_TOKEN", "session")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+
+    let (redirected, followed) = (redirected.join().unwrap(), followed.join().unwrap());
+    assert!(redirected[0].contains("authorization: aws4-hmac-sha256"));
+    assert!(redirected[0].contains("x-amz-security-token: session"));
+    assert!(!followed[0].contains("authorization"), "{}", followed[0]);
+    assert!(!followed[0].contains("x-amz-"), "{}", followed[0]);
+}